@@ -112,6 +112,14 @@ impl ErrorDetail {
     pub fn status(code: i32, msg: impl Into<String>) -> Self {
         Self::with_message(error_detail_union::Value::StatusCode(code), msg.into())
     }
+
+    #[inline]
+    pub fn schema_violation(path: String, reason: String, msg: impl Into<String>) -> Self {
+        Self::with_message(
+            error_detail_union::Value::SchemaViolation(SchemaViolation { path, reason }),
+            msg.into(),
+        )
+    }
 }
 
 impl Error {
@@ -159,6 +167,13 @@ impl Error {
         }
     }
 
+    #[inline]
+    pub fn schema_violation(path: String, reason: String, msg: impl Into<String>) -> Self {
+        Error {
+            details: vec![ErrorDetail::schema_violation(path, reason, msg)],
+        }
+    }
+
     #[inline]
     pub fn with_detail_value(value: error_detail_union::Value) -> Self {
         Error {
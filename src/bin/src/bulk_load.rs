@@ -0,0 +1,193 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use clap::Parser;
+use engula_api::server::v1::ShardDesc;
+use engula_client::{ClientOptions, Collection, EngulaClient};
+use engula_server::backup::{open_storage, ExternalStorage};
+use tracing::info;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Parser)]
+#[clap(about = "Bulk load records into a collection by ingesting SST files")]
+pub struct BulkLoadCommand {
+    /// Sets the address of the target cluster to operate
+    #[clap(long, default_value = "0.0.0.0:21805")]
+    addrs: Vec<String>,
+
+    #[clap(long)]
+    database: String,
+
+    #[clap(long)]
+    collection: String,
+
+    /// Sets the input file, each line is a record whose key and value are separated by the first
+    /// tab
+    #[clap(long, value_name = "FILE")]
+    input: String,
+
+    /// Sets the URL of the storage to place the SST files, it must be accessible by all nodes, eg.
+    /// 'file:///path/to/dir' or 's3://bucket/path/to/dir?endpoint=http://host:port'
+    #[clap(long, value_name = "URL")]
+    storage: String,
+
+    /// Sets the directory to build the SST files, default is the temporary directory
+    #[clap(long, value_name = "DIR")]
+    work_dir: Option<String>,
+
+    /// Sets the number of records sorted in memory, the SST files are written once it is reached
+    #[clap(long, default_value = "1000000")]
+    batch_records: usize,
+}
+
+/// The records and the written SST files of a shard.
+struct ShardBatch {
+    desc: ShardDesc,
+    records: Vec<(Vec<u8>, Vec<u8>)>,
+    files: Vec<String>,
+}
+
+struct Loader {
+    co: Collection,
+    storage_url: String,
+    storage: std::sync::Arc<dyn ExternalStorage>,
+    work_dir: PathBuf,
+    prefix: String,
+    next_file_id: usize,
+    shards: HashMap<u64, ShardBatch>,
+}
+
+impl BulkLoadCommand {
+    pub fn run(self) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        if let Err(err) = runtime.block_on(self.load()) {
+            eprintln!("bulk load: {err}");
+            std::process::exit(1);
+        }
+    }
+
+    async fn load(self) -> Result<()> {
+        let client = EngulaClient::new(ClientOptions::default(), self.addrs).await?;
+        let db = client.open_database(self.database).await?;
+        let co = db.open_collection(self.collection).await?;
+
+        let work_dir = self
+            .work_dir
+            .map(PathBuf::from)
+            .unwrap_or_else(|| std::env::temp_dir().join("engula-bulk-load"));
+        std::fs::create_dir_all(&work_dir)?;
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let mut loader = Loader {
+            prefix: format!("bulkload/{}/{timestamp}", co.desc().id),
+            co,
+            storage: open_storage(&self.storage)?,
+            storage_url: self.storage,
+            work_dir,
+            next_file_id: 0,
+            shards: HashMap::default(),
+        };
+
+        let mut num_records = 0;
+        let mut pending = 0;
+        let reader = BufReader::new(std::fs::File::open(&self.input)?);
+        for line in reader.split(b'\n') {
+            let line = line?;
+            if line.is_empty() {
+                continue;
+            }
+            let Some(pos) = line.iter().position(|c| *c == b'\t') else {
+                return Err(format!("line {} has no value", num_records + 1).into());
+            };
+            let (key, value) = (line[..pos].to_vec(), line[pos + 1..].to_vec());
+            loader.add(key, value).await?;
+            num_records += 1;
+            pending += 1;
+            if pending >= self.batch_records {
+                loader.flush().await?;
+                pending = 0;
+            }
+        }
+        loader.flush().await?;
+        loader.ingest().await?;
+        println!("{num_records} records are loaded");
+        Ok(())
+    }
+}
+
+impl Loader {
+    async fn add(&mut self, key: Vec<u8>, value: Vec<u8>) -> Result<()> {
+        let desc = self.co.locate(&key).await?;
+        self.shards
+            .entry(desc.id)
+            .or_insert_with(|| ShardBatch {
+                desc,
+                records: Vec::default(),
+                files: Vec::default(),
+            })
+            .records
+            .push((key, value));
+        Ok(())
+    }
+
+    /// Write the sorted records of each shard as a SST file, and upload it to the storage.
+    async fn flush(&mut self) -> Result<()> {
+        for batch in self.shards.values_mut() {
+            if batch.records.is_empty() {
+                continue;
+            }
+            let shard_id = batch.desc.id;
+            let path = self.work_dir.join(format!("{}.sst", self.next_file_id));
+            let name = format!("{}/{shard_id}/{}.sst", self.prefix, self.next_file_id);
+            self.next_file_id += 1;
+
+            let records = std::mem::take(&mut batch.records);
+            let num_records = engula_server::bulkload::write_sst_file(&path, &batch.desc, records)?;
+            upload(&*self.storage, &name, &path).await?;
+            info!("write {num_records} records of shard {shard_id} to {name}");
+            batch.files.push(name);
+        }
+        Ok(())
+    }
+
+    async fn ingest(&mut self) -> Result<()> {
+        for batch in self.shards.values() {
+            let shard_id = batch.desc.id;
+            self.co
+                .ingest_sst(shard_id, self.storage_url.clone(), batch.files.clone())
+                .await?;
+            info!(
+                "ingest {} sst files into shard {shard_id}",
+                batch.files.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+async fn upload(storage: &dyn ExternalStorage, name: &str, path: &Path) -> Result<()> {
+    storage.put_file(name, path).await?;
+    std::fs::remove_file(path)?;
+    Ok(())
+}
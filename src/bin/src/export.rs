@@ -0,0 +1,136 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use clap::Parser;
+use engula_client::{
+    export::{export_collection, import_collection, Format},
+    ClientOptions, Collection, EngulaClient,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+#[derive(Parser)]
+#[clap(about = "Export the records of a collection")]
+pub struct ExportCommand {
+    /// Sets the address of the target cluster to operate
+    #[clap(long, default_value = "0.0.0.0:21805")]
+    addrs: Vec<String>,
+
+    #[clap(long)]
+    db: String,
+
+    #[clap(long)]
+    coll: String,
+
+    /// Sets the output file, default or '-' is the stdout
+    #[clap(long, value_name = "FILE")]
+    output: Option<String>,
+
+    /// Sets the format of the records, 'json' or 'binary'
+    #[clap(long, default_value = "json")]
+    format: Format,
+
+    /// Sets the number of records scanned per request
+    #[clap(long, default_value = "1000")]
+    batch_size: u64,
+}
+
+#[derive(Parser)]
+#[clap(about = "Import the records exported by 'engula export' into a collection")]
+pub struct ImportCommand {
+    /// Sets the address of the target cluster to operate
+    #[clap(long, default_value = "0.0.0.0:21805")]
+    addrs: Vec<String>,
+
+    #[clap(long)]
+    db: String,
+
+    #[clap(long)]
+    coll: String,
+
+    /// Sets the input file, default is the stdin
+    #[clap(long, value_name = "FILE")]
+    input: Option<String>,
+
+    /// Sets the format of the records, 'json' or 'binary'
+    #[clap(long, default_value = "json")]
+    format: Format,
+
+    /// Sets the number of records written per batch
+    #[clap(long, default_value = "256")]
+    batch_size: usize,
+
+    /// Limits the number of records written per second, 0 means no limit
+    #[clap(long, default_value = "0")]
+    rate_limit: u64,
+}
+
+impl ExportCommand {
+    pub fn run(self) {
+        block_on(self.export());
+    }
+
+    async fn export(self) -> Result<()> {
+        let co = open_collection(self.addrs, self.db, self.coll).await?;
+        let (format, batch_size) = (self.format, self.batch_size);
+        let num_records = match self.output.as_deref() {
+            Some(path) if path != "-" => {
+                let file = std::fs::File::create(path)?;
+                export_collection(&co, file, format, batch_size).await?
+            }
+            _ => export_collection(&co, std::io::stdout().lock(), format, batch_size).await?,
+        };
+        eprintln!("{num_records} records are exported");
+        Ok(())
+    }
+}
+
+impl ImportCommand {
+    pub fn run(self) {
+        block_on(self.import());
+    }
+
+    async fn import(self) -> Result<()> {
+        let co = open_collection(self.addrs, self.db, self.coll).await?;
+        let (format, batch_size, rate_limit) = (self.format, self.batch_size, self.rate_limit);
+        let num_records = match &self.input {
+            Some(path) => {
+                let file = std::fs::File::open(path)?;
+                import_collection(&co, file, format, batch_size, rate_limit).await?
+            }
+            None => {
+                import_collection(&co, std::io::stdin(), format, batch_size, rate_limit).await?
+            }
+        };
+        eprintln!("{num_records} records are imported");
+        Ok(())
+    }
+}
+
+fn block_on<F: std::future::Future<Output = Result<()>>>(future: F) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    if let Err(err) = runtime.block_on(future) {
+        eprintln!("{err}");
+        std::process::exit(1);
+    }
+}
+
+async fn open_collection(addrs: Vec<String>, db: String, coll: String) -> Result<Collection> {
+    let client = EngulaClient::new(ClientOptions::default(), addrs).await?;
+    let db = client.open_database(db).await?;
+    Ok(db.open_collection(coll).await?)
+}
@@ -14,7 +14,10 @@
 #![feature(once_cell)]
 
 mod bench;
+mod bulk_load;
+mod export;
 mod shell;
+mod sim;
 
 use clap::{Parser, Subcommand};
 use engula_server::{Error, Result};
@@ -37,14 +40,20 @@ impl Command {
 #[derive(Subcommand)]
 enum SubCommand {
     Start(StartCommand),
+    Restore(RestoreCommand),
     Bench(bench::BenchCommand),
     Shell(shell::ShellCommand),
+    Sim(sim::SimCommand),
+    BulkLoad(bulk_load::BulkLoadCommand),
+    Export(export::ExportCommand),
+    Import(export::ImportCommand),
 }
 
 impl SubCommand {
     fn run(self) -> Result<()> {
         match self {
             SubCommand::Start(cmd) => cmd.run(),
+            SubCommand::Restore(cmd) => cmd.run(),
             SubCommand::Bench(cmd) => {
                 cmd.run();
                 Ok(())
@@ -53,6 +62,19 @@ impl SubCommand {
                 cmd.run();
                 Ok(())
             }
+            SubCommand::Sim(cmd) => cmd.run(),
+            SubCommand::BulkLoad(cmd) => {
+                cmd.run();
+                Ok(())
+            }
+            SubCommand::Export(cmd) => {
+                cmd.run();
+                Ok(())
+            }
+            SubCommand::Import(cmd) => {
+                cmd.run();
+                Ok(())
+            }
         }
     }
 }
@@ -92,6 +114,10 @@ struct StartCommand {
 
 impl StartCommand {
     fn run(self) -> Result<()> {
+        self.start(None, None)
+    }
+
+    fn start(self, restore_from: Option<String>, restore_time: Option<u64>) -> Result<()> {
         use engula_server::runtime::{ExecutorOwner, ShutdownNotifier, TaskPriority};
 
         let mut config = match load_config(&self) {
@@ -100,6 +126,12 @@ impl StartCommand {
                 return Err(Error::InvalidArgument(format!("Config: {e}")));
             }
         };
+        if restore_from.is_some() {
+            config.restore_from = restore_from;
+        }
+        if restore_time.is_some() {
+            config.restore_time = restore_time;
+        }
 
         if let Some(filename) = self.dump {
             let contents = toml::to_string(&config).expect("Config is serializable");
@@ -124,6 +156,35 @@ impl StartCommand {
     }
 }
 
+#[derive(Parser)]
+#[clap(about = "Bootstrap a new cluster and restore the databases and collections of a backup")]
+struct RestoreCommand {
+    /// Sets the URL of the backup, eg. 'file:///path/to/backup' or
+    /// 's3://bucket/path/to/backup?endpoint=http://host:port'
+    #[clap(long, value_name = "URL")]
+    backup: String,
+
+    /// Replays the archived log entries up to the unix timestamp in seconds
+    #[clap(long, value_name = "TIMESTAMP")]
+    restore_time: Option<u64>,
+
+    #[clap(flatten)]
+    start: StartCommand,
+}
+
+impl RestoreCommand {
+    fn run(self) -> Result<()> {
+        if self.start.join.is_some() {
+            return Err(Error::InvalidArgument(
+                "the restored cluster could not join another cluster".into(),
+            ));
+        }
+        let mut start = self.start;
+        start.init = true;
+        start.start(Some(self.backup), self.restore_time)
+    }
+}
+
 fn main() -> Result<()> {
     let default_panic = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |info| {
@@ -0,0 +1,79 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use clap::Parser;
+use engula_server::{
+    sim::{self, SimReport, SimSpec},
+    Error, Result,
+};
+
+#[derive(Parser)]
+#[clap(about = "Simulate the scheduling of a cluster")]
+pub struct SimCommand {
+    /// Sets the file describing the cluster and the events, see `conf/default-sim.toml`
+    #[clap(long, value_name = "FILE")]
+    conf: String,
+
+    /// Dump the report as toml file
+    #[clap(long, value_name = "FILE")]
+    dump: Option<String>,
+}
+
+impl SimCommand {
+    pub fn run(self) -> Result<()> {
+        let contents = std::fs::read_to_string(&self.conf)?;
+        let spec: SimSpec = toml::from_str(&contents)
+            .map_err(|e| Error::InvalidArgument(format!("Config: {e}")))?;
+        let report = sim::run(spec)?;
+        if let Some(filename) = self.dump {
+            let contents = toml::to_string(&report).expect("Report is serializable");
+            std::fs::write(filename, contents)?;
+        }
+        print_report(&report);
+        Ok(())
+    }
+}
+
+fn print_report(report: &SimReport) {
+    for phase in &report.phases {
+        let state = if phase.balanced {
+            "balanced"
+        } else {
+            "not balanced"
+        };
+        println!(
+            "== {}: {} actions, {state}",
+            phase.event,
+            phase.actions.len()
+        );
+        for action in &phase.actions {
+            println!(
+                "  [{:>4}] {:<14} {}",
+                action.step, action.policy, action.action
+            );
+        }
+    }
+
+    println!("== nodes");
+    println!(
+        "  {:>6} {:>6} {:>8} {:>8} {:>14} {:>10}",
+        "node", "alive", "replicas", "leaders", "disk_used", "load"
+    );
+    for n in &report.nodes {
+        println!(
+            "  {:>6} {:>6} {:>8} {:>8} {:>14} {:>10.2}",
+            n.node, n.alive, n.replica_count, n.leader_count, n.disk_used, n.load
+        );
+    }
+}
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
 use engula_api::{
     server::v1::{group_request_union::Request, group_response_union::Response, *},
@@ -22,7 +22,7 @@ use engula_api::{
 use crate::{
     conn_manager::ConnManager, discovery::StaticServiceDiscovery, group_client::GroupClient,
     metrics::*, record_latency, AdminRequestBuilder, AdminResponseExtractor, AppError, AppResult,
-    RetryState, RootClient, Router,
+    RetryState, RootClient, Router, RouterGroupState,
 };
 
 #[derive(Debug, Clone, Default)]
@@ -100,6 +100,32 @@ impl Client {
         }
     }
 
+    /// Update the fields of database listed in `update_mask`, see `UpdateDatabaseRequest` for the
+    /// supported fields.
+    pub async fn update_database(
+        &self,
+        name: String,
+        desc: DatabaseDesc,
+        update_mask: Vec<String>,
+    ) -> AppResult<Database> {
+        let root_client = self.inner.root_client.clone();
+        let resp = root_client
+            .admin(AdminRequestBuilder::update_database(
+                name.clone(),
+                desc,
+                update_mask,
+            ))
+            .await?;
+        match AdminResponseExtractor::update_database(resp) {
+            None => Err(AppError::NotFound(format!("database {name}"))),
+            Some(desc) => Ok(Database {
+                rpc_timeout: self.inner.opts.timeout,
+                desc,
+                client: self.clone(),
+            }),
+        }
+    }
+
     pub async fn delete_database(&self, name: String) -> AppResult<()> {
         let root_client = self.inner.root_client.clone();
         let resp = root_client
@@ -189,6 +215,18 @@ impl Database {
         &self,
         name: String,
         partition: Option<Partition>,
+    ) -> AppResult<Collection> {
+        self.create_collection_with_schema(name, partition, None)
+            .await
+    }
+
+    /// Create a collection whose values are validated against the schema, the writes violating
+    /// the schema are rejected with `AppError::InvalidArgument`.
+    pub async fn create_collection_with_schema(
+        &self,
+        name: String,
+        partition: Option<Partition>,
+        schema: Option<ValueSchema>,
     ) -> AppResult<Collection> {
         let client = self.client.clone();
         let db_desc = self.desc.clone();
@@ -198,6 +236,7 @@ impl Database {
                 db_desc,
                 name.clone(),
                 partition.map(Into::into),
+                schema,
             ))
             .await?;
         match AdminResponseExtractor::create_collection(resp) {
@@ -210,6 +249,35 @@ impl Database {
         }
     }
 
+    /// Update the fields of collection listed in `update_mask`, see `UpdateCollectionRequest` for
+    /// the supported fields.
+    pub async fn update_collection(
+        &self,
+        name: String,
+        desc: CollectionDesc,
+        update_mask: Vec<String>,
+    ) -> AppResult<Collection> {
+        let client = self.client.clone();
+        let db_desc = self.desc.clone();
+        let root_client = client.inner.root_client.clone();
+        let resp = root_client
+            .admin(AdminRequestBuilder::update_collection(
+                db_desc,
+                name.clone(),
+                desc,
+                update_mask,
+            ))
+            .await?;
+        match AdminResponseExtractor::update_collection(resp) {
+            None => Err(AppError::NotFound(format!("collection {name}"))),
+            Some(co_desc) => Ok(Collection {
+                rpc_timeout: self.rpc_timeout,
+                co_desc,
+                client: client.clone(),
+            }),
+        }
+    }
+
     pub async fn delete_collection(&self, name: String) -> AppResult<()> {
         let client = self.client.clone();
         let db_desc = self.desc.clone();
@@ -259,6 +327,39 @@ impl Database {
         }
     }
 
+    /// Create a secondary index on the collection. The existing records are indexed in background,
+    /// the index is not available for scanning until the backfilling is finished.
+    pub async fn create_index(
+        &self,
+        co_name: String,
+        index_name: String,
+        extractor: index_desc::Extractor,
+    ) -> AppResult<Collection> {
+        let client = self.client.clone();
+        let db_desc = self.desc.clone();
+        let root_client = client.inner.root_client.clone();
+        let index = IndexDesc {
+            name: index_name,
+            extractor: Some(extractor),
+            ..Default::default()
+        };
+        let resp = root_client
+            .admin(AdminRequestBuilder::create_index(
+                db_desc,
+                co_name.clone(),
+                index,
+            ))
+            .await?;
+        match AdminResponseExtractor::create_index(resp) {
+            None => Err(AppError::NotFound(format!("collection {co_name}"))),
+            Some(co_desc) => Ok(Collection {
+                rpc_timeout: self.rpc_timeout,
+                co_desc,
+                client: client.clone(),
+            }),
+        }
+    }
+
     #[allow(dead_code)]
     pub fn name(&self) -> String {
         self.desc.name.to_owned()
@@ -345,6 +446,143 @@ impl Collection {
         }
     }
 
+    /// Return the records whose indexed value equals to `value`, the index must be public.
+    pub async fn index_scan(
+        &self,
+        index_name: &str,
+        value: Vec<u8>,
+    ) -> AppResult<Vec<(Vec<u8>, Vec<u8>)>> {
+        let index = self
+            .co_desc
+            .indexes
+            .iter()
+            .find(|i| i.name == index_name)
+            .ok_or_else(|| AppError::NotFound(format!("index {index_name}")))?;
+        if index.state != index_desc::State::Public as i32 {
+            return Err(AppError::InvalidArgument(format!(
+                "index {index_name} is backfilling"
+            )));
+        }
+
+        let mut retry_state = RetryState::new(self.rpc_timeout);
+        loop {
+            match self
+                .index_scan_inner(index, &value, retry_state.timeout())
+                .await
+            {
+                Ok(records) => return Ok(records),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
+    /// Return the shards of the collection, along with the epochs of the groups they are located
+    /// in.
+    pub async fn shards(&self) -> AppResult<Vec<(ShardDesc, u64)>> {
+        let mut retry_state = RetryState::new(self.rpc_timeout);
+        loop {
+            match self
+                .client
+                .inner
+                .router
+                .find_collection_shard_epochs(self.co_desc.id)
+            {
+                Ok(shards) => return Ok(shards),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
+    /// Scan at most `limit` records of the shard in order, after `start_key` exclusively. The
+    /// records are scanned at the `epoch` of the group returned by [`Collection::shards`], and
+    /// `None` is returned once the epoch is changed, since the shards of the group might be
+    /// changed. An empty result means that all records of the shard have been scanned.
+    pub async fn scan_shard(
+        &self,
+        shard_id: u64,
+        epoch: u64,
+        start_key: Option<Vec<u8>>,
+        limit: u64,
+    ) -> AppResult<Option<Vec<(Vec<u8>, Vec<u8>)>>> {
+        let mut retry_state = RetryState::new(self.rpc_timeout);
+        loop {
+            match self
+                .scan_shard_inner(shard_id, epoch, &start_key, limit, retry_state.timeout())
+                .await
+            {
+                Ok(records) => return Ok(Some(records)),
+                Err(crate::Error::EpochNotMatch(_)) => return Ok(None),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
+    /// Put the records, the records located in the same group are written by one batch write
+    /// request.
+    pub async fn batch_put(&self, records: &[(Vec<u8>, Vec<u8>)]) -> AppResult<()> {
+        CLIENT_DATABASE_BYTES_TOTAL.rx.inc_by(
+            records
+                .iter()
+                .map(|(key, value)| (key.len() + value.len()) as u64)
+                .sum(),
+        );
+        let mut retry_state = RetryState::new(self.rpc_timeout);
+        loop {
+            match self.batch_put_inner(records, retry_state.timeout()).await {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
+    /// Return the shard which the key belongs to.
+    pub async fn locate(&self, key: &[u8]) -> AppResult<ShardDesc> {
+        let mut retry_state = RetryState::new(self.rpc_timeout);
+        loop {
+            match self
+                .client
+                .inner
+                .router
+                .find_shard(self.co_desc.clone(), key)
+            {
+                Ok((_, shard)) => return Ok(shard),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
+    /// Ingest the SST files placed in the external storage `storage` into the shard. The records
+    /// of the files must be encoded with the key layout of the server, and belong to the shard.
+    pub async fn ingest_sst(
+        &self,
+        shard_id: u64,
+        storage: String,
+        files: Vec<String>,
+    ) -> AppResult<()> {
+        let mut retry_state = RetryState::new(self.rpc_timeout);
+        loop {
+            match self
+                .ingest_sst_inner(shard_id, &storage, &files, retry_state.timeout())
+                .await
+            {
+                Ok(()) => return Ok(()),
+                Err(err) => {
+                    retry_state.retry(err).await?;
+                }
+            }
+        }
+    }
+
     async fn delete_inner(&self, key: &[u8], timeout: Option<Duration>) -> crate::Result<()> {
         let router = self.client.inner.router.clone();
         let (group, shard) = router.find_shard(self.co_desc.clone(), key)?;
@@ -422,6 +660,153 @@ impl Collection {
         }
     }
 
+    async fn index_scan_inner(
+        &self,
+        index: &IndexDesc,
+        value: &[u8],
+        timeout: Option<Duration>,
+    ) -> crate::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        // The index entries are co-located with the primary keys, so all shards are visited.
+        let router = self.client.inner.router.clone();
+        let shards = router.find_collection_shards(self.co_desc.id)?;
+        let mut records = Vec::new();
+        for shard in shards {
+            let group = router.find_group_by_shard(shard.id)?;
+            let mut client = GroupClient::new(
+                group,
+                self.client.inner.router.clone(),
+                self.client.inner.conn_manager.clone(),
+            );
+            let req = Request::IndexScan(ShardIndexScanRequest {
+                shard_id: shard.id,
+                index_id: index.id,
+                value: value.to_owned(),
+                limit: 0,
+            });
+            if let Some(duration) = timeout {
+                client.set_timeout(duration);
+            }
+            match client.request(&req).await? {
+                Response::IndexScan(resp) => {
+                    records.extend(resp.data.into_iter().map(|d| (d.key, d.value)));
+                }
+                _ => {
+                    return Err(crate::Error::Internal(wrap(
+                        "invalid response type, IndexScan is required",
+                    )))
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    async fn scan_shard_inner(
+        &self,
+        shard_id: u64,
+        epoch: u64,
+        start_key: &Option<Vec<u8>>,
+        limit: u64,
+        timeout: Option<Duration>,
+    ) -> crate::Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let router = self.client.inner.router.clone();
+        let group = router.find_group_by_shard(shard_id)?;
+        if group.epoch != epoch {
+            return Err(crate::Error::EpochNotMatch(GroupDesc {
+                id: group.id,
+                epoch: group.epoch,
+                ..Default::default()
+            }));
+        }
+        let mut client = GroupClient::new(
+            group,
+            self.client.inner.router.clone(),
+            self.client.inner.conn_manager.clone(),
+        );
+        let req = Request::Scan(ShardScanRequest {
+            shard_id,
+            limit,
+            exclude_start_key: true,
+            start_key: start_key.clone(),
+            ..Default::default()
+        });
+        if let Some(duration) = timeout {
+            client.set_timeout(duration);
+        }
+        match client.accurate_request(&req).await? {
+            Response::Scan(ShardScanResponse { data }) => {
+                Ok(data.into_iter().map(|d| (d.key, d.value)).collect())
+            }
+            _ => Err(crate::Error::Internal(wrap(
+                "invalid response type, Scan is required",
+            ))),
+        }
+    }
+
+    async fn batch_put_inner(
+        &self,
+        records: &[(Vec<u8>, Vec<u8>)],
+        timeout: Option<Duration>,
+    ) -> crate::Result<()> {
+        let router = self.client.inner.router.clone();
+        let mut groups: HashMap<u64, (RouterGroupState, Vec<ShardPutRequest>)> = HashMap::default();
+        for (key, value) in records {
+            let (group, shard) = router.find_shard(self.co_desc.clone(), key)?;
+            groups
+                .entry(group.id)
+                .or_insert_with(|| (group, Vec::default()))
+                .1
+                .push(ShardPutRequest {
+                    shard_id: shard.id,
+                    put: Some(PutRequest {
+                        key: key.to_owned(),
+                        value: value.to_owned(),
+                    }),
+                });
+        }
+        for (group, puts) in groups.into_values() {
+            let mut client = GroupClient::new(
+                group,
+                self.client.inner.router.clone(),
+                self.client.inner.conn_manager.clone(),
+            );
+            let req = Request::BatchWrite(BatchWriteRequest {
+                deletes: vec![],
+                puts,
+            });
+            if let Some(duration) = timeout {
+                client.set_timeout(duration);
+            }
+            client.request(&req).await?;
+        }
+        Ok(())
+    }
+
+    async fn ingest_sst_inner(
+        &self,
+        shard_id: u64,
+        storage: &str,
+        files: &[String],
+        timeout: Option<Duration>,
+    ) -> crate::Result<()> {
+        let router = self.client.inner.router.clone();
+        let group = router.find_group_by_shard(shard_id)?;
+        let mut client = GroupClient::new(
+            group,
+            self.client.inner.router.clone(),
+            self.client.inner.conn_manager.clone(),
+        );
+        let req = Request::IngestSst(IngestSstRequest {
+            shard_id,
+            storage: storage.to_owned(),
+            files: files.to_owned(),
+        });
+        if let Some(duration) = timeout {
+            client.set_timeout(duration);
+        }
+        client.request(&req).await?;
+        Ok(())
+    }
+
     #[allow(dead_code)]
     fn name(&self) -> String {
         self.co_desc.name.to_owned()
@@ -132,6 +132,7 @@ impl From<engula_api::server::v1::Error> for Error {
             }
             Some(Value::NotMatch(v)) => Error::EpochNotMatch(v.descriptor.unwrap_or_default()),
             Some(Value::StatusCode(v)) => Status::new(v.into(), msg).into(),
+            Some(Value::SchemaViolation(_)) => Error::InvalidArgument(msg),
             _ => Status::internal(format!("unknown error detail, msg: {msg}")).into(),
         }
     }
@@ -0,0 +1,215 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export the records of a collection into a stream, and import the exported records into a
+//! collection.
+
+use std::{
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{AppError, AppResult, Collection};
+
+/// The format of the exported records.
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    /// Each line is a JSON object `{"key":"<hex>","value":"<hex>"}`.
+    Json,
+    /// Each record is the key and the value, both are prefixed by the length in big-endian u32.
+    Binary,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonRecord {
+    key: String,
+    value: String,
+}
+
+/// Export the records of the collection into the writer, and return the number of exported
+/// records.
+///
+/// Each shard is scanned at the epoch of its group when the exporting starts. The records are
+/// streamed as they are scanned, so the exporting fails instead of starting over once any of the
+/// epochs is changed, since the shards might be moved or changed in the meantime. The records
+/// written during exporting might not be exported.
+pub async fn export_collection<W: Write>(
+    co: &Collection,
+    writer: W,
+    format: Format,
+    batch_size: u64,
+) -> AppResult<usize> {
+    let mut writer = BufWriter::new(writer);
+    let mut num_records = 0;
+    for (shard, epoch) in co.shards().await? {
+        let mut start_key = None;
+        loop {
+            let records = co
+                .scan_shard(shard.id, epoch, start_key.take(), batch_size)
+                .await?;
+            let Some(records) = records else {
+                return Err(AppError::Internal(
+                    format!(
+                        "the shard {} of collection {} is changed during exporting, export again",
+                        shard.id,
+                        co.desc().name
+                    )
+                    .into(),
+                ));
+            };
+            let Some((last_key, _)) = records.last() else {
+                break;
+            };
+            start_key = Some(last_key.clone());
+            for (key, value) in &records {
+                write_record(&mut writer, format, key, value)?;
+            }
+            num_records += records.len();
+        }
+    }
+    writer.flush().map_err(io_error)?;
+    Ok(num_records)
+}
+
+/// Import the records exported by [`export_collection`] into the collection, and return the
+/// number of imported records. At most `rate_limit` records are written per second, 0 means no
+/// limit.
+pub async fn import_collection<R: Read>(
+    co: &Collection,
+    input: R,
+    format: Format,
+    batch_size: usize,
+    rate_limit: u64,
+) -> AppResult<u64> {
+    let mut reader = BufReader::new(input);
+    let start_at = Instant::now();
+    let batch_size = batch_size.max(1);
+    let mut num_records = 0;
+    let mut batch = Vec::with_capacity(batch_size);
+    loop {
+        let record = read_record(&mut reader, format)?;
+        let finished = record.is_none();
+        batch.extend(record);
+        if batch.len() < batch_size && !finished {
+            continue;
+        }
+
+        if !batch.is_empty() {
+            co.batch_put(&batch).await?;
+            num_records += batch.len() as u64;
+            batch.clear();
+        }
+        if finished {
+            break;
+        }
+        if rate_limit != 0 {
+            // Sleep until the written records are allowed by the rate.
+            let expected = Duration::from_secs_f64(num_records as f64 / rate_limit as f64);
+            if let Some(duration) = expected.checked_sub(start_at.elapsed()) {
+                tokio::time::sleep(duration).await;
+            }
+        }
+    }
+    Ok(num_records)
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "binary" => Ok(Format::Binary),
+            _ => Err(format!(
+                "unknown format {s}, 'json' or 'binary' is expected"
+            )),
+        }
+    }
+}
+
+fn write_record<W: Write>(
+    writer: &mut W,
+    format: Format,
+    key: &[u8],
+    value: &[u8],
+) -> AppResult<()> {
+    match format {
+        Format::Json => {
+            let record = JsonRecord {
+                key: hex::encode(key),
+                value: hex::encode(value),
+            };
+            serde_json::to_writer(&mut *writer, &record).map_err(io_error)?;
+            writer.write_all(b"\n").map_err(io_error)?;
+        }
+        Format::Binary => {
+            for data in [key, value] {
+                writer
+                    .write_all(&(data.len() as u32).to_be_bytes())
+                    .map_err(io_error)?;
+                writer.write_all(data).map_err(io_error)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Read the next record, `None` is returned if the end of input is reached.
+fn read_record<R: BufRead>(
+    reader: &mut R,
+    format: Format,
+) -> AppResult<Option<(Vec<u8>, Vec<u8>)>> {
+    match format {
+        Format::Json => {
+            let mut line = String::new();
+            loop {
+                line.clear();
+                if reader.read_line(&mut line).map_err(io_error)? == 0 {
+                    return Ok(None);
+                }
+                if !line.trim().is_empty() {
+                    break;
+                }
+            }
+            let record: JsonRecord = serde_json::from_str(&line)
+                .map_err(|err| AppError::InvalidArgument(format!("json record {err}")))?;
+            let from_hex = |s: &str| {
+                hex::decode(s).map_err(|err| AppError::InvalidArgument(format!("hex {s}: {err}")))
+            };
+            Ok(Some((from_hex(&record.key)?, from_hex(&record.value)?)))
+        }
+        Format::Binary => {
+            if reader.fill_buf().map_err(io_error)?.is_empty() {
+                return Ok(None);
+            }
+            let mut read_data = || -> std::io::Result<Vec<u8>> {
+                let mut len = [0u8; 4];
+                reader.read_exact(&mut len)?;
+                let mut data = vec![0u8; u32::from_be_bytes(len) as usize];
+                reader.read_exact(&mut data)?;
+                Ok(data)
+            };
+            let key = read_data().map_err(io_error)?;
+            let value = read_data().map_err(io_error)?;
+            Ok(Some((key, value)))
+        }
+    }
+}
+
+fn io_error<E: Into<std::io::Error>>(err: E) -> AppError {
+    AppError::Internal(Box::new(err.into()))
+}
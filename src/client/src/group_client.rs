@@ -358,6 +358,20 @@ impl GroupClient {
 
 impl GroupClient {
     pub async fn request(&mut self, request: &Request) -> Result<Response> {
+        self.request_with_epoch(request, false).await
+    }
+
+    /// Issue the request at the epoch of the applied group state, `EpochNotMatch` is returned
+    /// instead of retrying if the epoch of the group is changed.
+    pub async fn accurate_request(&mut self, request: &Request) -> Result<Response> {
+        self.request_with_epoch(request, true).await
+    }
+
+    async fn request_with_epoch(
+        &mut self,
+        request: &Request,
+        accurate_epoch: bool,
+    ) -> Result<Response> {
         let op = |ctx: InvokeContext, client: NodeClient| {
             let latency = take_group_request_metrics(request);
             let req = BatchRequest {
@@ -382,7 +396,7 @@ impl GroupClient {
 
         let opt = InvokeOpt {
             request: Some(request),
-            accurate_epoch: false,
+            accurate_epoch,
             ignore_transport_error: false,
         };
         self.invoke_with_opt(op, opt).await
@@ -537,6 +551,47 @@ impl GroupClient {
         })
     }
 
+    /// Build index entries for a batch of records start from `start_key`, the key to continue
+    /// backfilling is returned, `None` means the shard has been finished.
+    pub async fn backfill_index(
+        &mut self,
+        shard_id: u64,
+        index_id: u64,
+        start_key: Vec<u8>,
+        limit: u64,
+    ) -> Result<Option<Vec<u8>>> {
+        let req = Request::BackfillIndex(BackfillIndexRequest {
+            shard_id,
+            index_id,
+            start_key,
+            limit,
+        });
+        match self.request(&req).await? {
+            Response::BackfillIndex(resp) => Ok(resp.next_key),
+            _ => Err(Error::Internal(
+                "invalid response type, `BackfillIndex` is required".into(),
+            )),
+        }
+    }
+
+    /// Replace the collection metadata of the shards of the collection in this group.
+    pub async fn sync_collection(
+        &mut self,
+        collection_id: u64,
+        meta: CollectionMeta,
+    ) -> Result<()> {
+        let req = Request::SyncCollection(SyncCollectionRequest {
+            collection_id,
+            meta: Some(meta),
+        });
+        match self.request(&req).await? {
+            Response::SyncCollection(_) => Ok(()),
+            _ => Err(Error::Internal(
+                "invalid response type, `SyncCollection` is required".into(),
+            )),
+        }
+    }
+
     pub async fn add_learner(&mut self, replica: u64, node: u64) -> Result<()> {
         let op = |ctx: InvokeContext, client: NodeClient| {
             let req = RequestBatchBuilder::new(ctx.node_id)
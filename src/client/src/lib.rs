@@ -18,6 +18,7 @@ mod app_client;
 mod conn_manager;
 mod discovery;
 pub mod error;
+pub mod export;
 mod group_client;
 mod metrics;
 mod migrate_client;
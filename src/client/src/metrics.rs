@@ -30,6 +30,10 @@ make_static_metric! {
             create_shard,
             move_replicas,
             change_replicas,
+            index_scan,
+            backfill_index,
+            ingest_sst,
+            sync_collection,
         }
     }
     pub struct GroupRequestDuration: Histogram {
@@ -44,6 +48,10 @@ make_static_metric! {
             create_shard,
             move_replicas,
             change_replicas,
+            index_scan,
+            backfill_index,
+            ingest_sst,
+            sync_collection,
         }
     }
 }
@@ -121,6 +129,22 @@ pub fn take_group_request_metrics(
             GROUP_CLIENT_GROUP_REQUEST_TOTAL.move_replicas.inc();
             Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.move_replicas)
         }
+        Request::IndexScan(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.index_scan.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.index_scan)
+        }
+        Request::BackfillIndex(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.backfill_index.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.backfill_index)
+        }
+        Request::IngestSst(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.ingest_sst.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.ingest_sst)
+        }
+        Request::SyncCollection(_) => {
+            GROUP_CLIENT_GROUP_REQUEST_TOTAL.sync_collection.inc();
+            Some(&GROUP_CLIENT_GROUP_REQUEST_DURATION_SECONDS.sync_collection)
+        }
     }
 }
 
@@ -101,6 +101,116 @@ impl Client {
         }
     }
 
+    // NOTE: This method is always called by the root group.
+    pub async fn unsafe_recover_replica(
+        &self,
+        replica_id: u64,
+        group: GroupDesc,
+    ) -> Result<GroupDesc, tonic::Status> {
+        let mut client = self.client.clone();
+        let req = UnsafeRecoverReplicaRequest {
+            replica_id,
+            group: Some(group),
+        };
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::UnsafeRecoverReplica(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::UnsafeRecoverReplica(resp)) => resp
+                .group
+                .ok_or_else(|| tonic::Status::internal("`group` is required".to_owned())),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `UnsafeRecoverReplicaResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    // NOTE: This method is always called by the root group.
+    pub async fn backup_replica(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+        destination: String,
+    ) -> Result<BackupReplicaResponse, tonic::Status> {
+        let mut client = self.client.clone();
+        let req = BackupReplicaRequest {
+            group_id,
+            replica_id,
+            destination,
+        };
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::BackupReplica(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::BackupReplica(resp)) => Ok(resp),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `BackupReplicaResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    // NOTE: This method is always called by the root group.
+    pub async fn restore_replica(&self, req: RestoreReplicaRequest) -> Result<(), tonic::Status> {
+        let mut client = self.client.clone();
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::RestoreReplica(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::RestoreReplica(_)) => Ok(()),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `RestoreReplicaResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    // NOTE: This method is always called by the leader of the group.
+    pub async fn get_checksum(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+        checksum_id: u64,
+    ) -> Result<Option<ReplicaChecksum>, tonic::Status> {
+        let mut client = self.client.clone();
+        let req = GetChecksumRequest {
+            group_id,
+            replica_id,
+            checksum_id,
+        };
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::GetChecksum(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::GetChecksum(resp)) => Ok(resp.checksum),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `GetChecksumResponse` is required".to_owned(),
+            )),
+        }
+    }
+
+    // NOTE: This method is always called by the leader of the group.
+    pub async fn stage_sst(&self, req: StageSstRequest) -> Result<Vec<u32>, tonic::Status> {
+        let mut client = self.client.clone();
+        let resp = client
+            .admin(NodeAdminRequest {
+                request: Some(node_admin_request::Request::StageSst(req)),
+            })
+            .await?;
+        match resp.into_inner().response {
+            Some(node_admin_response::Response::StageSst(resp)) => Ok(resp.checksums),
+            _ => Err(tonic::Status::internal(
+                "Invalid response type, `StageSstResponse` is required".to_owned(),
+            )),
+        }
+    }
+
     pub async fn batch_group_requests(
         &self,
         req: impl IntoRequest<BatchRequest>,
@@ -20,6 +20,7 @@ use engula_api::{
     v1::{create_collection_request::Partition, *},
 };
 use prost::Message;
+use prost_types::FieldMask;
 use tokio::sync::Mutex;
 use tonic::{transport::Channel, Code, Status, Streaming};
 use tracing::trace;
@@ -117,8 +118,12 @@ impl Client {
     pub async fn watch(
         &self,
         cur_group_epochs: HashMap<u64, u64>,
+        start_revision: u64,
     ) -> Result<Streaming<WatchResponse>> {
-        let req = WatchRequest { cur_group_epochs };
+        let req = WatchRequest {
+            cur_group_epochs,
+            start_revision,
+        };
         let res = self
             .invoke(|mut client| {
                 let req = req.clone();
@@ -309,6 +314,24 @@ impl AdminRequestBuilder {
         }
     }
 
+    pub fn update_database(
+        name: String,
+        database: DatabaseDesc,
+        update_mask: Vec<String>,
+    ) -> AdminRequest {
+        AdminRequest {
+            request: Some(AdminRequestUnion {
+                request: Some(admin_request_union::Request::UpdateDatabase(
+                    UpdateDatabaseRequest {
+                        name,
+                        database: Some(database),
+                        update_mask: Some(FieldMask { paths: update_mask }),
+                    },
+                )),
+            }),
+        }
+    }
+
     pub fn delete_database(name: String) -> AdminRequest {
         AdminRequest {
             request: Some(AdminRequestUnion {
@@ -343,6 +366,7 @@ impl AdminRequestBuilder {
         database: DatabaseDesc,
         co_name: String,
         partition: Option<Partition>,
+        schema: Option<ValueSchema>,
     ) -> AdminRequest {
         AdminRequest {
             request: Some(AdminRequestUnion {
@@ -351,6 +375,27 @@ impl AdminRequestBuilder {
                         name: co_name,
                         database: Some(database),
                         partition,
+                        schema,
+                    },
+                )),
+            }),
+        }
+    }
+
+    pub fn update_collection(
+        database: DatabaseDesc,
+        co_name: String,
+        collection: CollectionDesc,
+        update_mask: Vec<String>,
+    ) -> AdminRequest {
+        AdminRequest {
+            request: Some(AdminRequestUnion {
+                request: Some(admin_request_union::Request::UpdateCollection(
+                    UpdateCollectionRequest {
+                        name: co_name,
+                        database: Some(database),
+                        collection: Some(collection),
+                        update_mask: Some(FieldMask { paths: update_mask }),
                     },
                 )),
             }),
@@ -382,6 +427,20 @@ impl AdminRequestBuilder {
         }
     }
 
+    pub fn create_index(database: DatabaseDesc, co_name: String, index: IndexDesc) -> AdminRequest {
+        AdminRequest {
+            request: Some(AdminRequestUnion {
+                request: Some(admin_request_union::Request::CreateIndex(
+                    CreateIndexRequest {
+                        database: Some(database),
+                        collection: co_name,
+                        index: Some(index),
+                    },
+                )),
+            }),
+        }
+    }
+
     pub fn get_collection(database: DatabaseDesc, co_name: String) -> AdminRequest {
         AdminRequest {
             request: Some(AdminRequestUnion {
@@ -408,6 +467,17 @@ impl AdminResponseExtractor {
         }
     }
 
+    pub fn update_database(resp: AdminResponse) -> Option<DatabaseDesc> {
+        if let Some(AdminResponseUnion {
+            response: Some(admin_response_union::Response::UpdateDatabase(response)),
+        }) = resp.response
+        {
+            response.database
+        } else {
+            None
+        }
+    }
+
     pub fn delete_database(resp: AdminResponse) -> Option<()> {
         if let Some(AdminResponseUnion {
             response: Some(admin_response_union::Response::DeleteDatabase(_)),
@@ -452,6 +522,17 @@ impl AdminResponseExtractor {
         }
     }
 
+    pub fn update_collection(resp: AdminResponse) -> Option<CollectionDesc> {
+        if let Some(AdminResponseUnion {
+            response: Some(admin_response_union::Response::UpdateCollection(response)),
+        }) = resp.response
+        {
+            response.collection
+        } else {
+            None
+        }
+    }
+
     pub fn delete_collection(resp: AdminResponse) -> Option<()> {
         if let Some(AdminResponseUnion {
             response: Some(admin_response_union::Response::DeleteCollection(_)),
@@ -474,6 +555,17 @@ impl AdminResponseExtractor {
         }
     }
 
+    pub fn create_index(resp: AdminResponse) -> Option<CollectionDesc> {
+        if let Some(AdminResponseUnion {
+            response: Some(admin_response_union::Response::CreateIndex(response)),
+        }) = resp.response
+        {
+            response.collection
+        } else {
+            None
+        }
+    }
+
     pub fn get_collection(resp: AdminResponse) -> Option<CollectionDesc> {
         if let Some(AdminResponseUnion {
             response: Some(admin_response_union::Response::GetCollection(response)),
@@ -48,6 +48,10 @@ pub struct State {
     group_id_lookup: HashMap<u64 /* group */, RouterGroupState>,
 
     cached_group_states: HashMap<u64, GroupState>,
+
+    /// The revision of the latest change received, the watching resumes from it after
+    /// reconnecting.
+    revision: u64,
 }
 
 #[derive(Debug, Clone, Default)]
@@ -135,6 +139,39 @@ impl Router {
         Err(crate::Error::NotFound(format!("shard (key={:?})", key)))
     }
 
+    pub fn find_collection_shards(
+        &self,
+        collection_id: u64,
+    ) -> Result<Vec<ShardDesc>, crate::Error> {
+        let state = self.state.lock().unwrap();
+        state
+            .co_shards_lookup
+            .get(&collection_id)
+            .cloned()
+            .ok_or_else(|| crate::Error::NotFound(format!("shards (collection={collection_id})")))
+    }
+
+    /// Return the shards of the collection, along with the epochs of the groups they are located
+    /// in.
+    pub fn find_collection_shard_epochs(
+        &self,
+        collection_id: u64,
+    ) -> Result<Vec<(ShardDesc, u64)>, crate::Error> {
+        let state = self.state.lock().unwrap();
+        let shards = state.co_shards_lookup.get(&collection_id).ok_or_else(|| {
+            crate::Error::NotFound(format!("shards (collection={collection_id})"))
+        })?;
+        shards
+            .iter()
+            .map(|shard| {
+                let group = state.find_group_by_shard(shard.id).ok_or_else(|| {
+                    crate::Error::NotFound(format!("group (shard={:?})", shard.id))
+                })?;
+                Ok((shard.clone(), group.epoch))
+            })
+            .collect()
+    }
+
     pub fn find_group_by_shard(&self, shard: u64) -> Result<RouterGroupState, crate::Error> {
         let state = self.state.lock().unwrap();
         state
@@ -193,7 +230,7 @@ impl State {
                 let (id, name) = (db_desc.id, db_desc.name);
                 if let Some(old_desc) = self.db_id_lookup.insert(id, desc) {
                     if old_desc.name != name {
-                        self.db_name_lookup.remove(&name);
+                        self.db_name_lookup.remove(&old_desc.name);
                     }
                 }
                 self.db_name_lookup.insert(name, id);
@@ -285,15 +322,16 @@ async fn state_main(state: Arc<Mutex<State>>, root_client: RootClient) {
 
     let mut interval = 1;
     loop {
-        let cur_group_epochs = {
+        let (cur_group_epochs, revision) = {
             let state = state.lock().unwrap();
-            state
+            let cur_group_epochs = state
                 .group_id_lookup
                 .iter()
                 .map(|(id, s)| (*id, s.epoch))
-                .collect()
+                .collect();
+            (cur_group_epochs, state.revision)
         };
-        let events = match root_client.watch(cur_group_epochs).await {
+        let events = match root_client.watch(cur_group_epochs, revision).await {
             Ok(events) => events,
             Err(e) => {
                 warn!(err = ?e, "watch events");
@@ -310,8 +348,8 @@ async fn state_main(state: Arc<Mutex<State>>, root_client: RootClient) {
 
 async fn watch_events(state: &Mutex<State>, mut events: Streaming<WatchResponse>) {
     while let Some(event) = events.next().await {
-        let (updates, deletes) = match event {
-            Ok(resp) => (resp.updates, resp.deletes),
+        let (updates, deletes, revision) = match event {
+            Ok(resp) => (resp.updates, resp.deletes, resp.revision),
             Err(status) => {
                 warn!("WatchEvent error: {}", status);
                 continue;
@@ -329,6 +367,7 @@ async fn watch_events(state: &Mutex<State>, mut events: Streaming<WatchResponse>
                 state.apply_delete_event(event);
             }
         }
+        state.lock().unwrap().revision = revision;
     }
 }
 
@@ -368,6 +407,7 @@ mod tests {
                 slot_id: 1,
                 slots: 1,
             })),
+            ..Default::default()
         }
     }
 
@@ -377,6 +417,7 @@ mod tests {
             epoch,
             shards: vec![],
             replicas: vec![],
+            ..Default::default()
         }
     }
 
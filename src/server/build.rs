@@ -25,6 +25,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     tonic_build::configure().compile_with_config(
         config,
         &[
+            "proto/v1/backup.proto",
             "proto/v1/metadata.proto",
             "proto/v1/raft.proto",
             "proto/v1/schedule.proto",
@@ -0,0 +1,229 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use prost::Message;
+use tracing::debug;
+
+use super::ExternalStorage;
+use crate::{
+    serverpb::v1::{ArchivedLog, EvalResult, MigrationEvent, SyncOp},
+    Error, Result,
+};
+
+/// The name of the object of a chunk of the archived log entries. The indexes are padded so the
+/// objects of a group are listed in order.
+pub fn log_file(group_id: u64, first_index: u64, last_index: u64) -> String {
+    format!("logs/{group_id}/{first_index:020}-{last_index:020}")
+}
+
+fn parse_log_file(name: &str) -> Option<(u64, u64)> {
+    let (_, range) = name.rsplit_once('/')?;
+    let (first, last) = range.split_once('-')?;
+    Some((first.parse().ok()?, last.parse().ok()?))
+}
+
+/// Replay the archived log entries of the group after `apply_index`, the chunks archived after
+/// `restore_time` are skipped. The cut is made by the archived time of chunks rather than the time
+/// the entries are written, see `ArchivedLog::archived_time`. The index of the last replayed entry
+/// is returned.
+///
+/// An `Error::InvalidData` is returned if some entries are missing, in which case the state at
+/// `restore_time` could not be recovered.
+pub async fn replay_archived_logs<F>(
+    storage: &dyn ExternalStorage,
+    group_id: u64,
+    apply_index: u64,
+    restore_time: u64,
+    mut apply: F,
+) -> Result<u64>
+where
+    F: FnMut(u64, EvalResult) -> Result<()>,
+{
+    let mut chunks = storage
+        .list(&format!("logs/{group_id}/"))
+        .await?
+        .into_iter()
+        .filter_map(|name| parse_log_file(&name).map(|range| (range, name)))
+        .collect::<Vec<_>>();
+    chunks.sort_unstable();
+
+    let mut applied = apply_index;
+    for ((first_index, last_index), name) in chunks {
+        if last_index <= applied {
+            continue;
+        }
+        let chunk = ArchivedLog::decode(&*storage.get(&name).await?)?;
+        if chunk.archived_time > restore_time {
+            break;
+        }
+        if first_index > applied + 1 {
+            return Err(Error::InvalidData(format!(
+                "the archived log entries of group {group_id} in [{}, {first_index}) are missing",
+                applied + 1
+            )));
+        }
+        debug!("group {group_id} replay archived log entries [{first_index}, {last_index}]");
+        for entry in chunk.entries {
+            if entry.index > applied {
+                let eval_result = EvalResult::decode(&*entry.data)?;
+                if let Some(what) = eval_result.op.as_deref().and_then(unreplayable_op) {
+                    return Err(Error::InvalidData(format!(
+                        "the archived log entry {} of group {group_id} {what}, which could not be \
+                         replayed",
+                        entry.index
+                    )));
+                }
+                apply(entry.index, eval_result)?;
+            }
+        }
+        applied = last_index;
+    }
+    Ok(applied)
+}
+
+/// The operations changing the shards of the group, or carrying the data outside of the log, could
+/// not be replayed onto a restored group. The others only change the local states or the metadata
+/// restored from the manifest, they are skipped by replaying.
+fn unreplayable_op(op: &SyncOp) -> Option<&'static str> {
+    if op.add_shard.is_some() {
+        Some("adds a shard")
+    } else if op.ingest_sst.is_some() {
+        Some("ingests the external sst files")
+    } else if op
+        .migration
+        .as_ref()
+        .map(|m| m.event == MigrationEvent::Apply as i32)
+        .unwrap_or_default()
+    {
+        Some("moves a shard")
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::{backup::LocalStorage, runtime::ExecutorOwner, serverpb::v1::ArchivedEntry};
+
+    async fn put_chunk(storage: &LocalStorage, first_index: u64, last_index: u64, time: u64) {
+        let chunk = ArchivedLog {
+            group_id: 1,
+            first_index,
+            last_index,
+            archived_time: time,
+            entries: (first_index..=last_index)
+                .map(|index| ArchivedEntry {
+                    index,
+                    term: 1,
+                    data: EvalResult::default().encode_to_vec(),
+                })
+                .collect(),
+        };
+        storage
+            .put(&log_file(1, first_index, last_index), chunk.encode_to_vec())
+            .await
+            .unwrap();
+    }
+
+    async fn replay(storage: &LocalStorage, apply_index: u64, time: u64) -> Result<Vec<u64>> {
+        let mut indexes = vec![];
+        replay_archived_logs(storage, 1, apply_index, time, |index, _| {
+            indexes.push(index);
+            Ok(())
+        })
+        .await?;
+        Ok(indexes)
+    }
+
+    #[test]
+    fn replay_archived_logs_until() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async {
+            let dir = TempDir::new("replay_archived_logs_until").unwrap();
+            let storage = LocalStorage::new(dir.path());
+            put_chunk(&storage, 1, 10, 100).await;
+            // The overlapped chunk archived by a new leader.
+            put_chunk(&storage, 8, 12, 200).await;
+            put_chunk(&storage, 13, 20, 300).await;
+
+            assert_eq!(
+                replay(&storage, 5, 100).await.unwrap(),
+                (6..=10).collect::<Vec<_>>()
+            );
+            assert_eq!(
+                replay(&storage, 5, 250).await.unwrap(),
+                (6..=12).collect::<Vec<_>>()
+            );
+            assert_eq!(
+                replay(&storage, 12, 300).await.unwrap(),
+                (13..=20).collect::<Vec<_>>()
+            );
+            assert!(replay(&storage, 20, 300).await.unwrap().is_empty());
+
+            put_chunk(&storage, 25, 30, 400).await;
+            assert!(matches!(
+                replay(&storage, 20, 400).await,
+                Err(Error::InvalidData(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn replay_unreplayable_ops() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async {
+            let dir = TempDir::new("replay_unreplayable_ops").unwrap();
+            let storage = LocalStorage::new(dir.path());
+            let entry = |index: u64, op: Box<SyncOp>| ArchivedEntry {
+                index,
+                term: 1,
+                data: EvalResult {
+                    batch: None,
+                    op: Some(op),
+                }
+                .encode_to_vec(),
+            };
+            let chunk = ArchivedLog {
+                group_id: 1,
+                first_index: 1,
+                last_index: 2,
+                archived_time: 100,
+                entries: vec![
+                    entry(1, SyncOp::archive_state(0)),
+                    entry(2, SyncOp::ingest_sst(1, 1, 1)),
+                ],
+            };
+            storage
+                .put(&log_file(1, 1, 2), chunk.encode_to_vec())
+                .await
+                .unwrap();
+
+            // The local states are skipped, but the ingested files are not archived.
+            assert!(matches!(
+                replay(&storage, 0, 100).await,
+                Err(Error::InvalidData(_))
+            ));
+            assert_eq!(replay(&storage, 2, 100).await.unwrap(), Vec::<u64>::new());
+        });
+    }
+
+    #[test]
+    fn parse_log_file_name() {
+        assert_eq!(parse_log_file(&log_file(3, 10, 20)), Some((10, 20)));
+        assert_eq!(parse_log_file("logs/3/MANIFEST"), None);
+    }
+}
@@ -0,0 +1,46 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The backups of a cluster. A backup is placed in a directory of the external storage:
+//!
+//! ```text
+//! <destination>/
+//!     MANIFEST                the encoded `BackupManifest`, written after all groups are done
+//!     groups/<group_id>/      the checkpoint files of each group
+//!     logs/<group_id>/        the log entries archived after the checkpoints, if enabled
+//! ```
+//!
+//! A backup is complete only if the `MANIFEST` exists. The archived log entries are replayed to
+//! restore the state at a point after the backup.
+//!
+//! A backup is NOT a consistent snapshot of the cluster: the checkpoints of groups are taken one
+//! by one without a common cut, so a restore without replaying the archived logs may observe a
+//! write in one group but miss an earlier write in another. See `BackupManifest`.
+
+mod archive;
+mod s3;
+mod storage;
+
+pub use self::{
+    archive::{log_file, replay_archived_logs},
+    storage::{open_storage, ExternalStorage, LocalStorage},
+};
+
+/// The name of the manifest object of a backup.
+pub const MANIFEST: &str = "MANIFEST";
+
+/// The name of the object of a group's checkpoint file.
+pub fn group_file(group_id: u64, file_name: &str) -> String {
+    format!("groups/{group_id}/{file_name}")
+}
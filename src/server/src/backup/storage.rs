@@ -0,0 +1,212 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use super::s3::S3Storage;
+use crate::{Error, Result};
+
+/// A destination the backups are written to. The objects are addressed by the names separated
+/// by `/`, relative to the root of the storage.
+#[crate::async_trait]
+pub trait ExternalStorage: Send + Sync {
+    /// Write the object, the existing one is overwritten.
+    async fn put(&self, name: &str, data: Vec<u8>) -> Result<()>;
+
+    /// Write the content of the local file as the object.
+    async fn put_file(&self, name: &str, path: &Path) -> Result<()>;
+
+    /// Read the object, an `std::io::ErrorKind::NotFound` error is returned if it doesn't exist.
+    async fn get(&self, name: &str) -> Result<Vec<u8>>;
+
+    /// Read the object to the local file.
+    async fn get_file(&self, name: &str, path: &Path) -> Result<()>;
+
+    /// The names of the objects starting with the prefix.
+    async fn list(&self, prefix: &str) -> Result<Vec<String>>;
+
+    async fn exists(&self, name: &str) -> Result<bool> {
+        match self.get(name).await {
+            Ok(_) => Ok(true),
+            Err(Error::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+}
+
+/// Open the storage of the URL. The supported schemes are:
+/// - `file:///path/to/dir`, or an absolute path, the local file system.
+/// - `s3://bucket/path/to/dir?endpoint=http://host:port&region=us-east-1`, the S3-compatible object
+///   store. The credentials are read from `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` of the
+///   environment, and the requests are not signed if they are not set.
+pub fn open_storage(url: &str) -> Result<Arc<dyn ExternalStorage>> {
+    if url.starts_with('/') {
+        return Ok(Arc::new(LocalStorage::new(url)));
+    }
+    let parsed = url::Url::parse(url)
+        .map_err(|e| Error::InvalidArgument(format!("storage url {url}: {e}")))?;
+    match parsed.scheme() {
+        "file" => Ok(Arc::new(LocalStorage::new(parsed.path()))),
+        "s3" => Ok(Arc::new(S3Storage::from_url(&parsed)?)),
+        scheme => Err(Error::InvalidArgument(format!(
+            "storage url {url}: unsupported scheme {scheme}"
+        ))),
+    }
+}
+
+/// The storage placed on the local file system, or a mounted network file system. The file
+/// operations are blocking, so they are executed by the blocking threads of the runtime.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        LocalStorage {
+            root: root.as_ref().to_owned(),
+        }
+    }
+
+    fn path_of(&self, name: &str) -> PathBuf {
+        self.root.join(name.trim_start_matches('/'))
+    }
+}
+
+/// Write the file atomically, so a partially written object is never observed.
+fn write_atomic(path: &Path, write: impl FnOnce(&Path) -> Result<()>) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let tmp = path.with_extension(format!("tmp.{}", uuid::Uuid::new_v4()));
+    write(&tmp)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Run the blocking file operations without blocking the async runtime.
+async fn blocking<F, T>(func: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(func)
+        .await
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?
+}
+
+fn list_files(root: &Path, prefix: &str) -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let mut dirs = vec![root.to_owned()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err.into()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let Ok(relative) = path.strip_prefix(root) else {
+                continue;
+            };
+            let name = relative.to_string_lossy().to_string();
+            if name.starts_with(prefix) && !name.contains(".tmp.") {
+                names.push(name);
+            }
+        }
+    }
+    names.sort_unstable();
+    Ok(names)
+}
+
+#[crate::async_trait]
+impl ExternalStorage for LocalStorage {
+    async fn put(&self, name: &str, data: Vec<u8>) -> Result<()> {
+        let path = self.path_of(name);
+        blocking(move || write_atomic(&path, |tmp| Ok(std::fs::write(tmp, &data)?))).await
+    }
+
+    async fn put_file(&self, name: &str, path: &Path) -> Result<()> {
+        let target = self.path_of(name);
+        let source = path.to_owned();
+        blocking(move || {
+            write_atomic(&target, |tmp| {
+                std::fs::copy(&source, tmp)?;
+                Ok(())
+            })
+        })
+        .await
+    }
+
+    async fn get(&self, name: &str) -> Result<Vec<u8>> {
+        let path = self.path_of(name);
+        blocking(move || Ok(std::fs::read(path)?)).await
+    }
+
+    async fn get_file(&self, name: &str, path: &Path) -> Result<()> {
+        let source = self.path_of(name);
+        let target = path.to_owned();
+        blocking(move || {
+            std::fs::copy(source, target)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<String>> {
+        let root = self.root.clone();
+        let prefix = prefix.to_owned();
+        blocking(move || list_files(&root, &prefix)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::runtime::ExecutorOwner;
+
+    #[test]
+    fn local_storage() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async {
+            let dir = TempDir::new("local_storage").unwrap();
+            let url = format!("file://{}", dir.path().display());
+            let storage = open_storage(&url).unwrap();
+
+            storage.put("MANIFEST", b"manifest".to_vec()).await.unwrap();
+            let file = dir.path().join("local.sst");
+            std::fs::write(&file, b"sst").unwrap();
+            storage.put_file("groups/1/0.sst", &file).await.unwrap();
+
+            assert_eq!(storage.get("MANIFEST").await.unwrap(), b"manifest");
+            assert!(!storage.exists("groups/2/0.sst").await.unwrap());
+            assert_eq!(
+                storage.list("groups/").await.unwrap(),
+                vec!["groups/1/0.sst".to_owned()]
+            );
+
+            let target = dir.path().join("target.sst");
+            storage.get_file("groups/1/0.sst", &target).await.unwrap();
+            assert_eq!(std::fs::read(&target).unwrap(), b"sst");
+        });
+    }
+}
@@ -33,7 +33,12 @@ use crate::{
 /// The main entrance of engula server.
 pub fn run(config: Config, executor: Executor, shutdown: Shutdown) -> Result<()> {
     executor.block_on(async {
-        let engines = Engines::open(&config.root_dir, &config.db)?;
+        if let Some(destination) = config.node.replica.log_archive.as_ref() {
+            // The log entries are retained until they are archived, so a destination the
+            // archivers couldn't open is rejected here instead of growing the logs forever.
+            crate::backup::open_storage(destination)?;
+        }
+        let engines = Engines::open(&config.root_dir, &config.db, &config.encryption)?;
 
         let root_list = if config.init {
             vec![config.addr.clone()]
@@ -112,6 +117,11 @@ async fn bootstrap_or_join_cluster(
             "both cluster and node are initialized, node id {}",
             node_ident.node_id
         );
+        if let Some(backup) = config.restore_from.as_ref() {
+            // The restore job is submitted along with the bootstrapping of root, it is skipped
+            // if the root is already bootstrapped.
+            warn!("the node is initialized, the restoring from {backup} might be skipped");
+        }
         node.reload_root_from_engine().await?;
         return Ok(node_ident);
     }
@@ -124,6 +134,7 @@ async fn bootstrap_or_join_cluster(
             &config.addr,
             config.join_list.clone(),
             config.cpu_nums,
+            config.locality.to_node_locality(),
             root_client,
         )
         .await?
@@ -135,6 +146,7 @@ async fn try_join_cluster(
     local_addr: &str,
     join_list: Vec<String>,
     cpu_nums: u32,
+    locality: NodeLocality,
     root_client: &RootClient,
 ) -> Result<NodeIdent> {
     info!("try join a bootstrapted cluster");
@@ -158,6 +170,7 @@ async fn try_join_cluster(
     let req = JoinNodeRequest {
         addr: local_addr.to_owned(),
         capacity: Some(capacity),
+        locality: Some(locality),
     };
 
     let mut backoff: u64 = 1;
@@ -224,6 +237,7 @@ async fn write_initial_cluster_data(node: &Node, addr: &str) -> Result<()> {
             node_id: FIRST_NODE_ID,
             role: ReplicaRole::Voter.into(),
         }],
+        ..Default::default()
     };
     node.create_replica(FIRST_REPLICA_ID, group).await?;
 
@@ -237,6 +251,7 @@ async fn write_initial_cluster_data(node: &Node, addr: &str) -> Result<()> {
             node_id: FIRST_NODE_ID,
             role: ReplicaRole::Voter.into(),
         }],
+        ..Default::default()
     };
     node.create_replica(INIT_USER_REPLICA_ID, init_group)
         .await?;
@@ -259,5 +274,10 @@ async fn write_initial_cluster_data(node: &Node, addr: &str) -> Result<()> {
 pub(crate) fn open_engine_with_default_config<P: AsRef<std::path::Path>>(
     path: P,
 ) -> Result<crate::engine::RawDb> {
-    crate::engine::open_engine(&crate::DbConfig::default(), path)
+    use std::sync::Arc;
+
+    use crate::encryption::DataKeyManager;
+
+    let key_manager = Arc::new(DataKeyManager::plaintext());
+    crate::engine::open_engine(&crate::DbConfig::default(), path, key_manager)
 }
@@ -0,0 +1,64 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Build the SST files to bulk load the records of a shard. The records are encoded with the key
+//! layout of the group engine, so the files could be ingested by the `IngestSst` request directly.
+
+use std::path::Path;
+
+use engula_api::{server::v1::ShardDesc, shard};
+
+use crate::{engine::encode_record, node::replica::FLAT_KEY_VERSION, Error, Result};
+
+/// Write the records of the shard to the SST file, the number of written records is returned.
+///
+/// The records are sorted before writing, and the last one wins if there exists duplicated keys.
+pub fn write_sst_file<P: AsRef<Path>>(
+    path: P,
+    shard: &ShardDesc,
+    mut records: Vec<(Vec<u8>, Vec<u8>)>,
+) -> Result<usize> {
+    use rocksdb::{Options, SstFileWriter};
+
+    if records.is_empty() {
+        return Err(Error::InvalidArgument("no records to write".into()));
+    }
+    if let Some((key, _)) = records
+        .iter()
+        .find(|(key, _)| key.is_empty() || !shard::belong_to(shard, key))
+    {
+        return Err(Error::InvalidArgument(format!(
+            "key {key:?} doesn't belong to shard {}",
+            shard.id
+        )));
+    }
+
+    // The sort is stable, so the last one of the duplicated keys is placed first after reversing.
+    records.reverse();
+    records.sort_by(|a, b| a.0.cmp(&b.0));
+    records.dedup_by(|a, b| a.0 == b.0);
+
+    let collection_id = shard.collection_id;
+    let slot = shard::slot(shard);
+    let opts = Options::default();
+    let mut writer = SstFileWriter::create(&opts);
+    writer.open(path.as_ref())?;
+    for (key, value) in &records {
+        // The encoding is memcomparable, so the encoded keys are still sorted.
+        let (key, value) = encode_record(collection_id, slot, key, value, FLAT_KEY_VERSION);
+        writer.put(key, value)?;
+    }
+    writer.finish()?;
+    Ok(records.len())
+}
@@ -12,8 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{path::PathBuf, time::Duration};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
+use engula_api::server::v1::NodeLocality;
 use rocksdb::DBCompressionType;
 use serde::{Deserialize, Serialize};
 
@@ -34,6 +35,22 @@ pub struct Config {
 
     pub join_list: Vec<String>,
 
+    /// The URL of a backup to restore from, it only takes effect when the cluster is
+    /// bootstrapped. The databases and collections of the backup are restored by a background
+    /// job once the cluster is bootstrapped.
+    #[serde(default)]
+    pub restore_from: Option<String>,
+
+    /// Replay the archived log entries of the backup up to the unix timestamp in seconds, when
+    /// restoring from a backup.
+    #[serde(default)]
+    pub restore_time: Option<u64>,
+
+    /// The locality labels of this node, the replicas of a group are spread across different
+    /// localities as much as possible.
+    #[serde(default)]
+    pub locality: LocalityConfig,
+
     #[serde(default)]
     pub node: NodeConfig,
 
@@ -48,6 +65,29 @@ pub struct Config {
 
     #[serde(default)]
     pub db: DbConfig,
+
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+}
+
+#[derive(Default, Clone, Debug, Deserialize, Serialize)]
+pub struct LocalityConfig {
+    #[serde(default)]
+    pub region: String,
+
+    #[serde(default)]
+    pub zone: String,
+
+    #[serde(default)]
+    pub rack: String,
+
+    #[serde(default)]
+    pub host: String,
+
+    /// The custom labels of this node, eg. `disk = "ssd"`, which could be matched by the
+    /// placement constraints of collections.
+    #[serde(default)]
+    pub labels: HashMap<String, String>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -83,6 +123,41 @@ pub struct ReplicaConfig {
     /// Default: 64MB.
     pub snap_file_size: u64,
 
+    /// Stream the snapshot to followers by iterating a consistent view of the group, instead of
+    /// staging the snapshot files on local disk before sending.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub snap_streaming: bool,
+
+    /// The URL of the backup destination which the applied log entries are archived to, it
+    /// makes the point-in-time recovery possible. The entries are retained until they are
+    /// archived.
+    ///
+    /// Default: disabled
+    #[serde(default)]
+    pub log_archive: Option<String>,
+
+    /// The intervals of archiving log entries, in seconds.
+    ///
+    /// Default: 60
+    #[serde(default = "default_log_archive_interval_sec")]
+    pub log_archive_interval_sec: u64,
+
+    /// The intervals of scrubbing, in seconds. The leader asks all replicas to compute the
+    /// checksums of the group at the same applied index, and compares them to detect the silent
+    /// data corruption. 0 means the scrubbing is disabled.
+    ///
+    /// Default: 86400
+    #[serde(default = "default_scrub_interval_sec")]
+    pub scrub_interval_sec: u64,
+
+    /// Replace the replicas whose checksums are different from the majority.
+    ///
+    /// Default: false
+    #[serde(default)]
+    pub scrub_replace_corrupted: bool,
+
     #[serde(skip)]
     pub testing_knobs: ReplicaTestingKnobs,
 }
@@ -187,6 +262,38 @@ pub struct RaftConfig {
     /// Default: false
     pub enable_log_recycle: bool,
 
+    /// Limit the number of snapshots sent concurrently by a node.
+    ///
+    /// Default: 4
+    #[serde(default = "default_snap_max_concurrent")]
+    pub snap_max_concurrent_sends: usize,
+
+    /// Limit the number of snapshots received concurrently by a node.
+    ///
+    /// Default: 4
+    #[serde(default = "default_snap_max_concurrent")]
+    pub snap_max_concurrent_recvs: usize,
+
+    /// Limit the number of streaming snapshots opened by a node. Each of them pins an iterator
+    /// and a thread until it is recycled, so the snapshot files are staged instead once the limit
+    /// is reached.
+    ///
+    /// Default: 4
+    #[serde(default = "default_snap_max_concurrent")]
+    pub snap_max_concurrent_streams: usize,
+
+    /// Limit the bytes of snapshots sent per second by a node. 0 means no limit.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub snap_send_bytes_per_sec: u64,
+
+    /// Limit the bytes of snapshots received per second by a node. 0 means no limit.
+    ///
+    /// Default: 0
+    #[serde(default)]
+    pub snap_recv_bytes_per_sec: u64,
+
     #[serde(skip)]
     pub testing_knobs: RaftTestingKnobs,
 }
@@ -202,6 +309,103 @@ pub struct RootConfig {
     pub heartbeat_timeout_sec: u64,
     pub schedule_interval_sec: u64,
     pub max_create_group_retry_before_rollback: u64,
+
+    /// Balance the disk usage of nodes by moving replicas out of nearly-full disks.
+    ///
+    /// Default: true
+    #[serde(default = "default_enable_disk_balance")]
+    pub enable_disk_balance: bool,
+
+    /// No replicas will be placed on a node whose disk usage ratio exceeds the high watermark, and
+    /// the replicas of it will be moved out.
+    ///
+    /// Default: 0.85
+    #[serde(default = "default_disk_high_watermark")]
+    pub disk_high_watermark: f64,
+
+    /// The replicas moved out of the nearly-full disks are only placed on the nodes whose disk
+    /// usage ratio is below the low watermark after moving.
+    ///
+    /// Default: 0.7
+    #[serde(default = "default_disk_low_watermark")]
+    pub disk_low_watermark: f64,
+
+    /// Balance the read and write load of nodes by moving leaders and replicas.
+    ///
+    /// Default: true
+    #[serde(default = "default_enable_load_balance")]
+    pub enable_load_balance: bool,
+
+    /// A node is considered overloaded only if its load exceeds the mean by this fraction, and
+    /// the load moved to another node must not make it exceed the mean by half of this fraction.
+    ///
+    /// Default: 0.2
+    #[serde(default = "default_load_balance_threshold")]
+    pub load_balance_threshold: f64,
+
+    /// The number of recent metadata changes kept by root, the watchers reconnecting within them
+    /// could resume from their revisions instead of receiving the full state again.
+    ///
+    /// Default: 4096
+    #[serde(default = "default_watch_log_capacity")]
+    pub watch_log_capacity: usize,
+}
+
+/// The encryption at rest of the raft logs under `log/`, the snapshot files under `snap/` and the
+/// values of the user data under `db/`. They are encrypted by the data keys, which are encrypted
+/// by the master key and stored along with the data. The keys, including the index keys which
+/// consist of the indexed values, and the local states of rocksdb are kept in plaintext, since the
+/// rocksdb binding doesn't expose the encrypted env.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EncryptionConfig {
+    /// The method to encrypt the new files and values. The existing ones are still readable after
+    /// the method is changed, as long as the data keys are accessible.
+    ///
+    /// Default: plaintext
+    #[serde(default)]
+    pub method: EncryptionMethod,
+
+    /// The intervals of rotating the data key, in seconds. The new files are encrypted by the new
+    /// data key, and the existing files are still decrypted by the old ones.
+    ///
+    /// Default: 604800
+    #[serde(default = "default_data_key_rotation_period_sec")]
+    pub data_key_rotation_period_sec: u64,
+
+    /// The master key to encrypt the data keys.
+    ///
+    /// Default: plaintext
+    #[serde(default)]
+    pub master_key: MasterKeyConfig,
+
+    /// The master key before rotating to `master_key`. The data keys are re-encrypted by
+    /// `master_key` once the node starts, after that it could be removed from the config.
+    ///
+    /// Default: none
+    #[serde(default)]
+    pub previous_master_key: Option<MasterKeyConfig>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum EncryptionMethod {
+    #[default]
+    #[serde(rename = "plaintext")]
+    Plaintext,
+    #[serde(rename = "aes256-ctr")]
+    Aes256Ctr,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MasterKeyConfig {
+    /// The data keys are stored in plaintext, it is only allowed if the encryption is disabled.
+    #[default]
+    Plaintext,
+    /// A hex-encoded 256 bits key stored in a local file.
+    File { path: PathBuf },
+    /// A stand-in of the key management service, which keeps the master keys under `key_dir`
+    /// and creates them on first use. The node only knows the key id, the same as a real KMS.
+    Kms { key_id: String, key_dir: PathBuf },
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -211,6 +415,18 @@ pub struct ExecutorConfig {
     pub max_blocking_threads: Option<usize>,
 }
 
+impl LocalityConfig {
+    pub(crate) fn to_node_locality(&self) -> NodeLocality {
+        NodeLocality {
+            region: self.region.clone(),
+            zone: self.zone.clone(),
+            rack: self.rack.clone(),
+            host: self.host.clone(),
+            labels: self.labels.clone(),
+        }
+    }
+}
+
 impl Default for NodeConfig {
     fn default() -> Self {
         NodeConfig {
@@ -226,6 +442,11 @@ impl Default for ReplicaConfig {
     fn default() -> Self {
         ReplicaConfig {
             snap_file_size: 64 * 1024 * 1024 * 1024,
+            snap_streaming: false,
+            log_archive: None,
+            log_archive_interval_sec: default_log_archive_interval_sec(),
+            scrub_interval_sec: default_scrub_interval_sec(),
+            scrub_replace_corrupted: false,
             testing_knobs: ReplicaTestingKnobs::default(),
         }
     }
@@ -369,11 +590,27 @@ impl Default for RaftConfig {
             max_inflight_msgs: 10 * 1000,
             engine_slow_io_threshold_ms: None,
             enable_log_recycle: false,
+            snap_max_concurrent_sends: default_snap_max_concurrent(),
+            snap_max_concurrent_recvs: default_snap_max_concurrent(),
+            snap_max_concurrent_streams: default_snap_max_concurrent(),
+            snap_send_bytes_per_sec: 0,
+            snap_recv_bytes_per_sec: 0,
             testing_knobs: RaftTestingKnobs::default(),
         }
     }
 }
 
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        EncryptionConfig {
+            method: EncryptionMethod::default(),
+            data_key_rotation_period_sec: default_data_key_rotation_period_sec(),
+            master_key: MasterKeyConfig::default(),
+            previous_master_key: None,
+        }
+    }
+}
+
 impl RootConfig {
     pub fn heartbeat_interval(&self) -> Duration {
         Duration::from_secs(self.liveness_threshold_sec - self.heartbeat_timeout_sec)
@@ -392,10 +629,56 @@ impl Default for RootConfig {
             heartbeat_timeout_sec: 4,
             schedule_interval_sec: 3,
             max_create_group_retry_before_rollback: 10,
+            enable_disk_balance: default_enable_disk_balance(),
+            disk_high_watermark: default_disk_high_watermark(),
+            disk_low_watermark: default_disk_low_watermark(),
+            enable_load_balance: default_enable_load_balance(),
+            load_balance_threshold: default_load_balance_threshold(),
+            watch_log_capacity: default_watch_log_capacity(),
         }
     }
 }
 
+fn default_enable_disk_balance() -> bool {
+    true
+}
+
+fn default_disk_high_watermark() -> f64 {
+    0.85
+}
+
+fn default_disk_low_watermark() -> f64 {
+    0.7
+}
+
+fn default_enable_load_balance() -> bool {
+    true
+}
+
+fn default_load_balance_threshold() -> f64 {
+    0.2
+}
+
+fn default_watch_log_capacity() -> usize {
+    4096
+}
+
+fn default_log_archive_interval_sec() -> u64 {
+    60
+}
+
+fn default_scrub_interval_sec() -> u64 {
+    86400
+}
+
+fn default_snap_max_concurrent() -> usize {
+    4
+}
+
+fn default_data_key_rotation_period_sec() -> u64 {
+    7 * 24 * 3600
+}
+
 fn adaptive_block_cache_size() -> usize {
     if cfg!(test) {
         return 32 << 20;
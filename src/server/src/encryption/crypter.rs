@@ -0,0 +1,162 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use aes::Aes256;
+use ctr::{
+    cipher::{InnerIvInit, KeyInit, KeyIvInit, StreamCipher, StreamCipherSeek},
+    Ctr128BE,
+};
+use rand::RngCore;
+
+use crate::{Error, Result};
+
+pub(crate) const KEY_LEN: usize = 32;
+pub(crate) const IV_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+type Aes256Ctr = Ctr128BE<Aes256>;
+
+/// Encrypt or decrypt the content of a file with AES-256-CTR. The keystream could be seeked to
+/// any offset, so the file is read and written at random positions without padding.
+#[derive(Clone)]
+pub(crate) struct FileCrypter {
+    key: [u8; KEY_LEN],
+    iv: [u8; IV_LEN],
+}
+
+impl FileCrypter {
+    pub fn new(key: [u8; KEY_LEN], iv: [u8; IV_LEN]) -> Self {
+        FileCrypter { key, iv }
+    }
+
+    /// Apply the keystream started from `offset` of the file to `buf`, it both encrypts and
+    /// decrypts.
+    pub fn apply_keystream(&self, offset: u64, buf: &mut [u8]) {
+        let mut cipher =
+            Aes256Ctr::new_from_slices(&self.key, &self.iv).expect("the length of key and iv");
+        cipher.seek(offset);
+        cipher.apply_keystream(buf);
+    }
+}
+
+/// Encrypt or decrypt values with AES-256-CTR, each value has its own iv. The key schedule is
+/// expanded once, so it is cheap to apply to many small values.
+pub(crate) struct ValueCrypter {
+    cipher: Aes256,
+}
+
+impl ValueCrypter {
+    pub fn new(key: &[u8; KEY_LEN]) -> Self {
+        ValueCrypter {
+            cipher: Aes256::new(key.into()),
+        }
+    }
+
+    /// Apply the keystream of `iv` to `buf`, it both encrypts and decrypts.
+    pub fn apply_keystream(&self, iv: &[u8; IV_LEN], buf: &mut [u8]) {
+        let mut cipher = Aes256Ctr::inner_iv_init(self.cipher.clone(), iv.into());
+        cipher.apply_keystream(buf);
+    }
+}
+
+/// Encrypt `plaintext` with AES-256-GCM, the random nonce is prepended to the ciphertext.
+pub(crate) fn seal(key: &[u8], plaintext: &[u8]) -> Result<Vec<u8>> {
+    use aes_gcm::{
+        aead::{Aead, KeyInit},
+        Aes256Gcm, Nonce,
+    };
+
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|_| Error::InvalidArgument(format!("the length of key should be {KEY_LEN}")))?;
+    let nonce = random_bytes::<NONCE_LEN>();
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| Error::InvalidData("encrypt with master key".to_owned()))?;
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+/// Decrypt the content sealed by `seal`. It fails if the key is wrong or the content is tampered.
+pub(crate) fn unseal(key: &[u8], sealed: &[u8]) -> Result<Vec<u8>> {
+    use aes_gcm::{
+        aead::{Aead, KeyInit},
+        Aes256Gcm, Nonce,
+    };
+
+    let cipher = Aes256Gcm::new_from_slice(key)
+        .map_err(|_| Error::InvalidArgument(format!("the length of key should be {KEY_LEN}")))?;
+    if sealed.len() < NONCE_LEN {
+        return Err(Error::InvalidData("sealed content is truncated".to_owned()));
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| {
+            Error::InvalidData("sealed content, the master key is wrong or tampered".to_owned())
+        })
+}
+
+pub(crate) fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0u8; N];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keystream_at_any_offset() {
+        let crypter = FileCrypter::new(random_bytes(), random_bytes());
+        let plaintext = (0..1000u32).map(|i| i as u8).collect::<Vec<_>>();
+        let mut ciphertext = plaintext.clone();
+        crypter.apply_keystream(0, &mut ciphertext);
+        assert_ne!(ciphertext, plaintext);
+
+        // Decrypt in pieces which are not aligned to the block size.
+        let mut decrypted = ciphertext.clone();
+        for (offset, chunk) in [(0, 7), (7, 100), (107, 893)] {
+            crypter.apply_keystream(offset as u64, &mut decrypted[offset..offset + chunk]);
+        }
+        assert_eq!(decrypted, plaintext);
+    }
+
+    #[test]
+    fn value_crypter_matches_file_crypter() {
+        let key = random_bytes::<KEY_LEN>();
+        let iv = random_bytes::<IV_LEN>();
+        let plaintext = (0..100u32).map(|i| i as u8).collect::<Vec<_>>();
+        let mut expected = plaintext.clone();
+        FileCrypter::new(key, iv).apply_keystream(0, &mut expected);
+
+        let crypter = ValueCrypter::new(&key);
+        let mut ciphertext = plaintext.clone();
+        crypter.apply_keystream(&iv, &mut ciphertext);
+        assert_eq!(ciphertext, expected);
+        crypter.apply_keystream(&iv, &mut ciphertext);
+        assert_eq!(ciphertext, plaintext);
+    }
+
+    #[test]
+    fn seal_and_unseal() {
+        let key = random_bytes::<KEY_LEN>();
+        let sealed = seal(&key, b"data key").unwrap();
+        assert_eq!(unseal(&key, &sealed).unwrap(), b"data key");
+
+        let other = random_bytes::<KEY_LEN>();
+        assert!(unseal(&other, &sealed).is_err());
+    }
+}
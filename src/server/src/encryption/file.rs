@@ -0,0 +1,294 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Read, Seek, SeekFrom, Write},
+    os::unix::fs::FileExt,
+    path::Path,
+    sync::Arc,
+};
+
+use super::crypter::{FileCrypter, IV_LEN, KEY_LEN};
+
+/// The magic number of the encrypted files. The files without it are treated as plaintext, so the
+/// files written before the encryption is enabled are still readable.
+const MAGIC: &[u8; 8] = b"ENGULAEF";
+
+/// The header of an encrypted file: the magic number, the id of the data key and the iv.
+pub(crate) const HEADER_LEN: u64 = 32;
+
+/// A file whose content is encrypted transparently. The offsets are relative to the end of the
+/// header, so it has the same layout as the plaintext file for the readers and writers.
+pub struct EncryptedFile {
+    file: File,
+    crypter: Option<FileCrypter>,
+    header_len: u64,
+}
+
+pub struct FileReader {
+    file: Arc<EncryptedFile>,
+    offset: u64,
+}
+
+pub struct FileWriter {
+    file: Arc<EncryptedFile>,
+    offset: u64,
+}
+
+impl EncryptedFile {
+    /// Create a file, or truncate it if it already exists. The content is encrypted by the data
+    /// key `key_id` if it is specified.
+    pub(crate) fn create(path: &Path, key: Option<(u64, [u8; KEY_LEN])>) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        let Some((key_id, key)) = key else {
+            return Ok(EncryptedFile {
+                file,
+                crypter: None,
+                header_len: 0,
+            });
+        };
+
+        let iv = super::crypter::random_bytes::<IV_LEN>();
+        let mut header = Vec::with_capacity(HEADER_LEN as usize);
+        header.extend_from_slice(MAGIC);
+        header.extend_from_slice(&key_id.to_le_bytes());
+        header.extend_from_slice(&iv);
+        file.write_all_at(&header, 0)?;
+        file.sync_all()?;
+        Ok(EncryptedFile {
+            file,
+            crypter: Some(FileCrypter::new(key, iv)),
+            header_len: HEADER_LEN,
+        })
+    }
+
+    /// Open an existing file, the data key of it is looked up by `find_key`.
+    pub(crate) fn open<F>(path: &Path, find_key: F) -> io::Result<Self>
+    where
+        F: FnOnce(u64) -> io::Result<[u8; KEY_LEN]>,
+    {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut header = [0u8; HEADER_LEN as usize];
+        let mut num_read = 0;
+        while num_read < header.len() {
+            let n = file.read_at(&mut header[num_read..], num_read as u64)?;
+            if n == 0 {
+                break;
+            }
+            num_read += n;
+        }
+        if num_read < header.len() || header[..MAGIC.len()] != MAGIC[..] {
+            return Ok(EncryptedFile {
+                file,
+                crypter: None,
+                header_len: 0,
+            });
+        }
+
+        let key_id = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let iv: [u8; IV_LEN] = header[16..].try_into().unwrap();
+        let key = find_key(key_id)?;
+        Ok(EncryptedFile {
+            file,
+            crypter: Some(FileCrypter::new(key, iv)),
+            header_len: HEADER_LEN,
+        })
+    }
+
+    #[inline]
+    pub fn is_encrypted(&self) -> bool {
+        self.crypter.is_some()
+    }
+
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+        let n = self.file.read_at(buf, self.header_len + offset)?;
+        if let Some(crypter) = &self.crypter {
+            crypter.apply_keystream(offset, &mut buf[..n]);
+        }
+        Ok(n)
+    }
+
+    pub fn write_all_at(&self, buf: &[u8], offset: u64) -> io::Result<()> {
+        match &self.crypter {
+            Some(crypter) => {
+                let mut buf = buf.to_owned();
+                crypter.apply_keystream(offset, &mut buf);
+                self.file.write_all_at(&buf, self.header_len + offset)
+            }
+            None => self.file.write_all_at(buf, offset),
+        }
+    }
+
+    /// Returns the size of the content, excluding the header.
+    pub fn size(&self) -> io::Result<u64> {
+        let len = self.file.metadata()?.len();
+        Ok(len.saturating_sub(self.header_len))
+    }
+
+    pub fn set_len(&self, len: u64) -> io::Result<()> {
+        self.file.set_len(self.header_len + len)
+    }
+
+    /// Preallocate the space of `[offset, offset + len)` without changing the size of file.
+    pub fn allocate(&self, offset: u64, len: u64) -> io::Result<()> {
+        use std::os::unix::io::AsRawFd;
+
+        let offset = self.header_len + offset;
+        let ret = unsafe {
+            libc::fallocate(
+                self.file.as_raw_fd(),
+                libc::FALLOC_FL_KEEP_SIZE,
+                offset as libc::off_t,
+                len as libc::off_t,
+            )
+        };
+        if ret != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub fn sync(&self) -> io::Result<()> {
+        self.file.sync_all()
+    }
+}
+
+impl FileReader {
+    pub fn new(file: Arc<EncryptedFile>) -> Self {
+        FileReader { file, offset: 0 }
+    }
+}
+
+impl Read for FileReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.file.read_at(buf, self.offset)?;
+        self.offset += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for FileReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.offset = seek_offset(&self.file, self.offset, pos)?;
+        Ok(self.offset)
+    }
+}
+
+impl FileWriter {
+    pub fn new(file: Arc<EncryptedFile>) -> Self {
+        FileWriter { file, offset: 0 }
+    }
+
+    #[inline]
+    pub fn file(&self) -> &EncryptedFile {
+        &self.file
+    }
+}
+
+impl Write for FileWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write_all_at(buf, self.offset)?;
+        self.offset += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Seek for FileWriter {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.offset = seek_offset(&self.file, self.offset, pos)?;
+        Ok(self.offset)
+    }
+}
+
+fn seek_offset(file: &EncryptedFile, current: u64, pos: SeekFrom) -> io::Result<u64> {
+    let (base, delta) = match pos {
+        SeekFrom::Start(offset) => return Ok(offset),
+        SeekFrom::End(delta) => (file.size()?, delta),
+        SeekFrom::Current(delta) => (current, delta),
+    };
+    let offset = if delta >= 0 {
+        base.checked_add(delta as u64)
+    } else {
+        base.checked_sub(delta.unsigned_abs())
+    };
+    offset.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "invalid seek to a negative or overflowing position",
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::encryption::crypter::random_bytes;
+
+    #[test]
+    fn read_and_write_encrypted_file() {
+        let dir = TempDir::new("encrypted-file").unwrap();
+        let path = dir.path().join("file");
+        let key = random_bytes::<KEY_LEN>();
+
+        let file = Arc::new(EncryptedFile::create(&path, Some((1, key))).unwrap());
+        let mut writer = FileWriter::new(file.clone());
+        writer.write_all(b"hello, ").unwrap();
+        writer.write_all(b"world").unwrap();
+        assert_eq!(file.size().unwrap(), 12);
+
+        // The content on disk is encrypted.
+        let raw = std::fs::read(&path).unwrap();
+        assert_eq!(raw.len() as u64, HEADER_LEN + 12);
+        assert!(!raw.windows(5).any(|w| w == b"world"));
+
+        let file = EncryptedFile::open(&path, |key_id| {
+            assert_eq!(key_id, 1);
+            Ok(key)
+        })
+        .unwrap();
+        assert!(file.is_encrypted());
+        let mut reader = FileReader::new(Arc::new(file));
+        reader.seek(SeekFrom::Start(7)).unwrap();
+        let mut content = String::new();
+        reader.read_to_string(&mut content).unwrap();
+        assert_eq!(content, "world");
+    }
+
+    #[test]
+    fn read_plaintext_file() {
+        let dir = TempDir::new("plaintext-file").unwrap();
+        let path = dir.path().join("file");
+        std::fs::write(&path, b"plaintext").unwrap();
+
+        let file = EncryptedFile::open(&path, |_| unreachable!()).unwrap();
+        assert!(!file.is_encrypted());
+        let mut content = String::new();
+        FileReader::new(Arc::new(file))
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, "plaintext");
+    }
+}
@@ -0,0 +1,160 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{io::Result, path::Path, sync::Arc};
+
+use raft_engine::env::{FileSystem, Handle, WriteExt};
+
+use super::{DataKeyManager, EncryptedFile, FileReader, FileWriter};
+
+/// The file system of raft engine, the log files are encrypted by the data keys.
+#[derive(Clone)]
+pub struct EncryptedFileSystem {
+    key_manager: Arc<DataKeyManager>,
+}
+
+impl EncryptedFileSystem {
+    pub fn new(key_manager: Arc<DataKeyManager>) -> Self {
+        EncryptedFileSystem { key_manager }
+    }
+}
+
+impl Default for EncryptedFileSystem {
+    fn default() -> Self {
+        EncryptedFileSystem::new(Arc::new(DataKeyManager::plaintext()))
+    }
+}
+
+impl FileSystem for EncryptedFileSystem {
+    type Handle = EncryptedFile;
+    type Reader = FileReader;
+    type Writer = FileWriter;
+
+    fn create<P: AsRef<Path>>(&self, path: P) -> Result<Self::Handle> {
+        self.key_manager.create_file(path.as_ref())
+    }
+
+    fn open<P: AsRef<Path>>(&self, path: P) -> Result<Self::Handle> {
+        self.key_manager.open_file(path.as_ref())
+    }
+
+    fn delete<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn rename<P: AsRef<Path>>(&self, src_path: P, dst_path: P) -> Result<()> {
+        std::fs::rename(src_path, dst_path)
+    }
+
+    fn new_reader(&self, handle: Arc<Self::Handle>) -> Result<Self::Reader> {
+        Ok(FileReader::new(handle))
+    }
+
+    fn new_writer(&self, handle: Arc<Self::Handle>) -> Result<Self::Writer> {
+        Ok(FileWriter::new(handle))
+    }
+}
+
+impl Handle for EncryptedFile {
+    fn truncate(&self, offset: usize) -> Result<()> {
+        self.set_len(offset as u64)
+    }
+
+    fn file_size(&self) -> Result<usize> {
+        Ok(self.size()? as usize)
+    }
+
+    fn sync(&self) -> Result<()> {
+        EncryptedFile::sync(self)
+    }
+}
+
+impl WriteExt for FileWriter {
+    fn truncate(&mut self, offset: usize) -> Result<()> {
+        self.file().set_len(offset as u64)
+    }
+
+    fn allocate(&mut self, offset: usize, size: usize) -> Result<()> {
+        self.file().allocate(offset as u64, size as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use raft_engine::{Config, Engine, LogBatch, MessageExt};
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::{
+        encryption::crypter::random_bytes, EncryptionConfig, EncryptionMethod, MasterKeyConfig,
+    };
+
+    #[derive(Clone)]
+    struct MessageExtTyped;
+
+    impl MessageExt for MessageExtTyped {
+        type Entry = raft::eraftpb::Entry;
+
+        fn index(e: &Self::Entry) -> u64 {
+            e.index
+        }
+    }
+
+    #[test]
+    fn raft_engine_with_encrypted_file_system() {
+        let dir = TempDir::new("encrypted-raft-engine").unwrap();
+        let master_key = dir.path().join("master.key");
+        std::fs::write(&master_key, hex::encode(random_bytes::<32>())).unwrap();
+        let cfg = EncryptionConfig {
+            method: EncryptionMethod::Aes256Ctr,
+            master_key: MasterKeyConfig::File { path: master_key },
+            ..Default::default()
+        };
+        let key_manager = Arc::new(DataKeyManager::open(&dir.path().join("keys"), &cfg).unwrap());
+        let engine_dir = dir.path().join("engine");
+        let engine_cfg = Config {
+            dir: engine_dir.to_str().unwrap().to_owned(),
+            ..Default::default()
+        };
+
+        let entries = (1..=10u64)
+            .map(|index| raft::eraftpb::Entry {
+                index,
+                term: 1,
+                data: b"secret entry".to_vec(),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        {
+            let file_system = Arc::new(EncryptedFileSystem::new(key_manager.clone()));
+            let engine = Engine::open_with_file_system(engine_cfg.clone(), file_system).unwrap();
+            let mut batch = LogBatch::default();
+            batch.add_entries::<MessageExtTyped>(1, &entries).unwrap();
+            engine.write(&mut batch, true).unwrap();
+        }
+
+        for entry in std::fs::read_dir(&engine_dir).unwrap() {
+            let content = std::fs::read(entry.unwrap().path()).unwrap();
+            assert!(!content.windows(12).any(|w| w == b"secret entry"));
+        }
+
+        let file_system = Arc::new(EncryptedFileSystem::new(key_manager));
+        let engine = Engine::open_with_file_system(engine_cfg, file_system).unwrap();
+        let mut recovered = vec![];
+        engine
+            .fetch_entries_to::<MessageExtTyped>(1, 1, 11, None, &mut recovered)
+            .unwrap();
+        assert_eq!(recovered, entries);
+    }
+}
@@ -0,0 +1,490 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use super::{
+    crypter::{random_bytes, ValueCrypter, IV_LEN, KEY_LEN},
+    file::EncryptedFile,
+    master_key::{open_master_key, MasterKey},
+};
+use crate::{EncryptionConfig, EncryptionMethod, Error, MasterKeyConfig, Result};
+
+/// The file which stores the data keys encrypted by the master key.
+const KEY_DICT_FILE: &str = "KEYS";
+const KEY_DICT_TEMP: &str = "KEYS.tmp";
+
+/// The header of an encrypted value: the id of the data key and the iv.
+const VALUE_HEADER_LEN: usize = core::mem::size_of::<u64>() + IV_LEN;
+
+/// The data keys of a node. The new files are encrypted by the current data key, which is rotated
+/// periodically. The old data keys are kept to decrypt the existing files.
+pub struct DataKeyManager {
+    method: EncryptionMethod,
+    rotation_period: Duration,
+    /// The dir of the key dictionary, nothing is persisted if it is `None`.
+    dir: Option<PathBuf>,
+    master_key: Box<dyn MasterKey>,
+    master_key_cfg: MasterKeyConfig,
+    dict: Mutex<KeyDictionary>,
+    /// The crypters of the values, which are built once for each data key.
+    value_crypters: RwLock<ValueCrypters>,
+}
+
+#[derive(Default)]
+struct ValueCrypters {
+    /// The crypter of the current data key, it is reset once the data key is rotated.
+    current: Option<(u64, Arc<ValueCrypter>)>,
+    crypters: HashMap<u64, Arc<ValueCrypter>>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct KeyDictionary {
+    /// The id of the data key to encrypt the new files, 0 means no data key is created.
+    current_key_id: u64,
+    keys: BTreeMap<u64, DataKey>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct DataKey {
+    key: Vec<u8>,
+    method: EncryptionMethod,
+    /// The unix timestamp in seconds when the key is created.
+    created_at: u64,
+}
+
+/// The metadata of the keys, the key materials are never exposed.
+#[derive(Debug, Serialize)]
+pub struct EncryptionMetadata {
+    pub method: EncryptionMethod,
+    pub master_key: MasterKeyConfig,
+    pub data_key_rotation_period_sec: u64,
+    pub current_key_id: Option<u64>,
+    pub data_keys: Vec<DataKeyMetadata>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DataKeyMetadata {
+    pub key_id: u64,
+    pub method: EncryptionMethod,
+    pub created_at: u64,
+}
+
+impl DataKeyManager {
+    /// Load the data keys from `dir`, and rotate the current data key if it is expired. The data
+    /// keys encrypted by the previous master key are re-encrypted by the current one.
+    pub(crate) fn open(dir: &Path, cfg: &EncryptionConfig) -> Result<Self> {
+        if cfg.method != EncryptionMethod::Plaintext && cfg.master_key == MasterKeyConfig::Plaintext
+        {
+            return Err(Error::InvalidArgument(
+                "the master key is required to encrypt data keys".to_owned(),
+            ));
+        }
+
+        std::fs::create_dir_all(dir)?;
+        let master_key = open_master_key(&cfg.master_key)?;
+        let (dict, reencrypt) = match load_dictionary(dir, &*master_key) {
+            Ok(dict) => (dict, false),
+            Err(err) => match &cfg.previous_master_key {
+                Some(previous) => {
+                    warn!("load data keys with master key: {err}, try the previous master key");
+                    let previous = open_master_key(previous)?;
+                    (load_dictionary(dir, &*previous)?, true)
+                }
+                None => return Err(err),
+            },
+        };
+
+        let manager = DataKeyManager {
+            method: cfg.method,
+            rotation_period: Duration::from_secs(cfg.data_key_rotation_period_sec),
+            dir: Some(dir.to_owned()),
+            master_key,
+            master_key_cfg: cfg.master_key.clone(),
+            dict: Mutex::new(dict),
+            value_crypters: RwLock::default(),
+        };
+        if reencrypt {
+            let dict = manager.dict.lock().unwrap();
+            manager.save_dictionary(&dict)?;
+            info!("data keys are re-encrypted by the new master key");
+        }
+        manager.maybe_rotate()?;
+        Ok(manager)
+    }
+
+    /// A manager which encrypts nothing, it couldn't open the encrypted files.
+    pub fn plaintext() -> Self {
+        DataKeyManager {
+            method: EncryptionMethod::Plaintext,
+            rotation_period: Duration::ZERO,
+            dir: None,
+            master_key: open_master_key(&MasterKeyConfig::Plaintext).unwrap(),
+            master_key_cfg: MasterKeyConfig::Plaintext,
+            dict: Mutex::default(),
+            value_crypters: RwLock::default(),
+        }
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.method != EncryptionMethod::Plaintext
+    }
+
+    /// Rotate the current data key if it is older than the rotation period, or the encryption
+    /// method is changed. Returns whether the data key is rotated.
+    pub fn maybe_rotate(&self) -> Result<bool> {
+        if !self.is_enabled() {
+            return Ok(false);
+        }
+        let mut dict = self.dict.lock().unwrap();
+        if let Some(current) = dict.keys.get(&dict.current_key_id) {
+            let age = unix_secs().saturating_sub(current.created_at);
+            if current.method == self.method && age < self.rotation_period.as_secs() {
+                return Ok(false);
+            }
+        }
+        self.rotate_locked(&mut dict)?;
+        Ok(true)
+    }
+
+    /// Rotate the current data key immediately, and returns the id of the new data key.
+    pub fn rotate(&self) -> Result<u64> {
+        if !self.is_enabled() {
+            return Err(Error::InvalidArgument("encryption is disabled".to_owned()));
+        }
+        let mut dict = self.dict.lock().unwrap();
+        self.rotate_locked(&mut dict)
+    }
+
+    pub fn metadata(&self) -> EncryptionMetadata {
+        let dict = self.dict.lock().unwrap();
+        EncryptionMetadata {
+            method: self.method,
+            master_key: self.master_key_cfg.clone(),
+            data_key_rotation_period_sec: self.rotation_period.as_secs(),
+            current_key_id: dict
+                .keys
+                .get(&dict.current_key_id)
+                .map(|_| dict.current_key_id),
+            data_keys: dict
+                .keys
+                .iter()
+                .map(|(id, key)| DataKeyMetadata {
+                    key_id: *id,
+                    method: key.method,
+                    created_at: key.created_at,
+                })
+                .collect(),
+        }
+    }
+
+    /// Create a file which is encrypted by the current data key, or truncate it if it already
+    /// exists. The file is plaintext if the encryption is disabled.
+    pub fn create_file(&self, path: &Path) -> io::Result<EncryptedFile> {
+        if !self.is_enabled() {
+            return EncryptedFile::create(path, None);
+        }
+        EncryptedFile::create(path, Some(self.current_key()?))
+    }
+
+    /// Open a file, which might be encrypted by any data key or plaintext.
+    pub fn open_file(&self, path: &Path) -> io::Result<EncryptedFile> {
+        EncryptedFile::open(path, |key_id| {
+            self.find_key(key_id).map_err(|err| {
+                io::Error::new(err.kind(), format!("{err}, file {}", path.display()))
+            })
+        })
+    }
+
+    /// Encrypt a value by the current data key, the id of the data key and the iv are prepended
+    /// to the ciphertext. It is used to encrypt the values stored in rocksdb, whose files couldn't
+    /// be encrypted by the bound rocksdb.
+    pub fn encrypt_value(&self, value: &[u8]) -> Result<Vec<u8>> {
+        let (key_id, crypter) = self.current_value_crypter()?;
+        let iv = random_bytes::<IV_LEN>();
+        let mut sealed = Vec::with_capacity(VALUE_HEADER_LEN + value.len());
+        sealed.extend_from_slice(&key_id.to_le_bytes());
+        sealed.extend_from_slice(&iv);
+        sealed.extend_from_slice(value);
+        crypter.apply_keystream(&iv, &mut sealed[VALUE_HEADER_LEN..]);
+        Ok(sealed)
+    }
+
+    /// Decrypt a value encrypted by `encrypt_value`.
+    pub fn decrypt_value(&self, sealed: &[u8]) -> Result<Vec<u8>> {
+        if sealed.len() < VALUE_HEADER_LEN {
+            return Err(Error::InvalidData(
+                "encrypted value is truncated".to_owned(),
+            ));
+        }
+        let (key_id, rest) = sealed.split_at(core::mem::size_of::<u64>());
+        let (iv, ciphertext) = rest.split_at(IV_LEN);
+        let key_id = u64::from_le_bytes(key_id.try_into().unwrap());
+        let crypter = self.value_crypter(key_id)?;
+        let mut value = ciphertext.to_vec();
+        crypter.apply_keystream(iv.try_into().unwrap(), &mut value);
+        Ok(value)
+    }
+
+    fn current_value_crypter(&self) -> io::Result<(u64, Arc<ValueCrypter>)> {
+        if let Some(current) = self.value_crypters.read().unwrap().current.clone() {
+            return Ok(current);
+        }
+        // Hold the dictionary, so the data key isn't rotated before its crypter is cached.
+        let dict = self.dict.lock().unwrap();
+        let (key_id, key) = current_key(&dict)?;
+        let crypter = self.value_crypter_of(key_id, &key);
+        self.value_crypters.write().unwrap().current = Some((key_id, crypter.clone()));
+        Ok((key_id, crypter))
+    }
+
+    fn value_crypter(&self, key_id: u64) -> io::Result<Arc<ValueCrypter>> {
+        if let Some(crypter) = self.value_crypters.read().unwrap().crypters.get(&key_id) {
+            return Ok(crypter.clone());
+        }
+        let key = self.find_key(key_id)?;
+        Ok(self.value_crypter_of(key_id, &key))
+    }
+
+    fn value_crypter_of(&self, key_id: u64, key: &[u8; KEY_LEN]) -> Arc<ValueCrypter> {
+        self.value_crypters
+            .write()
+            .unwrap()
+            .crypters
+            .entry(key_id)
+            .or_insert_with(|| Arc::new(ValueCrypter::new(key)))
+            .clone()
+    }
+
+    fn current_key(&self) -> io::Result<(u64, [u8; KEY_LEN])> {
+        current_key(&self.dict.lock().unwrap())
+    }
+
+    fn find_key(&self, key_id: u64) -> io::Result<[u8; KEY_LEN]> {
+        let dict = self.dict.lock().unwrap();
+        let key = dict.keys.get(&key_id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("data key {key_id} is not found"),
+            )
+        })?;
+        to_key(&key.key)
+    }
+
+    fn rotate_locked(&self, dict: &mut KeyDictionary) -> Result<u64> {
+        let mut key_id = 0;
+        while key_id == 0 || dict.keys.contains_key(&key_id) {
+            key_id = rand::random();
+        }
+        let key = DataKey {
+            key: random_bytes::<KEY_LEN>().to_vec(),
+            method: self.method,
+            created_at: unix_secs(),
+        };
+
+        // Only switch to the new data key once it is persisted, otherwise the files encrypted by
+        // it are unreadable after restarting.
+        let previous_key_id = dict.current_key_id;
+        dict.keys.insert(key_id, key);
+        dict.current_key_id = key_id;
+        if let Err(err) = self.save_dictionary(dict) {
+            dict.keys.remove(&key_id);
+            dict.current_key_id = previous_key_id;
+            return Err(err);
+        }
+        // The values are encrypted by the new data key from now on.
+        self.value_crypters.write().unwrap().current = None;
+        info!("data key is rotated from {previous_key_id} to {key_id}");
+        Ok(key_id)
+    }
+
+    fn save_dictionary(&self, dict: &KeyDictionary) -> Result<()> {
+        use std::{fs::OpenOptions, io::Write, os::unix::fs::OpenOptionsExt};
+
+        let Some(dir) = self.dir.as_ref() else {
+            return Ok(());
+        };
+
+        let content = serde_json::to_vec(dict)
+            .map_err(|e| Error::InvalidData(format!("encode data keys: {e}")))?;
+        let content = self.master_key.encrypt(&content)?;
+
+        let temp = dir.join(KEY_DICT_TEMP);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&temp)?;
+        file.write_all(&content)?;
+        file.sync_all()?;
+        drop(file);
+
+        std::fs::rename(temp, dir.join(KEY_DICT_FILE))?;
+        std::fs::File::open(dir)?.sync_all()?;
+        Ok(())
+    }
+}
+
+fn load_dictionary(dir: &Path, master_key: &dyn MasterKey) -> Result<KeyDictionary> {
+    let path = dir.join(KEY_DICT_FILE);
+    if !std::fs::try_exists(&path)? {
+        return Ok(KeyDictionary::default());
+    }
+    let content = std::fs::read(&path)?;
+    let content = master_key.decrypt(&content)?;
+    serde_json::from_slice(&content)
+        .map_err(|e| Error::InvalidData(format!("data keys, the master key might be wrong: {e}")))
+}
+
+fn current_key(dict: &KeyDictionary) -> io::Result<(u64, [u8; KEY_LEN])> {
+    let current = dict
+        .keys
+        .get(&dict.current_key_id)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no data key is available"))?;
+    Ok((dict.current_key_id, to_key(&current.key)?))
+}
+
+fn to_key(key: &[u8]) -> io::Result<[u8; KEY_LEN]> {
+    key.try_into()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid length of data key"))
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        sync::Arc,
+    };
+
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::encryption::{FileReader, FileWriter};
+
+    fn config(dir: &Path, key_file: &str) -> EncryptionConfig {
+        let path = dir.join(key_file);
+        if !path.exists() {
+            std::fs::write(&path, hex::encode(random_bytes::<KEY_LEN>())).unwrap();
+        }
+        EncryptionConfig {
+            method: EncryptionMethod::Aes256Ctr,
+            master_key: MasterKeyConfig::File { path },
+            ..Default::default()
+        }
+    }
+
+    fn write_file(manager: &DataKeyManager, path: &Path, content: &[u8]) {
+        let file = manager.create_file(path).unwrap();
+        FileWriter::new(Arc::new(file)).write_all(content).unwrap();
+    }
+
+    fn read_file(manager: &DataKeyManager, path: &Path) -> Vec<u8> {
+        let file = manager.open_file(path).unwrap();
+        let mut content = vec![];
+        FileReader::new(Arc::new(file))
+            .read_to_end(&mut content)
+            .unwrap();
+        content
+    }
+
+    #[test]
+    fn rotate_data_key() {
+        let dir = TempDir::new("rotate-data-key").unwrap();
+        let key_dir = dir.path().join("keys");
+        let cfg = config(dir.path(), "master.key");
+        let manager = DataKeyManager::open(&key_dir, &cfg).unwrap();
+        let first_key_id = manager.metadata().current_key_id.unwrap();
+        assert!(!manager.maybe_rotate().unwrap());
+
+        let old_file = dir.path().join("old");
+        write_file(&manager, &old_file, b"old");
+        let second_key_id = manager.rotate().unwrap();
+        assert_ne!(first_key_id, second_key_id);
+        let new_file = dir.path().join("new");
+        write_file(&manager, &new_file, b"new");
+
+        // The files encrypted by the old data keys are still readable after restarting.
+        drop(manager);
+        let manager = DataKeyManager::open(&key_dir, &cfg).unwrap();
+        let metadata = manager.metadata();
+        assert_eq!(metadata.current_key_id, Some(second_key_id));
+        assert_eq!(metadata.data_keys.len(), 2);
+        assert_eq!(read_file(&manager, &old_file), b"old");
+        assert_eq!(read_file(&manager, &new_file), b"new");
+    }
+
+    #[test]
+    fn rotate_master_key() {
+        let dir = TempDir::new("rotate-master-key").unwrap();
+        let key_dir = dir.path().join("keys");
+        let cfg = config(dir.path(), "master-1.key");
+        let manager = DataKeyManager::open(&key_dir, &cfg).unwrap();
+        let path = dir.path().join("file");
+        write_file(&manager, &path, b"content");
+        drop(manager);
+
+        // The data keys couldn't be decrypted by another master key.
+        let mut new_cfg = config(dir.path(), "master-2.key");
+        assert!(DataKeyManager::open(&key_dir, &new_cfg).is_err());
+
+        new_cfg.previous_master_key = Some(cfg.master_key.clone());
+        let manager = DataKeyManager::open(&key_dir, &new_cfg).unwrap();
+        assert_eq!(read_file(&manager, &path), b"content");
+        drop(manager);
+
+        // The previous master key is no longer required once the data keys are re-encrypted.
+        new_cfg.previous_master_key = None;
+        let manager = DataKeyManager::open(&key_dir, &new_cfg).unwrap();
+        assert_eq!(read_file(&manager, &path), b"content");
+    }
+
+    #[test]
+    fn encrypt_and_decrypt_value() {
+        let dir = TempDir::new("encrypt-value").unwrap();
+        let cfg = config(dir.path(), "master.key");
+        let manager = DataKeyManager::open(&dir.path().join("keys"), &cfg).unwrap();
+
+        let sealed = manager.encrypt_value(b"value").unwrap();
+        assert!(!sealed.windows(5).any(|w| w == b"value"));
+        // The same value is encrypted with different ivs.
+        assert_ne!(manager.encrypt_value(b"value").unwrap(), sealed);
+
+        // The values encrypted by the old data keys are still readable after rotating.
+        manager.rotate().unwrap();
+        assert_eq!(manager.decrypt_value(&sealed).unwrap(), b"value");
+        // The new values are encrypted by the new data key.
+        let new_sealed = manager.encrypt_value(b"value").unwrap();
+        assert_ne!(new_sealed[..8], sealed[..8]);
+        assert_eq!(manager.decrypt_value(&new_sealed).unwrap(), b"value");
+        assert!(manager.decrypt_value(&sealed[..10]).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use tracing::info;
+
+use super::crypter::{random_bytes, seal, unseal, KEY_LEN};
+use crate::{Error, MasterKeyConfig, Result};
+
+/// The master key encrypts the data keys, so that they could be stored along with the data.
+pub(crate) trait MasterKey: Send + Sync {
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>>;
+
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>>;
+}
+
+struct PlaintextKey;
+
+/// A master key whose material is accessible by the node, eg. loaded from a local file.
+struct LocalKey {
+    key: [u8; KEY_LEN],
+}
+
+pub(crate) fn open_master_key(cfg: &MasterKeyConfig) -> Result<Box<dyn MasterKey>> {
+    Ok(match cfg {
+        MasterKeyConfig::Plaintext => Box::new(PlaintextKey),
+        MasterKeyConfig::File { path } => Box::new(LocalKey {
+            key: read_key_file(path)?,
+        }),
+        MasterKeyConfig::Kms { key_id, key_dir } => Box::new(LocalKey {
+            key: kms_key(key_id, key_dir)?,
+        }),
+    })
+}
+
+impl MasterKey for PlaintextKey {
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        Ok(plaintext.to_owned())
+    }
+
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        Ok(ciphertext.to_owned())
+    }
+}
+
+impl MasterKey for LocalKey {
+    fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>> {
+        seal(&self.key, plaintext)
+    }
+
+    fn decrypt(&self, ciphertext: &[u8]) -> Result<Vec<u8>> {
+        unseal(&self.key, ciphertext)
+    }
+}
+
+fn read_key_file(path: &Path) -> Result<[u8; KEY_LEN]> {
+    let content = std::fs::read_to_string(path)?;
+    let bytes = hex::decode(content.trim()).map_err(|e| {
+        Error::InvalidArgument(format!("master key {} is not hex: {e}", path.display()))
+    })?;
+    bytes.try_into().map_err(|_| {
+        Error::InvalidArgument(format!(
+            "master key {} should be {} bits",
+            path.display(),
+            KEY_LEN * 8
+        ))
+    })
+}
+
+/// Fetch the master key from the stand-in of the key management service, the key is created if
+/// it doesn't exist.
+fn kms_key(key_id: &str, key_dir: &Path) -> Result<[u8; KEY_LEN]> {
+    use std::{fs::OpenOptions, io::Write, os::unix::fs::OpenOptionsExt};
+
+    if key_id.is_empty()
+        || !key_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return Err(Error::InvalidArgument(format!(
+            "kms key id {key_id:?} should be non-empty alphanumeric"
+        )));
+    }
+
+    let path = key_dir.join(format!("{key_id}.key"));
+    if !std::fs::try_exists(&path)? {
+        std::fs::create_dir_all(key_dir)?;
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)?;
+        file.write_all(hex::encode(random_bytes::<KEY_LEN>()).as_bytes())?;
+        file.sync_all()?;
+        info!("kms creates master key {key_id}");
+    }
+    read_key_file(&path)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn file_master_key() {
+        let dir = TempDir::new("file-master-key").unwrap();
+        let path = dir.path().join("master.key");
+        std::fs::write(&path, format!("{}\n", hex::encode([7u8; KEY_LEN]))).unwrap();
+        let key = open_master_key(&MasterKeyConfig::File { path: path.clone() }).unwrap();
+        let ciphertext = key.encrypt(b"data key").unwrap();
+        assert_eq!(key.decrypt(&ciphertext).unwrap(), b"data key");
+
+        std::fs::write(&path, hex::encode([7u8; 16])).unwrap();
+        assert!(open_master_key(&MasterKeyConfig::File { path }).is_err());
+    }
+
+    #[test]
+    fn kms_master_key_is_created_once() {
+        let dir = TempDir::new("kms-master-key").unwrap();
+        let cfg = MasterKeyConfig::Kms {
+            key_id: "key-1".to_owned(),
+            key_dir: dir.path().to_owned(),
+        };
+        let ciphertext = open_master_key(&cfg).unwrap().encrypt(b"data key").unwrap();
+        let key = open_master_key(&cfg).unwrap();
+        assert_eq!(key.decrypt(&ciphertext).unwrap(), b"data key");
+
+        let cfg = MasterKeyConfig::Kms {
+            key_id: "../key-1".to_owned(),
+            key_dir: dir.path().to_owned(),
+        };
+        assert!(open_master_key(&cfg).is_err());
+    }
+}
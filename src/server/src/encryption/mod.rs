@@ -0,0 +1,34 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The encryption at rest. The files are encrypted by the data keys with AES-256-CTR, and the
+//! data keys are encrypted by the master key, which is not stored along with the data.
+//!
+//! The files of rocksdb couldn't be encrypted by the bound rocksdb, so the values of the user data
+//! are encrypted by the group engine before they are written into rocksdb, and the external SST
+//! files are rewritten with encrypted values when they are staged. The keys are stored in
+//! plaintext, since the shards are partitioned by the order of them. Note that the keys of the
+//! index entries consist of the indexed values, so they are not protected either.
+
+mod crypter;
+mod file;
+mod file_system;
+mod key_manager;
+mod master_key;
+
+pub use self::{
+    file::{EncryptedFile, FileReader, FileWriter},
+    file_system::EncryptedFileSystem,
+    key_manager::{DataKeyManager, DataKeyMetadata, EncryptionMetadata},
+};
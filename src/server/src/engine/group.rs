@@ -28,6 +28,7 @@ use tracing::{info, warn};
 use super::RawDb;
 use crate::{
     constants::{INITIAL_EPOCH, LOCAL_COLLECTION_ID},
+    encryption::DataKeyManager,
     serverpb::v1::*,
     EngineConfig, Error, Result,
 };
@@ -37,6 +38,7 @@ pub struct WriteStates {
     pub apply_state: Option<ApplyState>,
     pub descriptor: Option<GroupDesc>,
     pub migration_state: Option<MigrationState>,
+    pub archive_state: Option<ArchiveState>,
 }
 
 #[derive(Default)]
@@ -68,10 +70,12 @@ struct GroupEngineCore {
     migration_state: Option<MigrationState>,
 }
 
-/// Traverse the data of the group engine, but don't care about the data format.
+/// Traverse the data of the group engine, but don't care about the data format. The values are
+/// decrypted, so they could be loaded by the engines of other nodes.
 pub(crate) struct RawIterator<'a> {
     apply_state: ApplyState,
     descriptor: GroupDesc,
+    key_manager: &'a DataKeyManager,
     db_iter: rocksdb::DBIterator<'a>,
 }
 
@@ -102,6 +106,7 @@ pub(crate) struct Snapshot<'a> {
 
 pub(crate) struct SnapshotCore<'a> {
     expect_slot: Option<u32>,
+    key_manager: &'a DataKeyManager,
     db_iter: rocksdb::DBIterator<'a>,
     current_key: Option<Vec<u8>>,
     cached_entry: Option<MvccEntry>,
@@ -132,9 +137,13 @@ pub(crate) enum SnapshotMode<'a> {
     Prefix { key: &'a [u8] },
 }
 
+/// Move the writes into the column family, and encrypt the values of the user data.
 struct ColumnFamilyDecorator<'a, 'b> {
     cf_handle: Arc<rocksdb::BoundColumnFamily<'b>>,
+    key_manager: &'a DataKeyManager,
     wb: &'a mut rocksdb::WriteBatch,
+    /// The first error of encrypting values, the batch is not committed if any.
+    error: Option<Error>,
 }
 
 struct SlowIoGuard {
@@ -160,6 +169,7 @@ impl GroupEngine {
             epoch: INITIAL_EPOCH,
             shards: vec![],
             replicas: vec![],
+            ..Default::default()
         };
 
         let cf_handle = raw_db
@@ -241,27 +251,83 @@ impl GroupEngine {
         self.core.read().unwrap().migration_state.clone()
     }
 
+    /// Return the archived position of the log entries, `None` if they are never archived.
+    pub fn archive_state(&self) -> Result<Option<ArchiveState>> {
+        internal::archive_state(&self.raw_db, &self.cf_handle())
+    }
+
+    /// Return the data keys which encrypt the values of the user data.
+    #[inline]
+    pub fn key_manager(&self) -> &DataKeyManager {
+        &self.raw_db.key_manager
+    }
+
     /// Return the group descriptor.
     #[inline]
     pub fn descriptor(&self) -> GroupDesc {
         self.core.read().unwrap().group_desc.clone()
     }
 
+    /// Set the desired number of voters of the group, it is persisted along with the descriptor.
+    pub fn set_replication_factor(&self, replication_factor: u32) -> Result<()> {
+        let mut desc = self.descriptor();
+        if desc.replication_factor == replication_factor {
+            return Ok(());
+        }
+        desc.replication_factor = replication_factor;
+        let states = WriteStates {
+            descriptor: Some(desc),
+            ..Default::default()
+        };
+        self.commit(WriteBatch::default(), states, true)
+    }
+
     /// Return the persisted apply state of raft.
     #[inline]
     pub fn flushed_apply_state(&self) -> Result<ApplyState> {
         internal::flushed_apply_state(&self.raw_db, &self.cf_handle())
     }
 
+    /// Return the approximate bytes of the data of this group.
+    pub fn approximate_size(&self) -> u64 {
+        const PROPERTIES: [&str; 2] = [
+            "rocksdb.total-sst-files-size",
+            "rocksdb.size-all-mem-tables",
+        ];
+        let cf_handle = self.cf_handle();
+        PROPERTIES
+            .iter()
+            .filter_map(|name| {
+                self.raw_db
+                    .property_int_value_cf(&cf_handle, name)
+                    .ok()
+                    .flatten()
+            })
+            .sum()
+    }
+
+    /// Flush the in-memory writes of this group into persistent storage.
+    pub fn flush(&self) -> Result<()> {
+        self.raw_db.flush_cf(&self.cf_handle())?;
+        Ok(())
+    }
+
     /// Get key value from the corresponding shard.
     pub async fn get(&self, shard_id: u64, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let value = self.get_expirable(shard_id, key).await?;
+        Ok(value.map(|(value, _)| value))
+    }
+
+    /// Get key value and its expiration time from the corresponding shard, the expiration time is
+    /// 0 if the value never expires.
+    pub async fn get_expirable(&self, shard_id: u64, key: &[u8]) -> Result<Option<(Vec<u8>, u64)>> {
         let snapshot_mode = SnapshotMode::Key { key };
         let mut snapshot = self.snapshot(shard_id, snapshot_mode)?;
         if let Some(iter) = snapshot.mvcc_iter() {
             let mut iter = iter?;
             if let Some(entry) = iter.next() {
                 let entry = entry?;
-                return Ok(entry.value().map(ToOwned::to_owned));
+                return Ok(entry.value().map(|v| (v.to_owned(), entry.expire_at())));
             }
         }
         Ok(None)
@@ -275,15 +341,34 @@ impl GroupEngine {
         key: &[u8],
         value: &[u8],
         version: u64,
+    ) -> Result<()> {
+        self.put_expirable(wb, shard_id, key, value, version, 0)
+    }
+
+    /// Put key value into the corresponding shard, the value is invisible once the unix time in
+    /// seconds reaches `expire_at`. 0 means that the value never expires.
+    pub fn put_expirable(
+        &self,
+        wb: &mut WriteBatch,
+        shard_id: u64,
+        key: &[u8],
+        value: &[u8],
+        version: u64,
+        expire_at: u64,
     ) -> Result<()> {
         let desc = self.shard_desc(shard_id)?;
         let collection_id = desc.collection_id;
         debug_assert_ne!(collection_id, LOCAL_COLLECTION_ID);
         debug_assert!(shard::belong_to(&desc, key));
 
+        let value = if expire_at == 0 {
+            values::data(value)
+        } else {
+            values::expirable(value, expire_at)
+        };
         wb.put(
             keys::mvcc_key(collection_id, shard::slot(&desc), key, version),
-            values::data(value),
+            value,
         );
 
         Ok(())
@@ -332,6 +417,50 @@ impl GroupEngine {
         Ok(())
     }
 
+    /// Put index entry into the corresponding shard. The entry is keyed by the index id, with the
+    /// same slot of the shard, so that it is co-located with the primary key.
+    pub fn put_index(
+        &self,
+        wb: &mut WriteBatch,
+        shard_id: u64,
+        index_id: u64,
+        index_key: &[u8],
+        primary_key: &[u8],
+        version: u64,
+    ) -> Result<()> {
+        let desc = self.shard_desc(shard_id)?;
+        debug_assert_ne!(index_id, LOCAL_COLLECTION_ID);
+        debug_assert!(shard::belong_to(&desc, primary_key));
+
+        wb.put(
+            keys::mvcc_key(index_id, shard::slot(&desc), index_key, version),
+            values::data(primary_key),
+        );
+
+        Ok(())
+    }
+
+    pub fn delete_index(
+        &self,
+        wb: &mut WriteBatch,
+        shard_id: u64,
+        index_id: u64,
+        index_key: &[u8],
+        version: u64,
+    ) -> Result<()> {
+        let desc = self.shard_desc(shard_id)?;
+        debug_assert_ne!(index_id, LOCAL_COLLECTION_ID);
+
+        wb.delete(keys::mvcc_key(
+            index_id,
+            shard::slot(&desc),
+            index_key,
+            version,
+        ));
+
+        Ok(())
+    }
+
     #[inline]
     pub fn commit(&self, wb: WriteBatch, states: WriteStates, persisted: bool) -> Result<()> {
         self.group_commit(&[wb], states, persisted)
@@ -349,11 +478,16 @@ impl GroupEngine {
         let mut inner_wb = rocksdb::WriteBatch::default();
         let mut decorator = ColumnFamilyDecorator {
             cf_handle: cf_handle.clone(),
+            key_manager: self.key_manager(),
             wb: &mut inner_wb,
+            error: None,
         };
         for wb in wbs {
             wb.inner.iterate(&mut decorator);
         }
+        if let Some(err) = decorator.error {
+            return Err(err);
+        }
         states.write(&mut inner_wb, &cf_handle);
 
         let mut opts = WriteOptions::default();
@@ -407,7 +541,37 @@ impl GroupEngine {
         let iter = self
             .raw_db
             .iterator_cf_opt(&self.cf_handle(), opts, inner_mode);
-        Ok(Snapshot::new(collection_id, iter, mode, &desc))
+        Ok(Snapshot::new(
+            collection_id,
+            self.key_manager(),
+            iter,
+            mode,
+            &desc,
+        ))
+    }
+
+    /// Iterate the index entries with the specified prefix, which are co-located with the shard.
+    pub fn index_snapshot(&self, shard_id: u64, index_id: u64, prefix: &[u8]) -> Result<Snapshot> {
+        use rocksdb::{Direction, IteratorMode, ReadOptions};
+
+        let desc = self.shard_desc(shard_id)?;
+        debug_assert_ne!(index_id, LOCAL_COLLECTION_ID);
+        debug_assert!(!prefix.is_empty());
+
+        let opts = ReadOptions::default();
+        let key = keys::raw(index_id, shard::slot(&desc), prefix);
+        let inner_mode = IteratorMode::From(&key, Direction::Forward);
+        let iter = self
+            .raw_db
+            .iterator_cf_opt(&self.cf_handle(), opts, inner_mode);
+        let mode = SnapshotMode::Prefix { key: prefix };
+        Ok(Snapshot::new(
+            index_id,
+            self.key_manager(),
+            iter,
+            mode,
+            &desc,
+        ))
     }
 
     pub fn raw_iter(&self) -> Result<RawIterator> {
@@ -417,7 +581,7 @@ impl GroupEngine {
         let iter = self
             .raw_db
             .iterator_cf_opt(&self.cf_handle(), opts, IteratorMode::Start);
-        RawIterator::new(iter)
+        RawIterator::new(self.key_manager(), iter)
     }
 
     /// Ingest data into group engine.
@@ -439,6 +603,78 @@ impl GroupEngine {
         Ok(())
     }
 
+    /// Ingest the external SST files, the existing data is kept. The records of the files must be
+    /// encoded by `encode_record`, and belong to the shards of this group. The files are copied
+    /// into the engine, they are kept until the caller removes them. The values should be
+    /// encrypted by [`encrypt_sst_file`] before ingesting.
+    pub fn ingest_external_files<P: AsRef<Path>>(&self, files: Vec<P>) -> Result<()> {
+        use rocksdb::IngestExternalFileOptions;
+
+        let mut opts = IngestExternalFileOptions::default();
+        opts.set_move_files(false);
+        let cf_handle = self.cf_handle();
+        self.raw_db
+            .ingest_external_file_cf_opts(&cf_handle, &opts, files)?;
+        Ok(())
+    }
+
+    /// Move the data of the collections and indexes to the mapped ids, the data of the ids not
+    /// in the mapping is dropped. It is used to load the data of another cluster, and the engine
+    /// must not be serving any requests.
+    ///
+    /// The descriptor is not changed, the caller should update it to match the new ids.
+    pub fn rewrite_collections(&self, id_mapping: &HashMap<u64, u64>) -> Result<()> {
+        use rocksdb::{IteratorMode, ReadOptions, WriteOptions};
+
+        const REWRITE_BATCH_BYTES: usize = 4 << 20;
+        const L: usize = core::mem::size_of::<u64>();
+
+        let cf_handle = self.cf_handle();
+        let last_key = self
+            .raw_db
+            .iterator_cf_opt(&cf_handle, ReadOptions::default(), IteratorMode::End)
+            .next()
+            .transpose()?
+            .map(|(key, _)| key);
+        let Some(last_key) = last_key.filter(|k| !keys::is_local(k)) else {
+            return Ok(());
+        };
+
+        // The iterator is created before the data is dropped, so it still reads the old data.
+        let iter =
+            self.raw_db
+                .iterator_cf_opt(&cf_handle, ReadOptions::default(), IteratorMode::Start);
+
+        // All keys except the local ones are dropped, and then the mapped ones are written back.
+        let mut wb = rocksdb::WriteBatch::default();
+        let mut end = last_key.to_vec();
+        end.push(0);
+        wb.delete_range_cf(&cf_handle, keys::data_start(), end);
+        let mut opts = WriteOptions::default();
+        opts.disable_wal(true);
+        for item in iter {
+            let (key, value) = item?;
+            if keys::is_local(&key) || key.len() < L {
+                continue;
+            }
+            let mut id = [0u8; L];
+            id.copy_from_slice(&key[..L]);
+            let Some(new_id) = id_mapping.get(&u64::from_le_bytes(id)) else {
+                continue;
+            };
+            let mut new_key = Vec::with_capacity(key.len());
+            new_key.extend_from_slice(new_id.to_le_bytes().as_slice());
+            new_key.extend_from_slice(&key[L..]);
+            wb.put_cf(&cf_handle, new_key, value);
+            if wb.size_in_bytes() >= REWRITE_BATCH_BYTES {
+                self.raw_db.write_opt(std::mem::take(&mut wb), &opts)?;
+            }
+        }
+        self.raw_db.write_opt(wb, &opts)?;
+        self.flush()?;
+        Ok(())
+    }
+
     pub fn apply_core_states(
         &self,
         descriptor: Option<GroupDesc>,
@@ -470,8 +706,9 @@ impl GroupEngine {
         }
     }
 
+    /// Get the descriptor of the shard, including the migrating shard.
     #[inline]
-    fn shard_desc(&self, shard_id: u64) -> Result<ShardDesc> {
+    pub fn shard_desc(&self, shard_id: u64) -> Result<ShardDesc> {
         self.core
             .read()
             .expect("read lock")
@@ -497,7 +734,7 @@ impl GroupEngine {
 }
 
 impl<'a> RawIterator<'a> {
-    fn new(mut db_iter: rocksdb::DBIterator<'a>) -> Result<Self> {
+    fn new(key_manager: &'a DataKeyManager, mut db_iter: rocksdb::DBIterator<'a>) -> Result<Self> {
         use rocksdb::IteratorMode;
 
         let apply_state = next_message(&mut db_iter, &keys::apply_state())?;
@@ -507,6 +744,7 @@ impl<'a> RawIterator<'a> {
         Ok(RawIterator {
             apply_state,
             descriptor,
+            key_manager,
             db_iter,
         })
     }
@@ -520,21 +758,38 @@ impl<'a> RawIterator<'a> {
     pub fn descriptor(&self) -> &GroupDesc {
         &self.descriptor
     }
+
+    /// Seek to the first key which is not less than `key`, the view of data is not changed.
+    pub fn seek(&mut self, key: &[u8]) {
+        use rocksdb::{Direction, IteratorMode};
+
+        self.db_iter
+            .set_mode(IteratorMode::From(key, Direction::Forward));
+    }
+
+    /// Seek to the first key of the data of collections, the local states are skipped.
+    pub fn seek_to_data_start(&mut self) {
+        self.seek(&keys::data_start());
+    }
 }
 
 impl<'a> Iterator for RawIterator<'a> {
     /// Key value pairs.
-    type Item = <rocksdb::DBIterator<'a> as Iterator>::Item;
+    type Item = Result<(Box<[u8]>, Box<[u8]>)>;
 
-    #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        self.db_iter.next()
+        let (key, value) = match self.db_iter.next()? {
+            Ok(v) => v,
+            Err(err) => return Some(Err(err.into())),
+        };
+        Some(values::decrypt(self.key_manager, value).map(|value| (key, value)))
     }
 }
 
 impl<'a> Snapshot<'a> {
     fn new<'b>(
         collection_id: u64,
+        key_manager: &'a DataKeyManager,
         db_iter: rocksdb::DBIterator<'a>,
         snapshot_mode: SnapshotMode<'b>,
         desc: &ShardDesc,
@@ -569,6 +824,7 @@ impl<'a> Snapshot<'a> {
             range,
             core: RefCell::new(SnapshotCore {
                 expect_slot,
+                key_manager,
                 db_iter,
                 current_key: None,
                 cached_entry: None,
@@ -642,6 +898,10 @@ impl<'a> SnapshotCore<'a> {
             return None;
         }
 
+        let value = match values::decrypt(self.key_manager, value) {
+            Ok(v) => v,
+            Err(err) => return Some(Err(err)),
+        };
         self.cached_entry = Some(MvccEntry::new(self.expect_slot.is_some(), key, value));
         Some(Ok(()))
     }
@@ -701,16 +961,36 @@ impl MvccEntry {
         !u64::from_be_bytes(buf)
     }
 
-    /// Return value of this `MvccEntry`. `None` is returned if this entry is a tombstone.
+    /// Return value of this `MvccEntry`. `None` is returned if this entry is a tombstone, or the
+    /// value is expired.
     pub fn value(&self) -> Option<&[u8]> {
-        if self.value[0] == values::TOMBSTONE {
-            None
-        } else {
-            debug_assert_eq!(self.value[0], values::DATA);
-            Some(&self.value[1..])
+        match self.value[0] {
+            values::TOMBSTONE => None,
+            values::EXPIRABLE => {
+                if values::is_expired(self.expire_at()) {
+                    None
+                } else {
+                    Some(&self.value[values::EXPIRABLE_HEADER_LEN..])
+                }
+            }
+            tag => {
+                debug_assert_eq!(tag, values::DATA);
+                Some(&self.value[1..])
+            }
         }
     }
 
+    /// Return the expiration time of the value in seconds since the unix epoch, 0 means that the
+    /// value never expires.
+    pub fn expire_at(&self) -> u64 {
+        if self.value[0] != values::EXPIRABLE {
+            return 0;
+        }
+        let mut buf = [0u8; core::mem::size_of::<u64>()];
+        buf.copy_from_slice(&self.value[1..values::EXPIRABLE_HEADER_LEN]);
+        u64::from_be_bytes(buf)
+    }
+
     #[allow(dead_code)]
     pub fn is_tombstone(&self) -> bool {
         self.value[0] == values::TOMBSTONE
@@ -718,7 +998,7 @@ impl MvccEntry {
 
     #[allow(dead_code)]
     pub fn is_data(&self) -> bool {
-        self.value[0] == values::DATA
+        matches!(self.value[0], values::DATA | values::EXPIRABLE)
     }
 }
 
@@ -745,10 +1025,176 @@ impl<'a> Default for SnapshotMode<'a> {
     }
 }
 
+/// Encode a record with the layout of the group engine, it is used to build the SST files outside
+/// of the engine. The `slot` is required if the collection is hash partitioned.
+pub(crate) fn encode_record(
+    collection_id: u64,
+    slot: Option<u32>,
+    key: &[u8],
+    value: &[u8],
+    version: u64,
+) -> (Vec<u8>, Vec<u8>) {
+    (
+        keys::mvcc_key(collection_id, slot, key, version),
+        values::data(value),
+    )
+}
+
+/// Decode a record encoded by `encode_record`, and return the user key and value. `None` is
+/// returned if the record isn't encoded with the layout of the group engine, or it doesn't belong
+/// to the shard.
+pub(crate) fn decode_shard_record(
+    shard: &ShardDesc,
+    key: &[u8],
+    value: &[u8],
+) -> Option<(Vec<u8>, Vec<u8>)> {
+    const L: usize = core::mem::size_of::<u64>();
+
+    let slot = shard::slot(shard);
+    let prefix_len = L + if slot.is_some() { 4 } else { 0 };
+    if key.len() < prefix_len + 9 + L || value.first() != Some(&values::DATA) {
+        return None;
+    }
+    if key[..L] != shard.collection_id.to_le_bytes() {
+        return None;
+    }
+    if let Some(slot) = slot {
+        if key[L..prefix_len] != slot.to_le_bytes() {
+            return None;
+        }
+    }
+
+    // Each group of the memcomparable key ends with a marker, the last one tells the number of
+    // the filled bytes and the others are `9`.
+    let encoded_user_key = &key[prefix_len..(key.len() - L)];
+    if encoded_user_key.len() % 9 != 0 {
+        return None;
+    }
+    let mut groups = encoded_user_key.chunks(9).peekable();
+    while let Some(group) = groups.next() {
+        let marker = group[8];
+        let valid = if groups.peek().is_some() {
+            marker == b'9'
+        } else {
+            (b'1'..=b'8').contains(&marker)
+                && group[(marker - b'0') as usize..8].iter().all(|b| *b == 0)
+        };
+        if !valid {
+            return None;
+        }
+    }
+
+    let (user_key, _) = keys::revert_mvcc_key(key, slot.is_some());
+    if !shard::belong_to(shard, &user_key) {
+        return None;
+    }
+    Some((user_key, value[1..].to_vec()))
+}
+
+/// Read the records of an external SST file in order, the encrypted values are decrypted.
+pub(crate) fn read_sst_file<F>(
+    key_manager: &DataKeyManager,
+    path: &Path,
+    scratch_dir: &Path,
+    mut f: F,
+) -> Result<()>
+where
+    F: FnMut(&[u8], &[u8]) -> Result<()>,
+{
+    scan_sst_file(path, scratch_dir, |iter| {
+        for item in iter {
+            let (key, value) = item?;
+            let value = values::decrypt(key_manager, value)?;
+            f(&key, &value)?;
+        }
+        Ok(())
+    })
+}
+
+/// Encrypt the values of the user data in an external SST file, so they are not stored as
+/// plaintext once the file is ingested. The file is replaced by the encrypted one, and it is kept
+/// as it is if the encryption is disabled.
+pub(crate) fn encrypt_sst_file(
+    key_manager: &DataKeyManager,
+    path: &Path,
+    scratch_dir: &Path,
+) -> Result<()> {
+    if !key_manager.is_enabled() {
+        return Ok(());
+    }
+    let encrypted = path.with_extension("encrypted");
+    let num_records = scan_sst_file(path, scratch_dir, |iter| {
+        let records = iter.map(|item| -> Result<(Vec<u8>, Vec<u8>)> {
+            let (key, value) = item?;
+            Ok((key.into_vec(), value.into_vec()))
+        });
+        write_encrypted_sst_file(key_manager, &encrypted, records)
+    })?;
+    if num_records > 0 {
+        std::fs::rename(&encrypted, path)?;
+    }
+    Ok(())
+}
+
+/// Write the sorted records into an SST file, the values of the user data are encrypted by the
+/// data key. The number of written records is returned, and the file isn't created if there is no
+/// record.
+pub(crate) fn write_encrypted_sst_file<I>(
+    key_manager: &DataKeyManager,
+    path: &Path,
+    records: I,
+) -> Result<usize>
+where
+    I: Iterator<Item = Result<(Vec<u8>, Vec<u8>)>>,
+{
+    use rocksdb::{Options, SstFileWriter};
+
+    let mut records = records.peekable();
+    if records.peek().is_none() {
+        return Ok(0);
+    }
+    let opts = Options::default();
+    let mut writer = SstFileWriter::create(&opts);
+    writer.open(path)?;
+    let mut num_records = 0;
+    for record in records {
+        let (key, value) = record?;
+        let value = values::encrypt(key_manager, &key, &value)?.unwrap_or(value);
+        writer.put(key, value)?;
+        num_records += 1;
+    }
+    writer.finish()?;
+    Ok(num_records)
+}
+
+/// Scan an external SST file. The bound rocksdb doesn't expose the reader of SST files, so the
+/// file is opened by a scratch db placed in `scratch_dir`, which is removed before returning.
+fn scan_sst_file<F, T>(path: &Path, scratch_dir: &Path, f: F) -> Result<T>
+where
+    F: FnOnce(rocksdb::DBIterator) -> Result<T>,
+{
+    use rocksdb::{IngestExternalFileOptions, IteratorMode, Options, DB};
+
+    let result = (|| -> Result<T> {
+        let mut opts = Options::default();
+        opts.create_if_missing(true);
+        let db = DB::open(&opts, scratch_dir)?;
+        let mut ingest_opts = IngestExternalFileOptions::default();
+        ingest_opts.set_move_files(false);
+        db.ingest_external_file_opts(&ingest_opts, vec![path])?;
+        f(db.iterator(IteratorMode::Start))
+    })();
+    if scratch_dir.exists() {
+        std::fs::remove_dir_all(scratch_dir)?;
+    }
+    result
+}
+
 mod keys {
     const APPLY_STATE: &[u8] = b"APPLY_STATE";
     const DESCRIPTOR: &[u8] = b"DESCRIPTOR";
     const MIGRATE_STATE: &[u8] = b"MIGRATE_STATE";
+    const ARCHIVE_STATE: &[u8] = b"ARCHIVE_STATE";
 
     #[inline]
     pub fn raw(collection_id: u64, slot: Option<u32>, key: &[u8]) -> Vec<u8> {
@@ -827,6 +1273,21 @@ mod keys {
         (buf, slot)
     }
 
+    /// Whether the key belongs to the local states, instead of the data of collections.
+    #[inline]
+    pub fn is_local(key: &[u8]) -> bool {
+        key.starts_with(super::LOCAL_COLLECTION_ID.to_le_bytes().as_slice())
+    }
+
+    /// The first key of the data of collections.
+    #[inline]
+    pub fn data_start() -> Vec<u8> {
+        let mut buf = super::LOCAL_COLLECTION_ID.to_le_bytes().to_vec();
+        let last = buf.len() - 1;
+        buf[last] += 1;
+        buf
+    }
+
     #[inline]
     pub fn apply_state() -> Vec<u8> {
         let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + APPLY_STATE.len());
@@ -850,11 +1311,29 @@ mod keys {
         buf.extend_from_slice(MIGRATE_STATE);
         buf
     }
+
+    #[inline]
+    pub fn archive_state() -> Vec<u8> {
+        let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + ARCHIVE_STATE.len());
+        buf.extend_from_slice(super::LOCAL_COLLECTION_ID.to_le_bytes().as_slice());
+        buf.extend_from_slice(ARCHIVE_STATE);
+        buf
+    }
 }
 
 mod values {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use super::keys;
+    use crate::{encryption::DataKeyManager, Result};
+
     pub(super) const DATA: u8 = 0;
     pub(super) const TOMBSTONE: u8 = 1;
+    /// The value is encrypted by the data key, the plaintext starts with one of the other tags.
+    pub(super) const ENCRYPTED: u8 = 2;
+    /// The data is followed by the big endian expiration time in seconds since the unix epoch.
+    pub(super) const EXPIRABLE: u8 = 3;
+    pub(super) const EXPIRABLE_HEADER_LEN: usize = 1 + core::mem::size_of::<u64>();
 
     #[inline]
     pub fn tombstone() -> &'static [u8] {
@@ -867,11 +1346,60 @@ mod values {
         buf.extend_from_slice(v);
         buf
     }
+
+    pub fn expirable(v: &[u8], expire_at: u64) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(v.len() + EXPIRABLE_HEADER_LEN);
+        buf.push(EXPIRABLE);
+        buf.extend_from_slice(&expire_at.to_be_bytes());
+        buf.extend_from_slice(v);
+        buf
+    }
+
+    pub fn is_expired(expire_at: u64) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        expire_at <= now
+    }
+
+    /// Encrypt the value of the user data. `None` is returned if the value should be stored as
+    /// it is: the encryption is disabled, the value is already encrypted or it belongs to the
+    /// local states.
+    pub fn encrypt(
+        key_manager: &DataKeyManager,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Option<Vec<u8>>> {
+        if !key_manager.is_enabled() || keys::is_local(key) || value.first() == Some(&ENCRYPTED) {
+            return Ok(None);
+        }
+        let sealed = key_manager.encrypt_value(value)?;
+        let mut buf = Vec::with_capacity(sealed.len() + 1);
+        buf.push(ENCRYPTED);
+        buf.extend_from_slice(&sealed);
+        Ok(Some(buf))
+    }
+
+    /// Decrypt the value if it is encrypted. The values written before the encryption is enabled
+    /// are plaintext, they are returned as they are.
+    pub fn decrypt(key_manager: &DataKeyManager, value: Box<[u8]>) -> Result<Box<[u8]>> {
+        if value.first() != Some(&ENCRYPTED) {
+            return Ok(value);
+        }
+        Ok(key_manager.decrypt_value(&value[1..])?.into_boxed_slice())
+    }
 }
 
 impl<'a, 'b> rocksdb::WriteBatchIterator for ColumnFamilyDecorator<'a, 'b> {
     fn put(&mut self, key: Box<[u8]>, value: Box<[u8]>) {
-        self.wb.put_cf(&self.cf_handle, key, value);
+        match values::encrypt(self.key_manager, &key, &value) {
+            Ok(Some(value)) => self.wb.put_cf(&self.cf_handle, key, value),
+            Ok(None) => self.wb.put_cf(&self.cf_handle, key, value),
+            Err(err) => {
+                self.error.get_or_insert(err);
+            }
+        }
     }
 
     fn delete(&mut self, key: Box<[u8]>) {
@@ -924,6 +1452,13 @@ impl WriteStates {
                 wb.delete_cf(cf_handle, keys::migrate_state());
             }
         }
+        if let Some(archive_state) = &self.archive_state {
+            wb.put_cf(
+                cf_handle,
+                keys::archive_state(),
+                archive_state.encode_to_vec(),
+            );
+        }
     }
 }
 
@@ -979,6 +1514,17 @@ mod internal {
         }
     }
 
+    pub(super) fn archive_state(
+        db: &RawDb,
+        cf_handle: &impl rocksdb::AsColumnFamilyRef,
+    ) -> Result<Option<ArchiveState>> {
+        if let Some(v) = db.get_pinned_cf(cf_handle, keys::archive_state())? {
+            Ok(Some(ArchiveState::decode(v.as_ref())?))
+        } else {
+            Ok(None)
+        }
+    }
+
     pub(super) fn flushed_apply_state(
         db: &RawDb,
         cf_handle: &impl rocksdb::AsColumnFamilyRef,
@@ -1018,6 +1564,8 @@ fn next_message<T: prost::Message + Default>(
 
 #[cfg(test)]
 mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
     use engula_api::server::v1::ShardDesc;
     use tempdir::TempDir;
 
@@ -1132,6 +1680,7 @@ mod tests {
                     id: shard_id,
                     collection_id: 1,
                     partition: Some(Partition::Range(RangePartition { start, end })),
+                    ..Default::default()
                 }],
                 ..Default::default()
             }),
@@ -1370,6 +1919,35 @@ mod tests {
         });
     }
 
+    #[test]
+    fn hide_expired_values() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let group_engine = create_engine(executor.clone(), 1, 1);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut wb = WriteBatch::default();
+        group_engine
+            .put_expirable(&mut wb, 1, b"a12345678", b"123", 123, now - 1)
+            .unwrap();
+        group_engine
+            .put_expirable(&mut wb, 1, b"b12345678", b"123", 123, now + 3600)
+            .unwrap();
+        group_engine
+            .commit(wb, WriteStates::default(), false)
+            .unwrap();
+
+        executor.block_on(async move {
+            let v = group_engine.get(1, b"a12345678").await.unwrap();
+            assert!(v.is_none());
+
+            let v = group_engine.get_expirable(1, b"b12345678").await.unwrap();
+            assert_eq!(v, Some((b"123".to_vec(), now + 3600)));
+        });
+    }
+
     #[test]
     fn iterate_in_range() {
         let executor_owner = ExecutorOwner::new(1);
@@ -1398,6 +1976,7 @@ mod tests {
                             start: vec![],
                             end: b"b".to_vec(),
                         })),
+                        ..Default::default()
                     },
                     ShardDesc {
                         id: 2,
@@ -1406,6 +1985,7 @@ mod tests {
                             start: b"b".to_vec(),
                             end: vec![],
                         })),
+                        ..Default::default()
                     },
                 ],
                 ..Default::default()
@@ -1459,6 +2039,7 @@ mod tests {
                             slot_id: shard_1_slot_id,
                             slots,
                         })),
+                        ..Default::default()
                     },
                     ShardDesc {
                         id: 2,
@@ -1467,6 +2048,7 @@ mod tests {
                             slot_id: shard_2_slot_id,
                             slots,
                         })),
+                        ..Default::default()
                     },
                 ],
                 ..Default::default()
@@ -1504,6 +2086,66 @@ mod tests {
         assert!(user_data_iter.next().is_none());
     }
 
+    #[test]
+    fn rewrite_collections() {
+        use shard_desc::*;
+
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let group_engine = create_engine(executor.clone(), 1, 1);
+        let mut wb = WriteBatch::default();
+        group_engine.put(&mut wb, 1, b"a", b"1", 123).unwrap();
+        group_engine.put(&mut wb, 1, b"b", b"2", 123).unwrap();
+        group_engine
+            .put_index(&mut wb, 1, 2, b"idx", b"a", 123)
+            .unwrap();
+        group_engine
+            .put_index(&mut wb, 1, 3, b"idx", b"b", 123)
+            .unwrap();
+        group_engine
+            .commit(wb, WriteStates::default(), false)
+            .unwrap();
+
+        // The index 3 is dropped.
+        let id_mapping = HashMap::from([(1, 5), (2, 6)]);
+        group_engine.rewrite_collections(&id_mapping).unwrap();
+        let desc = GroupDesc {
+            id: 1,
+            shards: vec![ShardDesc {
+                id: 1,
+                collection_id: 5,
+                partition: Some(Partition::Range(RangePartition {
+                    start: vec![],
+                    end: vec![],
+                })),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let states = WriteStates {
+            descriptor: Some(desc),
+            ..Default::default()
+        };
+        group_engine
+            .commit(WriteBatch::default(), states, false)
+            .unwrap();
+
+        let ids = group_engine
+            .raw_iter()
+            .unwrap()
+            .map(|item| item.unwrap().0)
+            .filter(|key| !keys::is_local(key))
+            .map(|key| u64::from_le_bytes(key[..8].try_into().unwrap()))
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec![5, 5, 6]);
+        executor.block_on(async move {
+            let v = group_engine.get(1, b"a").await.unwrap();
+            assert_eq!(v, Some(b"1".to_vec()));
+            let v = group_engine.get(1, b"b").await.unwrap();
+            assert_eq!(v, Some(b"2".to_vec()));
+        });
+    }
+
     #[test]
     fn cf_id_irrelevant_write_batch() {
         let executor_owner = ExecutorOwner::new(1);
@@ -1518,4 +2160,199 @@ mod tests {
 
         engine_2.commit(wb, WriteStates::default(), false).unwrap();
     }
+
+    #[test]
+    fn ingest_external_sst_files() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let group_engine = create_engine(executor.clone(), 1, 1);
+
+        let mut wb = WriteBatch::default();
+        group_engine
+            .put(&mut wb, 1, b"a", b"1", u64::MAX - 1)
+            .unwrap();
+        group_engine
+            .put(&mut wb, 1, b"c", b"3", u64::MAX - 1)
+            .unwrap();
+        group_engine
+            .commit(wb, WriteStates::default(), false)
+            .unwrap();
+
+        let dir = TempDir::new("ingest_external_sst_files").unwrap();
+        let path = dir.path().join("1.sst");
+        let records = vec![
+            (b"b".to_vec(), b"2".to_vec()),
+            (b"a".to_vec(), b"0".to_vec()),
+            (b"a".to_vec(), b"11".to_vec()),
+        ];
+        assert_eq!(
+            crate::bulkload::write_sst_file(&path, &group_engine.shard_desc(1).unwrap(), records)
+                .unwrap(),
+            2
+        );
+        group_engine.ingest_external_files(vec![path]).unwrap();
+
+        executor.block_on(async {
+            let get = |key: &'static [u8]| group_engine.get(1, key);
+            assert_eq!(get(b"a").await.unwrap(), Some(b"11".to_vec()));
+            assert_eq!(get(b"b").await.unwrap(), Some(b"2".to_vec()));
+            assert_eq!(get(b"c").await.unwrap(), Some(b"3".to_vec()));
+        });
+    }
+
+    #[test]
+    fn read_and_decode_sst_file() {
+        use shard_desc::*;
+
+        let shard_of = |partition| ShardDesc {
+            id: 1,
+            collection_id: 1,
+            partition: Some(partition),
+            ..Default::default()
+        };
+        let range_shard = shard_of(Partition::Range(RangePartition {
+            start: b"a".to_vec(),
+            end: b"c".to_vec(),
+        }));
+        let dir = TempDir::new("read_and_decode_sst_file").unwrap();
+        let path = dir.path().join("1.sst");
+        let records = vec![
+            (b"b".to_vec(), b"2".to_vec()),
+            (b"a-long-key-in-groups".to_vec(), b"1".to_vec()),
+        ];
+        crate::bulkload::write_sst_file(&path, &range_shard, records.clone()).unwrap();
+
+        let mut decoded = vec![];
+        let scratch_dir = dir.path().join("scratch");
+        let key_manager = DataKeyManager::plaintext();
+        read_sst_file(&key_manager, &path, &scratch_dir, |key, value| {
+            decoded.push(decode_shard_record(&range_shard, key, value).unwrap());
+            Ok(())
+        })
+        .unwrap();
+        assert!(!scratch_dir.exists());
+        assert_eq!(decoded, vec![records[1].clone(), records[0].clone()]);
+
+        let (key, value) = encode_record(1, None, b"b", b"2", u64::MAX - 1);
+        let other_collection = encode_record(2, None, b"b", b"2", u64::MAX - 1).0;
+        let out_of_range = encode_record(1, None, b"c", b"3", u64::MAX - 1).0;
+        assert!(decode_shard_record(&range_shard, &key, &value).is_some());
+        assert!(decode_shard_record(&range_shard, &key, values::tombstone()).is_none());
+        assert!(decode_shard_record(&range_shard, &other_collection, &value).is_none());
+        assert!(decode_shard_record(&range_shard, &out_of_range, &value).is_none());
+        assert!(decode_shard_record(&range_shard, &key[..key.len() - 1], &value).is_none());
+
+        let slots = 4;
+        let slot_id = shard::key_slot(b"b", slots);
+        let hash_shard = shard_of(Partition::Hash(HashPartition { slot_id, slots }));
+        let (key, value) = encode_record(1, Some(slot_id), b"b", b"2", u64::MAX - 1);
+        let other_slot = encode_record(1, Some(slot_id + 1), b"b", b"2", u64::MAX - 1).0;
+        assert!(decode_shard_record(&hash_shard, &key, &value).is_some());
+        assert!(decode_shard_record(&hash_shard, &other_slot, &value).is_none());
+    }
+
+    #[test]
+    fn encrypt_values_at_rest() {
+        use shard_desc::*;
+
+        use crate::{
+            engine::open_engine, DbConfig, EncryptionConfig, EncryptionMethod, MasterKeyConfig,
+        };
+
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        let dir = TempDir::new("encrypt_values_at_rest").unwrap();
+        let master_key = dir.path().join("master.key");
+        std::fs::write(&master_key, hex::encode(rand::random::<[u8; 32]>())).unwrap();
+        let cfg = EncryptionConfig {
+            method: EncryptionMethod::Aes256Ctr,
+            master_key: MasterKeyConfig::File { path: master_key },
+            ..Default::default()
+        };
+        let key_manager = DataKeyManager::open(&dir.path().join("keys"), &cfg).unwrap();
+        let db_dir = dir.path().join("db");
+        let db = open_engine(&DbConfig::default(), db_dir, Arc::new(key_manager)).unwrap();
+        let db = Arc::new(db);
+        let group_engine = executor
+            .block_on(GroupEngine::create(
+                &EngineConfig::default(),
+                db.clone(),
+                1,
+                1,
+            ))
+            .unwrap();
+        let states = WriteStates {
+            descriptor: Some(GroupDesc {
+                id: 1,
+                shards: vec![ShardDesc {
+                    id: 1,
+                    collection_id: 1,
+                    partition: Some(Partition::Range(RangePartition::default())),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        group_engine
+            .commit(WriteBatch::default(), states, false)
+            .unwrap();
+
+        let mut wb = WriteBatch::default();
+        group_engine
+            .put(&mut wb, 1, b"a", b"written", u64::MAX - 1)
+            .unwrap();
+        group_engine
+            .commit(wb, WriteStates::default(), false)
+            .unwrap();
+        let path = dir.path().join("1.sst");
+        let records = vec![(b"b".to_vec(), b"ingested".to_vec())];
+        crate::bulkload::write_sst_file(&path, &group_engine.shard_desc(1).unwrap(), records)
+            .unwrap();
+        let key_manager = group_engine.key_manager();
+        let scratch_dir = dir.path().join("scratch");
+        encrypt_sst_file(key_manager, &path, &scratch_dir).unwrap();
+
+        // The values of the staged file are encrypted, and they are decrypted when it is read.
+        let tags = scan_sst_file(&path, &scratch_dir, |iter| {
+            iter.map(|item| -> Result<u8> { Ok(item?.1[0]) })
+                .collect::<Result<Vec<_>>>()
+        })
+        .unwrap();
+        assert_eq!(tags, vec![values::ENCRYPTED]);
+        let mut staged = vec![];
+        read_sst_file(key_manager, &path, &scratch_dir, |_, value| {
+            staged.push(value.to_vec());
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(staged, vec![values::data(b"ingested")]);
+        group_engine.ingest_external_files(vec![path]).unwrap();
+
+        // The values of the user data are encrypted in rocksdb, but the local states aren't.
+        let cf_handle = db.cf_handle(&group_engine.name).unwrap();
+        let mut num_values = 0;
+        for item in db.db.iterator_cf(&cf_handle, rocksdb::IteratorMode::Start) {
+            let (key, value) = item.unwrap();
+            if !keys::is_local(&key) {
+                assert_eq!(value[0], values::ENCRYPTED);
+                num_values += 1;
+            }
+        }
+        assert_eq!(num_values, 2);
+        assert!(group_engine.flushed_apply_state().is_ok());
+
+        // The values are decrypted when they are read.
+        executor.block_on(async {
+            let get = |key: &'static [u8]| group_engine.get(1, key);
+            assert_eq!(get(b"a").await.unwrap(), Some(b"written".to_vec()));
+            assert_eq!(get(b"b").await.unwrap(), Some(b"ingested".to_vec()));
+        });
+        for item in group_engine.raw_iter().unwrap() {
+            let (key, value) = item.unwrap();
+            if !keys::is_local(&key) {
+                assert_eq!(value[0], values::DATA);
+            }
+        }
+    }
 }
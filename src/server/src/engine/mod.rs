@@ -23,21 +23,33 @@ use std::{
 use tracing::info;
 
 pub(crate) use self::{
-    group::{GroupEngine, RawIterator, SnapshotMode, WriteBatch, WriteStates},
+    group::{
+        decode_shard_record, encode_record, encrypt_sst_file, read_sst_file,
+        write_encrypted_sst_file, GroupEngine, RawIterator, SnapshotMode, WriteBatch, WriteStates,
+    },
     state::StateEngine,
 };
-use crate::{DbConfig, Result};
+use crate::{
+    encryption::{DataKeyManager, EncryptedFileSystem},
+    raftgroup::RaftEngine,
+    DbConfig, EncryptionConfig, Result,
+};
 
 // The disk layouts.
 const LAYOUT_DATA: &str = "db";
 const LAYOUT_LOG: &str = "log";
 const LAYOUT_SNAP: &str = "snap";
+const LAYOUT_BACKUP: &str = "backup";
+const LAYOUT_INGEST: &str = "ingest";
+const LAYOUT_ENCRYPTION: &str = "encryption";
 
 type DbResult<T> = Result<T, rocksdb::Error>;
 
 pub(crate) struct RawDb {
     pub options: rocksdb::Options,
     pub db: rocksdb::DB,
+    /// The data keys to encrypt the values of the user data.
+    pub key_manager: Arc<DataKeyManager>,
 }
 
 impl RawDb {
@@ -99,6 +111,15 @@ impl RawDb {
         self.db.iterator_cf_opt(cf_handle, readopts, mode)
     }
 
+    #[inline]
+    pub fn property_int_value_cf(
+        &self,
+        cf: &impl rocksdb::AsColumnFamilyRef,
+        name: &str,
+    ) -> DbResult<Option<u64>> {
+        self.db.property_int_value_cf(cf, name)
+    }
+
     #[inline]
     pub fn ingest_external_file_cf_opts<P: AsRef<Path>>(
         &self,
@@ -113,33 +134,49 @@ impl RawDb {
 #[derive(Clone)]
 pub(crate) struct Engines {
     log_path: PathBuf,
-    _db_path: PathBuf,
-    log: Arc<raft_engine::Engine>,
+    db_path: PathBuf,
+    log: Arc<RaftEngine>,
     db: Arc<RawDb>,
     state: StateEngine,
+    key_manager: Arc<DataKeyManager>,
 }
 
 impl Engines {
-    pub(crate) fn open(root_dir: &Path, db_cfg: &DbConfig) -> Result<Self> {
+    pub(crate) fn open(
+        root_dir: &Path,
+        db_cfg: &DbConfig,
+        encryption_cfg: &EncryptionConfig,
+    ) -> Result<Self> {
         let db_path = root_dir.join(LAYOUT_DATA);
         let log_path = root_dir.join(LAYOUT_LOG);
-        let db = Arc::new(open_engine(db_cfg, &db_path)?);
-        let log = Arc::new(open_raft_engine(&log_path)?);
+        let key_manager = Arc::new(DataKeyManager::open(
+            &root_dir.join(LAYOUT_ENCRYPTION),
+            encryption_cfg,
+        )?);
+        let db = Arc::new(open_engine(db_cfg, &db_path, key_manager.clone())?);
+        let log = Arc::new(open_raft_engine(&log_path, key_manager.clone())?);
         let state = StateEngine::new(log.clone());
         Ok(Engines {
             log_path,
-            _db_path: db_path,
+            db_path,
             log,
             db,
             state,
+            key_manager,
         })
     }
 
     #[inline]
-    pub(crate) fn log(&self) -> Arc<raft_engine::Engine> {
+    pub(crate) fn log(&self) -> Arc<RaftEngine> {
         self.log.clone()
     }
 
+    /// The data keys to encrypt the raft logs, snapshot files and the values of the user data.
+    #[inline]
+    pub(crate) fn key_manager(&self) -> Arc<DataKeyManager> {
+        self.key_manager.clone()
+    }
+
     #[inline]
     pub(crate) fn db(&self) -> Arc<RawDb> {
         self.db.clone()
@@ -154,9 +191,47 @@ impl Engines {
     pub(crate) fn snap_dir(&self) -> PathBuf {
         self.log_path.join(LAYOUT_SNAP)
     }
+
+    /// The directory to stage the checkpoints before they are uploaded to the backup storage.
+    #[inline]
+    pub(crate) fn backup_dir(&self) -> PathBuf {
+        self.log_path.join(LAYOUT_BACKUP)
+    }
+
+    /// The directory to stage the external SST files before they are ingested.
+    #[inline]
+    pub(crate) fn ingest_dir(&self) -> PathBuf {
+        self.log_path.join(LAYOUT_INGEST)
+    }
+
+    /// Return the total and used bytes of the disk which stores the data.
+    pub(crate) fn disk_usage(&self) -> Result<(u64, u64)> {
+        disk_usage(&self.db_path)
+    }
+}
+
+#[allow(clippy::unnecessary_cast)]
+fn disk_usage(path: &Path) -> Result<(u64, u64)> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let mut stat = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+    if unsafe { libc::statvfs(path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let stat = unsafe { stat.assume_init() };
+    let fragment_size = stat.f_frsize as u64;
+    let capacity = stat.f_blocks as u64 * fragment_size;
+    let used = capacity.saturating_sub(stat.f_bfree as u64 * fragment_size);
+    Ok((capacity, used))
 }
 
-pub(crate) fn open_engine<P: AsRef<Path>>(cfg: &DbConfig, path: P) -> Result<RawDb> {
+pub(crate) fn open_engine<P: AsRef<Path>>(
+    cfg: &DbConfig,
+    path: P,
+    key_manager: Arc<DataKeyManager>,
+) -> Result<RawDb> {
     use rocksdb::DB;
 
     std::fs::create_dir_all(&path)?;
@@ -171,13 +246,21 @@ pub(crate) fn open_engine<P: AsRef<Path>>(cfg: &DbConfig, path: P) -> Result<Raw
                 path,
                 cfs.into_iter().map(|name| (name, options.clone())),
             )?;
-            Ok(RawDb { db, options })
+            Ok(RawDb {
+                db,
+                options,
+                key_manager,
+            })
         }
         Err(e) => {
             if e.as_ref().ends_with("CURRENT: No such file or directory") {
                 info!("create new local db");
                 let db = DB::open(&options, &path)?;
-                Ok(RawDb { db, options })
+                Ok(RawDb {
+                    db,
+                    options,
+                    key_manager,
+                })
             } else {
                 Err(e.into())
             }
@@ -185,8 +268,8 @@ pub(crate) fn open_engine<P: AsRef<Path>>(cfg: &DbConfig, path: P) -> Result<Raw
     }
 }
 
-fn open_raft_engine(log_path: &Path) -> Result<raft_engine::Engine> {
-    use raft_engine::{Config, Engine};
+fn open_raft_engine(log_path: &Path, key_manager: Arc<DataKeyManager>) -> Result<RaftEngine> {
+    use raft_engine::Config;
     let engine_dir = log_path.join("engine");
     let snap_dir = log_path.join("snap");
     create_dir_all_if_not_exists(&engine_dir)?;
@@ -196,7 +279,8 @@ fn open_raft_engine(log_path: &Path) -> Result<raft_engine::Engine> {
         enable_log_recycle: false,
         ..Default::default()
     };
-    Ok(Engine::open(engine_cfg)?)
+    let file_system = Arc::new(EncryptedFileSystem::new(key_manager));
+    Ok(RaftEngine::open_with_file_system(engine_cfg, file_system)?)
 }
 
 fn create_dir_all_if_not_exists<P: AsRef<Path>>(dir: &P) -> Result<()> {
@@ -16,7 +16,7 @@ use std::sync::Arc;
 
 use engula_api::server::v1::*;
 
-use crate::{constants::STATE_REPLICA_ID, serverpb::v1::*, Result};
+use crate::{constants::STATE_REPLICA_ID, raftgroup::RaftEngine, serverpb::v1::*, Result};
 
 /// A structure supports saving and loading local states.
 ///
@@ -32,11 +32,11 @@ pub struct StateEngine
 where
     Self: Send + Sync,
 {
-    raw: Arc<raft_engine::Engine>,
+    raw: Arc<RaftEngine>,
 }
 
 impl StateEngine {
-    pub fn new(raw: Arc<raft_engine::Engine>) -> Self {
+    pub fn new(raw: Arc<RaftEngine>) -> Self {
         StateEngine { raw }
     }
 
@@ -22,6 +22,9 @@ pub enum Error {
     #[error("invalid argument {0}")]
     InvalidArgument(String),
 
+    #[error("invalid argument value violates schema at {path}: {reason}")]
+    SchemaViolation { path: String, reason: String },
+
     #[error("deadline exceeded {0}")]
     DeadlineExceeded(String),
 
@@ -129,6 +132,16 @@ impl From<Error> for tonic::Status {
 
         match e {
             Error::InvalidArgument(msg) => Status::invalid_argument(msg),
+            Error::SchemaViolation { path, reason } => {
+                let msg = schema_violation_message(&path, &reason);
+                Status::with_details(
+                    Code::InvalidArgument,
+                    msg.clone(),
+                    v1::Error::schema_violation(path, reason, msg)
+                        .encode_to_vec()
+                        .into(),
+                )
+            }
             Error::DeadlineExceeded(msg) => Status::deadline_exceeded(msg),
             err @ Error::DatabaseNotFound(_) => Status::not_found(err.to_string()),
             err @ Error::AlreadyExists(_) => Status::already_exists(err.to_string()),
@@ -179,6 +192,10 @@ impl From<Error> for tonic::Status {
     }
 }
 
+fn schema_violation_message(path: &str, reason: &str) -> String {
+    format!("value violates schema at {path}: {reason}")
+}
+
 impl From<futures::channel::oneshot::Canceled> for Error {
     fn from(_: futures::channel::oneshot::Canceled) -> Self {
         Error::Canceled
@@ -213,6 +230,10 @@ impl From<Error> for engula_api::server::v1::Error {
             Error::EpochNotMatch(desc) => v1::Error::not_match(desc),
 
             Error::InvalidArgument(msg) => v1::Error::status(Code::InvalidArgument.into(), msg),
+            Error::SchemaViolation { path, reason } => {
+                let msg = schema_violation_message(&path, &reason);
+                v1::Error::schema_violation(path, reason, msg)
+            }
             Error::DeadlineExceeded(msg) => v1::Error::status(Code::DeadlineExceeded.into(), msg),
 
             Error::Forward(_) => panic!("Forward only used inside node"),
@@ -244,6 +265,17 @@ impl From<engula_api::server::v1::Error> for Error {
     }
 }
 
+impl From<engula_client::AppError> for Error {
+    fn from(err: engula_client::AppError) -> Self {
+        match err {
+            engula_client::AppError::InvalidArgument(v) => Error::InvalidArgument(v),
+            engula_client::AppError::DeadlineExceeded(v) => Error::DeadlineExceeded(v),
+            engula_client::AppError::AlreadyExists(v) => Error::AlreadyExists(v),
+            err => Error::Rpc(err.into()),
+        }
+    }
+}
+
 impl From<engula_client::Error> for Error {
     fn from(err: engula_client::Error) -> Self {
         match err {
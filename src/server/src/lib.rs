@@ -29,6 +29,9 @@ mod schedule;
 mod service;
 mod transport;
 
+pub mod backup;
+pub mod bulkload;
+pub mod encryption;
 pub mod node;
 pub mod raftgroup;
 pub mod runtime;
@@ -40,7 +43,7 @@ pub use crate::{
     bootstrap::run,
     config::*,
     error::{Error, Result},
-    root::diagnosis,
+    root::{diagnosis, sim},
     service::Server,
 };
 
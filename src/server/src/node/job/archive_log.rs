@@ -0,0 +1,190 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{sync::Arc, time::Duration};
+
+use prost::Message;
+use raft::prelude::EntryType;
+use tracing::{debug, error, warn};
+
+use crate::{
+    backup::{self, ExternalStorage},
+    node::Replica,
+    raftgroup::{read_entries, RaftEngine},
+    runtime::{sync::WaitGroup, TaskPriority},
+    serverpb::v1::{ArchivedEntry, ArchivedLog, EvalResult, SyncOp},
+    ReplicaConfig, Result,
+};
+
+/// Archive the log entries applied by the group to the backup destination, it only works on the
+/// leader. The archived position is replicated by proposing a `SyncOp`, and the entries after it
+/// are retained by all replicas.
+pub(crate) fn setup(
+    cfg: ReplicaConfig,
+    replica: Arc<Replica>,
+    raft_engine: Arc<RaftEngine>,
+    wait_group: WaitGroup,
+) {
+    let Some(destination) = cfg.log_archive.clone() else {
+        return;
+    };
+
+    let group_id = replica.replica_info().group_id;
+    crate::runtime::current().spawn(Some(group_id), TaskPriority::IoLow, async move {
+        archive_main(cfg, destination, replica, raft_engine).await;
+        drop(wait_group);
+    });
+}
+
+async fn archive_main(
+    cfg: ReplicaConfig,
+    destination: String,
+    replica: Arc<Replica>,
+    raft_engine: Arc<RaftEngine>,
+) {
+    let info = replica.replica_info();
+    let group_id = info.group_id;
+    let replica_id = info.replica_id;
+
+    // The destination is checked when the server starts, an error here means that the log
+    // entries are retained forever, so it is fatal.
+    let storage = backup::open_storage(&destination).unwrap_or_else(|err| {
+        panic!("group {group_id} replica {replica_id} open log archive {destination}: {err}")
+    });
+
+    while let Ok(Some(term)) = replica.on_leader("archive_log", false).await {
+        // Sleep in small steps, so the replica could be shutdown timely.
+        for _ in 0..cfg.log_archive_interval_sec.max(1) {
+            crate::runtime::time::sleep(Duration::from_secs(1)).await;
+            if info.is_terminated() {
+                break;
+            }
+        }
+        match replica.on_leader("archive_log", true).await {
+            Ok(Some(current_term)) if current_term == term => {}
+            Ok(_) => continue,
+            Err(_) => break,
+        }
+        if let Err(err) = archive_applied_entries(&*storage, &replica, &raft_engine).await {
+            warn!("group {group_id} replica {replica_id} archive log entries: {err}");
+        }
+    }
+    debug!("group {group_id} replica {replica_id} log archiver is stopped");
+}
+
+async fn archive_applied_entries(
+    storage: &dyn ExternalStorage,
+    replica: &Replica,
+    raft_engine: &RaftEngine,
+) -> Result<()> {
+    const MAX_CHUNK_SIZE: usize = 4 << 20;
+
+    let info = replica.replica_info();
+    let group_id = info.group_id;
+    let replica_id = info.replica_id;
+    let group_engine = replica.group_engine();
+    let applied_index = group_engine.flushed_apply_state()?.index;
+    let first_index = raft_engine
+        .first_index(replica_id)
+        .unwrap_or(applied_index + 1);
+
+    let archived_index = match group_engine.archive_state()? {
+        Some(state) => state.index,
+        None => {
+            // Retain the entries from now on, before any entry is archived.
+            let index = first_index - 1;
+            replica.archive_log(index).await?;
+            index
+        }
+    };
+
+    let mut next_index = archived_index + 1;
+    if next_index < first_index {
+        warn!(
+            "group {group_id} replica {replica_id} log entries in [{next_index}, {first_index}) are compacted before archiving"
+        );
+        next_index = first_index;
+    }
+
+    while next_index <= applied_index {
+        let entries = read_entries(
+            raft_engine,
+            replica_id,
+            next_index,
+            applied_index + 1,
+            MAX_CHUNK_SIZE,
+        )?;
+        let (Some(first), Some(last)) = (entries.first(), entries.last()) else {
+            break;
+        };
+        let (first_index, last_index) = (first.index, last.index);
+        let entries = entries
+            .into_iter()
+            .filter(|e| e.get_entry_type() == EntryType::EntryNormal && !is_noop(&e.data))
+            .map(|e| ArchivedEntry {
+                index: e.index,
+                term: e.term,
+                data: e.data.to_vec(),
+            })
+            .collect::<Vec<_>>();
+        if entries.is_empty() {
+            // Advancing the archived position is itself a log entry, so proposing it for the
+            // chunks carrying nothing would keep an idle group archiving forever. These entries
+            // are retained until the next chunk carrying writes covers them.
+            break;
+        }
+        let chunk = ArchivedLog {
+            group_id,
+            first_index,
+            last_index,
+            archived_time: unix_timestamp_secs(),
+            entries,
+        };
+        storage
+            .put(
+                &backup::log_file(group_id, first_index, last_index),
+                chunk.encode_to_vec(),
+            )
+            .await?;
+        replica.archive_log(last_index).await?;
+        debug!("group {group_id} replica {replica_id} archive log entries [{first_index}, {last_index}]");
+        next_index = last_index + 1;
+    }
+    Ok(())
+}
+
+/// Whether the entry changes nothing to restore: the empty entries appended by new leaders, and the
+/// proposals only advancing the archived position.
+fn is_noop(data: &[u8]) -> bool {
+    if data.is_empty() {
+        return true;
+    }
+    match EvalResult::decode(data) {
+        Ok(EvalResult {
+            batch: None,
+            op: Some(op),
+        }) => match op.archive_state.as_ref() {
+            Some(state) => SyncOp::archive_state(state.index) == op,
+            None => false,
+        },
+        _ => false,
+    }
+}
+
+fn unix_timestamp_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -19,7 +19,7 @@ use tracing::error;
 use crate::{
     engine::{Engines, GroupEngine, RawDb, StateEngine},
     node::metrics::*,
-    raftgroup::destory_storage,
+    raftgroup::{destory_storage, RaftEngine},
     record_latency,
     runtime::TaskPriority,
     serverpb::v1::ReplicaLocalState,
@@ -48,7 +48,7 @@ async fn destory_replica(
     replica_id: u64,
     state_engine: StateEngine,
     raw_db: Arc<RawDb>,
-    raft_engine: Arc<raft_engine::Engine>,
+    raft_engine: Arc<RaftEngine>,
 ) -> Result<()> {
     record_latency!(take_destory_replica_metrics());
     match GroupEngine::destory(group_id, replica_id, raw_db).await {
@@ -85,7 +85,7 @@ mod tests {
 
         let executor_owner = ExecutorOwner::new(1);
 
-        use raft_engine::{Config, Engine};
+        use raft_engine::Config;
         let engine_dir = log_path.join("engine");
         let snap_dir = log_path.join("snap");
         create_dir_all_if_not_exists(&engine_dir).unwrap();
@@ -94,7 +94,8 @@ mod tests {
             dir: engine_dir.to_str().unwrap().to_owned(),
             ..Default::default()
         };
-        let engine = Arc::new(Engine::open(engine_cfg).unwrap());
+        let engine =
+            Arc::new(RaftEngine::open_with_file_system(engine_cfg, Arc::default()).unwrap());
         let state_engine = StateEngine::new(engine.clone());
         executor_owner.executor().block_on(async {
             destory_replica(group_id, replica_id, state_engine, raw_db, engine)
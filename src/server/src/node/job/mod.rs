@@ -12,8 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod archive_log;
 mod destory_replica;
 mod report_state;
+mod rotate_data_key;
 
+pub(crate) use archive_log::setup as setup_archive_log;
 pub(crate) use destory_replica::setup as setup_destory_replica;
 pub(crate) use report_state::{setup as setup_report_state, StateChannel};
+pub(crate) use rotate_data_key::setup as setup_rotate_data_key;
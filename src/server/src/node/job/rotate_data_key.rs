@@ -0,0 +1,41 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{sync::Arc, time::Duration};
+
+use tracing::{info, warn};
+
+use crate::{encryption::DataKeyManager, runtime::TaskPriority};
+
+/// The interval to check whether the current data key is expired.
+const CHECK_INTERVAL: Duration = Duration::from_secs(600);
+
+/// Rotate the data key once it is older than the rotation period. The files encrypted by the
+/// previous keys are still readable, only the new files are encrypted by the new key.
+pub(crate) fn setup(key_manager: Arc<DataKeyManager>) {
+    if !key_manager.is_enabled() {
+        return;
+    }
+
+    crate::runtime::current().spawn(None, TaskPriority::IoLow, async move {
+        loop {
+            crate::runtime::time::sleep(CHECK_INTERVAL).await;
+            match key_manager.maybe_rotate() {
+                Ok(true) => info!("the data key is rotated"),
+                Ok(false) => {}
+                Err(err) => warn!("rotate data key: {err}"),
+            }
+        }
+    });
+}
@@ -21,6 +21,7 @@ pub mod route_table;
 
 use std::{
     collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
@@ -34,10 +35,20 @@ pub use self::{
     route_table::{RaftRouteTable, ReplicaRouteTable},
 };
 use crate::{
+    backup::{self, ExternalStorage},
     constants::ROOT_GROUP_ID,
-    engine::{Engines, GroupEngine, RawDb, StateEngine},
-    node::replica::{fsm::GroupStateMachine, ExecCtx, LeaseState, LeaseStateObserver, ReplicaInfo},
-    raftgroup::{snap::RecycleSnapMode, ChannelManager, RaftManager, RaftNodeFacade, SnapManager},
+    encryption::DataKeyManager,
+    engine::{encrypt_sst_file, Engines, GroupEngine, RawDb, StateEngine, WriteBatch, WriteStates},
+    node::replica::{
+        fsm::{
+            apply_snapshot, sst_stage_dir, staged_sst_file, GroupSnapshotBuilder, GroupStateMachine,
+        },
+        ChecksumTable, ExecCtx, LeaseState, LeaseStateObserver, ReplicaInfo, ScrubReport, SstStage,
+    },
+    raftgroup::{
+        snap::RecycleSnapMode, write_recovered_state, ChannelManager, RaftManager, RaftNodeFacade,
+        SnapLimiter, SnapManager,
+    },
     runtime::sync::WaitGroup,
     schedule::MoveReplicasProvider,
     serverpb::v1::*,
@@ -104,7 +115,9 @@ impl Node {
         )
         .await;
         let snap_dir = engines.snap_dir();
-        let snap_mgr = SnapManager::recovery(snap_dir).await?;
+        let snap_mgr =
+            SnapManager::recovery(snap_dir, SnapLimiter::new(&cfg.raft), engines.key_manager())
+                .await?;
         let raft_mgr =
             RaftManager::open(cfg.raft.clone(), engines.log(), snap_mgr, trans_mgr).await?;
         let migrate_ctrl = MigrateController::new(cfg.node.clone(), transport_manager.clone());
@@ -135,6 +148,7 @@ impl Node {
 
         node_state.ident = Some(node_ident.to_owned());
         node_state.channel = Some(setup_report_state(&self.transport_manager));
+        setup_rotate_data_key(self.engines.key_manager());
 
         let node_id = node_ident.node_id;
         for (group_id, replica_id, state) in self.state_engine.replica_states().await? {
@@ -193,6 +207,17 @@ impl Node {
         // state. In this way, even if the node is restarted before the group is
         // successfully created, a replica can be recreated by retrying.
         Replica::create(replica_id, &group, &self.raft_mgr).await?;
+        // The replication factor isn't replicated until the replica receives a snapshot, it is
+        // required to decide the priority of the snapshot.
+        let group_engine = open_group_engine(
+            &self.cfg.engine,
+            self.engines.db(),
+            group_id,
+            replica_id,
+            ReplicaLocalState::Initial,
+        )
+        .await?;
+        group_engine.set_replication_factor(group.replication_factor)?;
         self.state_engine
             .save_replica_state(group_id, replica_id, ReplicaLocalState::Initial)
             .await?;
@@ -289,6 +314,106 @@ impl Node {
         Ok(())
     }
 
+    /// Unsafely recover the group of the specified replica from this replica alone.
+    ///
+    /// The replica will be restarted with a group descriptor which only contains itself as voter,
+    /// and all raft logs not yet applied will be dropped. The epoch of the new descriptor is larger
+    /// than both the local and the `root_desc`, so that the stale members could be removed by root.
+    pub async fn unsafe_recover_replica(
+        &self,
+        replica_id: u64,
+        root_desc: &GroupDesc,
+    ) -> Result<GroupDesc> {
+        let _mut_guard = self.replica_mutation.lock().await;
+
+        let group_id = root_desc.id;
+        let replica = match self.replica_route_table.find(group_id) {
+            Some(replica) if replica.replica_info().replica_id == replica_id => replica,
+            _ => return Err(Error::GroupNotFound(group_id)),
+        };
+        let info = replica.replica_info();
+        if info.local_state() != ReplicaLocalState::Normal {
+            return Err(Error::InvalidArgument(format!(
+                "group {group_id} replica {replica_id} is not in normal state"
+            )));
+        }
+
+        warn!("group {group_id} unsafe recover from replica {replica_id}");
+        replica.shutdown(root_desc).await?;
+        self.replica_route_table.remove(group_id);
+        self.raft_route_table.delete(replica_id);
+
+        let (wait_group, channel) = {
+            let mut node_state = self.node_state.lock().await;
+            node_state.serving_groups.remove(&group_id);
+            let ctx = node_state
+                .serving_replicas
+                .remove(&replica_id)
+                .expect("replica should exists before recovering");
+            (ctx.wait_group, node_state.channel.as_ref().unwrap().clone())
+        };
+        wait_group.wait().await;
+        drop(replica);
+
+        let group_engine = open_group_engine(
+            &self.cfg.engine,
+            self.engines.db(),
+            group_id,
+            replica_id,
+            ReplicaLocalState::Normal,
+        )
+        .await?;
+        let local_desc = group_engine.descriptor();
+        let replica_desc = ReplicaDesc {
+            id: replica_id,
+            node_id: info.node_id,
+            role: ReplicaRole::Voter as i32,
+        };
+        let desc = GroupDesc {
+            epoch: local_desc.epoch.max(root_desc.epoch) + 1,
+            replicas: vec![replica_desc.clone()],
+            ..local_desc
+        };
+        let states = WriteStates {
+            descriptor: Some(desc.clone()),
+            ..Default::default()
+        };
+        group_engine.commit(WriteBatch::default(), states, true)?;
+
+        // Ensure all applied data are persisted, since the unapplied logs will be dropped and
+        // nothing could be replayed after restarting.
+        group_engine.flush()?;
+        let apply_state = group_engine.flushed_apply_state()?;
+        drop(group_engine);
+
+        self.raft_mgr
+            .snapshot_manager()
+            .recycle_snapshots(replica_id, RecycleSnapMode::All);
+        write_recovered_state(
+            &self.raft_mgr.engine(),
+            replica_id,
+            EntryId {
+                index: apply_state.index,
+                term: apply_state.term,
+            },
+        )
+        .await?;
+
+        let context = self
+            .serve_replica(group_id, replica_desc, ReplicaLocalState::Normal, channel)
+            .await?;
+        let mut node_state = self.node_state.lock().await;
+        node_state.serving_replicas.insert(replica_id, context);
+        node_state.serving_groups.insert(group_id);
+
+        info!(
+            "group {group_id} unsafe recover from replica {replica_id} success, epoch {}",
+            desc.epoch
+        );
+
+        Ok(desc)
+    }
+
     /// Open, recover replica and start serving.
     async fn serve_replica(
         &self,
@@ -316,6 +441,7 @@ impl Node {
             group_engine.migration_state(),
             sender,
         )));
+        let checksums = Arc::new(ChecksumTable::default());
         let raft_node = start_raft_group(
             &self.cfg,
             &self.raft_mgr,
@@ -323,6 +449,8 @@ impl Node {
             lease_state.clone(),
             channel.clone(),
             group_engine.clone(),
+            self.engines.ingest_dir(),
+            checksums.clone(),
             wait_group.clone(),
         )
         .await?;
@@ -340,6 +468,7 @@ impl Node {
             raft_node.clone(),
             group_engine,
             move_replicas_provider.clone(),
+            checksums,
         );
         let replica = Arc::new(replica);
         self.replica_route_table.update(replica.clone());
@@ -358,6 +487,12 @@ impl Node {
             schedule_state_observer,
             wait_group.clone(),
         );
+        self::job::setup_archive_log(
+            self.cfg.replica.clone(),
+            replica.clone(),
+            self.raft_mgr.engine(),
+            wait_group.clone(),
+        );
 
         // Now that all initialization work is done, the replica is ready to serve, mark it as
         // normal state.
@@ -410,6 +545,12 @@ impl Node {
             }
         };
 
+        if let Some(group_request_union::Request::IngestSst(req)) =
+            request.request.as_ref().and_then(|r| r.request.as_ref())
+        {
+            return self.ingest_sst(&replica, req).await;
+        }
+
         forwardable_execute(&self.migrate_ctrl, &replica, &ExecCtx::default(), request).await
     }
 
@@ -488,8 +629,12 @@ impl Node {
         &self.raft_mgr
     }
 
+    #[inline]
+    pub fn key_manager(&self) -> Arc<DataKeyManager> {
+        self.engines.key_manager()
+    }
+
     pub async fn collect_stats(&self, _req: &CollectStatsRequest) -> CollectStatsResponse {
-        // TODO(walter) add read/write qps.
         let mut ns = NodeStats::default();
         let mut group_stats = vec![];
         let mut replica_stats = vec![];
@@ -511,27 +656,44 @@ impl Node {
                     // filter out the replica be removed by change_replica.
                     ns.group_count += 1;
                 }
+                let load = replica.load_rates();
+                ns.read_qps += load.read_qps as f32;
+                ns.write_qps += load.write_qps as f32;
                 let replica_state = replica.replica_state();
                 if replica_state.role == RaftRole::Leader as i32 {
                     ns.leader_count += 1;
                     let gs = GroupStats {
                         group_id: info.group_id,
                         shard_count: descriptor.shards.len() as u64,
-                        read_qps: 0.,
-                        write_qps: 0.,
+                        read_qps: load.read_qps as f32,
+                        write_qps: load.write_qps as f32,
                     };
                     group_stats.push(gs);
                 }
                 let rs = ReplicaStats {
                     replica_id: info.replica_id,
                     group_id: info.group_id,
-                    read_qps: 0.,
-                    write_qps: 0.,
+                    read_qps: load.read_qps as f32,
+                    write_qps: load.write_qps as f32,
+                    data_size: replica.approximate_size(),
+                    read_bytes_per_sec: load.read_bytes_per_sec as f32,
+                    write_bytes_per_sec: load.write_bytes_per_sec as f32,
                 };
                 replica_stats.push(rs);
             }
         }
 
+        match self.engines.disk_usage() {
+            Ok((capacity, used)) => {
+                ns.disk_capacity = capacity;
+                ns.disk_used = used;
+                ns.available_space = capacity - used;
+            }
+            Err(err) => {
+                warn!("collect disk usage: {err:?}");
+            }
+        }
+
         CollectStatsResponse {
             node_stats: Some(ns),
             group_stats,
@@ -624,6 +786,399 @@ impl Node {
         resp
     }
 
+    /// Take a checkpoint of the group on the leader replica, and upload the files to the
+    /// `destination` storage under `groups/<group_id>/`.
+    ///
+    /// The checkpoint is a consistent view of the group at the returned apply index, the caller
+    /// needs to check the returned descriptor to see which shards are covered.
+    pub async fn backup_replica(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+        destination: &str,
+    ) -> Result<BackupReplicaResponse> {
+        let replica = match self.replica_route_table.find(group_id) {
+            Some(replica) if replica.replica_info().replica_id == replica_id => replica,
+            _ => return Err(Error::GroupNotFound(group_id)),
+        };
+        if replica.on_leader("backup", true).await?.is_none() {
+            return Err(Error::NotLeader(group_id, 0, None));
+        }
+
+        let storage = backup::open_storage(destination)?;
+        let base_dir = self
+            .engines
+            .backup_dir()
+            .join(uuid::Uuid::new_v4().to_string());
+        let builder = GroupSnapshotBuilder::new(self.cfg.replica.clone(), replica.group_engine());
+        let result = upload_checkpoint(&*storage, &builder, group_id, &base_dir).await;
+        if let Err(err) = std::fs::remove_dir_all(&base_dir) {
+            warn!(
+                "group {group_id} replica {replica_id} remove backup dir {}: {err}",
+                base_dir.display()
+            );
+        }
+        let resp = result?;
+        info!(
+            "group {group_id} replica {replica_id} backup {} files ({} bytes) at index {} to {destination}",
+            resp.files.len(),
+            resp.size,
+            resp.apply_index
+        );
+        Ok(resp)
+    }
+
+    /// Return the checksum computed by the replica, `None` if the replica hasn't applied the
+    /// checksum request yet.
+    pub fn get_checksum(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+        checksum_id: u64,
+    ) -> Result<Option<ReplicaChecksum>> {
+        match self.replica_route_table.find(group_id) {
+            Some(replica) if replica.replica_info().replica_id == replica_id => {
+                Ok(replica.checksum_table().get(checksum_id))
+            }
+            _ => Err(Error::GroupNotFound(group_id)),
+        }
+    }
+
+    /// Download the SST files to be ingested into the replica, and return their crc32. The files
+    /// are staged until the ingestion is applied, or removed if the request has no files.
+    pub async fn stage_sst(&self, req: &StageSstRequest) -> Result<Vec<u32>> {
+        let group_id = req.group_id;
+        match self.replica_route_table.find(group_id) {
+            Some(replica) if replica.replica_info().replica_id == req.replica_id => {}
+            _ => return Err(Error::GroupNotFound(group_id)),
+        }
+
+        let stage_dir = sst_stage_dir(&self.engines.ingest_dir(), group_id, req.stage_id);
+        if req.files.is_empty() {
+            if stage_dir.exists() {
+                std::fs::remove_dir_all(&stage_dir)?;
+            }
+            return Ok(vec![]);
+        }
+        let key_manager = self.engines.key_manager();
+        let result = stage_sst_files(key_manager, &stage_dir, &req.storage, &req.files).await;
+        if result.is_err() && stage_dir.exists() {
+            std::fs::remove_dir_all(&stage_dir)?;
+        }
+        result
+    }
+
+    /// Ingest the SST files into a shard, the replica must be the leader. The files are staged on
+    /// all replicas before the ingestion is proposed, so applying it doesn't access the external
+    /// storage. The files are staged again if the replicas change before proposing.
+    async fn ingest_sst(&self, replica: &Replica, req: &IngestSstRequest) -> Result<GroupResponse> {
+        use self::replica::retry::do_ingest_sst;
+
+        const MAX_STAGE_TIMES: usize = 3;
+
+        if req.files.is_empty() {
+            return Err(Error::InvalidArgument(
+                "IngestSstRequest::files is empty".into(),
+            ));
+        }
+
+        let group_id = replica.replica_info().group_id;
+        let mut times = 0;
+        loop {
+            times += 1;
+            replica.check_leader_early()?;
+            let desc = replica.descriptor();
+            let mut stage = SstStage {
+                id: rand::random(),
+                epoch: desc.epoch,
+                files: vec![],
+                proposed: false,
+            };
+            let result = match self
+                .stage_sst_on_replicas(replica, &desc, &stage, req)
+                .await
+            {
+                Ok(files) => {
+                    stage.files = files;
+                    do_ingest_sst(replica, &mut stage, req).await
+                }
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(()) => {
+                    let resp = group_response_union::Response::IngestSst(IngestSstResponse {});
+                    return Ok(GroupResponse::new(resp));
+                }
+                Err(err) if !stage.proposed => {
+                    self.abort_staged_sst(replica, &desc, stage.id).await;
+                    match err {
+                        Error::EpochNotMatch(_) if times < MAX_STAGE_TIMES => {
+                            info!("group {group_id} replicas are changed, stage sst files again");
+                        }
+                        err => return Err(err),
+                    }
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Stage the SST files on all replicas of the descriptor, the staged files of the local
+    /// replica are returned once the checksums of all replicas are matched.
+    async fn stage_sst_on_replicas(
+        &self,
+        replica: &Replica,
+        desc: &GroupDesc,
+        stage: &SstStage,
+        req: &IngestSstRequest,
+    ) -> Result<Vec<PathBuf>> {
+        let group_id = desc.id;
+        let local_replica_id = replica.replica_info().replica_id;
+        let stage_req = |replica_id| StageSstRequest {
+            group_id,
+            replica_id,
+            stage_id: stage.id,
+            storage: req.storage.clone(),
+            files: req.files.clone(),
+        };
+        // The local replica stages first, so the files missing in the storage fail the request
+        // before the others download them.
+        let checksums = self.stage_sst(&stage_req(local_replica_id)).await?;
+        let stage_req = &stage_req;
+        let remote_checksums = desc
+            .replicas
+            .iter()
+            .filter(|r| r.id != local_replica_id)
+            .map(|r| async move {
+                // The errors of the remote replicas are not the ones of this request.
+                let stage_remote = async {
+                    let client = self.transport_manager.find_node_client(r.node_id)?;
+                    Ok::<_, engula_client::Error>(client.stage_sst(stage_req(r.id)).await?)
+                };
+                match stage_remote.await {
+                    Ok(checksums) => Ok((r.id, checksums)),
+                    Err(err) => Err(Error::Rpc(tonic::Status::unavailable(format!(
+                        "replica {} of group {group_id} stage sst files: {err}",
+                        r.id
+                    )))),
+                }
+            });
+        for (replica_id, remote) in futures::future::try_join_all(remote_checksums).await? {
+            if remote != checksums {
+                return Err(Error::InvalidData(format!(
+                    "checksums of the sst files staged by replica {replica_id} of group {group_id}"
+                )));
+            }
+        }
+
+        let stage_dir = sst_stage_dir(&self.engines.ingest_dir(), group_id, stage.id);
+        Ok((0..req.files.len())
+            .map(|i| staged_sst_file(&stage_dir, i))
+            .collect())
+    }
+
+    /// Remove the SST files staged for an ingestion which isn't proposed, the errors are ignored
+    /// since the replicas might be unreachable.
+    async fn abort_staged_sst(&self, replica: &Replica, desc: &GroupDesc, stage_id: u64) {
+        let group_id = desc.id;
+        let local_replica_id = replica.replica_info().replica_id;
+        for r in &desc.replicas {
+            let req = StageSstRequest {
+                group_id,
+                replica_id: r.id,
+                stage_id,
+                ..Default::default()
+            };
+            let result = if r.id == local_replica_id {
+                self.stage_sst(&req).await.map(|_| ())
+            } else {
+                match self.transport_manager.find_node_client(r.node_id) {
+                    Ok(client) => client.stage_sst(req).await.map(|_| ()).map_err(Into::into),
+                    Err(err) => Err(err.into()),
+                }
+            };
+            if let Err(err) = result {
+                warn!(
+                    "group {group_id} replica {} remove staged sst files of stage {stage_id}: {err}",
+                    r.id
+                );
+            }
+        }
+    }
+
+    /// Return the results of the last scrubbing of the groups, which is performed by the leader
+    /// replicas of this node.
+    pub async fn scrub_reports(&self) -> HashMap<u64, ScrubReport> {
+        let mut reports = HashMap::default();
+        for group_id in self.serving_group_id_list().await {
+            if let Some(replica) = self.replica_route_table.find(group_id) {
+                if let Some(report) = replica.checksum_table().last_report() {
+                    reports.insert(group_id, report);
+                }
+            }
+        }
+        reports
+    }
+
+    /// Create a replica with the data of a group in the backup, the replica is the only voter of
+    /// the new group. The archived log entries are replayed if `restore_time` is specified, and
+    /// the ids of the collections and indexes are rewritten by `id_mapping`.
+    ///
+    /// NOTE: This function is idempotent.
+    pub async fn restore_replica(&self, req: RestoreReplicaRequest) -> Result<()> {
+        let Some(group) = req.group.as_ref() else {
+            return Err(Error::InvalidArgument("the field `group` is empty".into()));
+        };
+        let group_id = group.id;
+        let replica_id = req.replica_id;
+        let backup = &req.backup;
+        let _mut_guard = self.replica_mutation.lock().await;
+        if self.check_replica_existence(group_id, replica_id).await? {
+            return Ok(());
+        }
+
+        let storage = backup::open_storage(backup)?;
+        let base_dir = self
+            .engines
+            .backup_dir()
+            .join(uuid::Uuid::new_v4().to_string());
+        let result = self
+            .load_backup_files(group, &*storage, &req, &base_dir)
+            .await;
+        if let Err(err) = std::fs::remove_dir_all(&base_dir) {
+            warn!(
+                "group {group_id} replica {replica_id} remove restore dir {}: {err}",
+                base_dir.display()
+            );
+        }
+        let apply_state = result?;
+
+        // The replica state is saved at last, so a partially loaded replica is never served.
+        write_recovered_state(&self.raft_mgr.engine(), replica_id, apply_state.clone()).await?;
+        self.state_engine
+            .save_replica_state(group_id, replica_id, ReplicaLocalState::Normal)
+            .await?;
+        info!(
+            "group {group_id} restore replica {replica_id} from {backup} success, apply state {apply_state:?}"
+        );
+
+        let mut node_state = self.node_state.lock().await;
+        if node_state.is_bootstrapped() {
+            let node_id = node_state.ident.as_ref().unwrap().node_id;
+            let desc = ReplicaDesc {
+                id: replica_id,
+                node_id,
+                ..Default::default()
+            };
+            let context = self
+                .serve_replica(
+                    group_id,
+                    desc,
+                    ReplicaLocalState::Normal,
+                    node_state.channel.as_ref().unwrap().clone(),
+                )
+                .await?;
+            node_state.serving_replicas.insert(replica_id, context);
+            node_state.serving_groups.insert(group_id);
+        }
+
+        Ok(())
+    }
+
+    /// Download the files of the backup and load them into the group engine of the replica.
+    async fn load_backup_files(
+        &self,
+        group: &GroupDesc,
+        storage: &dyn ExternalStorage,
+        req: &RestoreReplicaRequest,
+        base_dir: &Path,
+    ) -> Result<EntryId> {
+        let group_id = group.id;
+        let replica_id = req.replica_id;
+        std::fs::create_dir_all(base_dir)?;
+        let key_manager = self.engines.key_manager();
+        for name in &req.files {
+            let Some(file_name) = Path::new(name).file_name() else {
+                return Err(Error::InvalidData(format!("backup file {name}")));
+            };
+            let path = base_dir.join(file_name);
+            storage.get_file(name, &path).await?;
+            // The backups are plaintext, so the values are encrypted before they are loaded.
+            if path.extension().map(|ext| ext == "sst").unwrap_or_default() {
+                encrypt_sst_file(&key_manager, &path, &path.with_extension("scan"))?;
+            }
+        }
+
+        // Clean the leftover of the previous attempt, if any.
+        let raw_db = self.engines.db();
+        if GroupEngine::open(&self.cfg.engine, raw_db.clone(), group_id, replica_id)
+            .await?
+            .is_some()
+        {
+            GroupEngine::destory(group_id, replica_id, raw_db.clone()).await?;
+        }
+        let group_engine =
+            GroupEngine::create(&self.cfg.engine, raw_db, group_id, replica_id).await?;
+        apply_snapshot(&group_engine, replica_id, base_dir)?;
+        let apply_state = group_engine.flushed_apply_state()?;
+        if req.restore_time != 0 {
+            let replayed = backup::replay_archived_logs(
+                storage,
+                req.source_group,
+                req.apply_index,
+                req.restore_time,
+                |_, eval_result| {
+                    if let Some(wb) = eval_result.batch {
+                        group_engine.commit(
+                            WriteBatch::new(&wb.data),
+                            WriteStates::default(),
+                            false,
+                        )?;
+                    }
+                    Ok(())
+                },
+            )
+            .await;
+            let replayed_index = match replayed {
+                Ok(index) => index,
+                Err(Error::InvalidData(msg)) => {
+                    // The state at the restore time could not be recovered from this backup, the
+                    // retrying never succeeds.
+                    drop(group_engine);
+                    GroupEngine::destory(group_id, replica_id, self.engines.db()).await?;
+                    return Err(Error::InvalidArgument(format!(
+                        "restore group {} to {}: {msg}",
+                        req.source_group, req.restore_time
+                    )));
+                }
+                Err(err) => return Err(err),
+            };
+            info!(
+                "group {group_id} replica {replica_id} replay archived log entries of group {} in [{}, {replayed_index}]",
+                req.source_group,
+                req.apply_index + 1,
+            );
+        }
+        group_engine.rewrite_collections(&req.id_mapping)?;
+
+        // The log entries before the restored state belong to the source group, so they are
+        // considered as archived.
+        let states = WriteStates {
+            descriptor: Some(group.clone()),
+            archive_state: Some(ArchiveState {
+                index: apply_state.index,
+            }),
+            ..Default::default()
+        };
+        group_engine.commit(WriteBatch::default(), states, true)?;
+        group_engine.flush()?;
+        let apply_state = group_engine.flushed_apply_state()?;
+        Ok(EntryId {
+            index: apply_state.index,
+            term: apply_state.term,
+        })
+    }
+
     #[inline]
     async fn serving_group_id_list(&self) -> Vec<u64> {
         let node_state = self.node_state.lock().await;
@@ -656,6 +1211,84 @@ async fn open_group_engine(
     }
 }
 
+async fn upload_checkpoint(
+    storage: &dyn ExternalStorage,
+    builder: &GroupSnapshotBuilder,
+    group_id: u64,
+    base_dir: &Path,
+) -> Result<BackupReplicaResponse> {
+    // The backups are restored by the other clusters without the data keys of this node, so the
+    // files are uploaded in plaintext.
+    let (apply_state, desc) = builder.sst_checkpoint(base_dir).await?;
+    let mut local_files = std::fs::read_dir(base_dir)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>>>()?;
+    local_files.sort_unstable();
+
+    let mut files = Vec::with_capacity(local_files.len());
+    let mut size = 0;
+    for path in local_files {
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        let name = backup::group_file(group_id, file_name);
+        storage.put_file(&name, &path).await?;
+        size += std::fs::metadata(&path)?.len();
+        files.push(name);
+    }
+    Ok(BackupReplicaResponse {
+        desc: Some(desc),
+        apply_index: apply_state.index,
+        apply_term: apply_state.term,
+        files,
+        size,
+    })
+}
+
+/// Download the SST files into the stage directory, and return the crc32 of them. The crc32 is
+/// computed before the values are encrypted by the data key of this node, so it is comparable
+/// across replicas.
+async fn stage_sst_files(
+    key_manager: Arc<DataKeyManager>,
+    stage_dir: &Path,
+    storage: &str,
+    files: &[String],
+) -> Result<Vec<u32>> {
+    let storage = backup::open_storage(storage)?;
+    std::fs::create_dir_all(stage_dir)?;
+    let mut checksums = Vec::with_capacity(files.len());
+    for (i, file) in files.iter().enumerate() {
+        let path = staged_sst_file(stage_dir, i);
+        storage.get_file(file, &path).await?;
+        let key_manager = key_manager.clone();
+        let checksum = tokio::task::spawn_blocking(move || {
+            let checksum = file_crc32(&path)?;
+            encrypt_sst_file(&key_manager, &path, &path.with_extension("scan"))?;
+            Ok::<_, Error>(checksum)
+        })
+        .await
+        .expect("the task staging sst file is panicked")?;
+        checksums.push(checksum);
+    }
+    Ok(checksums)
+}
+
+fn file_crc32(path: &Path) -> Result<u32> {
+    use std::io::Read;
+
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buf = vec![0; 64 << 10];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+    }
+    Ok(hasher.finalize())
+}
+
 async fn start_raft_group(
     cfg: &NodeConfig,
     raft_mgr: &RaftManager,
@@ -663,6 +1296,8 @@ async fn start_raft_group(
     lease_state: Arc<std::sync::Mutex<LeaseState>>,
     channel: StateChannel,
     group_engine: GroupEngine,
+    ingest_dir: PathBuf,
+    checksums: Arc<ChecksumTable>,
     wait_group: WaitGroup,
 ) -> Result<RaftNodeFacade> {
     let group_id = info.group_id;
@@ -675,7 +1310,9 @@ async fn start_raft_group(
         cfg.replica.clone(),
         info.clone(),
         group_engine.clone(),
+        ingest_dir,
         state_observer.clone(),
+        checksums,
     );
     raft_mgr
         .start_raft_group(
@@ -713,7 +1350,7 @@ mod tests {
             ..Default::default()
         };
 
-        let engines = Engines::open(&config.root_dir, &config.db).unwrap();
+        let engines = Engines::open(&config.root_dir, &config.db, &config.encryption).unwrap();
         let transport_manager = TransportManager::new(vec![], engines.state()).await;
         Node::new(config, engines, transport_manager).await.unwrap()
     }
@@ -748,6 +1385,7 @@ mod tests {
                     node_id: 1,
                     role: ReplicaRole::Voter.into(),
                 }],
+                ..Default::default()
             };
             node.create_replica(replica_id, group).await.unwrap();
 
@@ -772,6 +1410,7 @@ mod tests {
                 epoch: INITIAL_EPOCH,
                 shards: vec![],
                 replicas: vec![],
+                ..Default::default()
             };
             node.create_replica(replica_id, group).await.unwrap();
         });
@@ -803,6 +1442,7 @@ mod tests {
                 epoch: INITIAL_EPOCH,
                 shards: vec![],
                 replicas: vec![],
+                ..Default::default()
             };
             node.create_replica(replica_id, group.clone())
                 .await
@@ -835,6 +1475,7 @@ mod tests {
                 epoch: INITIAL_EPOCH,
                 shards: vec![],
                 replicas: vec![],
+                ..Default::default()
             };
             node.create_replica(replica_id, group.clone())
                 .await
@@ -873,6 +1514,7 @@ mod tests {
                 epoch: INITIAL_EPOCH,
                 shards: vec![],
                 replicas: vec![],
+                ..Default::default()
             };
             node.create_replica(replica_id, group.clone())
                 .await
@@ -904,6 +1546,7 @@ mod tests {
                 epoch: INITIAL_EPOCH,
                 shards: vec![],
                 replicas: vec![],
+                ..Default::default()
             };
             node.create_replica(replica_id, group.clone())
                 .await
@@ -951,6 +1594,7 @@ mod tests {
                     node_id: 1,
                     role: ReplicaRole::Voter as i32,
                 }],
+                ..Default::default()
             };
             node.create_replica(replica_id, group.clone())
                 .await
@@ -968,12 +1612,14 @@ mod tests {
                     id: shard_id,
                     collection_id: 123,
                     partition: Some(Partition::Range(RangePartition::default())),
+                    ..Default::default()
                 }],
                 replicas: vec![ReplicaDesc {
                     id: new_replica_id,
                     node_id: 1,
                     role: ReplicaRole::Voter as i32,
                 }],
+                ..Default::default()
             };
             node.create_replica(new_replica_id, group.clone())
                 .await
@@ -1017,6 +1663,7 @@ mod tests {
                     node_id: 1,
                     role: ReplicaRole::Voter as i32,
                 }],
+                ..Default::default()
             };
             node.create_replica(replica_id, group.clone())
                 .await
@@ -1046,12 +1693,14 @@ mod tests {
                     id: shard_id,
                     collection_id: 123,
                     partition: Some(Partition::Range(RangePartition::default())),
+                    ..Default::default()
                 }],
                 replicas: vec![ReplicaDesc {
                     id: new_replica_id,
                     node_id: 1,
                     role: ReplicaRole::Voter as i32,
                 }],
+                ..Default::default()
             };
             node.create_replica(new_replica_id, group.clone())
                 .await
@@ -0,0 +1,286 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::VecDeque,
+    sync::{mpsc, Arc, Mutex},
+};
+
+use engula_api::server::v1::ReplicaChecksum;
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{
+    engine::{GroupEngine, RawIterator},
+    Result,
+};
+
+/// The number of the latest checksums retained by a replica, the older ones are no longer
+/// collected by the leader.
+const MAX_RETAINED_CHECKSUMS: usize = 8;
+
+/// The checksums computed by a replica, and the result of the last scrubbing if the replica is
+/// the leader.
+#[derive(Default)]
+pub struct ChecksumTable {
+    inner: Mutex<ChecksumTableInner>,
+}
+
+#[derive(Default)]
+struct ChecksumTableInner {
+    checksums: VecDeque<ReplicaChecksum>,
+    last_report: Option<ScrubReport>,
+}
+
+/// The result of comparing the checksums of all replicas of a group.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ScrubReport {
+    pub checksum_id: u64,
+    /// The applied index the checksums are computed at.
+    pub index: u64,
+    /// The unix timestamp in seconds when the comparison is finished.
+    pub finished_at: u64,
+    /// The replicas whose checksums are the same as the majority.
+    pub matched: Vec<u64>,
+    /// The replicas whose checksums are different from the majority, or failed to compute.
+    pub mismatched: Vec<u64>,
+    /// The replicas which didn't report their checksums in time.
+    pub missing: Vec<u64>,
+}
+
+impl ChecksumTable {
+    pub fn get(&self, checksum_id: u64) -> Option<ReplicaChecksum> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .checksums
+            .iter()
+            .find(|c| c.checksum_id == checksum_id)
+            .cloned()
+    }
+
+    pub fn last_report(&self) -> Option<ScrubReport> {
+        self.inner.lock().unwrap().last_report.clone()
+    }
+
+    pub fn set_last_report(&self, report: ScrubReport) {
+        self.inner.lock().unwrap().last_report = Some(report);
+    }
+
+    fn start(&self, checksum_id: u64, index: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.checksums.len() >= MAX_RETAINED_CHECKSUMS {
+            inner.checksums.pop_front();
+        }
+        inner.checksums.push_back(ReplicaChecksum {
+            checksum_id,
+            index,
+            ..Default::default()
+        });
+    }
+
+    fn finish(&self, checksum_id: u64, result: Result<Vec<u8>>) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(entry) = inner
+            .checksums
+            .iter_mut()
+            .find(|c| c.checksum_id == checksum_id) else {
+            return;
+        };
+        entry.finished = true;
+        match result {
+            Ok(checksum) => entry.checksum = checksum,
+            Err(err) => entry.error = err.to_string(),
+        }
+    }
+}
+
+/// Compute the checksum of the records of the group in a dedicated thread. The view of the
+/// records is pinned before returning, so the checksum covers exactly the records applied before
+/// `index`, even if the following entries are applied during the computation.
+///
+/// The failures are recorded in the table instead of being returned, since they usually mean the
+/// data of this replica is corrupted, which is what the checksum is used to detect.
+pub(crate) fn compute_checksum(
+    engine: GroupEngine,
+    table: Arc<ChecksumTable>,
+    checksum_id: u64,
+    index: u64,
+) {
+    table.start(checksum_id, index);
+
+    let (sender, receiver) = mpsc::sync_channel(1);
+    let cloned_table = table.clone();
+    let spawn_result = std::thread::Builder::new()
+        .name("compute-checksum".to_owned())
+        .spawn(move || {
+            let mut iter = match engine.raw_iter() {
+                Ok(iter) => iter,
+                Err(err) => {
+                    sender.send(()).unwrap_or_default();
+                    cloned_table.finish(checksum_id, Err(err));
+                    return;
+                }
+            };
+            sender.send(()).unwrap_or_default();
+            let result = digest(&mut iter);
+            match &result {
+                Ok(_) => info!("compute checksum {checksum_id} at index {index} finished"),
+                Err(err) => warn!("compute checksum {checksum_id} at index {index}: {err}"),
+            }
+            cloned_table.finish(checksum_id, result);
+        });
+    match spawn_result {
+        // Wait until the view of records is pinned.
+        Ok(_) => receiver.recv().unwrap_or_default(),
+        Err(err) => table.finish(checksum_id, Err(err.into())),
+    }
+}
+
+/// Digest the records of collections. The local states, eg. the apply state, are excluded, since
+/// they are not updated at the same pace between replicas.
+fn digest(iter: &mut RawIterator<'_>) -> Result<Vec<u8>> {
+    use sha2::{Digest, Sha256};
+
+    let mut hasher = Sha256::new();
+    iter.seek_to_data_start();
+    for item in iter {
+        let (key, value) = item?;
+        hasher.update((key.len() as u64).to_le_bytes());
+        hasher.update(&key);
+        hasher.update((value.len() as u64).to_le_bytes());
+        hasher.update(&value);
+    }
+    Ok(hasher.finalize().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, time::Duration};
+
+    use engula_api::server::v1::{
+        shard_desc::{Partition, RangePartition},
+        GroupDesc, ShardDesc,
+    };
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::{
+        engine::{WriteBatch, WriteStates},
+        runtime::ExecutorOwner,
+        serverpb::v1::ApplyState,
+        EngineConfig,
+    };
+
+    async fn create_engine(dir: &Path, replica_id: u64) -> GroupEngine {
+        use crate::bootstrap::open_engine_with_default_config;
+
+        let db = open_engine_with_default_config(dir).unwrap();
+        let db = Arc::new(db);
+
+        let group_engine = GroupEngine::create(&EngineConfig::default(), db, 1, replica_id)
+            .await
+            .unwrap();
+        let states = WriteStates {
+            descriptor: Some(GroupDesc {
+                id: 1,
+                shards: vec![ShardDesc {
+                    id: 1,
+                    collection_id: 1,
+                    partition: Some(Partition::Range(RangePartition {
+                        start: vec![],
+                        end: vec![],
+                    })),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        group_engine
+            .commit(WriteBatch::default(), states, false)
+            .unwrap();
+        group_engine
+    }
+
+    fn put_data(engine: &GroupEngine, keys: std::ops::Range<usize>, index: u64) {
+        let mut wb = WriteBatch::default();
+        for i in keys {
+            let key = format!("key-{i}");
+            engine.put(&mut wb, 1, key.as_bytes(), b"value", 0).unwrap();
+        }
+        let states = WriteStates {
+            apply_state: Some(ApplyState { index, term: 1 }),
+            ..Default::default()
+        };
+        engine.commit(wb, states, false).unwrap();
+    }
+
+    fn wait_checksum(table: &ChecksumTable, checksum_id: u64) -> ReplicaChecksum {
+        for _ in 0..100 {
+            let checksum = table.get(checksum_id).unwrap();
+            if checksum.finished {
+                return checksum;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        panic!("compute checksum {checksum_id} is timeout");
+    }
+
+    #[test]
+    fn checksum_ignores_local_states_and_pins_view() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let dir = TempDir::new("checksum_ignores_local_states").unwrap();
+            let left = create_engine(&dir.path().join("left"), 1).await;
+            let right = create_engine(&dir.path().join("right"), 2).await;
+
+            // The same records are applied with different apply states.
+            put_data(&left, 0..100, 1);
+            put_data(&right, 0..50, 1);
+            put_data(&right, 50..100, 2);
+
+            let table = Arc::new(ChecksumTable::default());
+            compute_checksum(left.clone(), table.clone(), 1, 1);
+            // The records written after the view is pinned are not covered.
+            put_data(&left, 100..200, 3);
+            compute_checksum(right.clone(), table.clone(), 2, 2);
+
+            let left_checksum = wait_checksum(&table, 1);
+            let right_checksum = wait_checksum(&table, 2);
+            assert!(left_checksum.error.is_empty());
+            assert!(!left_checksum.checksum.is_empty());
+            assert_eq!(left_checksum.checksum, right_checksum.checksum);
+
+            compute_checksum(left, table.clone(), 3, 3);
+            let checksum = wait_checksum(&table, 3);
+            assert_ne!(checksum.checksum, right_checksum.checksum);
+        });
+    }
+
+    #[test]
+    fn checksum_table_retains_latest() {
+        let table = ChecksumTable::default();
+        for id in 0..(MAX_RETAINED_CHECKSUMS as u64 + 2) {
+            table.start(id, id);
+        }
+        assert!(table.get(0).is_none());
+        assert!(table.get(1).is_none());
+        assert!(!table.get(2).unwrap().finished);
+
+        table.finish(2, Ok(vec![1, 2, 3]));
+        let checksum = table.get(2).unwrap();
+        assert!(checksum.finished);
+        assert_eq!(checksum.checksum, vec![1, 2, 3]);
+    }
+}
@@ -13,8 +13,10 @@
 // limitations under the License.
 use engula_api::server::v1::BatchWriteRequest;
 
+use super::SchemaCache;
 use crate::{
     engine::{GroupEngine, WriteBatch},
+    error::BusyReason,
     node::replica::ExecCtx,
     serverpb::v1::{EvalResult, WriteBatchRep},
     Error, Result,
@@ -23,6 +25,7 @@ use crate::{
 pub(crate) async fn batch_write(
     exec_ctx: &ExecCtx,
     group_engine: &GroupEngine,
+    schemas: &SchemaCache,
     req: &BatchWriteRequest,
 ) -> Result<Option<EvalResult>> {
     if req.deletes.is_empty() && req.puts.is_empty() {
@@ -36,8 +39,10 @@ pub(crate) async fn batch_write(
             .as_ref()
             .ok_or_else(|| Error::InvalidArgument("ShardDeleteRequest::delete is None".into()))?;
         if exec_ctx.is_migrating_shard(req.shard_id) {
-            panic!("BatchWrite does not support migrating shard");
+            // The batch write is not forwarded, it will be retried once the migration finished.
+            return Err(Error::ServiceIsBusy(BusyReason::Migrating));
         }
+        super::index::update_entries(group_engine, &mut wb, req.shard_id, &del.key, None).await?;
         group_engine.delete(&mut wb, req.shard_id, &del.key, super::FLAT_KEY_VERSION)?;
     }
     for req in &req.puts {
@@ -45,15 +50,26 @@ pub(crate) async fn batch_write(
             .put
             .as_ref()
             .ok_or_else(|| Error::InvalidArgument("ShardPutRequest::put is None".into()))?;
+        schemas.validate_shard_value(group_engine, req.shard_id, &put.value)?;
         if exec_ctx.is_migrating_shard(req.shard_id) {
-            panic!("BatchWrite does not support migrating shard");
+            // The batch write is not forwarded, it will be retried once the migration finished.
+            return Err(Error::ServiceIsBusy(BusyReason::Migrating));
         }
-        group_engine.put(
+        super::index::update_entries(
+            group_engine,
+            &mut wb,
+            req.shard_id,
+            &put.key,
+            Some(&put.value),
+        )
+        .await?;
+        group_engine.put_expirable(
             &mut wb,
             req.shard_id,
             &put.key,
             &put.value,
             super::FLAT_KEY_VERSION,
+            super::expire_at(group_engine, req.shard_id)?,
         )?;
     }
     Ok(Some(EvalResult {
@@ -44,6 +44,7 @@ pub(crate) async fn delete(
     }
 
     let mut wb = WriteBatch::default();
+    super::index::update_entries(group_engine, &mut wb, req.shard_id, &delete.key, None).await?;
     if exec_ctx.forward_shard_id.is_some() {
         // Write tombstone for migrating shard, so that the a deleted key will be overwrite the key
         // ingested by background pulling. not visible.
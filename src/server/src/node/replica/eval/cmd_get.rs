@@ -31,15 +31,16 @@ pub(crate) async fn get(
         .as_ref()
         .ok_or_else(|| Error::InvalidArgument("ShardGetRequest::get is None".into()))?;
 
-    let value = engine.get(req.shard_id, &get.key).await?;
+    let value = engine.get_expirable(req.shard_id, &get.key).await?;
     if let Some(desc) = exec_ctx.migration_desc.as_ref() {
         let shard_id = desc.shard_desc.as_ref().unwrap().id;
         if shard_id == req.shard_id {
-            let payloads = if let Some(value) = value {
+            let payloads = if let Some((value, expire_at)) = value {
                 vec![ShardData {
                     key: get.key.clone(),
                     value,
                     version: super::MIGRATING_KEY_VERSION,
+                    expire_at,
                 }]
             } else {
                 Vec::default()
@@ -52,5 +53,5 @@ pub(crate) async fn get(
             return Err(Error::Forward(forward_ctx));
         }
     }
-    Ok(value)
+    Ok(value.map(|(value, _)| value))
 }
@@ -0,0 +1,97 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashSet, path::PathBuf};
+
+use engula_api::server::v1::IngestSstRequest;
+
+use super::SchemaCache;
+use crate::{
+    engine::{decode_shard_record, read_sst_file, GroupEngine, WriteBatch},
+    error::BusyReason,
+    node::replica::ExecCtx,
+    serverpb::v1::{EvalResult, SyncOp, WriteBatchRep},
+    Error, Result,
+};
+
+/// The SST files staged on all replicas of the group, see `Node::ingest_sst`.
+pub struct SstStage {
+    pub id: u64,
+    /// The epoch of the descriptor whose replicas the files are staged on.
+    pub epoch: u64,
+    /// The staged files of the local replica.
+    pub files: Vec<PathBuf>,
+    /// Whether the ingestion has been proposed, the staged files are still referred by the
+    /// proposal even if it fails.
+    pub proposed: bool,
+}
+
+/// Replicate the ingestion of the staged SST files, each replica moves its staged files into the
+/// engine when applying. Every record of the files must belong to the shard and conform to the
+/// schema of the collection, and the index entries of the records are written in the batch.
+pub(crate) async fn ingest_sst(
+    exec_ctx: &ExecCtx,
+    group_engine: &GroupEngine,
+    schemas: &SchemaCache,
+    stage: &SstStage,
+    req: &IngestSstRequest,
+) -> Result<EvalResult> {
+    // The ingested records are not visible to the migration, so they would be lost.
+    if let Some(desc) = exec_ctx.migration_desc.as_ref() {
+        if desc.shard_desc.as_ref().unwrap().id == req.shard_id {
+            return Err(Error::ServiceIsBusy(BusyReason::Migrating));
+        }
+    }
+
+    let shard = group_engine.shard_desc(req.shard_id)?;
+    let has_indexes = !super::index::shard_indexes(group_engine, shard.id)?.is_empty();
+    let mut wb = WriteBatch::default();
+    let mut indexed_keys = HashSet::new();
+    let key_manager = group_engine.key_manager();
+    // The later files shadow the former ones, and the latest version of a key is read first, so
+    // the index entries are built from the first record of each key in the reverse order.
+    for path in stage.files.iter().rev() {
+        let mut records = vec![];
+        let scratch_dir = path.with_extension("scan");
+        read_sst_file(key_manager, path, &scratch_dir, |key, value| {
+            let Some((key, value)) = decode_shard_record(&shard, key, value) else {
+                return Err(Error::InvalidArgument(format!(
+                    "sst file {} has records don't belong to shard {}",
+                    path.display(),
+                    shard.id
+                )));
+            };
+            schemas.validate_shard_value(group_engine, shard.id, &value)?;
+            if has_indexes && indexed_keys.insert(key.clone()) {
+                records.push((key, value));
+            }
+            Ok(())
+        })?;
+        for (key, value) in records {
+            super::index::update_entries(group_engine, &mut wb, shard.id, &key, Some(&value))
+                .await?;
+        }
+    }
+
+    Ok(EvalResult {
+        batch: Some(WriteBatchRep {
+            data: wb.data().to_owned(),
+        }),
+        op: Some(SyncOp::ingest_sst(
+            shard.id,
+            stage.id,
+            stage.files.len() as u32,
+        )),
+    })
+}
@@ -14,6 +14,7 @@
 
 use engula_api::server::v1::ShardPutRequest;
 
+use super::SchemaCache;
 use crate::{
     engine::{GroupEngine, WriteBatch},
     node::{migrate::ForwardCtx, replica::ExecCtx},
@@ -24,12 +25,14 @@ use crate::{
 pub(crate) async fn put(
     exec_ctx: &ExecCtx,
     group_engine: &GroupEngine,
+    schemas: &SchemaCache,
     req: &ShardPutRequest,
 ) -> Result<EvalResult> {
     let put = req
         .put
         .as_ref()
         .ok_or_else(|| Error::InvalidArgument("ShardPutRequest::put is None".into()))?;
+    schemas.validate_shard_value(group_engine, req.shard_id, &put.value)?;
 
     if let Some(desc) = exec_ctx.migration_desc.as_ref() {
         let shard_id = desc.shard_desc.as_ref().unwrap().id;
@@ -44,12 +47,21 @@ pub(crate) async fn put(
     }
 
     let mut wb = WriteBatch::default();
-    group_engine.put(
+    super::index::update_entries(
+        group_engine,
+        &mut wb,
+        req.shard_id,
+        &put.key,
+        Some(&put.value),
+    )
+    .await?;
+    group_engine.put_expirable(
         &mut wb,
         req.shard_id,
         &put.key,
         &put.value,
         super::FLAT_KEY_VERSION,
+        super::expire_at(group_engine, req.shard_id)?,
     )?;
     Ok(EvalResult {
         batch: Some(WriteBatchRep {
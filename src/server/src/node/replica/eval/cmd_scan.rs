@@ -49,6 +49,7 @@ async fn scan_prefix(
                     key: entry.user_key().to_owned(),
                     value,
                     version: entry.version(),
+                    expire_at: entry.expire_at(),
                 });
             }
         }
@@ -89,6 +90,7 @@ async fn scan_range(engine: &GroupEngine, req: &ShardScanRequest) -> Result<Shar
                     key,
                     value,
                     version,
+                    expire_at: entry.expire_at(),
                 });
             }
 
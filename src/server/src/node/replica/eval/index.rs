@@ -0,0 +1,571 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Maintain the secondary index entries of a shard.
+//!
+//! An index entry is keyed by `len(value) | value | primary key` under the index id, with the
+//! same slot of the indexed shard, and its value is the primary key. Since the entries might
+//! be stale (eg. a concurrent put of the same key), the readers must verify the indexed value
+//! against the primary record.
+//!
+//! The indexes are read from the collection metadata of the shard descriptor, which is
+//! synchronized by root, so the writes never depend on the indexes known by clients. The index
+//! entries are not migrated with the shard, instead the target group builds them for the ingested
+//! records, and the source group purges them with the records.
+
+use engula_api::{
+    server::v1::*,
+    shard,
+    v1::{index_desc, IndexDesc},
+};
+
+use crate::{
+    engine::{GroupEngine, WriteBatch},
+    serverpb::v1::{EvalResult, WriteBatchRep},
+    Error, Result,
+};
+
+/// Extract the indexed value from the record, `None` is returned if the record is not indexed.
+pub fn extract(index: &IndexDesc, value: &[u8]) -> Option<Vec<u8>> {
+    use serde_json::Value;
+
+    match index.extractor.as_ref()? {
+        index_desc::Extractor::ByteRange(range) => {
+            let offset = range.offset as usize;
+            let end = if range.length == 0 {
+                value.len()
+            } else {
+                offset + range.length as usize
+            };
+            if value.len() < end || end <= offset {
+                return None;
+            }
+            Some(value[offset..end].to_owned())
+        }
+        index_desc::Extractor::JsonPath(json_path) => {
+            let mut value: Value = serde_json::from_slice(value).ok()?;
+            for field in json_path.path.split('.').filter(|f| !f.is_empty()) {
+                value = value.get_mut(field)?.take();
+            }
+            match value {
+                Value::String(s) => Some(s.into_bytes()),
+                Value::Bool(_) | Value::Number(_) => Some(value.to_string().into_bytes()),
+                Value::Null | Value::Array(_) | Value::Object(_) => None,
+            }
+        }
+    }
+}
+
+/// The prefix of all index entries of the specified value.
+pub fn index_prefix(value: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(core::mem::size_of::<u32>() + value.len());
+    buf.extend_from_slice((value.len() as u32).to_be_bytes().as_slice());
+    buf.extend_from_slice(value);
+    buf
+}
+
+#[inline]
+pub fn index_key(value: &[u8], primary_key: &[u8]) -> Vec<u8> {
+    let mut buf = index_prefix(value);
+    buf.extend_from_slice(primary_key);
+    buf
+}
+
+/// The indexes of the collection which the shard belongs to.
+pub(crate) fn shard_indexes(group_engine: &GroupEngine, shard_id: u64) -> Result<Vec<IndexDesc>> {
+    let desc = group_engine.shard_desc(shard_id)?;
+    Ok(desc.collection_meta.map(|m| m.indexes).unwrap_or_default())
+}
+
+fn find_index(group_engine: &GroupEngine, shard_id: u64, index_id: u64) -> Result<IndexDesc> {
+    shard_indexes(group_engine, shard_id)?
+        .into_iter()
+        .find(|index| index.id == index_id)
+        .ok_or_else(|| {
+            Error::InvalidArgument(format!("index {index_id} not found in shard {shard_id}"))
+        })
+}
+
+/// Write the index entries changes of the key into the write batch, `None` value means the key is
+/// going to be deleted.
+pub(crate) async fn update_entries(
+    group_engine: &GroupEngine,
+    wb: &mut WriteBatch,
+    shard_id: u64,
+    key: &[u8],
+    value: Option<&[u8]>,
+) -> Result<()> {
+    let indexes = shard_indexes(group_engine, shard_id)?;
+    if indexes.is_empty() {
+        return Ok(());
+    }
+
+    let prev_value = group_engine.get(shard_id, key).await?;
+    for index in &indexes {
+        let prev = prev_value.as_ref().and_then(|v| extract(index, v));
+        let next = value.and_then(|v| extract(index, v));
+        if prev == next {
+            continue;
+        }
+        if let Some(prev) = prev {
+            group_engine.delete_index(
+                wb,
+                shard_id,
+                index.id,
+                &index_key(&prev, key),
+                super::FLAT_KEY_VERSION,
+            )?;
+        }
+        if let Some(next) = next {
+            group_engine.put_index(
+                wb,
+                shard_id,
+                index.id,
+                &index_key(&next, key),
+                key,
+                super::FLAT_KEY_VERSION,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Write the index entries of the records ingested by shard migration. The records shadowed by
+/// the forwarded writes are skipped, since the forwarded writes maintain their entries.
+pub(crate) async fn ingest_entries(
+    group_engine: &GroupEngine,
+    wb: &mut WriteBatch,
+    shard_id: u64,
+    chunk: &[ShardData],
+) -> Result<()> {
+    use crate::engine::SnapshotMode;
+
+    let indexes = shard_indexes(group_engine, shard_id)?;
+    if indexes.is_empty() {
+        return Ok(());
+    }
+
+    for data in chunk {
+        let shadowed = {
+            let snapshot_mode = SnapshotMode::Key { key: &data.key };
+            let mut snapshot = group_engine.snapshot(shard_id, snapshot_mode)?;
+            match snapshot.mvcc_iter() {
+                Some(iter) => match iter?.next() {
+                    Some(entry) => entry?.version() > super::MIGRATING_KEY_VERSION,
+                    None => false,
+                },
+                None => false,
+            }
+        };
+        if shadowed {
+            continue;
+        }
+        for index in &indexes {
+            if let Some(value) = extract(index, &data.value) {
+                group_engine.put_index(
+                    wb,
+                    shard_id,
+                    index.id,
+                    &index_key(&value, &data.key),
+                    &data.key,
+                    super::FLAT_KEY_VERSION,
+                )?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Scan the primary records whose indexed value equals to the request value.
+pub(crate) async fn index_scan(
+    engine: &GroupEngine,
+    req: &ShardIndexScanRequest,
+) -> Result<ShardScanResponse> {
+    let index = find_index(engine, req.shard_id, req.index_id)?;
+    let prefix = index_prefix(&req.value);
+    let desc = engine
+        .descriptor()
+        .shards
+        .into_iter()
+        .find(|s| s.id == req.shard_id)
+        .ok_or(Error::ShardNotFound(req.shard_id))?;
+
+    let mut primary_keys = Vec::new();
+    {
+        let mut snapshot = engine.index_snapshot(req.shard_id, index.id, &prefix)?;
+        for mvcc_iter in snapshot.iter() {
+            let mut mvcc_iter = mvcc_iter?;
+            if let Some(entry) = mvcc_iter.next() {
+                let entry = entry?;
+                if let Some(primary_key) = entry.value() {
+                    // Range shards of the same collection share the index space in a group.
+                    if shard::belong_to(&desc, primary_key) {
+                        primary_keys.push(primary_key.to_owned());
+                    }
+                }
+            }
+        }
+    }
+
+    let mut data = Vec::new();
+    for key in primary_keys {
+        let value = match engine.get(req.shard_id, &key).await? {
+            Some(value) => value,
+            None => continue,
+        };
+        // Skip the stale entries.
+        if extract(&index, &value).as_deref() != Some(req.value.as_slice()) {
+            continue;
+        }
+        data.push(ShardData {
+            key,
+            value,
+            version: super::FLAT_KEY_VERSION,
+            ..Default::default()
+        });
+        if req.limit != 0 && req.limit as usize == data.len() {
+            break;
+        }
+    }
+    Ok(ShardScanResponse { data })
+}
+
+/// Build index entries for a batch of the existing records of the shard.
+pub(crate) async fn backfill_index(
+    engine: &GroupEngine,
+    req: &BackfillIndexRequest,
+) -> Result<(Option<EvalResult>, BackfillIndexResponse)> {
+    use crate::engine::SnapshotMode;
+
+    // The index must be synchronized to the shard before backfilling, otherwise the concurrent
+    // writes would not maintain its entries.
+    let index = find_index(engine, req.shard_id, req.index_id)?;
+
+    let start_key = if req.start_key.is_empty() {
+        None
+    } else {
+        Some(req.start_key.as_slice())
+    };
+    let mut wb = WriteBatch::default();
+    let mut next_key = None;
+    let mut num_records = 0;
+    {
+        let snapshot_mode = SnapshotMode::Start { start_key };
+        let mut snapshot = engine.snapshot(req.shard_id, snapshot_mode)?;
+        for mvcc_iter in snapshot.iter() {
+            let mut mvcc_iter = mvcc_iter?;
+            if let Some(entry) = mvcc_iter.next() {
+                let entry = entry?;
+                if req.limit != 0 && num_records as u64 >= req.limit {
+                    next_key = Some(entry.user_key().to_owned());
+                    break;
+                }
+                num_records += 1;
+                if let Some(value) = entry.value().and_then(|v| extract(&index, v)) {
+                    let key = entry.user_key();
+                    engine.put_index(
+                        &mut wb,
+                        req.shard_id,
+                        index.id,
+                        &index_key(&value, key),
+                        key,
+                        super::FLAT_KEY_VERSION,
+                    )?;
+                }
+            }
+        }
+    }
+
+    let eval_result = if wb.is_empty() {
+        None
+    } else {
+        Some(EvalResult {
+            batch: Some(WriteBatchRep {
+                data: wb.data().to_owned(),
+            }),
+            ..Default::default()
+        })
+    };
+    Ok((eval_result, BackfillIndexResponse { next_key }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, sync::Arc};
+
+    use engula_api::{
+        server::v1::shard_desc::{Partition, RangePartition},
+        v1::{index_desc::*, DeleteRequest, PutRequest},
+    };
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::{
+        engine::WriteStates, node::replica::ExecCtx, runtime::ExecutorOwner, EngineConfig,
+    };
+
+    const INDEX_ID: u64 = 100;
+
+    fn json_index(path: &str) -> IndexDesc {
+        IndexDesc {
+            extractor: Some(Extractor::JsonPath(JsonPath { path: path.into() })),
+            ..Default::default()
+        }
+    }
+
+    fn group_desc(indexes: Vec<IndexDesc>) -> GroupDesc {
+        GroupDesc {
+            id: 1,
+            shards: vec![ShardDesc {
+                id: 1,
+                collection_id: 1,
+                partition: Some(Partition::Range(RangePartition {
+                    start: vec![],
+                    end: vec![],
+                })),
+                collection_meta: Some(CollectionMeta {
+                    epoch: 1,
+                    indexes,
+                    ..Default::default()
+                }),
+            }],
+            ..Default::default()
+        }
+    }
+
+    async fn create_engine(dir: &Path, indexes: Vec<IndexDesc>) -> GroupEngine {
+        use crate::bootstrap::open_engine_with_default_config;
+
+        let db = Arc::new(open_engine_with_default_config(dir).unwrap());
+        let group_engine = GroupEngine::create(&EngineConfig::default(), db, 1, 1)
+            .await
+            .unwrap();
+        update_indexes(&group_engine, indexes);
+        group_engine
+    }
+
+    fn update_indexes(engine: &GroupEngine, indexes: Vec<IndexDesc>) {
+        let states = WriteStates {
+            descriptor: Some(group_desc(indexes)),
+            ..Default::default()
+        };
+        engine.commit(WriteBatch::default(), states, false).unwrap();
+    }
+
+    fn apply(engine: &GroupEngine, eval_result: Option<EvalResult>) {
+        if let Some(batch) = eval_result.and_then(|r| r.batch) {
+            let wb = WriteBatch::new(&batch.data);
+            engine.commit(wb, WriteStates::default(), false).unwrap();
+        }
+    }
+
+    async fn put(engine: &GroupEngine, key: &[u8], value: &[u8]) {
+        let req = ShardPutRequest {
+            shard_id: 1,
+            put: Some(PutRequest {
+                key: key.to_owned(),
+                value: value.to_owned(),
+            }),
+            ..Default::default()
+        };
+        let eval_result = super::super::put(&ExecCtx::default(), engine, &req)
+            .await
+            .unwrap();
+        apply(engine, Some(eval_result));
+    }
+
+    async fn delete(engine: &GroupEngine, key: &[u8]) {
+        let req = ShardDeleteRequest {
+            shard_id: 1,
+            delete: Some(DeleteRequest {
+                key: key.to_owned(),
+            }),
+        };
+        let eval_result = super::super::delete(&ExecCtx::default(), engine, &req)
+            .await
+            .unwrap();
+        apply(engine, Some(eval_result));
+    }
+
+    async fn scan_keys(engine: &GroupEngine, value: &[u8]) -> Vec<Vec<u8>> {
+        let req = ShardIndexScanRequest {
+            shard_id: 1,
+            index_id: INDEX_ID,
+            value: value.to_owned(),
+            limit: 0,
+        };
+        let resp = index_scan(engine, &req).await.unwrap();
+        resp.data.into_iter().map(|d| d.key).collect()
+    }
+
+    fn count_entries(engine: &GroupEngine, value: &[u8]) -> usize {
+        let mut snapshot = engine
+            .index_snapshot(1, INDEX_ID, &index_prefix(value))
+            .unwrap();
+        let mut count = 0;
+        for mvcc_iter in snapshot.iter() {
+            let mut mvcc_iter = mvcc_iter.unwrap();
+            if let Some(entry) = mvcc_iter.next() {
+                if entry.unwrap().value().is_some() {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    fn name_index() -> IndexDesc {
+        IndexDesc {
+            id: INDEX_ID,
+            ..json_index("name")
+        }
+    }
+
+    #[test]
+    fn extract_byte_range() {
+        let index = IndexDesc {
+            extractor: Some(Extractor::ByteRange(ByteRange {
+                offset: 1,
+                length: 2,
+            })),
+            ..Default::default()
+        };
+        assert_eq!(extract(&index, b"abcd"), Some(b"bc".to_vec()));
+        assert_eq!(extract(&index, b"ab"), None);
+
+        let index = IndexDesc {
+            extractor: Some(Extractor::ByteRange(ByteRange {
+                offset: 2,
+                length: 0,
+            })),
+            ..Default::default()
+        };
+        assert_eq!(extract(&index, b"abcd"), Some(b"cd".to_vec()));
+        assert_eq!(extract(&index, b"ab"), None);
+    }
+
+    #[test]
+    fn extract_json_path() {
+        let value = br#"{"user": {"name": "walter", "age": 18, "tags": []}}"#;
+        assert_eq!(
+            extract(&json_index("user.name"), value),
+            Some(b"walter".to_vec())
+        );
+        assert_eq!(
+            extract(&json_index("user.age"), value),
+            Some(b"18".to_vec())
+        );
+        assert_eq!(extract(&json_index("user.tags"), value), None);
+        assert_eq!(extract(&json_index("user.email"), value), None);
+        assert_eq!(extract(&json_index("user"), b"not json"), None);
+    }
+
+    #[test]
+    fn index_key_prefix() {
+        let key = index_key(b"ab", b"c");
+        assert!(key.starts_with(&index_prefix(b"ab")));
+        assert!(!key.starts_with(&index_prefix(b"a")));
+    }
+
+    #[test]
+    fn put_and_delete_maintain_entries() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let dir = TempDir::new("put_and_delete_maintain_entries").unwrap();
+            let engine = create_engine(dir.path(), vec![name_index()]).await;
+
+            put(&engine, b"k1", br#"{"name": "a"}"#).await;
+            put(&engine, b"k2", br#"{"name": "a"}"#).await;
+            assert_eq!(
+                scan_keys(&engine, b"a").await,
+                vec![b"k1".to_vec(), b"k2".to_vec()]
+            );
+
+            // The entry of the previous value is removed.
+            put(&engine, b"k1", br#"{"name": "b"}"#).await;
+            assert_eq!(scan_keys(&engine, b"a").await, vec![b"k2".to_vec()]);
+            assert_eq!(scan_keys(&engine, b"b").await, vec![b"k1".to_vec()]);
+            assert_eq!(count_entries(&engine, b"a"), 1);
+
+            delete(&engine, b"k2").await;
+            assert!(scan_keys(&engine, b"a").await.is_empty());
+            assert_eq!(count_entries(&engine, b"a"), 0);
+
+            // The values without the indexed field are not indexed.
+            put(&engine, b"k3", br#"{"age": 1}"#).await;
+            assert_eq!(count_entries(&engine, b""), 0);
+        });
+    }
+
+    #[test]
+    fn writes_ignore_unknown_indexes() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let dir = TempDir::new("writes_ignore_unknown_indexes").unwrap();
+            let engine = create_engine(dir.path(), vec![]).await;
+
+            // The index is not synchronized to the shard yet.
+            put(&engine, b"k1", br#"{"name": "a"}"#).await;
+            assert_eq!(count_entries(&engine, b"a"), 0);
+            let req = ShardIndexScanRequest {
+                shard_id: 1,
+                index_id: INDEX_ID,
+                value: b"a".to_vec(),
+                limit: 0,
+            };
+            assert!(matches!(
+                index_scan(&engine, &req).await,
+                Err(Error::InvalidArgument(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn backfill_existing_records() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let dir = TempDir::new("backfill_existing_records").unwrap();
+            let engine = create_engine(dir.path(), vec![]).await;
+            for i in 0..10 {
+                let key = format!("key-{i}");
+                let value = format!(r#"{{"name": "{}"}}"#, i % 2);
+                put(&engine, key.as_bytes(), value.as_bytes()).await;
+            }
+
+            let mut req = BackfillIndexRequest {
+                shard_id: 1,
+                index_id: INDEX_ID,
+                start_key: vec![],
+                limit: 3,
+            };
+            assert!(backfill_index(&engine, &req).await.is_err());
+
+            update_indexes(&engine, vec![name_index()]);
+            // The writes after syncing maintain the entries by themselves.
+            put(&engine, b"key-10", br#"{"name": "0"}"#).await;
+            loop {
+                let (eval_result, resp) = backfill_index(&engine, &req).await.unwrap();
+                apply(&engine, eval_result);
+                match resp.next_key {
+                    Some(next_key) => req.start_key = next_key,
+                    None => break,
+                }
+            }
+
+            assert_eq!(scan_keys(&engine, b"0").await.len(), 6);
+            assert_eq!(scan_keys(&engine, b"1").await.len(), 5);
+            assert_eq!(count_entries(&engine, b"0"), 6);
+        });
+    }
+}
@@ -16,21 +16,49 @@ mod cmd_accept_shard;
 mod cmd_batch_write;
 mod cmd_delete;
 mod cmd_get;
+mod cmd_ingest_sst;
 mod cmd_move_replicas;
 mod cmd_put;
 mod cmd_scan;
+mod index;
+mod schema;
 
-use engula_api::server::v1::ShardDesc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use engula_api::server::v1::{CollectionMeta, ShardDesc};
 
 pub(crate) use self::{
-    cmd_accept_shard::accept_shard, cmd_batch_write::batch_write, cmd_delete::delete, cmd_get::get,
-    cmd_move_replicas::move_replicas, cmd_put::put, cmd_scan::scan,
+    cmd_accept_shard::accept_shard,
+    cmd_batch_write::batch_write,
+    cmd_delete::delete,
+    cmd_get::get,
+    cmd_ingest_sst::{ingest_sst, SstStage},
+    cmd_move_replicas::move_replicas,
+    cmd_put::put,
+    cmd_scan::scan,
+    index::{backfill_index, index_scan, ingest_entries, update_entries},
+    schema::{check_schema, SchemaCache},
 };
-use crate::serverpb::v1::EvalResult;
+use crate::{engine::GroupEngine, serverpb::v1::EvalResult, Result};
 
-const FLAT_KEY_VERSION: u64 = u64::MAX - 1;
+pub const FLAT_KEY_VERSION: u64 = u64::MAX - 1;
 pub const MIGRATING_KEY_VERSION: u64 = 0;
 
+/// The expiration time of the values put into the shard now, in seconds since the unix epoch. 0
+/// means that the values never expire.
+fn expire_at(group_engine: &GroupEngine, shard_id: u64) -> Result<u64> {
+    let desc = group_engine.shard_desc(shard_id)?;
+    let ttl = desc.collection_meta.map(|m| m.ttl).unwrap_or_default();
+    if ttl == 0 {
+        return Ok(0);
+    }
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    Ok(now.saturating_add(ttl))
+}
+
 pub fn add_shard(shard: ShardDesc) -> EvalResult {
     use crate::serverpb::v1::SyncOp;
 
@@ -39,3 +67,12 @@ pub fn add_shard(shard: ShardDesc) -> EvalResult {
         ..Default::default()
     }
 }
+
+pub fn sync_collection(collection_id: u64, meta: CollectionMeta) -> EvalResult {
+    use crate::serverpb::v1::SyncOp;
+
+    EvalResult {
+        op: Some(SyncOp::sync_collection(collection_id, meta)),
+        ..Default::default()
+    }
+}
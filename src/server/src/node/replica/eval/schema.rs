@@ -0,0 +1,685 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Validate the values of a collection against its value schema.
+//!
+//! Two kinds of schema are supported:
+//! - a subset of JSON Schema, see `ValueSchema::json_schema` for the supported keywords.
+//! - a protobuf message type, the value must be a well-formed encoding of the message and must not
+//!   contain unknown fields.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use engula_api::v1::{value_schema, ValueSchema};
+use prost::{
+    bytes::Buf,
+    encoding::{decode_key, decode_varint, WireType},
+    Message,
+};
+use prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, FileDescriptorSet,
+};
+use serde_json::Value;
+
+use crate::{engine::GroupEngine, Error, Result};
+
+const MAX_DEPTH: usize = 64;
+
+/// The compiled schemas of the collections served by a replica, indexed by collection id. The
+/// schema is compiled again once the epoch of the collection metadata changes, and the collections
+/// whose shards are no longer in the group are evicted.
+#[derive(Default)]
+pub struct SchemaCache {
+    schemas: Mutex<HashMap<u64, (u64, Arc<CompiledSchema>)>>,
+}
+
+/// A value schema which has been parsed, so that the values could be validated without parsing
+/// the schema again.
+pub enum CompiledSchema {
+    Json(Value),
+    Protobuf {
+        pool: DescriptorPool,
+        message_name: String,
+    },
+}
+
+impl CompiledSchema {
+    pub fn compile(schema: &ValueSchema) -> Result<Self> {
+        match schema.schema.as_ref() {
+            None => Err(Error::InvalidArgument("ValueSchema::schema is None".into())),
+            Some(value_schema::Schema::JsonSchema(json_schema)) => {
+                let schema: Value = serde_json::from_str(json_schema)
+                    .map_err(|e| Error::InvalidArgument(format!("invalid json schema: {e}")))?;
+                if !schema.is_object() && !schema.is_boolean() {
+                    return Err(Error::InvalidArgument(
+                        "invalid json schema: expect an object or a boolean".into(),
+                    ));
+                }
+                Ok(CompiledSchema::Json(schema))
+            }
+            Some(value_schema::Schema::Protobuf(protobuf)) => {
+                let pool = DescriptorPool::decode(&protobuf.file_descriptor_set)?;
+                pool.find(&protobuf.message_name).ok_or_else(|| {
+                    Error::InvalidArgument(format!(
+                        "message {} not found in file descriptor set",
+                        protobuf.message_name
+                    ))
+                })?;
+                Ok(CompiledSchema::Protobuf {
+                    pool,
+                    message_name: protobuf.message_name.clone(),
+                })
+            }
+        }
+    }
+
+    /// Validate the value against the schema, a `SchemaViolation` describing the first violation
+    /// is returned if the value doesn't conform to the schema.
+    pub fn validate(&self, value: &[u8]) -> Result<()> {
+        let violation = match self {
+            CompiledSchema::Json(schema) => match serde_json::from_slice::<Value>(value) {
+                Ok(value) => validate_json(schema, &value, "$", 0).err(),
+                Err(e) => Some(Violation::new("$", format!("not a json value: {e}"))),
+            },
+            CompiledSchema::Protobuf { pool, message_name } => {
+                // The message has been checked when compiling.
+                let message = pool.find(message_name).expect("message must exist");
+                validate_message(pool, message, value, message_name, 0).err()
+            }
+        };
+        match violation {
+            None => Ok(()),
+            Some(Violation { path, reason }) => Err(Error::SchemaViolation { path, reason }),
+        }
+    }
+}
+
+/// Check whether the schema itself is well-formed.
+pub fn check_schema(schema: &ValueSchema) -> Result<()> {
+    CompiledSchema::compile(schema).map(|_| ())
+}
+
+impl SchemaCache {
+    /// Validate the value against the schema of the collection which the shard belongs to.
+    pub fn validate_shard_value(
+        &self,
+        group_engine: &GroupEngine,
+        shard_id: u64,
+        value: &[u8],
+    ) -> Result<()> {
+        let desc = group_engine.shard_desc(shard_id)?;
+        let Some(meta) = desc.collection_meta.as_ref() else {
+            return Ok(());
+        };
+        let Some(schema) = meta.schema.as_ref().filter(|s| s.schema.is_some()) else {
+            return Ok(());
+        };
+        let live_collections = || {
+            group_engine
+                .descriptor()
+                .shards
+                .iter()
+                .map(|shard| shard.collection_id)
+                .collect()
+        };
+        self.compiled(desc.collection_id, meta.epoch, schema, live_collections)?
+            .validate(value)
+    }
+
+    fn compiled<F>(
+        &self,
+        collection_id: u64,
+        epoch: u64,
+        schema: &ValueSchema,
+        live_collections: F,
+    ) -> Result<Arc<CompiledSchema>>
+    where
+        F: FnOnce() -> HashSet<u64>,
+    {
+        if let Some((cached_epoch, compiled)) = self.schemas.lock().unwrap().get(&collection_id) {
+            if *cached_epoch == epoch {
+                return Ok(compiled.clone());
+            }
+        }
+
+        let compiled = Arc::new(CompiledSchema::compile(schema)?);
+        let live_collections = live_collections();
+        let mut schemas = self.schemas.lock().unwrap();
+        // The collection is dropped or its shards are moved out, once the shards leave the group.
+        schemas.retain(|id, _| live_collections.contains(id));
+        let cached_epoch = schemas.get(&collection_id).map(|(e, _)| *e);
+        // The shards of the group might not synchronize the latest metadata yet, only the latest
+        // one is cached.
+        if cached_epoch.map(|e| e < epoch).unwrap_or(true) {
+            schemas.insert(collection_id, (epoch, compiled.clone()));
+        }
+        Ok(compiled)
+    }
+}
+
+#[derive(Debug)]
+struct Violation {
+    path: String,
+    reason: String,
+}
+
+impl Violation {
+    fn new(path: &str, reason: impl Into<String>) -> Self {
+        Violation {
+            path: path.to_owned(),
+            reason: reason.into(),
+        }
+    }
+}
+
+type Validation = std::result::Result<(), Violation>;
+
+fn validate_json(schema: &Value, value: &Value, path: &str, depth: usize) -> Validation {
+    let schema = match schema {
+        Value::Bool(true) => return Ok(()),
+        Value::Bool(false) => return Err(Violation::new(path, "no value is allowed")),
+        Value::Object(schema) => schema,
+        _ => return Ok(()),
+    };
+    if depth > MAX_DEPTH {
+        return Err(Violation::new(path, "value is nested too deep"));
+    }
+
+    match schema.get("type") {
+        Some(Value::String(expect)) if !is_json_type(value, expect) => {
+            return Err(Violation::new(path, format!("expect {expect}")));
+        }
+        Some(Value::Array(expects))
+            if !expects
+                .iter()
+                .filter_map(Value::as_str)
+                .any(|expect| is_json_type(value, expect)) =>
+        {
+            let expects = expects
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join(" or ");
+            return Err(Violation::new(path, format!("expect {expects}")));
+        }
+        _ => {}
+    }
+
+    if let Some(Value::Array(candidates)) = schema.get("enum") {
+        if !candidates.contains(value) {
+            return Err(Violation::new(path, "value is not one of the enum"));
+        }
+    }
+
+    match value {
+        Value::Object(object) => {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for field in required.iter().filter_map(Value::as_str) {
+                    if !object.contains_key(field) {
+                        return Err(Violation::new(
+                            path,
+                            format!("required field `{field}` is missing"),
+                        ));
+                    }
+                }
+            }
+            let properties = schema.get("properties").and_then(Value::as_object);
+            for (field, value) in object {
+                let field_path = format!("{path}.{field}");
+                match properties.and_then(|p| p.get(field)) {
+                    Some(field_schema) => {
+                        validate_json(field_schema, value, &field_path, depth + 1)?
+                    }
+                    None => match schema.get("additionalProperties") {
+                        Some(Value::Bool(false)) => {
+                            return Err(Violation::new(&field_path, "unknown field"))
+                        }
+                        Some(additional) => {
+                            validate_json(additional, value, &field_path, depth + 1)?
+                        }
+                        None => {}
+                    },
+                }
+            }
+        }
+        Value::Array(items) => {
+            if let Some(item_schema) = schema.get("items") {
+                for (i, item) in items.iter().enumerate() {
+                    validate_json(item_schema, item, &format!("{path}[{i}]"), depth + 1)?;
+                }
+            }
+        }
+        Value::Number(number) => {
+            let number = number.as_f64().unwrap_or_default();
+            if let Some(minimum) = schema.get("minimum").and_then(Value::as_f64) {
+                if number < minimum {
+                    return Err(Violation::new(path, format!("less than minimum {minimum}")));
+                }
+            }
+            if let Some(maximum) = schema.get("maximum").and_then(Value::as_f64) {
+                if number > maximum {
+                    return Err(Violation::new(
+                        path,
+                        format!("greater than maximum {maximum}"),
+                    ));
+                }
+            }
+        }
+        Value::String(s) => {
+            let len = s.chars().count() as u64;
+            if let Some(min_len) = schema.get("minLength").and_then(Value::as_u64) {
+                if len < min_len {
+                    return Err(Violation::new(
+                        path,
+                        format!("shorter than minLength {min_len}"),
+                    ));
+                }
+            }
+            if let Some(max_len) = schema.get("maxLength").and_then(Value::as_u64) {
+                if len > max_len {
+                    return Err(Violation::new(
+                        path,
+                        format!("longer than maxLength {max_len}"),
+                    ));
+                }
+            }
+        }
+        Value::Null | Value::Bool(_) => {}
+    }
+    Ok(())
+}
+
+fn is_json_type(value: &Value, expect: &str) -> bool {
+    match expect {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64()
+                || value.is_u64()
+                || value.as_f64().map(|v| v.fract() == 0.0).unwrap_or_default()
+        }
+        _ => true,
+    }
+}
+
+/// The message types of a `FileDescriptorSet`, indexed by the full name without the leading dot.
+pub struct DescriptorPool {
+    messages: HashMap<String, DescriptorProto>,
+}
+
+impl DescriptorPool {
+    fn decode(file_descriptor_set: &[u8]) -> Result<Self> {
+        let set = FileDescriptorSet::decode(file_descriptor_set)
+            .map_err(|e| Error::InvalidArgument(format!("invalid file descriptor set: {e}")))?;
+        let mut messages = HashMap::default();
+        for file in set.file {
+            let package = file.package().to_owned();
+            for message in file.message_type {
+                Self::register(&mut messages, &package, message);
+            }
+        }
+        Ok(DescriptorPool { messages })
+    }
+
+    fn register(
+        messages: &mut HashMap<String, DescriptorProto>,
+        scope: &str,
+        message: DescriptorProto,
+    ) {
+        let full_name = if scope.is_empty() {
+            message.name().to_owned()
+        } else {
+            format!("{scope}.{}", message.name())
+        };
+        for nested in &message.nested_type {
+            Self::register(messages, &full_name, nested.clone());
+        }
+        messages.insert(full_name, message);
+    }
+
+    fn find(&self, name: &str) -> Option<&DescriptorProto> {
+        self.messages.get(name.trim_start_matches('.'))
+    }
+}
+
+fn validate_message(
+    pool: &DescriptorPool,
+    message: &DescriptorProto,
+    mut buf: &[u8],
+    path: &str,
+    depth: usize,
+) -> Validation {
+    if depth > MAX_DEPTH {
+        return Err(Violation::new(path, "message is nested too deep"));
+    }
+
+    let mut seen = Vec::new();
+    while buf.has_remaining() {
+        let (tag, wire_type) =
+            decode_key(&mut buf).map_err(|e| Violation::new(path, e.to_string()))?;
+        let field = message
+            .field
+            .iter()
+            .find(|f| f.number() as u32 == tag)
+            .ok_or_else(|| Violation::new(path, format!("unknown field {tag}")))?;
+        let field_path = format!("{path}.{}", field.name());
+        let field_type = field.r#type();
+        let repeated = field.label() == Label::Repeated;
+        seen.push(tag);
+
+        match (field_type, wire_type) {
+            (Type::String | Type::Bytes | Type::Message, WireType::LengthDelimited) => {
+                let payload = take_length_delimited(&mut buf, &field_path)?;
+                if field_type == Type::String && std::str::from_utf8(payload).is_err() {
+                    return Err(Violation::new(&field_path, "invalid utf-8 string"));
+                }
+                if field_type == Type::Message {
+                    let nested = pool.find(field.type_name()).ok_or_else(|| {
+                        Violation::new(
+                            &field_path,
+                            format!("message {} not found", field.type_name()),
+                        )
+                    })?;
+                    validate_message(pool, nested, payload, &field_path, depth + 1)?;
+                }
+            }
+            (_, WireType::LengthDelimited)
+                if repeated && scalar_wire_type(field_type).is_some() =>
+            {
+                // Packed repeated scalar fields.
+                let mut payload = take_length_delimited(&mut buf, &field_path)?;
+                let wire_type = scalar_wire_type(field_type).unwrap();
+                while payload.has_remaining() {
+                    skip_scalar(&mut payload, wire_type, &field_path)?;
+                }
+            }
+            (_, _) if scalar_wire_type(field_type) == Some(wire_type) => {
+                skip_scalar(&mut buf, wire_type, &field_path)?;
+            }
+            _ => {
+                return Err(Violation::new(
+                    &field_path,
+                    format!("expect {field_type:?}, but got wire type {wire_type:?}"),
+                ));
+            }
+        }
+    }
+
+    for field in &message.field {
+        if field.label() == Label::Required && !seen.contains(&(field.number() as u32)) {
+            return Err(Violation::new(
+                path,
+                format!("required field `{}` is missing", field.name()),
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn scalar_wire_type(field_type: Type) -> Option<WireType> {
+    match field_type {
+        Type::Int32
+        | Type::Int64
+        | Type::Uint32
+        | Type::Uint64
+        | Type::Sint32
+        | Type::Sint64
+        | Type::Bool
+        | Type::Enum => Some(WireType::Varint),
+        Type::Fixed32 | Type::Sfixed32 | Type::Float => Some(WireType::ThirtyTwoBit),
+        Type::Fixed64 | Type::Sfixed64 | Type::Double => Some(WireType::SixtyFourBit),
+        Type::String | Type::Bytes | Type::Message | Type::Group => None,
+    }
+}
+
+fn skip_scalar(buf: &mut &[u8], wire_type: WireType, path: &str) -> Validation {
+    let size = match wire_type {
+        WireType::Varint => {
+            decode_varint(buf).map_err(|e| Violation::new(path, e.to_string()))?;
+            return Ok(());
+        }
+        WireType::ThirtyTwoBit => 4,
+        WireType::SixtyFourBit => 8,
+        _ => unreachable!(),
+    };
+    if buf.remaining() < size {
+        return Err(Violation::new(path, "buffer underflow"));
+    }
+    buf.advance(size);
+    Ok(())
+}
+
+fn take_length_delimited<'a>(
+    buf: &mut &'a [u8],
+    path: &str,
+) -> std::result::Result<&'a [u8], Violation> {
+    let len = decode_varint(buf).map_err(|e| Violation::new(path, e.to_string()))? as usize;
+    if buf.remaining() < len {
+        return Err(Violation::new(path, "buffer underflow"));
+    }
+    let data: &'a [u8] = buf;
+    let (payload, rest) = data.split_at(len);
+    *buf = rest;
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use engula_api::v1::value_schema::{Protobuf, Schema};
+    use prost::encoding;
+    use prost_types::{FieldDescriptorProto, FileDescriptorProto};
+
+    use super::*;
+
+    fn validate(schema: &ValueSchema, value: &[u8]) -> Result<()> {
+        CompiledSchema::compile(schema)?.validate(value)
+    }
+
+    fn json_schema(schema: &str) -> ValueSchema {
+        ValueSchema {
+            schema: Some(Schema::JsonSchema(schema.to_owned())),
+        }
+    }
+
+    fn field(name: &str, number: i32, ty: Type, label: Label) -> FieldDescriptorProto {
+        FieldDescriptorProto {
+            name: Some(name.to_owned()),
+            number: Some(number),
+            r#type: Some(ty as i32),
+            label: Some(label as i32),
+            ..Default::default()
+        }
+    }
+
+    fn proto_schema() -> ValueSchema {
+        let mut address = field("address", 3, Type::Message, Label::Optional);
+        address.type_name = Some(".example.User.Address".to_owned());
+        let user = DescriptorProto {
+            name: Some("User".to_owned()),
+            field: vec![
+                field("name", 1, Type::String, Label::Optional),
+                field("scores", 2, Type::Int32, Label::Repeated),
+                address,
+            ],
+            nested_type: vec![DescriptorProto {
+                name: Some("Address".to_owned()),
+                field: vec![field("city", 1, Type::String, Label::Required)],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let set = FileDescriptorSet {
+            file: vec![FileDescriptorProto {
+                package: Some("example".to_owned()),
+                message_type: vec![user],
+                ..Default::default()
+            }],
+        };
+        ValueSchema {
+            schema: Some(Schema::Protobuf(Protobuf {
+                file_descriptor_set: set.encode_to_vec(),
+                message_name: "example.User".to_owned(),
+            })),
+        }
+    }
+
+    #[test]
+    fn check_invalid_schema() {
+        assert!(check_schema(&json_schema(r#"{"type": "object"}"#)).is_ok());
+        assert!(check_schema(&json_schema("{")).is_err());
+        assert!(check_schema(&json_schema("1")).is_err());
+        assert!(check_schema(&proto_schema()).is_ok());
+        assert!(check_schema(&ValueSchema::default()).is_err());
+
+        let mut schema = proto_schema();
+        if let Some(Schema::Protobuf(protobuf)) = schema.schema.as_mut() {
+            protobuf.message_name = "example.Order".to_owned();
+        }
+        assert!(check_schema(&schema).is_err());
+    }
+
+    #[test]
+    fn validate_json_schema() {
+        let schema = json_schema(
+            r#"{
+                "type": "object",
+                "required": ["name"],
+                "additionalProperties": false,
+                "properties": {
+                    "name": {"type": "string", "minLength": 1, "maxLength": 8},
+                    "age": {"type": "integer", "minimum": 0},
+                    "role": {"enum": ["admin", "guest"]},
+                    "tags": {"type": "array", "items": {"type": "string"}}
+                }
+            }"#,
+        );
+
+        let cases: Vec<(&str, Option<&str>)> = vec![
+            (r#"{"name": "walter", "age": 18, "tags": ["a"]}"#, None),
+            (r#"{"name": "walter", "role": "admin"}"#, None),
+            ("not json", Some("$")),
+            ("[]", Some("$")),
+            (r#"{"age": 18}"#, Some("$")),
+            (r#"{"name": ""}"#, Some("$.name")),
+            (r#"{"name": "walter-walter"}"#, Some("$.name")),
+            (r#"{"name": "walter", "age": 1.5}"#, Some("$.age")),
+            (r#"{"name": "walter", "age": -1}"#, Some("$.age")),
+            (r#"{"name": "walter", "role": "root"}"#, Some("$.role")),
+            (r#"{"name": "walter", "tags": [1]}"#, Some("$.tags[0]")),
+            (r#"{"name": "walter", "email": "a@b"}"#, Some("$.email")),
+        ];
+        for (value, expect) in cases {
+            match (validate(&schema, value.as_bytes()), expect) {
+                (Ok(()), None) => {}
+                (Err(Error::SchemaViolation { path, reason }), Some(expect)) => {
+                    assert_eq!(path, expect, "value {value}: {reason}");
+                    assert!(!reason.is_empty());
+                }
+                (r, _) => panic!("value {value}: expect {expect:?}, but got {r:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn validate_protobuf_schema() {
+        let schema = proto_schema();
+
+        let mut address = Vec::new();
+        encoding::string::encode(1, &"sz".to_owned(), &mut address);
+        let mut user = Vec::new();
+        encoding::string::encode(1, &"walter".to_owned(), &mut user);
+        encoding::int32::encode_packed(2, &[1, 2, 3], &mut user);
+        encoding::int32::encode(2, &4, &mut user);
+        encoding::bytes::encode(3, &address, &mut user);
+        assert!(validate(&schema, &user).is_ok());
+        assert!(validate(&schema, &[]).is_ok());
+
+        // unknown field
+        let mut value = user.clone();
+        encoding::string::encode(4, &"a@b".to_owned(), &mut value);
+        assert!(validate(&schema, &value).is_err());
+
+        // mismatched wire type
+        let mut value = Vec::new();
+        encoding::int32::encode(1, &1, &mut value);
+        assert!(validate(&schema, &value).is_err());
+
+        // invalid utf-8 string
+        let mut value = Vec::new();
+        encoding::bytes::encode(1, &vec![0xff, 0xfe], &mut value);
+        assert!(validate(&schema, &value).is_err());
+
+        // missing required field of nested message
+        let mut value = Vec::new();
+        encoding::bytes::encode(3, &Vec::<u8>::new(), &mut value);
+        assert!(validate(&schema, &value).is_err());
+
+        // truncated
+        assert!(validate(&schema, &user[..user.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn compiled_schema_cached_by_epoch() {
+        const COLLECTION_ID: u64 = 1;
+
+        let cache = SchemaCache::default();
+        let live = || HashSet::from([COLLECTION_ID]);
+        let string_schema = json_schema(r#"{"type": "string"}"#);
+        let first = cache
+            .compiled(COLLECTION_ID, 1, &string_schema, live)
+            .unwrap();
+        let cached = cache
+            .compiled(COLLECTION_ID, 1, &string_schema, live)
+            .unwrap();
+        assert!(Arc::ptr_eq(&first, &cached));
+
+        // The schema is compiled again once the epoch advances.
+        let number_schema = json_schema(r#"{"type": "number"}"#);
+        let second = cache
+            .compiled(COLLECTION_ID, 2, &number_schema, live)
+            .unwrap();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert!(second.validate(b"1").is_ok());
+
+        // The stale metadata doesn't replace the cached one.
+        let stale = cache
+            .compiled(COLLECTION_ID, 1, &string_schema, live)
+            .unwrap();
+        assert!(stale.validate(br#""a""#).is_ok());
+        let cached = cache
+            .compiled(COLLECTION_ID, 2, &number_schema, live)
+            .unwrap();
+        assert!(Arc::ptr_eq(&second, &cached));
+
+        // The collections which no longer have shards in the group are evicted.
+        let other = cache
+            .compiled(COLLECTION_ID + 1, 1, &string_schema, || {
+                HashSet::from([COLLECTION_ID + 1])
+            })
+            .unwrap();
+        assert_eq!(cache.schemas.lock().unwrap().len(), 1);
+        let cached = cache
+            .compiled(COLLECTION_ID + 1, 1, &string_schema, live)
+            .unwrap();
+        assert!(Arc::ptr_eq(&other, &cached));
+    }
+}
@@ -11,36 +11,87 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
-use std::path::Path;
+use std::{
+    path::Path,
+    sync::mpsc::{self, Receiver, Sender, SyncSender},
+};
 
 use engula_api::server::v1::GroupDesc;
 use tracing::{debug, error, info};
 
 use crate::{
-    engine::{GroupEngine, RawIterator},
-    raftgroup::SnapshotBuilder,
+    encryption::DataKeyManager,
+    engine::{write_encrypted_sst_file, GroupEngine, RawIterator},
+    raftgroup::{
+        snap::kv_file::{is_kv_file, KvFileReader, KvFileWriter},
+        SnapshotBuilder, SnapshotStream,
+    },
     serverpb::v1::ApplyState,
     Error, ReplicaConfig, Result,
 };
 
+type KeyValues = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// The dir in the snapshot to stage the SST files converted from the kv files.
+const SST_STAGE_DIR: &str = "sst";
+
 pub struct GroupSnapshotBuilder {
     cfg: ReplicaConfig,
     engine: GroupEngine,
 }
 
+/// Stream the data of a group engine. The iterator borrows the engine, so it is owned by a
+/// dedicated thread, which serves the chunk requests until the stream is dropped. The iterator
+/// pins the view of data, so the chunks are consistent even if the stream is resumed.
+pub struct GroupSnapshotStream {
+    apply_state: ApplyState,
+    descriptor: GroupDesc,
+    sender: Sender<ChunkRequest>,
+}
+
+struct ChunkRequest {
+    start_key: Vec<u8>,
+    limit: usize,
+    sender: SyncSender<Result<KeyValues>>,
+}
+
 impl GroupSnapshotBuilder {
     pub(crate) fn new(cfg: ReplicaConfig, engine: GroupEngine) -> Self {
         GroupSnapshotBuilder { cfg, engine }
     }
+
+    /// Stable the checkpoint to `base_dir` as plaintext SST files, which are ingested directly
+    /// when restoring. It is used by the backups, which are restored by the other clusters
+    /// without the data keys of this node.
+    pub(crate) async fn sst_checkpoint(&self, base_dir: &Path) -> Result<(ApplyState, GroupDesc)> {
+        std::fs::create_dir_all(base_dir)?;
+        let mut iter = self.engine.raw_iter()?;
+        for i in 0.. {
+            if write_partial_to_file(&self.cfg, &mut iter, base_dir, i)
+                .await?
+                .is_none()
+            {
+                break;
+            }
+        }
+
+        let apply_state = iter.apply_state().clone();
+        let descriptor = iter.descriptor().clone();
+        Ok((apply_state, descriptor))
+    }
 }
 
 #[crate::async_trait]
 impl SnapshotBuilder for GroupSnapshotBuilder {
-    async fn checkpoint(&self, base_dir: &Path) -> Result<(ApplyState, GroupDesc)> {
+    async fn checkpoint(
+        &self,
+        key_manager: &DataKeyManager,
+        base_dir: &Path,
+    ) -> Result<(ApplyState, GroupDesc)> {
         std::fs::create_dir_all(base_dir)?;
         let mut iter = self.engine.raw_iter()?;
         for i in 0.. {
-            if write_partial_to_file(&self.cfg, &mut iter, base_dir, i)
+            if write_partial_to_kv_file(&self.cfg, key_manager, &mut iter, base_dir, i)
                 .await?
                 .is_none()
             {
@@ -52,6 +103,103 @@ impl SnapshotBuilder for GroupSnapshotBuilder {
         let descriptor = iter.descriptor().clone();
         Ok((apply_state, descriptor))
     }
+
+    fn stream(&self) -> Result<Option<Box<dyn SnapshotStream>>> {
+        if !self.cfg.snap_streaming {
+            return Ok(None);
+        }
+        let stream = GroupSnapshotStream::open(self.engine.clone())?;
+        Ok(Some(Box::new(stream)))
+    }
+}
+
+impl GroupSnapshotStream {
+    pub(crate) fn open(engine: GroupEngine) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let (state_sender, state_receiver) = mpsc::sync_channel(1);
+        std::thread::Builder::new()
+            .name("snapshot-stream".to_owned())
+            .spawn(move || {
+                let mut iter = match engine.raw_iter() {
+                    Ok(iter) => iter,
+                    Err(err) => {
+                        state_sender.send(Err(err)).unwrap_or_default();
+                        return;
+                    }
+                };
+                let state = (iter.apply_state().clone(), iter.descriptor().clone());
+                state_sender.send(Ok(state)).unwrap_or_default();
+                serve_chunk_requests(&mut iter, receiver);
+            })?;
+
+        let (apply_state, descriptor) = state_receiver
+            .recv()
+            .map_err(|_| Error::InvalidData("snapshot stream thread is exited".to_owned()))??;
+        Ok(GroupSnapshotStream {
+            apply_state,
+            descriptor,
+            sender,
+        })
+    }
+}
+
+impl SnapshotStream for GroupSnapshotStream {
+    fn apply_state(&self) -> ApplyState {
+        self.apply_state.clone()
+    }
+
+    fn descriptor(&self) -> GroupDesc {
+        self.descriptor.clone()
+    }
+
+    fn next_chunk(&mut self, start_key: &[u8], limit: usize) -> Result<KeyValues> {
+        let exited = || Error::InvalidData("snapshot stream thread is exited".to_owned());
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let req = ChunkRequest {
+            start_key: start_key.to_owned(),
+            limit,
+            sender,
+        };
+        self.sender.send(req).map_err(|_| exited())?;
+        receiver.recv().map_err(|_| exited())?
+    }
+}
+
+fn serve_chunk_requests(iter: &mut RawIterator<'_>, receiver: Receiver<ChunkRequest>) {
+    // The key of the last returned chunk, the iterator is positioned after it.
+    let mut last_key: Option<Vec<u8>> = None;
+    while let Ok(req) = receiver.recv() {
+        let result = read_chunk(iter, &mut last_key, &req.start_key, req.limit);
+        req.sender.send(result).unwrap_or_default();
+    }
+}
+
+fn read_chunk(
+    iter: &mut RawIterator<'_>,
+    last_key: &mut Option<Vec<u8>>,
+    start_key: &[u8],
+    limit: usize,
+) -> Result<KeyValues> {
+    // Seek only if the stream is resumed or read by others, to avoid the cost of seeking.
+    if last_key.as_deref() != Some(start_key) {
+        iter.seek(start_key);
+    }
+
+    let mut key_values = vec![];
+    let mut size = 0;
+    while size < limit {
+        let Some(item) = iter.next() else {
+            break;
+        };
+        let (key, value) = item?;
+        if key.as_ref() == start_key {
+            continue;
+        }
+        size += key.len() + value.len();
+        key_values.push((key.to_vec(), value.to_vec()));
+    }
+    *last_key = key_values.last().map(|(key, _)| key.clone());
+    Ok(key_values)
 }
 
 /// Write partial of the iterator's data to the file, return `None` if all data is written.
@@ -96,6 +244,46 @@ async fn write_partial_to_file(
     }
 }
 
+/// Write partial of the iterator's data to the kv file, return `None` if all data is written.
+async fn write_partial_to_kv_file(
+    cfg: &ReplicaConfig,
+    key_manager: &DataKeyManager,
+    iter: &mut RawIterator<'_>,
+    base_dir: &Path,
+    file_no: usize,
+) -> Result<Option<()>> {
+    let file = base_dir.join(format!("{file_no}.kv"));
+    let mut writer: Option<KvFileWriter> = None;
+    let mut index = 0;
+    for item in iter.by_ref() {
+        let (key, value) = item?;
+        if writer.is_none() {
+            debug!("create kv file: {}", file.display());
+            writer = Some(KvFileWriter::create(key_manager, &file)?);
+        }
+
+        let writer = writer.as_mut().unwrap();
+        writer.put(&key, &value)?;
+        if writer.file_size() as u64 >= cfg.snap_file_size {
+            break;
+        }
+
+        index += 1;
+        if index % 1024 == 0 {
+            crate::runtime::yield_now().await;
+        }
+    }
+
+    if let Some(writer) = writer {
+        writer.finish()?;
+        Ok(Some(()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Replace the data of the group engine with the snapshot. The snapshot consists of the kv files
+/// written by [`GroupSnapshotBuilder`], or the SST files of a backup.
 pub(crate) fn apply_snapshot(engine: &GroupEngine, replica_id: u64, snap_dir: &Path) -> Result<()> {
     if !snap_dir.is_dir() {
         error!(
@@ -109,6 +297,7 @@ pub(crate) fn apply_snapshot(engine: &GroupEngine, replica_id: u64, snap_dir: &P
     }
 
     let mut files = vec![];
+    let mut kv_files = vec![];
     for entry in snap_dir.read_dir()? {
         let entry = entry?;
         let path = entry.path();
@@ -118,9 +307,50 @@ pub(crate) fn apply_snapshot(engine: &GroupEngine, replica_id: u64, snap_dir: &P
                 path.display()
             );
             files.push(path.file_name().unwrap().to_owned());
+        } else if is_kv_file(&path) {
+            debug!(
+                "replica {replica_id} apply snapshot with kv file {}",
+                path.display()
+            );
+            kv_files.push(path);
         }
     }
 
+    if !kv_files.is_empty() {
+        if !files.is_empty() {
+            return Err(Error::InvalidData(format!(
+                "{} has both sst and kv files",
+                snap_dir.display()
+            )));
+        }
+        // The files are named by their orders, and the keys are ordered across files. They are
+        // rewritten into the SST files whose values are encrypted, and then ingested at once, so
+        // the apply state is never persisted along with partial data.
+        kv_files.sort_unstable_by_key(|path| file_no(path));
+        let stage_dir = snap_dir.join(SST_STAGE_DIR);
+        if stage_dir.exists() {
+            std::fs::remove_dir_all(&stage_dir)?;
+        }
+        std::fs::create_dir_all(&stage_dir)?;
+        let key_manager = engine.key_manager();
+        let mut sst_files = Vec::with_capacity(kv_files.len());
+        for (i, path) in kv_files.iter().enumerate() {
+            let reader = KvFileReader::open(key_manager, path)?;
+            let sst_file = stage_dir.join(format!("{i}.sst"));
+            if write_encrypted_sst_file(key_manager, &sst_file, reader)? > 0 {
+                sst_files.push(sst_file);
+            }
+        }
+        engine.ingest(sst_files)?;
+        std::fs::remove_dir_all(&stage_dir)?;
+        info!(
+            "replica {replica_id} apply snapshot {}, apply state {:?}",
+            snap_dir.display(),
+            engine.flushed_apply_state()
+        );
+        return Ok(());
+    }
+
     if files.is_empty() {
         error!(
             "replica {replica_id} apply snapshot {}: snap dir is empty",
@@ -151,6 +381,13 @@ pub(crate) fn apply_snapshot(engine: &GroupEngine, replica_id: u64, snap_dir: &P
     Ok(())
 }
 
+fn file_no(path: &Path) -> u64 {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.parse().ok())
+        .unwrap_or(u64::MAX)
+}
+
 #[inline]
 fn is_sst_file<P: AsRef<Path>>(path: P) -> bool {
     let path = path.as_ref();
@@ -194,6 +431,7 @@ mod tests {
                         start: vec![],
                         end: vec![],
                     })),
+                    ..Default::default()
                 }],
                 ..Default::default()
             }),
@@ -240,7 +478,10 @@ mod tests {
             std::fs::create_dir_all(&snap_dir).unwrap();
             let data = snap_dir.join("DATA");
             let builder = GroupSnapshotBuilder::new(cfg, engine);
-            builder.checkpoint(&data).await.unwrap();
+            builder
+                .checkpoint(&DataKeyManager::plaintext(), &data)
+                .await
+                .unwrap();
         });
     }
 
@@ -266,8 +507,88 @@ mod tests {
             std::fs::create_dir_all(&snap_dir).unwrap();
             let data = snap_dir.join("DATA");
             let builder = GroupSnapshotBuilder::new(cfg, engine.clone());
-            builder.checkpoint(&data).await.unwrap();
+            builder
+                .checkpoint(engine.key_manager(), &data)
+                .await
+                .unwrap();
             apply_snapshot(&engine, 1, &data).unwrap();
         });
     }
+
+    #[test]
+    fn apply_snapshot_into_another_engine() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        executor.block_on(async {
+            let tmp_dir = TempDir::new("apply_snapshot_into_another_engine")
+                .unwrap()
+                .into_path();
+            let engine = create_engine(&tmp_dir.join("db"), 1, 1).await;
+            put_data(&engine, 1, "key", 128);
+
+            let cfg = ReplicaConfig {
+                snap_file_size: 64 * 1024,
+                ..Default::default()
+            };
+            let data = tmp_dir.join("snap").join("DATA");
+            let builder = GroupSnapshotBuilder::new(cfg, engine.clone());
+            builder
+                .checkpoint(engine.key_manager(), &data)
+                .await
+                .unwrap();
+
+            // The kv files are ingested at once, and the staged SST files are removed.
+            let target = create_engine(&tmp_dir.join("target"), 1, 1).await;
+            apply_snapshot(&target, 1, &data).unwrap();
+            assert!(!data.join(SST_STAGE_DIR).exists());
+            let read_all = |engine: &GroupEngine| {
+                engine
+                    .raw_iter()
+                    .unwrap()
+                    .map(|item| item.map(|(k, v)| (k.to_vec(), v.to_vec())))
+                    .collect::<std::result::Result<Vec<_>, _>>()
+                    .unwrap()
+            };
+            assert_eq!(read_all(&target), read_all(&engine));
+        });
+    }
+
+    #[test]
+    fn stream_consistent_view() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        executor.block_on(async {
+            let tmp_dir = TempDir::new("stream_consistent_view").unwrap().into_path();
+            let engine = create_engine(&tmp_dir, 1, 1).await;
+            put_data(&engine, 1, "key", 128);
+
+            let mut stream = GroupSnapshotStream::open(engine.clone()).unwrap();
+            let mut iter = engine.raw_iter().unwrap();
+            let expect = iter
+                .by_ref()
+                .map(|item| item.map(|(k, v)| (k.to_vec(), v.to_vec())))
+                .collect::<std::result::Result<Vec<_>, _>>()
+                .unwrap();
+
+            // The writes after opening are invisible to the stream.
+            put_data(&engine, 1, "new-key", 16);
+
+            let mut key_values = vec![];
+            let mut start_key = vec![];
+            loop {
+                let chunk = stream.next_chunk(&start_key, 4 * 1024).unwrap();
+                let Some((last_key, _)) = chunk.last() else {
+                    break;
+                };
+                start_key = last_key.clone();
+                key_values.extend(chunk);
+            }
+            assert_eq!(key_values, expect);
+
+            // Resume from the middle.
+            let (middle_key, _) = &expect[expect.len() / 2];
+            let chunk = stream.next_chunk(middle_key, usize::MAX).unwrap();
+            assert_eq!(chunk.as_slice(), &expect[expect.len() / 2 + 1..]);
+        });
+    }
 }
@@ -14,20 +14,25 @@
 
 mod checkpoint;
 
-use std::{collections::HashSet, path::Path, sync::Arc};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 use engula_api::server::v1::{
-    ChangeReplica, ChangeReplicaType, ChangeReplicas, GroupDesc, MigrationDesc, ReplicaDesc,
-    ReplicaRole,
+    ChangeReplica, ChangeReplicaType, ChangeReplicas, CollectionMeta, GroupDesc, MigrationDesc,
+    ReplicaDesc, ReplicaRole,
 };
 use tracing::{info, trace, warn};
 
-use super::ReplicaInfo;
+pub(crate) use self::checkpoint::{apply_snapshot, GroupSnapshotBuilder};
+use super::{checksum, ChecksumTable, ReplicaInfo};
 use crate::{
     engine::{GroupEngine, WriteBatch, WriteStates},
     raftgroup::{ApplyEntry, SnapshotBuilder, StateMachine},
     serverpb::v1::*,
-    ReplicaConfig, Result,
+    Error, ReplicaConfig, Result,
 };
 
 const SHARD_UPDATE_DELTA: u64 = 1 << 32;
@@ -60,7 +65,10 @@ where
     info: Arc<ReplicaInfo>,
 
     group_engine: GroupEngine,
+    /// The directory to stage the external SST files before they are ingested.
+    ingest_dir: PathBuf,
     observer: Box<dyn StateMachineObserver>,
+    checksums: Arc<ChecksumTable>,
 
     plugged_write_batches: Vec<WriteBatch>,
     plugged_write_states: WriteStates,
@@ -76,7 +84,9 @@ impl GroupStateMachine {
         cfg: ReplicaConfig,
         info: Arc<ReplicaInfo>,
         group_engine: GroupEngine,
+        ingest_dir: PathBuf,
         observer: Box<dyn StateMachineObserver>,
+        checksums: Arc<ChecksumTable>,
     ) -> Self {
         let apply_state = group_engine
             .flushed_apply_state()
@@ -85,7 +95,9 @@ impl GroupStateMachine {
             cfg,
             info,
             group_engine,
+            ingest_dir,
             observer,
+            checksums,
             plugged_write_batches: Vec::default(),
             plugged_write_states: WriteStates::default(),
             desc_updated: false,
@@ -115,7 +127,7 @@ impl GroupStateMachine {
         Ok(())
     }
 
-    fn apply_proposal(&mut self, eval_result: EvalResult) -> Result<()> {
+    fn apply_proposal(&mut self, index: u64, term: u64, eval_result: EvalResult) -> Result<()> {
         if let Some(wb) = eval_result.batch {
             self.plugged_write_batches.push(WriteBatch::new(&wb.data));
         }
@@ -136,6 +148,27 @@ impl GroupStateMachine {
             if let Some(m) = op.migration {
                 self.apply_migration_event(m, &mut desc);
             }
+            if let Some(archive_state) = op.archive_state {
+                trace!(
+                    "group {} log entries are archived to {}",
+                    self.info.group_id,
+                    archive_state.index
+                );
+                self.plugged_write_states.archive_state = Some(archive_state);
+            }
+            if let Some(ingest_sst) = op.ingest_sst {
+                self.apply_ingest_sst(ApplyState { index, term }, ingest_sst)?;
+            }
+            if let Some(ComputeChecksum { checksum_id }) = op.compute_checksum {
+                self.apply_compute_checksum(index, checksum_id)?;
+            }
+            if let Some(SyncCollection {
+                collection_id,
+                meta: Some(meta),
+            }) = op.sync_collection
+            {
+                self.apply_sync_collection(collection_id, meta, &mut desc);
+            }
 
             // Any sync_op will update group desc.
             self.plugged_write_states.descriptor = Some(desc);
@@ -144,6 +177,120 @@ impl GroupStateMachine {
         Ok(())
     }
 
+    fn apply_ingest_sst(&mut self, apply_state: ApplyState, ingest_sst: IngestSst) -> Result<()> {
+        let group_id = self.info.group_id;
+        let stage_id = ingest_sst.stage_id;
+        info!(
+            "group {group_id} ingest {} sst files of stage {stage_id} into shard {}",
+            ingest_sst.num_files, ingest_sst.shard_id
+        );
+
+        // The ingested records have larger sequences than the unflushed writes, which would be
+        // replayed after restarting and shadow the ingested records. So the writes before are
+        // persisted first, and the log entries after the flushed index start from this one.
+        self.group_engine.group_commit(
+            self.plugged_write_batches.as_slice(),
+            std::mem::take(&mut self.plugged_write_states),
+            false,
+        )?;
+        self.plugged_write_batches.clear();
+        self.group_engine.flush()?;
+
+        // The staged files are kept until the ingestion is persisted along with its apply state,
+        // after which this entry is never applied again. So a missing file means that it isn't
+        // staged by this replica, and the ingestion could not be applied.
+        let stage_dir = sst_stage_dir(&self.ingest_dir, group_id, stage_id);
+        let files = (0..ingest_sst.num_files as usize)
+            .map(|i| staged_sst_file(&stage_dir, i))
+            .collect::<Vec<_>>();
+        if let Some(path) = files.iter().find(|p| !p.exists()) {
+            return Err(Error::InvalidData(format!(
+                "group {group_id} ingest sst files of stage {stage_id}: {} is not staged",
+                path.display()
+            )));
+        }
+        // They are ingested one by one, since their key ranges might be overlapped. An ingestion
+        // interrupted by restarting is ingested again from the first file.
+        for path in files {
+            self.group_engine.ingest_external_files(vec![path])?;
+        }
+        let states = WriteStates {
+            apply_state: Some(apply_state),
+            ..Default::default()
+        };
+        self.group_engine
+            .commit(WriteBatch::default(), states, false)?;
+        self.group_engine.flush()?;
+
+        if let Err(err) = std::fs::remove_dir_all(&stage_dir) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                warn!(
+                    "group {group_id} remove sst stage {}: {err}",
+                    stage_dir.display()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_sync_collection(
+        &mut self,
+        collection_id: u64,
+        meta: CollectionMeta,
+        desc: &mut GroupDesc,
+    ) {
+        let mut updated = false;
+        for shard in desc
+            .shards
+            .iter_mut()
+            .filter(|s| s.collection_id == collection_id)
+        {
+            // The request might be delayed, so the metadata could only be advanced.
+            let epoch = shard.collection_meta.as_ref().map(|m| m.epoch);
+            if epoch.unwrap_or_default() < meta.epoch {
+                shard.collection_meta = Some(meta.clone());
+                updated = true;
+            }
+        }
+        if updated {
+            info!(
+                "group {} sync collection {collection_id} to epoch {}",
+                self.info.group_id, meta.epoch
+            );
+            self.desc_updated = true;
+            desc.epoch += SHARD_UPDATE_DELTA;
+        }
+    }
+
+    fn apply_compute_checksum(&mut self, index: u64, checksum_id: u64) -> Result<()> {
+        info!(
+            "group {} replica {} compute checksum {checksum_id} at index {index}",
+            self.info.group_id, self.info.replica_id
+        );
+
+        // The checksum covers all records applied before this entry.
+        self.commit_plugged_write_batches()?;
+        checksum::compute_checksum(
+            self.group_engine.clone(),
+            self.checksums.clone(),
+            checksum_id,
+            index,
+        );
+        Ok(())
+    }
+
+    fn commit_plugged_write_batches(&mut self) -> Result<()> {
+        if !self.plugged_write_batches.is_empty() {
+            self.group_engine.group_commit(
+                self.plugged_write_batches.as_slice(),
+                WriteStates::default(),
+                false,
+            )?;
+            self.plugged_write_batches.clear();
+        }
+        Ok(())
+    }
+
     fn apply_migration_event(&mut self, migration: Migration, group_desc: &mut GroupDesc) {
         let event = MigrationEvent::from_i32(migration.event).expect("unknown migration event");
         if let Some(desc) = migration.migration_desc.as_ref() {
@@ -304,7 +451,7 @@ impl StateMachine for GroupStateMachine {
                 self.apply_change_replicas(change_replicas)?;
             }
             ApplyEntry::Proposal { eval_result } => {
-                self.apply_proposal(eval_result)?;
+                self.apply_proposal(index, term, eval_result)?;
             }
         }
         self.plugged_write_states.apply_state = Some(ApplyState { index, term });
@@ -359,6 +506,30 @@ impl StateMachine for GroupStateMachine {
             .clone()
             .unwrap_or_else(|| self.group_engine.descriptor())
     }
+
+    fn archived_index(&self) -> Option<u64> {
+        // The archived position is ignored once the archiving is disabled, otherwise the log
+        // entries would be retained forever. If nothing is archived yet, all entries are retained
+        // until the archiver catches up.
+        self.cfg.log_archive.as_ref()?;
+        let archive_state = self
+            .group_engine
+            .archive_state()
+            .expect("access archive state");
+        Some(archive_state.map(|s| s.index).unwrap_or_default())
+    }
+}
+
+/// The directory the SST files to be ingested are staged in, before the ingestion is proposed.
+pub(crate) fn sst_stage_dir(ingest_dir: &Path, group_id: u64, stage_id: u64) -> PathBuf {
+    ingest_dir
+        .join(format!("{group_id}"))
+        .join(format!("{stage_id}"))
+}
+
+#[inline]
+pub(crate) fn staged_sst_file(stage_dir: &Path, index: usize) -> PathBuf {
+    stage_dir.join(format!("{index}.sst"))
 }
 
 impl ChangeReplicaKind {
@@ -531,7 +702,20 @@ fn check_not_in_joint_state(exist: &Option<&mut ReplicaDesc>) {
 
 #[cfg(test)]
 mod tests {
+    use tempdir::TempDir;
+
     use super::*;
+    use crate::{bootstrap::open_engine_with_default_config, runtime::ExecutorOwner, EngineConfig};
+
+    struct NoopObserver;
+
+    impl StateMachineObserver for NoopObserver {
+        fn on_descriptor_updated(&mut self, _: GroupDesc) {}
+
+        fn on_term_updated(&mut self, _: u64) {}
+
+        fn on_migrate_state_updated(&mut self, _: Option<MigrationState>) {}
+    }
 
     fn group_replicas(desc: &GroupDesc) -> Vec<(u64, ReplicaRole)> {
         let mut result: Vec<(u64, ReplicaRole)> = desc
@@ -633,6 +817,7 @@ mod tests {
                     role: ReplicaRole::Voter as i32,
                 },
             ],
+            ..Default::default()
         };
 
         for Test {
@@ -679,6 +864,7 @@ mod tests {
                     role: ReplicaRole::Voter as i32,
                 },
             ],
+            ..Default::default()
         };
 
         let tests = vec![
@@ -765,4 +951,89 @@ mod tests {
             assert_eq!(replicas, expects, "{tips}");
         }
     }
+
+    #[test]
+    fn archived_index_waits_for_archiver() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async {
+            let dir = TempDir::new("archived_index_waits_for_archiver").unwrap();
+            let db = Arc::new(open_engine_with_default_config(dir.path()).unwrap());
+            let group_engine = GroupEngine::create(&EngineConfig::default(), db, 1, 1)
+                .await
+                .unwrap();
+            let state_machine = |log_archive: Option<String>| {
+                let cfg = ReplicaConfig {
+                    log_archive,
+                    ..Default::default()
+                };
+                let replica = ReplicaDesc {
+                    id: 1,
+                    node_id: 1,
+                    ..Default::default()
+                };
+                let info = ReplicaInfo::new(&replica, 1, ReplicaLocalState::Normal);
+                GroupStateMachine::new(
+                    cfg,
+                    Arc::new(info),
+                    group_engine.clone(),
+                    dir.path().join("ingest"),
+                    Box::new(NoopObserver),
+                    Arc::default(),
+                )
+            };
+
+            assert_eq!(state_machine(None).archived_index(), None);
+            let log_archive = Some(format!("file://{}", dir.path().display()));
+            assert_eq!(state_machine(log_archive.clone()).archived_index(), Some(0));
+
+            let states = WriteStates {
+                archive_state: Some(ArchiveState { index: 10 }),
+                ..Default::default()
+            };
+            group_engine
+                .commit(WriteBatch::default(), states, false)
+                .unwrap();
+            assert_eq!(state_machine(log_archive).archived_index(), Some(10));
+            assert_eq!(state_machine(None).archived_index(), None);
+        });
+    }
+
+    #[test]
+    fn apply_ingest_sst_requires_staged_files() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async {
+            let dir = TempDir::new("apply_ingest_sst_requires_staged_files").unwrap();
+            let db = Arc::new(open_engine_with_default_config(dir.path()).unwrap());
+            let group_engine = GroupEngine::create(&EngineConfig::default(), db, 1, 1)
+                .await
+                .unwrap();
+            let replica = ReplicaDesc {
+                id: 1,
+                node_id: 1,
+                ..Default::default()
+            };
+            let info = ReplicaInfo::new(&replica, 1, ReplicaLocalState::Normal);
+            let mut state_machine = GroupStateMachine::new(
+                ReplicaConfig::default(),
+                Arc::new(info),
+                group_engine.clone(),
+                dir.path().join("ingest"),
+                Box::new(NoopObserver),
+                Arc::default(),
+            );
+
+            // The ingestion is never skipped silently, even if the files are not staged.
+            let ingest_sst = IngestSst {
+                shard_id: 1,
+                stage_id: 1,
+                num_files: 1,
+            };
+            let apply_state = ApplyState { index: 10, term: 1 };
+            assert!(matches!(
+                state_machine.apply_ingest_sst(apply_state, ingest_sst),
+                Err(Error::InvalidData(_))
+            ));
+            assert_ne!(group_engine.flushed_apply_state().unwrap().index, 10);
+        });
+    }
 }
@@ -0,0 +1,175 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::Instant,
+};
+
+/// The weight of the latest sample, the rates are smoothed by exponentially weighted moving
+/// average so that a short burst doesn't trigger rebalancing.
+const SMOOTHING_FACTOR: f64 = 0.5;
+
+/// The read and write rates of a replica.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct LoadRates {
+    pub read_qps: f64,
+    pub write_qps: f64,
+    pub read_bytes_per_sec: f64,
+    pub write_bytes_per_sec: f64,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Counters {
+    reads: u64,
+    writes: u64,
+    read_bytes: u64,
+    write_bytes: u64,
+}
+
+struct Sample {
+    at: Instant,
+    counters: Counters,
+    rates: Option<LoadRates>,
+}
+
+/// Records the requests served by a replica.
+pub struct ReplicaLoad {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    read_bytes: AtomicU64,
+    write_bytes: AtomicU64,
+    last_sample: Mutex<Sample>,
+}
+
+impl Default for ReplicaLoad {
+    fn default() -> Self {
+        ReplicaLoad {
+            reads: AtomicU64::default(),
+            writes: AtomicU64::default(),
+            read_bytes: AtomicU64::default(),
+            write_bytes: AtomicU64::default(),
+            last_sample: Mutex::new(Sample {
+                at: Instant::now(),
+                counters: Counters::default(),
+                rates: None,
+            }),
+        }
+    }
+}
+
+impl ReplicaLoad {
+    pub fn record_read(&self, bytes: usize) {
+        self.reads.fetch_add(1, Ordering::Relaxed);
+        self.read_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_write(&self, bytes: usize) {
+        self.writes.fetch_add(1, Ordering::Relaxed);
+        self.write_bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Return the smoothed rates since the last sampling.
+    pub fn sample(&self) -> LoadRates {
+        self.sample_at(Instant::now())
+    }
+
+    fn sample_at(&self, now: Instant) -> LoadRates {
+        let counters = Counters {
+            reads: self.reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            read_bytes: self.read_bytes.load(Ordering::Relaxed),
+            write_bytes: self.write_bytes.load(Ordering::Relaxed),
+        };
+
+        let mut last_sample = self.last_sample.lock().unwrap();
+        let elapsed = now.saturating_duration_since(last_sample.at).as_secs_f64();
+        if elapsed <= 0.0 {
+            return last_sample.rates.unwrap_or_default();
+        }
+
+        let prev = last_sample.counters;
+        let rate = |cur: u64, prev: u64| cur.saturating_sub(prev) as f64 / elapsed;
+        let current = LoadRates {
+            read_qps: rate(counters.reads, prev.reads),
+            write_qps: rate(counters.writes, prev.writes),
+            read_bytes_per_sec: rate(counters.read_bytes, prev.read_bytes),
+            write_bytes_per_sec: rate(counters.write_bytes, prev.write_bytes),
+        };
+        let rates = match last_sample.rates {
+            None => current,
+            Some(prev) => {
+                let smooth =
+                    |cur: f64, prev: f64| cur * SMOOTHING_FACTOR + prev * (1.0 - SMOOTHING_FACTOR);
+                LoadRates {
+                    read_qps: smooth(current.read_qps, prev.read_qps),
+                    write_qps: smooth(current.write_qps, prev.write_qps),
+                    read_bytes_per_sec: smooth(current.read_bytes_per_sec, prev.read_bytes_per_sec),
+                    write_bytes_per_sec: smooth(
+                        current.write_bytes_per_sec,
+                        prev.write_bytes_per_sec,
+                    ),
+                }
+            }
+        };
+        *last_sample = Sample {
+            at: now,
+            counters,
+            rates: Some(rates),
+        };
+        rates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn replica_load_sample() {
+        let load = ReplicaLoad::default();
+        let start = load.last_sample.lock().unwrap().at;
+
+        for _ in 0..100 {
+            load.record_read(10);
+        }
+        for _ in 0..20 {
+            load.record_write(100);
+        }
+        let rates = load.sample_at(start + Duration::from_secs(10));
+        assert_eq!(
+            rates,
+            LoadRates {
+                read_qps: 10.0,
+                write_qps: 2.0,
+                read_bytes_per_sec: 100.0,
+                write_bytes_per_sec: 200.0,
+            }
+        );
+
+        // The rates are smoothed.
+        let rates = load.sample_at(start + Duration::from_secs(20));
+        assert_eq!(rates.read_qps, 5.0);
+        assert_eq!(rates.write_qps, 1.0);
+
+        // Sampling at the same instant doesn't change the rates.
+        let same = load.sample_at(start + Duration::from_secs(20));
+        assert_eq!(rates, same);
+    }
+}
@@ -33,13 +33,15 @@ impl Replica {
         self.check_migrating_request_early(shard_id)?;
 
         let mut wb = WriteBatch::default();
+        super::eval::ingest_entries(&self.group_engine, &mut wb, shard_id, &chunk).await?;
         for data in &chunk {
-            self.group_engine.put(
+            self.group_engine.put_expirable(
                 &mut wb,
                 shard_id,
                 &data.key,
                 &data.value,
                 super::eval::MIGRATING_KEY_VERSION,
+                data.expire_at,
             )?;
         }
 
@@ -69,7 +71,14 @@ impl Replica {
         self.check_migrating_request_early(shard_id)?;
 
         let mut wb = WriteBatch::default();
+        let mut last_key: Option<&[u8]> = None;
         for (key, version) in keys {
+            // The versions of a key are adjacent, purge the index entries of the latest one.
+            if last_key != Some(key.as_slice()) {
+                super::eval::update_entries(&self.group_engine, &mut wb, shard_id, key, None)
+                    .await?;
+                last_key = Some(key.as_slice());
+            }
             self.group_engine.delete(&mut wb, shard_id, key, *version)?;
         }
 
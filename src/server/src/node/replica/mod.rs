@@ -12,8 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod checksum;
 mod eval;
 pub mod fsm;
+mod load;
 mod migrate;
 pub mod retry;
 mod state;
@@ -30,7 +32,12 @@ use engula_api::{
 use serde::Serialize;
 use tracing::info;
 
-pub use self::state::{LeaseState, LeaseStateObserver};
+pub(crate) use self::eval::{check_schema as check_value_schema, SstStage, FLAT_KEY_VERSION};
+pub use self::{
+    checksum::{ChecksumTable, ScrubReport},
+    load::LoadRates,
+    state::{LeaseState, LeaseStateObserver},
+};
 pub use crate::raftgroup::RaftNodeFacade as RaftSender;
 use crate::{
     engine::GroupEngine,
@@ -88,6 +95,9 @@ where
     lease_state: Arc<Mutex<LeaseState>>,
     move_replicas_provider: Arc<MoveReplicasProvider>,
     meta_acl: Arc<tokio::sync::RwLock<()>>,
+    load: load::ReplicaLoad,
+    checksums: Arc<ChecksumTable>,
+    schemas: eval::SchemaCache,
 }
 
 impl Replica {
@@ -121,6 +131,7 @@ impl Replica {
         raft_node: RaftNodeFacade,
         group_engine: GroupEngine,
         move_replicas_provider: Arc<MoveReplicasProvider>,
+        checksums: Arc<ChecksumTable>,
     ) -> Self {
         Replica {
             info,
@@ -129,6 +140,9 @@ impl Replica {
             lease_state,
             move_replicas_provider,
             meta_acl: Arc::default(),
+            load: load::ReplicaLoad::default(),
+            checksums,
+            schemas: eval::SchemaCache::default(),
         }
     }
 
@@ -233,6 +247,18 @@ impl Replica {
         self.lease_state.lock().unwrap().descriptor.clone()
     }
 
+    /// Return the read and write rates served by this replica since the last call.
+    #[inline]
+    pub fn load_rates(&self) -> LoadRates {
+        self.load.sample()
+    }
+
+    /// Return the approximate bytes of the data of this replica.
+    #[inline]
+    pub fn approximate_size(&self) -> u64 {
+        self.group_engine.approximate_size()
+    }
+
     #[inline]
     pub fn replica_state(&self) -> ReplicaState {
         self.lease_state.lock().unwrap().replica_state.clone()
@@ -264,6 +290,48 @@ impl Replica {
             raft,
         })
     }
+
+    /// Advance the archived position of the log entries of this group to `index`, so the
+    /// entries before it could be compacted.
+    pub async fn archive_log(&self, index: u64) -> Result<()> {
+        self.check_leader_early()?;
+        let eval_result = EvalResult {
+            batch: None,
+            op: Some(SyncOp::archive_state(index)),
+        };
+        self.raft_node.clone().propose(eval_result).await
+    }
+
+    /// Ask all replicas to compute the checksum of the data of this group, at the same applied
+    /// index.
+    pub async fn compute_checksum(&self, checksum_id: u64) -> Result<()> {
+        self.check_leader_early()?;
+        let eval_result = EvalResult {
+            batch: None,
+            op: Some(SyncOp::compute_checksum(checksum_id)),
+        };
+        self.raft_node.clone().propose(eval_result).await
+    }
+
+    /// Replicate the ingestion of the SST files staged on all replicas. The metadata isn't changed
+    /// until the ingestion is applied, so the replicas applying it are the ones which staged the
+    /// files. The writes of the group wait until the files are checked and the ingestion applied.
+    pub async fn ingest_sst(&self, stage: &mut SstStage, req: &IngestSstRequest) -> Result<()> {
+        let _acl_guard = self.take_write_acl_guard().await;
+        let request = Request::IngestSst(req.clone());
+        let mut exec_ctx = ExecCtx::with_epoch(stage.epoch);
+        self.check_request_early(&mut exec_ctx, &request)?;
+        let eval_result =
+            eval::ingest_sst(&exec_ctx, &self.group_engine, &self.schemas, stage, req).await?;
+        stage.proposed = true;
+        self.raft_node.clone().propose(eval_result).await
+    }
+
+    /// The checksums computed by this replica, and the result of the last scrubbing.
+    #[inline]
+    pub fn checksum_table(&self) -> &ChecksumTable {
+        &self.checksums
+    }
 }
 
 impl Replica {
@@ -307,7 +375,8 @@ impl Replica {
                 (None, Response::Get(resp))
             }
             Request::Put(req) => {
-                let eval_result = eval::put(exec_ctx, &self.group_engine, req).await?;
+                let eval_result =
+                    eval::put(exec_ctx, &self.group_engine, &self.schemas, req).await?;
                 (Some(eval_result), Response::Put(PutResponse {}))
             }
             Request::Delete(req) => {
@@ -319,9 +388,23 @@ impl Replica {
                 (None, Response::Scan(eval_result))
             }
             Request::BatchWrite(req) => {
-                let eval_result = eval::batch_write(exec_ctx, &self.group_engine, req).await?;
+                let eval_result =
+                    eval::batch_write(exec_ctx, &self.group_engine, &self.schemas, req).await?;
                 (eval_result, Response::BatchWrite(BatchWriteResponse {}))
             }
+            Request::IndexScan(req) => {
+                let resp = eval::index_scan(&self.group_engine, req).await?;
+                (None, Response::IndexScan(resp))
+            }
+            Request::BackfillIndex(req) => {
+                let (eval_result, resp) = eval::backfill_index(&self.group_engine, req).await?;
+                (eval_result, Response::BackfillIndex(resp))
+            }
+            Request::IngestSst(_) => {
+                return Err(Error::InvalidArgument(
+                    "IngestSstRequest should be staged by the node before executing".into(),
+                ));
+            }
             Request::CreateShard(req) => {
                 // TODO(walter) check the existing of shard.
                 let shard = req
@@ -332,6 +415,26 @@ impl Replica {
                 let resp = CreateShardResponse {};
                 (Some(eval::add_shard(shard)), Response::CreateShard(resp))
             }
+            Request::SyncCollection(req) => {
+                let meta = req.meta.as_ref().cloned().ok_or_else(|| {
+                    Error::InvalidArgument("SyncCollectionRequest::meta is None".into())
+                })?;
+                let migrating_collection = exec_ctx
+                    .migration_desc
+                    .as_ref()
+                    .and_then(|m| m.shard_desc.as_ref())
+                    .map(|d| d.collection_id);
+                if migrating_collection == Some(req.collection_id) {
+                    // The descriptor of the migrating shard is carried by the migration state, so
+                    // it is updated after the migration finished.
+                    return Err(Error::ServiceIsBusy(BusyReason::Migrating));
+                }
+                let resp = SyncCollectionResponse {};
+                (
+                    Some(eval::sync_collection(req.collection_id, meta)),
+                    Response::SyncCollection(resp),
+                )
+            }
             Request::ChangeReplicas(req) => {
                 if let Some(change) = &req.change_replicas {
                     self.raft_node.clone().change_config(change.clone()).await?;
@@ -367,6 +470,16 @@ impl Replica {
             self.raft_node.clone().propose(eval_result).await?;
         }
 
+        match request {
+            Request::Get(_) | Request::Scan(_) | Request::IndexScan(_) => {
+                self.load.record_read(resp.encoded_len());
+            }
+            Request::Put(_) | Request::Delete(_) | Request::BatchWrite(_) => {
+                self.load.record_write(request.encoded_len());
+            }
+            _ => {}
+        }
+
         Ok(resp)
     }
 
@@ -405,7 +518,7 @@ impl Replica {
         }
     }
 
-    fn check_leader_early(&self) -> Result<()> {
+    pub(crate) fn check_leader_early(&self) -> Result<()> {
         let lease_state = self.lease_state.lock().unwrap();
         if !lease_state.is_ready_for_serving() {
             Err(Error::NotLeader(
@@ -506,11 +619,15 @@ pub(self) fn is_change_meta_request(request: &Request) -> bool {
         | Request::CreateShard(_)
         | Request::AcceptShard(_)
         | Request::MoveReplicas(_)
+        | Request::SyncCollection(_)
         | Request::Transfer(_) => true,
         Request::Get(_)
         | Request::Put(_)
         | Request::Delete(_)
         | Request::BatchWrite(_)
-        | Request::Scan(_) => false,
+        | Request::Scan(_)
+        | Request::IndexScan(_)
+        | Request::BackfillIndex(_)
+        | Request::IngestSst(_) => false,
     }
 }
@@ -19,7 +19,7 @@ use engula_api::{
     shard,
 };
 
-use super::{ExecCtx, Replica};
+use super::{ExecCtx, Replica, SstStage};
 use crate::{
     node::{metrics::NODE_RETRY_TOTAL, migrate::MigrateController},
     serverpb::v1::MigrationEvent,
@@ -49,6 +49,25 @@ pub async fn do_migration(
     }
 }
 
+/// Replicate the ingestion of the staged SST files, and retry with the same stage if the replica
+/// is busy.
+pub async fn do_ingest_sst(
+    replica: &Replica,
+    stage: &mut SstStage,
+    req: &IngestSstRequest,
+) -> Result<()> {
+    loop {
+        match replica.ingest_sst(stage, req).await {
+            Err(Error::ServiceIsBusy(_)) | Err(Error::GroupNotReady(_)) => {
+                // sleep and retry.
+                NODE_RETRY_TOTAL.inc();
+                crate::runtime::time::sleep(Duration::from_micros(200)).await;
+            }
+            result => return result,
+        }
+    }
+}
+
 /// A wrapper function that detects and completes retries as quickly as possible.
 #[inline]
 pub async fn execute(
@@ -155,6 +174,9 @@ fn is_executable(descriptor: &GroupDesc, request: &Request) -> bool {
                 is_target_shard_exists(descriptor, req.shard_id, &req.delete.as_ref().unwrap().key)
             }
             Request::Scan(req) => is_scan_retryable(descriptor, req),
+            Request::IndexScan(req) => is_shard_exists(descriptor, req.shard_id),
+            Request::BackfillIndex(req) => is_shard_exists(descriptor, req.shard_id),
+            Request::IngestSst(req) => is_shard_exists(descriptor, req.shard_id),
             Request::BatchWrite(req) => {
                 for delete in &req.deletes {
                     if !is_target_shard_exists(
@@ -192,6 +214,11 @@ fn is_target_shard_exists(desc: &GroupDesc, shard_id: u64, key: &[u8]) -> bool {
         .unwrap_or_default()
 }
 
+#[inline]
+fn is_shard_exists(desc: &GroupDesc, shard_id: u64) -> bool {
+    desc.shards.iter().any(|s| s.id == shard_id)
+}
+
 fn is_scan_retryable(desc: &GroupDesc, req: &ShardScanRequest) -> bool {
     if let Some(prefix) = &req.prefix {
         return is_target_shard_exists(desc, req.shard_id, prefix);
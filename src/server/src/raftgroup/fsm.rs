@@ -17,6 +17,7 @@ use std::path::Path;
 use engula_api::server::v1::{ChangeReplicas, GroupDesc};
 
 use crate::{
+    encryption::DataKeyManager,
     serverpb::v1::{ApplyState, EvalResult},
     Result,
 };
@@ -42,11 +43,40 @@ pub trait StateMachine: Send {
 
     /// Return the latest index which persisted in disk.
     fn flushed_index(&self) -> u64;
+
+    /// Return the index of the last archived entry, the entries after it must not be compacted.
+    /// `None` means that the entries are not archived, and `Some(0)` means that nothing is
+    /// archived yet.
+    fn archived_index(&self) -> Option<u64> {
+        None
+    }
 }
 
 /// An abstraction of snapshot generation.
 #[crate::async_trait]
 pub trait SnapshotBuilder: Send + Sync {
-    /// Stable this checkpoint to `base_dir`, and returns applied index and group descriptor.
-    async fn checkpoint(&self, base_dir: &Path) -> Result<(ApplyState, GroupDesc)>;
+    /// Stable this checkpoint to `base_dir`, and returns applied index and group descriptor. The
+    /// files must be written through the `key_manager`, so they are encrypted at rest.
+    async fn checkpoint(
+        &self,
+        key_manager: &DataKeyManager,
+        base_dir: &Path,
+    ) -> Result<(ApplyState, GroupDesc)>;
+
+    /// Open a consistent view of the state machine, so the snapshot could be streamed to the
+    /// followers without staging files. `None` is returned if the streaming snapshot is disabled.
+    fn stream(&self) -> Result<Option<Box<dyn SnapshotStream>>> {
+        Ok(None)
+    }
+}
+
+/// A consistent view of the state machine, which is read in key order.
+pub trait SnapshotStream: Send {
+    fn apply_state(&self) -> ApplyState;
+
+    fn descriptor(&self) -> GroupDesc;
+
+    /// Read the key values after `start_key` (exclusive), the total bytes of the returned key
+    /// values is limited by `limit` roughly. An empty chunk means that all data is read.
+    fn next_chunk(&mut self, start_key: &[u8], limit: usize) -> Result<Vec<(Vec<u8>, Vec<u8>)>>;
 }
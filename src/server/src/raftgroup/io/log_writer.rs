@@ -23,7 +23,7 @@ use futures::{
     StreamExt,
 };
 
-use crate::Result;
+use crate::{raftgroup::RaftEngine, Result};
 
 #[derive(Clone)]
 pub struct LogWriter {
@@ -43,7 +43,7 @@ struct LogRequest {
 type LogResponse = Result<(), String>;
 
 impl LogWriter {
-    pub fn new(max_io_batch_size: u64, engine: Arc<raft_engine::Engine>) -> LogWriter {
+    pub fn new(max_io_batch_size: u64, engine: Arc<RaftEngine>) -> LogWriter {
         let (join_handle, sender) = start_log_writer(max_io_batch_size, engine);
         LogWriter {
             sender,
@@ -79,7 +79,7 @@ impl Drop for WriterInner {
 
 fn start_log_writer(
     max_io_batch_size: u64,
-    engine: Arc<raft_engine::Engine>,
+    engine: Arc<RaftEngine>,
 ) -> (std::thread::JoinHandle<()>, mpsc::Sender<LogRequest>) {
     // Each worker sends at most one request, 1024 is large enough.
     let (sender, receiver) = mpsc::channel::<LogRequest>(1024);
@@ -96,7 +96,7 @@ fn start_log_writer(
 
 async fn log_writer_main(
     max_io_batch_size: usize,
-    engine: Arc<raft_engine::Engine>,
+    engine: Arc<RaftEngine>,
     receiver: mpsc::Receiver<LogRequest>,
 ) {
     let mut receiver = receiver;
@@ -15,9 +15,15 @@ use std::{sync::Arc, time::Duration};
 
 use tracing::{debug, warn};
 
-use crate::runtime::{current, TaskPriority};
+use crate::{
+    raftgroup::RaftEngine,
+    runtime::{current, TaskPriority},
+};
 
-pub async fn start_purging_expired_files(engine: Arc<raft_engine::Engine>) {
+/// Purge the files of raft engine periodically. The files are only purged after the log entries
+/// in them are compacted, and the compaction is bounded by the archived position if the log
+/// archiving is enabled, so the unarchived entries are never purged.
+pub async fn start_purging_expired_files(engine: Arc<RaftEngine>) {
     current().spawn(None, TaskPriority::IoLow, async move {
         loop {
             crate::runtime::time::sleep(Duration::from_secs(10)).await;
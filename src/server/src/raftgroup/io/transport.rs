@@ -173,6 +173,9 @@ pub async fn retrive_snapshot(
     trans_mgr: &ChannelManager,
     target_replica: ReplicaDesc,
     snapshot_id: Vec<u8>,
+    start_key: Vec<u8>,
+    start_file: u32,
+    high_priority: bool,
 ) -> Result<impl futures::Stream<Item = Result<SnapshotChunk, tonic::Status>>> {
     let node_desc = resolve_address(&*trans_mgr.resolver, target_replica.node_id).await?;
     let address = format!("http://{}", node_desc.addr);
@@ -180,6 +183,9 @@ pub async fn retrive_snapshot(
     let request = SnapshotRequest {
         replica_id: target_replica.id,
         snapshot_id,
+        start_key,
+        high_priority,
+        start_file,
     };
     let resp = client.retrieve_snapshot(request).await?;
     Ok(resp.into_inner())
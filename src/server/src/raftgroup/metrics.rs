@@ -50,6 +50,20 @@ make_static_metric! {
             read_index,
         }
     }
+    struct SnapshotTransfer: IntGauge {
+        "type" => {
+            send,
+            recv,
+            stream,
+        }
+    }
+    struct SnapshotCorruption: IntCounter {
+        "type" => {
+            file,
+            key_values,
+            install,
+        }
+    }
 }
 
 lazy_static! {
@@ -128,6 +142,34 @@ lazy_static! {
     .unwrap();
 }
 
+lazy_static! {
+    pub static ref RAFTGROUP_SNAPSHOT_QUEUE_DEPTH_VEC: IntGaugeVec = register_int_gauge_vec!(
+        "raftgroup_snapshot_queue_depth",
+        "The number of snapshot transfers waiting for the concurrency limit of raftgroup",
+        &["type"],
+    )
+    .unwrap();
+    pub static ref RAFTGROUP_SNAPSHOT_QUEUE_DEPTH: SnapshotTransfer =
+        SnapshotTransfer::from(&RAFTGROUP_SNAPSHOT_QUEUE_DEPTH_VEC);
+    pub static ref RAFTGROUP_SNAPSHOT_RUNNING_VEC: IntGaugeVec = register_int_gauge_vec!(
+        "raftgroup_snapshot_running",
+        "The number of running snapshot transfers of raftgroup",
+        &["type"],
+    )
+    .unwrap();
+    pub static ref RAFTGROUP_SNAPSHOT_RUNNING: SnapshotTransfer =
+        SnapshotTransfer::from(&RAFTGROUP_SNAPSHOT_RUNNING_VEC);
+    pub static ref RAFTGROUP_SNAPSHOT_CORRUPTED_TOTAL_VEC: IntCounterVec =
+        register_int_counter_vec!(
+            "raftgroup_snapshot_corrupted_total",
+            "The total corrupted snapshot files and chunks received by raftgroup",
+            &["type"],
+        )
+        .unwrap();
+    pub static ref RAFTGROUP_SNAPSHOT_CORRUPTED_TOTAL: SnapshotCorruption =
+        SnapshotCorruption::from(&RAFTGROUP_SNAPSHOT_CORRUPTED_TOTAL_VEC);
+}
+
 lazy_static! {
     pub static ref RAFTGROUP_DOWNLOAD_SNAPSHOT_TOTAL: IntCounter = register_int_counter!(
         "raftgroup_download_snapshot_total",
@@ -28,23 +28,30 @@ use engula_api::server::v1::*;
 use raft::prelude::{
     ConfChangeSingle, ConfChangeTransition, ConfChangeType, ConfChangeV2, ConfState,
 };
+use tracing::error;
 
 pub use self::{
     facade::RaftNodeFacade,
-    fsm::{ApplyEntry, SnapshotBuilder, StateMachine},
+    fsm::{ApplyEntry, SnapshotBuilder, SnapshotStream, StateMachine},
     io::{retrive_snapshot, AddressResolver, ChannelManager},
     monitor::*,
-    snap::SnapManager,
-    storage::{destory as destory_storage, write_initial_state},
+    snap::{SnapLimiter, SnapManager},
+    storage::{
+        destory as destory_storage, read_entries, write_initial_state, write_recovered_state,
+    },
     worker::{RaftGroupState, StateObserver},
 };
 use self::{io::LogWriter, worker::RaftWorker};
 use crate::{
+    encryption::EncryptedFileSystem,
     raftgroup::io::start_purging_expired_files,
     runtime::{sync::WaitGroup, TaskPriority},
     RaftConfig, Result,
 };
 
+/// The raft engine, whose log files are encrypted if the encryption is enabled.
+pub type RaftEngine = raft_engine::Engine<EncryptedFileSystem>;
+
 /// `ReadPolicy` is used to control `RaftNodeFacade::read` behavior.
 #[derive(Debug, Clone, Copy)]
 pub enum ReadPolicy {
@@ -60,7 +67,7 @@ pub enum ReadPolicy {
 #[derive(Clone)]
 pub struct RaftManager {
     pub cfg: RaftConfig,
-    engine: Arc<raft_engine::Engine>,
+    engine: Arc<RaftEngine>,
     log_writer: LogWriter,
     transport_mgr: ChannelManager,
     snap_mgr: SnapManager,
@@ -69,7 +76,7 @@ pub struct RaftManager {
 impl RaftManager {
     pub(crate) async fn open(
         cfg: RaftConfig,
-        engine: Arc<raft_engine::Engine>,
+        engine: Arc<RaftEngine>,
         snap_mgr: SnapManager,
         transport_mgr: ChannelManager,
     ) -> Result<Self> {
@@ -85,7 +92,7 @@ impl RaftManager {
     }
 
     #[inline]
-    pub fn engine(&self) -> Arc<raft_engine::Engine> {
+    pub fn engine(&self) -> Arc<RaftEngine> {
         self.engine.clone()
     }
 
@@ -113,8 +120,9 @@ impl RaftManager {
         let facade = RaftNodeFacade::open(worker.request_sender());
         let log_writer = self.log_writer.clone();
         crate::runtime::current().spawn(Some(group_id), TaskPriority::High, async move {
-            // TODO(walter) handle result.
-            worker.run(log_writer).await.unwrap();
+            if let Err(err) = worker.run(log_writer).await {
+                error!("group {group_id} replica {replica_id} raft worker is stopped: {err}");
+            }
             drop(wait_group);
         });
         Ok(facade)
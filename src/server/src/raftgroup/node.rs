@@ -24,7 +24,7 @@ use super::{
     monitor::{record_perf_point, AdvancePerfContext},
     snap::apply::apply_snapshot,
     storage::Storage,
-    RaftManager, SnapManager,
+    RaftEngine, RaftManager, SnapManager,
 };
 use crate::{error::BusyReason, Error, Result};
 
@@ -56,7 +56,11 @@ pub(super) trait AdvanceTemplate {
 
     fn mut_replica_cache(&mut self) -> &mut ReplicaCache;
 
-    fn apply_snapshot<M: StateMachine>(&mut self, applier: &mut Applier<M>, snapshot: &Snapshot);
+    fn apply_snapshot<M: StateMachine>(
+        &mut self,
+        applier: &mut Applier<M>,
+        snapshot: &Snapshot,
+    ) -> Result<()>;
 }
 
 pub struct RaftNode<M: StateMachine> {
@@ -81,7 +85,7 @@ where
         state_machine: M,
     ) -> Result<Self> {
         let mut applier = Applier::new(group_id, state_machine);
-        try_apply_fresh_snapshot(replica_id, &mgr.snap_mgr, &mut applier).await;
+        try_apply_fresh_snapshot(replica_id, &mgr.snap_mgr, &mut applier).await?;
 
         let cfg = &mgr.cfg;
         let applied = applier.flushed_index();
@@ -264,14 +268,14 @@ where
         &mut self,
         perf_ctx: &mut AdvancePerfContext,
         template: &mut impl AdvanceTemplate,
-    ) -> Option<WriteTask> {
+    ) -> Result<Option<WriteTask>> {
         self.advance_read_requests();
         if !self.raw_node.has_ready() {
             if !self.read_states.is_empty() {
                 self.applier
                     .apply_read_states(std::mem::take(&mut self.read_states));
             }
-            return None;
+            return Ok(None);
         }
 
         record_perf_point(&mut perf_ctx.take_ready);
@@ -296,7 +300,7 @@ where
             template.send_messages(ready.take_messages());
         }
 
-        self.handle_apply(perf_ctx, template, &mut ready);
+        self.handle_apply(perf_ctx, template, &mut ready)?;
 
         let write_task = self.build_write_task(&mut ready);
         if write_task.is_none() {
@@ -306,7 +310,7 @@ where
         } else {
             self.raw_node.advance_append_async(ready);
         }
-        write_task
+        Ok(write_task)
     }
 
     pub(super) fn post_advance(
@@ -356,7 +360,7 @@ where
         perf_ctx: &mut AdvancePerfContext,
         template: &mut impl AdvanceTemplate,
         ready: &mut Ready,
-    ) {
+    ) -> Result<()> {
         if !self.read_states.is_empty() {
             self.applier
                 .apply_read_states(std::mem::take(&mut self.read_states));
@@ -386,8 +390,9 @@ where
         }
 
         if !ready.snapshot().is_empty() {
-            template.apply_snapshot(&mut self.applier, ready.snapshot());
+            template.apply_snapshot(&mut self.applier, ready.snapshot())?;
         }
+        Ok(())
     }
 
     fn build_write_task(&mut self, ready: &mut Ready) -> Option<WriteTask> {
@@ -465,7 +470,8 @@ async fn try_apply_fresh_snapshot<M>(
     replica_id: u64,
     snap_mgr: &SnapManager,
     applier: &mut Applier<M>,
-) where
+) -> Result<()>
+where
     M: StateMachine,
 {
     if let Some(info) = snap_mgr.latest_snap(replica_id) {
@@ -476,15 +482,16 @@ async fn try_apply_fresh_snapshot<M>(
                 apply_state.index, apply_state.term,
                 applier.flushed_index()
             );
-            apply_snapshot(replica_id, snap_mgr, applier, &info.to_raft_snapshot());
+            apply_snapshot(replica_id, snap_mgr, applier, &info.to_raft_snapshot())?;
         }
     }
+    Ok(())
 }
 
 async fn try_reset_storage_state(
     replica_id: u64,
     snap_mgr: &SnapManager,
-    engine: &raft_engine::Engine,
+    engine: &RaftEngine,
     storage: &mut Storage,
 ) -> Result<()> {
     if let Some(info) = snap_mgr.latest_snap(replica_id) {
@@ -575,14 +582,14 @@ mod tests {
     async fn try_recover_snapshot<M>(
         replica_id: u64,
         snap_mgr: &SnapManager,
-        engine: &raft_engine::Engine,
+        engine: &RaftEngine,
         storage: &mut Storage,
         applier: &mut Applier<M>,
     ) -> crate::Result<()>
     where
         M: StateMachine,
     {
-        try_apply_fresh_snapshot(replica_id, snap_mgr, applier).await;
+        try_apply_fresh_snapshot(replica_id, snap_mgr, applier).await?;
         try_reset_storage_state(replica_id, snap_mgr, engine, storage).await
     }
 
@@ -613,7 +620,7 @@ mod tests {
                 dir: dir.path().join("db").to_str().unwrap().to_owned(),
                 ..Default::default()
             };
-            let engine = Arc::new(Engine::open(cfg).unwrap());
+            let engine = Arc::new(RaftEngine::open_with_file_system(cfg, Arc::default()).unwrap());
 
             write_initial_state(&RaftConfig::default(), engine.as_ref(), 1, vec![], vec![])
                 .await
@@ -674,7 +681,7 @@ mod tests {
                 dir: dir.path().join("db").to_str().unwrap().to_owned(),
                 ..Default::default()
             };
-            let engine = Arc::new(Engine::open(cfg).unwrap());
+            let engine = Arc::new(RaftEngine::open_with_file_system(cfg, Arc::default()).unwrap());
 
             write_initial_state(&RaftConfig::default(), engine.as_ref(), 1, vec![], vec![])
                 .await
@@ -717,7 +724,11 @@ mod tests {
         });
     }
 
-    async fn insert_entries(engine: Arc<Engine>, storage: &mut Storage, entries: Vec<(u64, u64)>) {
+    async fn insert_entries(
+        engine: Arc<RaftEngine>,
+        storage: &mut Storage,
+        entries: Vec<(u64, u64)>,
+    ) {
         let entries: Vec<Entry> = entries
             .into_iter()
             .map(|(idx, term)| {
@@ -746,7 +757,7 @@ mod tests {
                 dir: dir.path().join("db").to_str().unwrap().to_owned(),
                 ..Default::default()
             };
-            let engine = Arc::new(Engine::open(cfg).unwrap());
+            let engine = Arc::new(RaftEngine::open_with_file_system(cfg, Arc::default()).unwrap());
 
             write_initial_state(&RaftConfig::default(), engine.as_ref(), 1, vec![], vec![])
                 .await
@@ -859,6 +870,7 @@ mod tests {
                         role: ReplicaRole::Voter as i32,
                         ..Default::default()
                     }],
+                    ..Default::default()
                 }
             }
 
@@ -876,7 +888,7 @@ mod tests {
                 dir: dir.path().join("db").to_str().unwrap().to_owned(),
                 ..Default::default()
             };
-            let engine = Arc::new(Engine::open(cfg).unwrap());
+            let engine = Arc::new(RaftEngine::open_with_file_system(cfg, Arc::default()).unwrap());
             let snap_dir = dir.path().join("snap");
             let snap_mgr = SnapManager::new(snap_dir.clone());
             let resolver = Arc::new(MockedAddressResolver {});
@@ -936,9 +948,9 @@ mod tests {
                     &mut self,
                     applier: &mut Applier<M>,
                     snapshot: &Snapshot,
-                ) {
+                ) -> crate::Result<()> {
                     use crate::raftgroup::snap::apply::apply_snapshot;
-                    apply_snapshot(1, &self.snap_mgr, applier, snapshot);
+                    apply_snapshot(1, &self.snap_mgr, applier, snapshot)
                 }
             }
 
@@ -947,7 +959,7 @@ mod tests {
                 replica_cache: ReplicaCache::default(),
             };
             let mut perf_ctx = AdvancePerfContext::default();
-            while let Some(task) = node.advance(&mut perf_ctx, &mut template) {
+            while let Some(task) = node.advance(&mut perf_ctx, &mut template).unwrap() {
                 let mut batch = LogBatch::default();
                 node.mut_store()
                     .write(&mut batch, &task)
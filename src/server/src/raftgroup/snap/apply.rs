@@ -16,7 +16,7 @@ use raft::prelude::Snapshot;
 use super::{SnapManager, SNAP_DATA};
 use crate::{
     raftgroup::{applier::Applier, metrics::*, StateMachine},
-    record_latency,
+    record_latency, Result,
 };
 
 pub fn apply_snapshot<M: StateMachine>(
@@ -24,7 +24,7 @@ pub fn apply_snapshot<M: StateMachine>(
     snap_mgr: &SnapManager,
     applier: &mut Applier<M>,
     snapshot: &Snapshot,
-) {
+) -> Result<()> {
     record_latency!(take_apply_snapshot_metrics());
     let snap_id = &snapshot.data;
     let snap_info = snap_mgr
@@ -32,7 +32,5 @@ pub fn apply_snapshot<M: StateMachine>(
         .expect("The snapshot should does not be gc before apply");
     // TODO(walter) check snapshot data integrity.
     let snap_dir = snap_info.base_dir.join(SNAP_DATA);
-    applier
-        .apply_snapshot(&snap_dir)
-        .expect("apply snapshot must success, because the data integrity might broken");
+    applier.apply_snapshot(&snap_dir)
 }
@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::path::Path;
+use std::{path::Path, sync::Arc};
 
 use futures::{channel::mpsc, SinkExt};
 use prost::Message;
@@ -20,6 +20,7 @@ use tracing::{error, info};
 
 use super::{SnapManager, SNAP_DATA};
 use crate::{
+    encryption::{DataKeyManager, FileReader, FileWriter},
     raftgroup::{
         fsm::SnapshotBuilder,
         metrics::*,
@@ -64,14 +65,33 @@ pub(super) async fn create_snapshot(
     builder: Box<dyn SnapshotBuilder>,
 ) -> Result<Vec<u8>> {
     record_latency!(take_create_snapshot_metrics());
+    match snap_mgr.limiter().try_acquire_stream() {
+        Some(permit) => {
+            if let Some(stream) = builder.stream()? {
+                let apply_state = stream.apply_state();
+                info!(
+                    "replica {replica_id} create streaming snapshot at index {} term {}",
+                    apply_state.index, apply_state.term
+                );
+                return Ok(snap_mgr.install_stream(replica_id, stream, permit));
+            }
+        }
+        None => {
+            info!(
+                "replica {replica_id} stage the snapshot files since too many streaming snapshots are opened"
+            );
+        }
+    }
+
     let snap_dir = snap_mgr.create(replica_id);
     info!(
         "replica {replica_id} begin create snapshot at {}",
         snap_dir.display()
     );
 
+    let key_manager = snap_mgr.key_manager();
     let data = snap_dir.join(SNAP_DATA);
-    let (apply_state, descriptor) = builder.checkpoint(&data).await?;
+    let (apply_state, descriptor) = builder.checkpoint(key_manager, &data).await?;
     if !std::fs::try_exists(&data)? {
         panic!("Checkpoint did not generate any data.");
     }
@@ -84,10 +104,10 @@ pub(super) async fn create_snapshot(
             if path.is_dir() {
                 panic!("Not supported");
             }
-            files.push(read_file_meta(&path).await?);
+            files.push(read_file_meta(key_manager, &path).await?);
         }
     } else {
-        files.push(read_file_meta(&data).await?);
+        files.push(read_file_meta(key_manager, &data).await?);
     }
 
     let snap_meta = SnapshotMeta {
@@ -96,7 +116,7 @@ pub(super) async fn create_snapshot(
         files,
     };
 
-    stable_snapshot_meta(&snap_dir, &snap_meta).await?;
+    stable_snapshot_meta(key_manager, &snap_dir, &snap_meta).await?;
 
     info!(
         "replica {replica_id} create snapshot {} success",
@@ -106,16 +126,20 @@ pub(super) async fn create_snapshot(
     Ok(snap_mgr.install(replica_id, &snap_dir, &snap_meta))
 }
 
-pub(super) async fn stable_snapshot_meta(base_dir: &Path, snap_meta: &SnapshotMeta) -> Result<()> {
-    use std::{fs::OpenOptions, io::Write};
+pub(super) async fn stable_snapshot_meta(
+    key_manager: &DataKeyManager,
+    base_dir: &Path,
+    snap_meta: &SnapshotMeta,
+) -> Result<()> {
+    use std::io::Write;
 
     let content = snap_meta.encode_to_vec();
 
     let tmp = base_dir.join(SNAP_TEMP);
-    let mut file = OpenOptions::new().write(true).create(true).open(&tmp)?;
-    file.write_all(&content)?;
-    file.sync_all()?;
-    drop(file);
+    let mut writer = FileWriter::new(Arc::new(key_manager.create_file(&tmp)?));
+    writer.write_all(&content)?;
+    writer.file().sync()?;
+    drop(writer);
 
     let meta = base_dir.join(SNAP_META);
     std::fs::rename(tmp, meta)?;
@@ -125,14 +149,15 @@ pub(super) async fn stable_snapshot_meta(base_dir: &Path, snap_meta: &SnapshotMe
     Ok(())
 }
 
-async fn read_file_meta(filename: &Path) -> Result<SnapshotFile> {
-    use std::{
-        fs::OpenOptions,
-        io::{ErrorKind, Read},
-    };
+/// Read the size and crc32 of the plaintext content of a snapshot file.
+pub(super) async fn read_file_meta(
+    key_manager: &DataKeyManager,
+    filename: &Path,
+) -> Result<SnapshotFile> {
+    use std::io::{ErrorKind, Read};
 
     let mut buf = vec![0; 4096];
-    let mut file = OpenOptions::new().read(true).open(filename)?;
+    let mut file = FileReader::new(Arc::new(key_manager.open_file(filename)?));
     let mut hasher = crc32fast::Hasher::new();
 
     let mut size: u64 = 0;
@@ -13,50 +13,112 @@
 // limitations under the License.
 
 use std::{
-    ffi::OsString,
-    fs::File,
-    os::unix::ffi::OsStringExt,
+    ffi::{OsStr, OsString},
+    os::unix::ffi::{OsStrExt, OsStringExt},
     path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
 };
 
 use engula_api::server::v1::ReplicaDesc;
 use futures::{channel::mpsc, SinkExt, StreamExt};
 use raft::eraftpb::Message;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-use super::SnapManager;
+use super::{
+    kv_file::{KvFileReader, KvFileWriter},
+    SnapLimiter, SnapManager, SnapPriority, SNAP_DATA,
+};
 use crate::{
+    constants::REPLICA_PER_GROUP,
+    encryption::{DataKeyManager, FileWriter},
     raftgroup::{metrics::*, retrive_snapshot, worker::Request, ChannelManager},
     record_latency,
     runtime::TaskPriority,
-    serverpb::v1::{snapshot_chunk, SnapshotChunk, SnapshotFile, SnapshotMeta},
+    serverpb::v1::{snapshot_chunk, SnapshotChunk, SnapshotFile, SnapshotKeyValues, SnapshotMeta},
     Error, Result,
 };
 
+/// The limit bytes of the key values buffered before writing to a kv file.
+const STREAM_FILE_SIZE: usize = 32 * 1024 * 1024;
+
+/// The max times to resume the snapshot if the connection is broken or the received data is
+/// corrupted.
+const MAX_RESUME_TIMES: usize = 8;
+
+const RESUME_INTERVAL: Duration = Duration::from_millis(500);
+
 struct PartialFile {
     meta: SnapshotFile,
-    file: File,
+    file: FileWriter,
     size: usize,
     crc32: crc32fast::Hasher,
 }
 
+/// The key values received from a streaming snapshot, they are written to kv files in batch.
+#[derive(Default)]
+struct StreamingState {
+    /// The last received key, the snapshot is resumed after it.
+    last_key: Vec<u8>,
+    key_values: Vec<(Vec<u8>, Vec<u8>)>,
+    num_bytes: usize,
+    next_file_no: usize,
+}
+
+/// Where to resume an interrupted snapshot transfer.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(super) struct ResumePoint {
+    /// The last received key of the streaming snapshot.
+    pub start_key: Vec<u8>,
+    /// The index of the first file which is not received completely.
+    pub start_file: usize,
+}
+
 struct SnapshotBuilder {
     replica_id: u64,
     base_dir: PathBuf,
+    key_manager: Arc<DataKeyManager>,
     meta: SnapshotMeta,
+    meta_received: bool,
     file_name: Vec<u8>,
     file: Option<PartialFile>,
+    streaming: Option<StreamingState>,
 }
 
 impl SnapshotBuilder {
-    fn new(replica_id: u64, base_dir: &Path) -> Self {
+    fn new(replica_id: u64, base_dir: &Path, key_manager: Arc<DataKeyManager>) -> Self {
         SnapshotBuilder {
             replica_id,
             base_dir: base_dir.to_owned(),
+            key_manager,
             meta: SnapshotMeta::default(),
+            meta_received: false,
             file_name: vec![],
             file: None,
+            streaming: None,
+        }
+    }
+
+    /// Returns the point to resume the snapshot, `None` if the snapshot is completely received.
+    fn resume_point(&self) -> Option<ResumePoint> {
+        if self.meta_received {
+            return None;
         }
+        Some(match &self.streaming {
+            Some(state) => ResumePoint {
+                start_key: state.last_key.clone(),
+                ..Default::default()
+            },
+            None => ResumePoint {
+                start_file: self.meta.files.len(),
+                ..Default::default()
+            },
+        })
+    }
+
+    /// Discard the partially received file, it will be received again after resuming.
+    fn discard_partial_file(&mut self) {
+        self.file = None;
     }
 
     async fn append(&mut self, chunk: SnapshotChunk) -> Result<()> {
@@ -70,15 +132,109 @@ impl SnapshotBuilder {
                 None => Err(Error::InvalidData("missing file meta".to_string())),
             },
             Some(snapshot_chunk::Value::Meta(meta)) => {
+                if self.streaming.is_some() {
+                    self.flush_key_values().await?;
+                } else {
+                    self.finish_partial_file().await?;
+                    if self.meta.files != meta.files {
+                        return Err(Error::InvalidData(format!(
+                            "the received snapshot files {:?} are different from the meta {:?}",
+                            self.meta.files, meta.files
+                        )));
+                    }
+                }
                 self.meta.apply_state = meta.apply_state;
                 self.meta.group_desc = meta.group_desc;
+                self.meta.stream_crc32 = meta.stream_crc32;
+                self.meta_received = true;
                 Ok(())
             }
+            Some(snapshot_chunk::Value::KeyValues(key_values)) => {
+                self.append_key_values(key_values).await
+            }
             None => Ok(()),
         }
     }
 
+    async fn append_key_values(&mut self, chunk: SnapshotKeyValues) -> Result<()> {
+        if self.file.is_some() {
+            return Err(Error::InvalidData(
+                "key values are mixed with snapshot files".to_string(),
+            ));
+        }
+        if chunk.keys.len() != chunk.values.len() {
+            return Err(Error::InvalidData(format!(
+                "the number of keys {} and values {} are not equal",
+                chunk.keys.len(),
+                chunk.values.len()
+            )));
+        }
+        let crc32 = super::key_values_crc32(&chunk.keys, &chunk.values);
+        if crc32 != chunk.crc32 {
+            RAFTGROUP_SNAPSHOT_CORRUPTED_TOTAL.key_values.inc();
+            return Err(Error::InvalidData(format!(
+                "checksum of streaming snapshot key values is not equals, expect {}, but got {crc32}",
+                chunk.crc32
+            )));
+        }
+
+        let state = self.streaming.get_or_insert_with(StreamingState::default);
+        for (key, value) in chunk.keys.into_iter().zip(chunk.values.into_iter()) {
+            // The kv files are applied in order, so the keys must be added in order.
+            if key <= state.last_key {
+                return Err(Error::InvalidData(format!(
+                    "the streaming snapshot key {key:?} is out of order"
+                )));
+            }
+            RAFTGROUP_DOWNLOAD_SNAPSHOT_BYTES_TOTAL.inc_by((key.len() + value.len()) as u64);
+            state.num_bytes += key.len() + value.len();
+            state.last_key = key.clone();
+            state.key_values.push((key, value));
+        }
+
+        if state.num_bytes >= STREAM_FILE_SIZE {
+            self.flush_key_values().await?;
+        }
+        Ok(())
+    }
+
+    /// Write the buffered key values of the streaming snapshot to a new kv file.
+    async fn flush_key_values(&mut self) -> Result<()> {
+        let state = self.streaming.as_mut().unwrap();
+        if state.key_values.is_empty() {
+            return Ok(());
+        }
+
+        let data_dir = self.base_dir.join(SNAP_DATA);
+        std::fs::create_dir_all(&data_dir)?;
+        let path = data_dir.join(format!("{}.kv", state.next_file_no));
+        state.next_file_no += 1;
+        debug!(
+            "replica {} write {} streaming snapshot key values to {}",
+            self.replica_id,
+            state.key_values.len(),
+            path.display()
+        );
+
+        let mut writer = KvFileWriter::create(&self.key_manager, &path)?;
+        for (key, value) in state.key_values.drain(..) {
+            writer.put(&key, &value)?;
+        }
+        writer.finish()?;
+        state.num_bytes = 0;
+
+        let file_meta = super::create::read_file_meta(&self.key_manager, &path).await?;
+        self.meta.files.push(file_meta);
+        Ok(())
+    }
+
     async fn switch_file(&mut self, file_meta: SnapshotFile) -> Result<()> {
+        if self.streaming.is_some() {
+            return Err(Error::InvalidData(
+                "snapshot files are mixed with key values".to_string(),
+            ));
+        }
+
         self.finish_partial_file().await?;
 
         let name = file_meta.name.clone();
@@ -91,29 +247,96 @@ impl SnapshotBuilder {
         }
 
         self.file_name = name;
-        self.file = Some(PartialFile::new(self.replica_id, &path, file_meta)?);
+        self.file = Some(PartialFile::new(
+            self.replica_id,
+            &self.key_manager,
+            &path,
+            file_meta,
+        )?);
 
         Ok(())
     }
 
+    /// Validate and record the partially received file. The corrupted file is not recorded, so it
+    /// will be received again after resuming.
     async fn finish_partial_file(&mut self) -> Result<()> {
         if let Some(file) = self.file.take() {
-            self.meta.files.push(file.finish().await?);
+            match file.finish().await {
+                Ok(file_meta) => self.meta.files.push(file_meta),
+                Err(err @ Error::InvalidData(_)) => {
+                    RAFTGROUP_SNAPSHOT_CORRUPTED_TOTAL.file.inc();
+                    return Err(err);
+                }
+                Err(err) => return Err(err),
+            }
         }
         Ok(())
     }
 
     async fn finish(mut self) -> Result<SnapshotMeta> {
         self.finish_partial_file().await?;
-        super::create::stable_snapshot_meta(&self.base_dir, &self.meta).await?;
+        self.verify_files().await?;
+        // The snapshot files are never empty, so a snapshot without files is a streaming snapshot
+        // without any key values.
+        if self.streaming.is_some() || self.meta.files.is_empty() {
+            self.verify_key_values().await?;
+        }
+        super::create::stable_snapshot_meta(&self.key_manager, &self.base_dir, &self.meta).await?;
         Ok(self.meta)
     }
+
+    /// Read the files back to verify their checksums and sizes against the snapshot meta, so that
+    /// a snapshot corrupted after receiving is never installed.
+    async fn verify_files(&self) -> Result<()> {
+        for expect in &self.meta.files {
+            let path = self.base_dir.join(OsStr::from_bytes(&expect.name));
+            let actual = super::create::read_file_meta(&self.key_manager, &path).await?;
+            if actual.crc32 != expect.crc32 || actual.size != expect.size {
+                RAFTGROUP_SNAPSHOT_CORRUPTED_TOTAL.install.inc();
+                return Err(Error::InvalidData(format!(
+                    "snapshot file {} is corrupted, expect crc32 {} size {}, but got crc32 {} size {}",
+                    path.display(),
+                    expect.crc32,
+                    expect.size,
+                    actual.crc32,
+                    actual.size
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read the staged key values of the streaming snapshot back to verify them against the
+    /// checksum sent by the sender in the trailer.
+    async fn verify_key_values(&self) -> Result<()> {
+        let mut hasher = crc32fast::Hasher::new();
+        for file in &self.meta.files {
+            let path = self.base_dir.join(OsStr::from_bytes(&file.name));
+            for record in KvFileReader::open(&self.key_manager, &path)? {
+                let (key, value) = record?;
+                super::hash_key_value(&mut hasher, &key, &value);
+            }
+            crate::runtime::yield_now().await;
+        }
+        let crc32 = hasher.finalize();
+        if crc32 != self.meta.stream_crc32 {
+            RAFTGROUP_SNAPSHOT_CORRUPTED_TOTAL.install.inc();
+            return Err(Error::InvalidData(format!(
+                "checksum of streaming snapshot is not equals, expect {}, but got {crc32}",
+                self.meta.stream_crc32
+            )));
+        }
+        Ok(())
+    }
 }
 
 impl PartialFile {
-    fn new(replica_id: u64, path: &Path, file_meta: SnapshotFile) -> Result<Self> {
-        use std::fs::OpenOptions;
-
+    fn new(
+        replica_id: u64,
+        key_manager: &DataKeyManager,
+        path: &Path,
+        file_meta: SnapshotFile,
+    ) -> Result<Self> {
         debug!(
             "replica {replica_id} receive snapshot file {}, size {}, crc32 {}",
             path.display(),
@@ -121,11 +344,11 @@ impl PartialFile {
             file_meta.crc32
         );
 
-        let file = OpenOptions::new().write(true).create(true).open(path)?;
+        let file = key_manager.create_file(path)?;
 
         Ok(PartialFile {
             meta: file_meta,
-            file,
+            file: FileWriter::new(Arc::new(file)),
             size: 0,
             crc32: crc32fast::Hasher::new(),
         })
@@ -141,7 +364,7 @@ impl PartialFile {
     }
 
     async fn finish(self) -> Result<SnapshotFile> {
-        self.file.sync_all()?;
+        self.file.file().sync()?;
 
         if self.size as u64 != self.meta.size {
             return Err(Error::InvalidData(format!(
@@ -153,8 +376,8 @@ impl PartialFile {
         let crc32 = self.crc32.finalize();
         if crc32 != self.meta.crc32 {
             return Err(Error::InvalidData(format!(
-                "checksum is not equals, expect {}, but got {}",
-                self.meta.size, self.size
+                "checksum is not equals, expect {}, but got {crc32}",
+                self.meta.crc32
             )));
         }
 
@@ -162,8 +385,11 @@ impl PartialFile {
     }
 }
 
+/// Download the snapshot of the message in background. `replication_factor` is the desired
+/// number of voters of the group, 0 means the default replicas per group.
 pub fn dispatch_downloading_snap_task(
     replica_id: u64,
+    replication_factor: u32,
     mut sender: mpsc::Sender<Request>,
     snap_mgr: SnapManager,
     tran_mgr: ChannelManager,
@@ -171,7 +397,8 @@ pub fn dispatch_downloading_snap_task(
     mut msg: Message,
 ) {
     crate::runtime::current().spawn(None, TaskPriority::IoLow, async move {
-        match download_snap(replica_id, tran_mgr, snap_mgr, from_replica, &msg).await {
+        let priority = snap_priority(&msg, replica_id, replication_factor);
+        match download_snap(replica_id, tran_mgr, snap_mgr, from_replica, priority, &msg).await {
             Ok(snap_id) => {
                 msg.snapshot.as_mut().unwrap().data = snap_id;
                 let request = Request::InstallSnapshot { msg };
@@ -192,22 +419,75 @@ async fn download_snap(
     tran_mgr: ChannelManager,
     snap_mgr: SnapManager,
     from_replica: ReplicaDesc,
+    priority: SnapPriority,
     msg: &Message,
 ) -> Result<Vec<u8>> {
     record_latency!(take_download_snapshot_metrics());
     assert!(msg.has_snapshot() && !msg.get_snapshot().is_empty());
-    let snapshot = msg.get_snapshot();
-    let snapshot_id = snapshot.data.clone();
-    let chunk_stream = retrive_snapshot(&tran_mgr, from_replica, snapshot_id).await?;
-    save_snapshot(&snap_mgr, replica_id, chunk_stream).await
+    let snapshot_id = msg.get_snapshot().data.clone();
+    let _permit = snap_mgr.limiter().acquire_recv(priority).await;
+    let retrieve = |point: ResumePoint| {
+        retrive_snapshot(
+            &tran_mgr,
+            from_replica.clone(),
+            snapshot_id.clone(),
+            point.start_key,
+            point.start_file as u32,
+            priority == SnapPriority::High,
+        )
+    };
+    save_resumable_snapshot(&snap_mgr, replica_id, retrieve).await
+}
+
+/// A replica which requires snapshot couldn't replicate entries, so the group is under-replicated
+/// until the snapshot is installed if the other voters are fewer than the replication factor.
+fn snap_priority(msg: &Message, replica_id: u64, replication_factor: u32) -> SnapPriority {
+    let replication_factor = match replication_factor {
+        0 => REPLICA_PER_GROUP,
+        n => n as usize,
+    };
+    let conf_state = msg.get_snapshot().get_metadata().get_conf_state();
+    let num_other_voters = conf_state
+        .voters
+        .iter()
+        .filter(|id| **id != replica_id)
+        .count();
+    if num_other_voters < replication_factor {
+        SnapPriority::High
+    } else {
+        SnapPriority::Normal
+    }
 }
 
 pub(super) async fn save_snapshot<S>(
     snap_mgr: &SnapManager,
     replica_id: u64,
-    mut chunk_stream: S,
+    chunk_stream: S,
+) -> Result<Vec<u8>>
+where
+    S: futures::Stream<Item = Result<SnapshotChunk, tonic::Status>> + Unpin,
+{
+    let mut chunk_stream = Some(chunk_stream);
+    let retrieve = |_| {
+        let chunk_stream = chunk_stream.take();
+        async move {
+            chunk_stream.ok_or_else(|| Error::InvalidData("snapshot stream is broken".to_string()))
+        }
+    };
+    save_resumable_snapshot(snap_mgr, replica_id, retrieve).await
+}
+
+/// Like `save_snapshot`, but the snapshot is resumed by `retrieve` if the chunk stream is broken
+/// or the received data is corrupted. The streaming snapshot is resumed from the last received
+/// key, and the others from the first file not received completely.
+pub(super) async fn save_resumable_snapshot<F, Fut, S>(
+    snap_mgr: &SnapManager,
+    replica_id: u64,
+    mut retrieve: F,
 ) -> Result<Vec<u8>>
 where
+    F: FnMut(ResumePoint) -> Fut,
+    Fut: std::future::Future<Output = Result<S>>,
     S: futures::Stream<Item = Result<SnapshotChunk, tonic::Status>> + Unpin,
 {
     let base_dir = snap_mgr.create(replica_id);
@@ -217,12 +497,138 @@ where
     );
 
     std::fs::create_dir_all(&base_dir)?;
-    let mut snap_builder = SnapshotBuilder::new(replica_id, &base_dir);
+    let mut snap_builder =
+        SnapshotBuilder::new(replica_id, &base_dir, snap_mgr.key_manager().clone());
+    let mut resume_times = 0;
+    loop {
+        let point = snap_builder.resume_point().unwrap_or_default();
+        let result = match retrieve(point).await {
+            Ok(chunk_stream) => {
+                receive_chunks(snap_mgr.limiter(), &mut snap_builder, chunk_stream).await
+            }
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(()) => break,
+            Err(err)
+                if resume_times < MAX_RESUME_TIMES && snap_builder.resume_point().is_some() =>
+            {
+                resume_times += 1;
+                warn!("replica {replica_id} resume snapshot, times {resume_times}: {err}");
+                snap_builder.discard_partial_file();
+                crate::runtime::time::sleep(RESUME_INTERVAL).await;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    let snap_meta = snap_builder.finish().await?;
+    Ok(snap_mgr.install(replica_id, &base_dir, &snap_meta))
+}
+
+async fn receive_chunks<S>(
+    limiter: &SnapLimiter,
+    snap_builder: &mut SnapshotBuilder,
+    mut chunk_stream: S,
+) -> Result<()>
+where
+    S: futures::Stream<Item = Result<SnapshotChunk, tonic::Status>> + Unpin,
+{
+    use prost::Message as _;
+
     while let Some(resp) = chunk_stream.next().await {
         let chunk = resp?;
+        let delay = limiter.consume_recv(chunk.encoded_len());
         snap_builder.append(chunk).await?;
+        if !delay.is_zero() {
+            crate::runtime::time::sleep(delay).await;
+        }
     }
 
-    let snap_meta = snap_builder.finish().await?;
-    Ok(snap_mgr.install(replica_id, &base_dir, &snap_meta))
+    if snap_builder.resume_point().is_some() {
+        return Err(Error::InvalidData("snapshot is truncated".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use raft::eraftpb::{ConfState, Snapshot};
+
+    use super::*;
+
+    fn snapshot_msg(voters: Vec<u64>) -> Message {
+        let mut snapshot = Snapshot::default();
+        snapshot.mut_metadata().set_conf_state(ConfState {
+            voters,
+            ..Default::default()
+        });
+        let mut msg = Message::default();
+        msg.set_snapshot(snapshot);
+        msg
+    }
+
+    #[test]
+    fn snap_priority_of_under_replicated_group() {
+        struct Case {
+            voters: Vec<u64>,
+            replica_id: u64,
+            replication_factor: u32,
+            priority: SnapPriority,
+        }
+        let cases = vec![
+            // A lagging voter of a fully replicated group.
+            Case {
+                voters: vec![1, 2, 3],
+                replica_id: 3,
+                replication_factor: 3,
+                priority: SnapPriority::High,
+            },
+            // A learner replacing the lost voter.
+            Case {
+                voters: vec![1, 2],
+                replica_id: 3,
+                replication_factor: 3,
+                priority: SnapPriority::High,
+            },
+            // A learner added to a fully replicated group, eg. moving a replica.
+            Case {
+                voters: vec![1, 2, 3],
+                replica_id: 4,
+                replication_factor: 3,
+                priority: SnapPriority::Normal,
+            },
+            // A voter of a group having more voters than required.
+            Case {
+                voters: vec![1, 2, 3, 4],
+                replica_id: 4,
+                replication_factor: 3,
+                priority: SnapPriority::Normal,
+            },
+            // The default replicas per group is used.
+            Case {
+                voters: vec![1, 2],
+                replica_id: 3,
+                replication_factor: 0,
+                priority: SnapPriority::High,
+            },
+            Case {
+                voters: vec![1, 2, 3, 4],
+                replica_id: 4,
+                replication_factor: 5,
+                priority: SnapPriority::High,
+            },
+        ];
+        for case in cases {
+            let msg = snapshot_msg(case.voters.clone());
+            assert_eq!(
+                snap_priority(&msg, case.replica_id, case.replication_factor),
+                case.priority,
+                "voters {:?}, replica {}, replication factor {}",
+                case.voters,
+                case.replica_id,
+                case.replication_factor,
+            );
+        }
+    }
 }
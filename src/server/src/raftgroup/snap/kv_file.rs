@@ -0,0 +1,152 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The key values of a snapshot are staged in the kv files, which are written and read through
+//! the data keys, so they are never staged in plaintext if the encryption is enabled. Each record
+//! is the key and the value, both are prefixed by the length in big-endian u32.
+
+use std::{
+    io::{BufRead, BufReader, BufWriter, Read, Write},
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    encryption::{DataKeyManager, FileReader, FileWriter},
+    Error, Result,
+};
+
+pub const KV_FILE_EXTENSION: &str = "kv";
+
+pub struct KvFileWriter {
+    writer: BufWriter<FileWriter>,
+    size: usize,
+}
+
+pub struct KvFileReader {
+    reader: BufReader<FileReader>,
+}
+
+impl KvFileWriter {
+    /// Create a kv file, which is encrypted by the current data key.
+    pub fn create(key_manager: &DataKeyManager, path: &Path) -> Result<Self> {
+        let file = key_manager.create_file(path)?;
+        Ok(KvFileWriter {
+            writer: BufWriter::new(FileWriter::new(Arc::new(file))),
+            size: 0,
+        })
+    }
+
+    pub fn put(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        for data in [key, value] {
+            self.writer.write_all(&(data.len() as u32).to_be_bytes())?;
+            self.writer.write_all(data)?;
+            self.size += core::mem::size_of::<u32>() + data.len();
+        }
+        Ok(())
+    }
+
+    /// Return the bytes of the written records.
+    #[inline]
+    pub fn file_size(&self) -> usize {
+        self.size
+    }
+
+    /// Flush the buffered records and sync the file.
+    pub fn finish(self) -> Result<()> {
+        let writer = self
+            .writer
+            .into_inner()
+            .map_err(|err| Error::Io(err.into_error()))?;
+        writer.file().sync()?;
+        Ok(())
+    }
+}
+
+impl KvFileReader {
+    /// Open a kv file, which might be encrypted by any data key or plaintext.
+    pub fn open(key_manager: &DataKeyManager, path: &Path) -> Result<Self> {
+        let file = key_manager.open_file(path)?;
+        Ok(KvFileReader {
+            reader: BufReader::new(FileReader::new(Arc::new(file))),
+        })
+    }
+
+    fn read_data(&mut self) -> Result<Vec<u8>> {
+        let mut len = [0u8; 4];
+        self.reader.read_exact(&mut len)?;
+        let mut data = vec![0u8; u32::from_be_bytes(len) as usize];
+        self.reader.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    fn read_record(&mut self) -> Result<Option<(Vec<u8>, Vec<u8>)>> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let key = self.read_data()?;
+        let value = self.read_data()?;
+        Ok(Some((key, value)))
+    }
+}
+
+impl Iterator for KvFileReader {
+    /// Key value pairs.
+    type Item = Result<(Vec<u8>, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_record().transpose()
+    }
+}
+
+#[inline]
+pub fn is_kv_file<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    path.is_file()
+        && path
+            .extension()
+            .map(|ext| ext == KV_FILE_EXTENSION)
+            .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn write_and_read_kv_file() {
+        let dir = TempDir::new("write-and-read-kv-file").unwrap();
+        let path = dir.path().join("0.kv");
+        let key_manager = DataKeyManager::plaintext();
+        let key_values = vec![
+            (b"a".to_vec(), vec![]),
+            (b"b".to_vec(), b"value".to_vec()),
+            (vec![0xff; 100], vec![0xfe; 10000]),
+        ];
+
+        let mut writer = KvFileWriter::create(&key_manager, &path).unwrap();
+        for (key, value) in &key_values {
+            writer.put(key, value).unwrap();
+        }
+        assert_eq!(writer.file_size(), 8 * 3 + 1 + 1 + 5 + 100 + 10000);
+        writer.finish().unwrap();
+        assert!(is_kv_file(&path));
+
+        let reader = KvFileReader::open(&key_manager, &path).unwrap();
+        let read = reader.collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(read, key_values);
+    }
+}
@@ -0,0 +1,343 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use futures::channel::oneshot;
+use prometheus::IntGauge;
+
+use crate::{raftgroup::metrics::*, RaftConfig};
+
+/// The priority of a snapshot transfer, the transfers with higher priority are served first, and
+/// the transfers with the same priority are served in FIFO order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum SnapPriority {
+    /// The snapshot is used to rebuild a replica of the group whose other voters are fewer than
+    /// the replication factor, the group is under-replicated until it is finished.
+    High = 0,
+    Normal = 1,
+}
+
+/// Limits the concurrency and the bandwidth of the snapshot transfers of a node, so that
+/// rebuilding replicas doesn't saturate the network and disks.
+#[derive(Clone)]
+pub struct SnapLimiter {
+    send: Arc<TransferQueue>,
+    recv: Arc<TransferQueue>,
+    stream: Arc<TransferQueue>,
+    send_rate: Arc<RateLimiter>,
+    recv_rate: Arc<RateLimiter>,
+}
+
+/// A slot of the concurrent snapshot transfers, it is released after dropping.
+pub struct TransferPermit {
+    queue: Option<Arc<TransferQueue>>,
+}
+
+struct TransferQueue {
+    capacity: usize,
+    queue_depth: &'static IntGauge,
+    running: &'static IntGauge,
+    inner: Mutex<QueueInner>,
+}
+
+#[derive(Default)]
+struct QueueInner {
+    num_running: usize,
+    next_seq: u64,
+    waiters: BTreeMap<(SnapPriority, u64), oneshot::Sender<TransferPermit>>,
+}
+
+/// A token bucket, the bytes are consumed in advance and the caller waits until the debt is
+/// paid off.
+struct RateLimiter {
+    bytes_per_sec: u64,
+    inner: Mutex<RateInner>,
+}
+
+struct RateInner {
+    available: f64,
+    last_refill: Instant,
+}
+
+impl SnapLimiter {
+    pub fn new(cfg: &RaftConfig) -> Self {
+        SnapLimiter {
+            send: Arc::new(TransferQueue::new(
+                cfg.snap_max_concurrent_sends,
+                &RAFTGROUP_SNAPSHOT_QUEUE_DEPTH.send,
+                &RAFTGROUP_SNAPSHOT_RUNNING.send,
+            )),
+            recv: Arc::new(TransferQueue::new(
+                cfg.snap_max_concurrent_recvs,
+                &RAFTGROUP_SNAPSHOT_QUEUE_DEPTH.recv,
+                &RAFTGROUP_SNAPSHOT_RUNNING.recv,
+            )),
+            stream: Arc::new(TransferQueue::new(
+                cfg.snap_max_concurrent_streams,
+                &RAFTGROUP_SNAPSHOT_QUEUE_DEPTH.stream,
+                &RAFTGROUP_SNAPSHOT_RUNNING.stream,
+            )),
+            send_rate: Arc::new(RateLimiter::new(cfg.snap_send_bytes_per_sec)),
+            recv_rate: Arc::new(RateLimiter::new(cfg.snap_recv_bytes_per_sec)),
+        }
+    }
+
+    /// Wait until a snapshot is allowed to be sent.
+    pub async fn acquire_send(&self, priority: SnapPriority) -> TransferPermit {
+        self.send.acquire(priority).await
+    }
+
+    /// Wait until a snapshot is allowed to be received.
+    pub async fn acquire_recv(&self, priority: SnapPriority) -> TransferPermit {
+        self.recv.acquire(priority).await
+    }
+
+    /// Try to open a streaming snapshot, `None` is returned if too many of them are opened.
+    pub fn try_acquire_stream(&self) -> Option<TransferPermit> {
+        self.stream.try_acquire()
+    }
+
+    /// Consume the sent bytes, returns the duration to wait before sending the next chunk.
+    pub fn consume_send(&self, bytes: usize) -> Duration {
+        self.send_rate.consume(bytes)
+    }
+
+    /// Consume the received bytes, returns the duration to wait before receiving the next chunk.
+    pub fn consume_recv(&self, bytes: usize) -> Duration {
+        self.recv_rate.consume(bytes)
+    }
+}
+
+impl Default for SnapLimiter {
+    fn default() -> Self {
+        SnapLimiter::new(&RaftConfig::default())
+    }
+}
+
+impl TransferQueue {
+    fn new(capacity: usize, queue_depth: &'static IntGauge, running: &'static IntGauge) -> Self {
+        TransferQueue {
+            // A zero capacity would block all transfers.
+            capacity: capacity.max(1),
+            queue_depth,
+            running,
+            inner: Mutex::default(),
+        }
+    }
+
+    fn try_acquire(self: &Arc<Self>) -> Option<TransferPermit> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.num_running < self.capacity && inner.waiters.is_empty() {
+            inner.num_running += 1;
+            self.running.inc();
+            Some(TransferPermit {
+                queue: Some(self.clone()),
+            })
+        } else {
+            None
+        }
+    }
+
+    async fn acquire(self: &Arc<Self>, priority: SnapPriority) -> TransferPermit {
+        let receiver = {
+            let mut inner = self.inner.lock().unwrap();
+            if inner.num_running < self.capacity && inner.waiters.is_empty() {
+                inner.num_running += 1;
+                self.running.inc();
+                return TransferPermit {
+                    queue: Some(self.clone()),
+                };
+            }
+
+            let (sender, receiver) = oneshot::channel();
+            let seq = inner.next_seq;
+            inner.next_seq += 1;
+            inner.waiters.insert((priority, seq), sender);
+            self.queue_depth.inc();
+            receiver
+        };
+
+        // The sender is only dropped with the permit, and the queue lives as long as the permit.
+        receiver
+            .await
+            .expect("the waiter is dropped without being served")
+    }
+
+    /// Hand over the slot to the first waiter, or release it if there is no waiter.
+    fn release(self: &Arc<Self>) {
+        loop {
+            let sender = {
+                let mut inner = self.inner.lock().unwrap();
+                let Some(key) = inner.waiters.keys().next().cloned() else {
+                    inner.num_running -= 1;
+                    self.running.dec();
+                    return;
+                };
+                self.queue_depth.dec();
+                inner.waiters.remove(&key).unwrap()
+            };
+
+            let permit = TransferPermit {
+                queue: Some(self.clone()),
+            };
+            match sender.send(permit) {
+                Ok(()) => return,
+                // The waiter is canceled, try the next one without releasing the slot.
+                Err(mut permit) => permit.queue = None,
+            }
+        }
+    }
+}
+
+impl Drop for TransferPermit {
+    fn drop(&mut self) {
+        if let Some(queue) = self.queue.take() {
+            queue.release();
+        }
+    }
+}
+
+impl RateLimiter {
+    fn new(bytes_per_sec: u64) -> Self {
+        RateLimiter {
+            bytes_per_sec,
+            inner: Mutex::new(RateInner {
+                available: bytes_per_sec as f64,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    fn consume(&self, bytes: usize) -> Duration {
+        if self.bytes_per_sec == 0 {
+            return Duration::ZERO;
+        }
+
+        let rate = self.bytes_per_sec as f64;
+        let mut inner = self.inner.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(inner.last_refill);
+        // At most one second of bytes are accumulated, to limit the burst.
+        inner.available = (inner.available + elapsed.as_secs_f64() * rate).min(rate);
+        inner.last_refill = now;
+        inner.available -= bytes as f64;
+        if inner.available >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-inner.available / rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::{
+        future::{select, Either},
+        StreamExt,
+    };
+
+    use super::*;
+    use crate::runtime::{time::sleep, ExecutorOwner, TaskPriority};
+
+    fn limiter(max_concurrent: usize, bytes_per_sec: u64) -> SnapLimiter {
+        SnapLimiter::new(&RaftConfig {
+            snap_max_concurrent_sends: max_concurrent,
+            snap_max_concurrent_recvs: max_concurrent,
+            snap_max_concurrent_streams: max_concurrent,
+            snap_send_bytes_per_sec: bytes_per_sec,
+            snap_recv_bytes_per_sec: bytes_per_sec,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn transfer_queue_prioritize_and_fifo() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let limiter = limiter(1, 0);
+            let permit = limiter.acquire_send(SnapPriority::Normal).await;
+
+            let (sender, mut receiver) = futures::channel::mpsc::unbounded();
+            for (id, priority) in [
+                (1, SnapPriority::Normal),
+                (2, SnapPriority::High),
+                (3, SnapPriority::Normal),
+                (4, SnapPriority::High),
+            ] {
+                let limiter = limiter.clone();
+                let sender = sender.clone();
+                crate::runtime::current().spawn(None, TaskPriority::Low, async move {
+                    let _permit = limiter.acquire_send(priority).await;
+                    sender.unbounded_send(id).unwrap();
+                });
+                // Make sure the waiters are queued in order.
+                sleep(Duration::from_millis(10)).await;
+            }
+            drop(sender);
+            drop(permit);
+
+            let mut served = vec![];
+            while let Some(id) = receiver.next().await {
+                served.push(id);
+            }
+            assert_eq!(served, vec![2, 4, 1, 3]);
+        });
+    }
+
+    #[test]
+    fn transfer_queue_skip_canceled_waiter() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let limiter = limiter(1, 0);
+            let permit = limiter.acquire_recv(SnapPriority::Normal).await;
+
+            // The waiter is canceled after timeout.
+            let acquire = Box::pin(limiter.acquire_recv(SnapPriority::High));
+            let timeout = Box::pin(sleep(Duration::from_millis(10)));
+            match select(acquire, timeout).await {
+                Either::Left(_) => panic!("the permit should be acquired after releasing"),
+                Either::Right((_, acquire)) => drop(acquire),
+            }
+
+            drop(permit);
+            let _permit = limiter.acquire_recv(SnapPriority::Normal).await;
+        });
+    }
+
+    #[test]
+    fn try_acquire_stream() {
+        let limiter = limiter(1, 0);
+        let permit = limiter.try_acquire_stream();
+        assert!(permit.is_some());
+        assert!(limiter.try_acquire_stream().is_none());
+        drop(permit);
+        assert!(limiter.try_acquire_stream().is_some());
+    }
+
+    #[test]
+    fn rate_limiter() {
+        let rate = RateLimiter::new(1000);
+        assert_eq!(rate.consume(1000), Duration::ZERO);
+        let delay = rate.consume(500);
+        assert!(delay > Duration::from_millis(400) && delay <= Duration::from_millis(500));
+
+        let unlimited = RateLimiter::new(0);
+        assert_eq!(unlimited.consume(usize::MAX), Duration::ZERO);
+    }
+}
@@ -15,6 +15,8 @@
 pub mod apply;
 pub mod create;
 pub mod download;
+pub mod kv_file;
+pub mod limiter;
 pub mod send;
 
 use std::{
@@ -29,8 +31,19 @@ use futures::{channel::mpsc, StreamExt};
 use raft::prelude::{Snapshot, SnapshotMetadata};
 use tracing::{error, info, warn};
 
-pub use self::{create::dispatch_creating_snap_task, download::dispatch_downloading_snap_task};
-use crate::{runtime::TaskPriority, serverpb::v1::SnapshotMeta, Result};
+use self::limiter::TransferPermit;
+pub use self::{
+    create::dispatch_creating_snap_task,
+    download::dispatch_downloading_snap_task,
+    limiter::{SnapLimiter, SnapPriority},
+};
+use crate::{
+    encryption::{DataKeyManager, FileReader},
+    raftgroup::SnapshotStream,
+    runtime::TaskPriority,
+    serverpb::v1::SnapshotMeta,
+    Error, Result,
+};
 
 const SNAP_DATA: &str = "DATA";
 const SNAP_TEMP: &str = "TEMP";
@@ -49,12 +62,27 @@ pub struct SnapshotInfo {
     /// The parent dir of snapshot files.
     pub base_dir: PathBuf,
     pub meta: SnapshotMeta,
+    /// The consistent view of the state machine if it is a streaming snapshot, the data is read
+    /// from it during sending, and nothing is staged under `base_dir`.
+    pub stream: Option<Arc<Mutex<StreamingSnapshot>>>,
 
     /// The ref count of snapshot.
     ref_count: usize,
     created_at: Instant,
 }
 
+/// The data of a streaming snapshot, which also computes the checksum of the key values sent in
+/// the trailer.
+pub struct StreamingSnapshot {
+    stream: Box<dyn SnapshotStream>,
+    /// The largest key read from the stream, the key values before it are hashed in key order.
+    hashed_key: Vec<u8>,
+    hasher: crc32fast::Hasher,
+    crc32: Option<u32>,
+    /// Each streaming snapshot pins an iterator and a thread until it is recycled.
+    _permit: TransferPermit,
+}
+
 pub struct SnapshotGuard {
     replica_id: u64,
     info: SnapshotInfo,
@@ -79,6 +107,8 @@ where
 struct SnapManagerShared {
     root_dir: PathBuf,
     min_keep_intervals: Duration,
+    limiter: SnapLimiter,
+    key_manager: Arc<DataKeyManager>,
     inner: Mutex<SnapManagerInner>,
 }
 
@@ -95,6 +125,8 @@ impl SnapManager {
             shared: Arc::new(SnapManagerShared {
                 root_dir: dir,
                 min_keep_intervals: Duration::from_secs(0),
+                limiter: SnapLimiter::default(),
+                key_manager: Arc::new(DataKeyManager::plaintext()),
                 inner: Mutex::new(SnapManagerInner {
                     sender,
                     replicas: HashMap::default(),
@@ -103,7 +135,13 @@ impl SnapManager {
         }
     }
 
-    pub async fn recovery<P: AsRef<Path>>(root_dir: P) -> Result<SnapManager> {
+    pub async fn recovery<P: AsRef<Path>>(
+        root_dir: P,
+        limiter: SnapLimiter,
+        key_manager: Arc<DataKeyManager>,
+    ) -> Result<SnapManager> {
+        use std::io::Read;
+
         use prost::Message;
 
         let (mut sender, receiver) = mpsc::unbounded();
@@ -125,7 +163,9 @@ impl SnapManager {
                         .unwrap_or_default();
                     continue;
                 }
-                let bytes = std::fs::read(&meta_name)?;
+                let mut bytes = vec![];
+                FileReader::new(Arc::new(key_manager.open_file(&meta_name)?))
+                    .read_to_end(&mut bytes)?;
                 let snapshot_meta = match SnapshotMeta::decode(&*bytes) {
                     Ok(meta) => meta,
                     Err(e) => {
@@ -147,6 +187,7 @@ impl SnapManager {
                     snapshot_id,
                     base_dir: snap_dir,
                     meta: snapshot_meta,
+                    stream: None,
                     ref_count: 0,
                     created_at: Instant::now(),
                 };
@@ -169,11 +210,25 @@ impl SnapManager {
             shared: Arc::new(SnapManagerShared {
                 root_dir: root_dir.to_owned(),
                 min_keep_intervals: Duration::from_secs(180),
+                limiter,
+                key_manager,
                 inner: Mutex::new(SnapManagerInner { sender, replicas }),
             }),
         })
     }
 
+    /// The limiter of the snapshot transfers of this node.
+    #[inline]
+    pub fn limiter(&self) -> &SnapLimiter {
+        &self.shared.limiter
+    }
+
+    /// The key manager used to encrypt the snapshot files of this node.
+    #[inline]
+    pub fn key_manager(&self) -> &Arc<DataKeyManager> {
+        &self.shared.key_manager
+    }
+
     /// Mark group as creating, and return a dir to save snapshot.
     pub fn create(&self, replica_id: u64) -> PathBuf {
         let mut inner = self.shared.inner.lock().unwrap();
@@ -187,6 +242,35 @@ impl SnapManager {
     /// Install a snapshot and returns snapshot id.
     pub fn install(&self, replica_id: u64, dir_name: &Path, meta: &SnapshotMeta) -> Vec<u8> {
         // TODO(walter) check snapshot data integrity.
+        self.install_snapshot(replica_id, dir_name, meta.clone(), None)
+    }
+
+    /// Install a streaming snapshot and returns snapshot id. The data of the snapshot is read from
+    /// the stream when it is sent, so the snapshot is not persisted and lost after restarting. The
+    /// permit is released once the snapshot is recycled.
+    pub fn install_stream(
+        &self,
+        replica_id: u64,
+        stream: Box<dyn SnapshotStream>,
+        permit: TransferPermit,
+    ) -> Vec<u8> {
+        let dir_name = self.create(replica_id);
+        let meta = SnapshotMeta {
+            apply_state: Some(stream.apply_state()),
+            group_desc: Some(stream.descriptor()),
+            ..Default::default()
+        };
+        let stream = Arc::new(Mutex::new(StreamingSnapshot::new(stream, permit)));
+        self.install_snapshot(replica_id, &dir_name, meta, Some(stream))
+    }
+
+    fn install_snapshot(
+        &self,
+        replica_id: u64,
+        dir_name: &Path,
+        meta: SnapshotMeta,
+        stream: Option<Arc<Mutex<StreamingSnapshot>>>,
+    ) -> Vec<u8> {
         let mut inner = self.shared.inner.lock().unwrap();
         let replica = inner
             .replicas
@@ -201,14 +285,16 @@ impl SnapManager {
                 debug_assert!(snapshot_index < replica.next_snapshot_index);
 
                 info!(
-                    "replica {replica_id} install snap {snapshot_index}, dir {}",
-                    dir_name.display()
+                    "replica {replica_id} install snap {snapshot_index}, dir {}, streaming {}",
+                    dir_name.display(),
+                    stream.is_some()
                 );
 
                 replica.push(SnapshotInfo {
                     snapshot_id: snapshot_id.clone(),
                     base_dir: replica.base_dir.join(name),
-                    meta: meta.clone(),
+                    meta,
+                    stream,
                     ref_count: 0,
                     created_at: Instant::now(),
                 });
@@ -258,12 +344,15 @@ impl SnapManager {
                             && info.created_at + self.shared.min_keep_intervals < now
                             && info.ref_count == 0
                     })
+                    // Nothing is staged on disk for the streaming snapshot.
+                    .filter(|info| info.stream.is_none())
                     .map(|info| info.base_dir)
                     .collect::<Vec<_>>(),
                 RecycleSnapMode::All => {
                     let snapshots = replica
                         .snapshots
                         .iter()
+                        .filter(|info| info.stream.is_none())
                         .map(|info| info.base_dir.clone())
                         .collect::<Vec<_>>();
                     inner.replicas.remove(&replica_id);
@@ -342,6 +431,51 @@ impl SnapshotInfo {
     }
 }
 
+impl StreamingSnapshot {
+    fn new(stream: Box<dyn SnapshotStream>, permit: TransferPermit) -> Self {
+        StreamingSnapshot {
+            stream,
+            hashed_key: vec![],
+            hasher: crc32fast::Hasher::new(),
+            crc32: None,
+            _permit: permit,
+        }
+    }
+
+    /// Read the key values after `start_key` (exclusive), see [`SnapshotStream::next_chunk`]. The
+    /// snapshot might be resumed or sent to several replicas, the key values are only hashed when
+    /// they are read for the first time.
+    pub fn next_chunk(
+        &mut self,
+        start_key: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        if start_key > self.hashed_key.as_slice() {
+            return Err(Error::InvalidArgument(format!(
+                "the streaming snapshot is resumed after an unread key {start_key:?}"
+            )));
+        }
+
+        let key_values = self.stream.next_chunk(start_key, limit)?;
+        for (key, value) in &key_values {
+            if key > &self.hashed_key {
+                hash_key_value(&mut self.hasher, key, value);
+                self.hashed_key = key.clone();
+            }
+        }
+        if key_values.is_empty() && self.crc32.is_none() {
+            self.crc32 = Some(self.hasher.clone().finalize());
+        }
+        Ok(key_values)
+    }
+
+    /// The crc32 of all key values of the snapshot, `None` if they are not read completely.
+    #[inline]
+    pub fn crc32(&self) -> Option<u32> {
+        self.crc32
+    }
+}
+
 impl Drop for SnapshotGuard {
     fn drop(&mut self) {
         let mut inner = self.manager.shared.inner.lock().unwrap();
@@ -383,6 +517,23 @@ fn list_numeric_path(root: &Path) -> Result<Vec<(u64, PathBuf)>> {
     Ok(values)
 }
 
+/// The checksum of the key values of a streaming snapshot chunk. The lengths are hashed too, so
+/// that moving bytes between a key and a value changes the checksum.
+fn key_values_crc32(keys: &[Vec<u8>], values: &[Vec<u8>]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    for (key, value) in keys.iter().zip(values.iter()) {
+        hash_key_value(&mut hasher, key, value);
+    }
+    hasher.finalize()
+}
+
+fn hash_key_value(hasher: &mut crc32fast::Hasher, key: &[u8], value: &[u8]) {
+    hasher.update(&(key.len() as u64).to_le_bytes());
+    hasher.update(key);
+    hasher.update(&(value.len() as u64).to_le_bytes());
+    hasher.update(value);
+}
+
 async fn recycle_snapshot(mut receiver: mpsc::UnboundedReceiver<(u64, PathBuf)>) {
     while let Some((replica_id, snapshot_dir)) = receiver.next().await {
         if let Err(err) = std::fs::remove_dir_all(&snapshot_dir) {
@@ -411,11 +562,23 @@ mod tests {
 
     use super::*;
     use crate::{
-        raftgroup::SnapshotBuilder,
+        raftgroup::{metrics::*, SnapshotBuilder},
         runtime::{time::sleep, ExecutorOwner},
-        serverpb::v1::ApplyState,
+        serverpb::v1::{snapshot_chunk, ApplyState, SnapshotChunk},
     };
 
+    fn write_file(key_manager: &DataKeyManager, path: &Path, content: &[u8]) -> Result<()> {
+        use std::io::Write;
+
+        use crate::encryption::FileWriter;
+
+        let file = key_manager.create_file(path)?;
+        let mut writer = FileWriter::new(Arc::new(file));
+        writer.write_all(content)?;
+        writer.file().sync()?;
+        Ok(())
+    }
+
     struct SimpleSnapshotBuilder {
         index: u64,
         content: Vec<u8>,
@@ -423,12 +586,16 @@ mod tests {
 
     #[crate::async_trait]
     impl SnapshotBuilder for SimpleSnapshotBuilder {
-        async fn checkpoint(&self, base_dir: &Path) -> Result<(ApplyState, GroupDesc)> {
+        async fn checkpoint(
+            &self,
+            key_manager: &DataKeyManager,
+            base_dir: &Path,
+        ) -> Result<(ApplyState, GroupDesc)> {
             info!("create snapshot at: {}", base_dir.display());
             if let Some(parent) = base_dir.parent() {
                 std::fs::create_dir_all(parent)?;
             }
-            std::fs::write(base_dir, &self.content)?;
+            write_file(key_manager, base_dir, &self.content)?;
             info!("write snapshot content");
             let state = ApplyState {
                 index: self.index,
@@ -446,13 +613,17 @@ mod tests {
 
     #[crate::async_trait]
     impl SnapshotBuilder for MultiFilesSnapshotBuilder {
-        async fn checkpoint(&self, base_dir: &Path) -> Result<(ApplyState, GroupDesc)> {
+        async fn checkpoint(
+            &self,
+            key_manager: &DataKeyManager,
+            base_dir: &Path,
+        ) -> Result<(ApplyState, GroupDesc)> {
             info!("create snapshot at: {}", base_dir.display());
             std::fs::create_dir_all(base_dir)?;
             let file_1 = base_dir.join("1");
             let file_2 = base_dir.join("2");
-            std::fs::write(file_1, &self.content_1)?;
-            std::fs::write(file_2, &self.content_2)?;
+            write_file(key_manager, &file_1, &self.content_1)?;
+            write_file(key_manager, &file_2, &self.content_2)?;
             info!("write snapshot content");
             let state = ApplyState {
                 index: self.index,
@@ -462,6 +633,63 @@ mod tests {
         }
     }
 
+    #[derive(Clone)]
+    struct MemorySnapshotStream {
+        index: u64,
+        key_values: Vec<(Vec<u8>, Vec<u8>)>,
+    }
+
+    impl SnapshotStream for MemorySnapshotStream {
+        fn apply_state(&self) -> ApplyState {
+            ApplyState {
+                index: self.index,
+                term: 0,
+            }
+        }
+
+        fn descriptor(&self) -> GroupDesc {
+            GroupDesc::default()
+        }
+
+        fn next_chunk(
+            &mut self,
+            start_key: &[u8],
+            _limit: usize,
+        ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+            // Returns one key value per chunk.
+            Ok(self
+                .key_values
+                .iter()
+                .find(|(key, _)| key.as_slice() > start_key)
+                .cloned()
+                .into_iter()
+                .collect())
+        }
+    }
+
+    #[crate::async_trait]
+    impl SnapshotBuilder for MemorySnapshotStream {
+        async fn checkpoint(
+            &self,
+            key_manager: &DataKeyManager,
+            base_dir: &Path,
+        ) -> Result<(ApplyState, GroupDesc)> {
+            if let Some(parent) = base_dir.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut writer = kv_file::KvFileWriter::create(key_manager, base_dir)?;
+            for (key, value) in &self.key_values {
+                writer.put(key, value)?;
+            }
+            writer.finish()?;
+            Ok((self.apply_state(), self.descriptor()))
+        }
+
+        fn stream(&self) -> Result<Option<Box<dyn SnapshotStream>>> {
+            Ok(Some(Box::new(self.clone())))
+        }
+    }
+
     async fn build_snapshot(
         manager: &SnapManager,
         replica_id: u64,
@@ -483,7 +711,13 @@ mod tests {
 
             let replica_id_1: u64 = 1;
             let replica_id_2: u64 = 2;
-            let snap_manager = SnapManager::recovery(&root_dir).await.unwrap();
+            let snap_manager = SnapManager::recovery(
+                &root_dir,
+                SnapLimiter::default(),
+                Arc::new(DataKeyManager::plaintext()),
+            )
+            .await
+            .unwrap();
 
             let snap_id_1 = build_snapshot(&snap_manager, replica_id_1, 1, vec![1]).await;
             let snap_id_2 = build_snapshot(&snap_manager, replica_id_1, 2, vec![2]).await;
@@ -492,7 +726,13 @@ mod tests {
 
             drop(snap_manager);
 
-            let snap_manager = SnapManager::recovery(&root_dir).await.unwrap();
+            let snap_manager = SnapManager::recovery(
+                &root_dir,
+                SnapLimiter::default(),
+                Arc::new(DataKeyManager::plaintext()),
+            )
+            .await
+            .unwrap();
             for snap_id in &replica_snaps_1 {
                 assert!(
                     snap_manager
@@ -511,6 +751,58 @@ mod tests {
         });
     }
 
+    #[test]
+    fn recovery_encrypted_snapshot() {
+        use crate::{EncryptionConfig, EncryptionMethod, MasterKeyConfig};
+
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("snap-recovery-encrypted").unwrap();
+            let key_path = root_dir.path().join("master.key");
+            std::fs::write(&key_path, hex::encode([1u8; 32])).unwrap();
+            let cfg = EncryptionConfig {
+                method: EncryptionMethod::Aes256Ctr,
+                master_key: MasterKeyConfig::File { path: key_path },
+                ..Default::default()
+            };
+            let key_manager =
+                Arc::new(DataKeyManager::open(&root_dir.path().join("keys"), &cfg).unwrap());
+            let snap_dir = root_dir.path().join("snap");
+            std::fs::create_dir_all(&snap_dir).unwrap();
+
+            let replica_id = 1;
+            let content = b"encrypted snapshot content".to_vec();
+            let snap_manager =
+                SnapManager::recovery(&snap_dir, SnapLimiter::default(), key_manager.clone())
+                    .await
+                    .unwrap();
+            let snap_id = build_snapshot(&snap_manager, replica_id, 1, content.clone()).await;
+            drop(snap_manager);
+
+            let snap_manager =
+                SnapManager::recovery(&snap_dir, SnapLimiter::default(), key_manager)
+                    .await
+                    .unwrap();
+            let snap = snap_manager.lock_snap(replica_id, &snap_id).unwrap();
+            assert_eq!(snap.meta.apply_state.as_ref().unwrap().index, 1);
+            // The meta describes the plaintext content, but it is encrypted on disk.
+            assert_eq!(snap.meta.files[0].size, content.len() as u64);
+            assert_eq!(snap.meta.files[0].crc32, crc32fast::hash(&content));
+            let data = std::fs::read(snap.base_dir.join(SNAP_DATA)).unwrap();
+            assert!(!data.windows(content.len()).any(|w| w == content.as_slice()));
+            drop(snap);
+            drop(snap_manager);
+
+            // The snapshots couldn't be recovered without the data keys.
+            let plaintext = Arc::new(DataKeyManager::plaintext());
+            assert!(
+                SnapManager::recovery(&snap_dir, SnapLimiter::default(), plaintext)
+                    .await
+                    .is_err()
+            );
+        });
+    }
+
     #[test]
     fn send_and_save_snapshot() {
         let owner = ExecutorOwner::new(1);
@@ -519,16 +811,29 @@ mod tests {
             std::fs::create_dir_all(&root_dir).unwrap();
 
             let replica_id: u64 = 1;
-            let snap_manager = SnapManager::recovery(&root_dir).await.unwrap();
+            let snap_manager = SnapManager::recovery(
+                &root_dir,
+                SnapLimiter::default(),
+                Arc::new(DataKeyManager::plaintext()),
+            )
+            .await
+            .unwrap();
 
             // Prepare snapshot
             let content = vec![1, 2, 3, 4, 5, 6, 7];
             let snap_id = build_snapshot(&snap_manager, replica_id, 0, content.clone()).await;
 
             // Send snapshot on leader side.
-            let snapshot_chunk_stream = send::send_snapshot(&snap_manager, replica_id, snap_id)
-                .await
-                .unwrap();
+            let snapshot_chunk_stream = send::send_snapshot(
+                &snap_manager,
+                replica_id,
+                snap_id,
+                vec![],
+                0,
+                SnapPriority::Normal,
+            )
+            .await
+            .unwrap();
 
             // Save snapshot on follower side.
             let new_snap_id =
@@ -556,7 +861,13 @@ mod tests {
             std::fs::create_dir_all(&root_dir).unwrap();
 
             let replica_id: u64 = 1;
-            let snap_manager = SnapManager::recovery(&root_dir).await.unwrap();
+            let snap_manager = SnapManager::recovery(
+                &root_dir,
+                SnapLimiter::default(),
+                Arc::new(DataKeyManager::plaintext()),
+            )
+            .await
+            .unwrap();
 
             // Prepare snapshot
             let content_1 = vec![1, 2, 3, 4, 5, 6, 7, 1];
@@ -572,9 +883,16 @@ mod tests {
                 .unwrap();
 
             // Send snapshot on leader side.
-            let snapshot_chunk_stream = send::send_snapshot(&snap_manager, replica_id, snap_id)
-                .await
-                .unwrap();
+            let snapshot_chunk_stream = send::send_snapshot(
+                &snap_manager,
+                replica_id,
+                snap_id,
+                vec![],
+                0,
+                SnapPriority::Normal,
+            )
+            .await
+            .unwrap();
 
             // Save snapshot on follower side.
             let new_snap_id =
@@ -598,6 +916,262 @@ mod tests {
         });
     }
 
+    #[test]
+    fn send_and_save_streaming_snapshot_with_resuming() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("download-streaming-snapshot").unwrap();
+            std::fs::create_dir_all(&root_dir).unwrap();
+
+            let replica_id: u64 = 1;
+            let snap_manager = SnapManager::recovery(
+                &root_dir,
+                SnapLimiter::default(),
+                Arc::new(DataKeyManager::plaintext()),
+            )
+            .await
+            .unwrap();
+
+            // Prepare snapshot
+            let key_values = (0..10u8)
+                .map(|i| (vec![i + 1], vec![i; 16]))
+                .collect::<Vec<_>>();
+            let builder: Box<dyn SnapshotBuilder> = Box::new(MemorySnapshotStream {
+                index: 1,
+                key_values: key_values.clone(),
+            });
+            let snap_id = create::create_snapshot(replica_id, &snap_manager, builder)
+                .await
+                .unwrap();
+            let snap = snap_manager.lock_snap(replica_id, &snap_id).unwrap();
+            assert!(snap.stream.is_some());
+            assert!(!std::fs::try_exists(&snap.base_dir).unwrap());
+            drop(snap);
+
+            // The first transfer is broken after receiving 3 chunks.
+            let mut start_keys = vec![];
+            let retrieve = |point: download::ResumePoint| {
+                let is_first = start_keys.is_empty();
+                start_keys.push(point.start_key.clone());
+                let snap_manager = snap_manager.clone();
+                let snap_id = snap_id.clone();
+                async move {
+                    let stream = send::send_snapshot(
+                        &snap_manager,
+                        replica_id,
+                        snap_id,
+                        point.start_key,
+                        point.start_file,
+                        SnapPriority::High,
+                    )
+                    .await?;
+                    let take = if is_first { 3 } else { usize::MAX };
+                    Ok::<_, crate::Error>(stream.take(take))
+                }
+            };
+            let new_snap_id =
+                download::save_resumable_snapshot(&snap_manager, replica_id + 1, retrieve)
+                    .await
+                    .unwrap();
+            assert_eq!(start_keys, vec![vec![], vec![3]]);
+
+            // Validate snapshot content.
+            let snap = snap_manager.lock_snap(replica_id + 1, &new_snap_id);
+            assert!(snap.is_some());
+            let snap = snap.unwrap();
+            assert!(snap.stream.is_none());
+            assert_eq!(snap.meta.apply_state.as_ref().unwrap().index, 1);
+            assert_eq!(snap.meta.files.len(), 1);
+
+            let data = snap.base_dir.join(SNAP_DATA).join("0.kv");
+            let received = kv_file::KvFileReader::open(snap_manager.key_manager(), &data)
+                .unwrap()
+                .collect::<Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(received, key_values);
+        });
+    }
+
+    #[test]
+    fn save_streaming_snapshot_with_mismatched_checksum() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("download-streaming-snapshot-mismatched").unwrap();
+            std::fs::create_dir_all(&root_dir).unwrap();
+
+            let replica_id: u64 = 1;
+            let snap_manager = SnapManager::recovery(
+                &root_dir,
+                SnapLimiter::default(),
+                Arc::new(DataKeyManager::plaintext()),
+            )
+            .await
+            .unwrap();
+
+            let key_values = (0..10u8)
+                .map(|i| (vec![i + 1], vec![i; 16]))
+                .collect::<Vec<_>>();
+            let builder: Box<dyn SnapshotBuilder> = Box::new(MemorySnapshotStream {
+                index: 1,
+                key_values,
+            });
+            let snap_id = create::create_snapshot(replica_id, &snap_manager, builder)
+                .await
+                .unwrap();
+
+            // The checksum in the trailer is not matched with the received key values.
+            let corrupted = RAFTGROUP_SNAPSHOT_CORRUPTED_TOTAL.install.get();
+            let stream = send::send_snapshot(
+                &snap_manager,
+                replica_id,
+                snap_id,
+                vec![],
+                0,
+                SnapPriority::Normal,
+            )
+            .await
+            .unwrap()
+            .map(|mut chunk| {
+                if let Ok(SnapshotChunk {
+                    value: Some(snapshot_chunk::Value::Meta(meta)),
+                }) = &mut chunk
+                {
+                    meta.stream_crc32 ^= 0xFF;
+                }
+                chunk
+            });
+            let result = download::save_snapshot(&snap_manager, replica_id + 1, stream).await;
+            assert!(matches!(result, Err(crate::Error::InvalidData(_))));
+            assert!(RAFTGROUP_SNAPSHOT_CORRUPTED_TOTAL.install.get() > corrupted);
+        });
+    }
+
+    #[test]
+    fn stage_snapshot_files_if_too_many_streams() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("snap-too-many-streams").unwrap();
+            std::fs::create_dir_all(&root_dir).unwrap();
+
+            let limiter = SnapLimiter::new(&crate::RaftConfig {
+                snap_max_concurrent_streams: 1,
+                ..Default::default()
+            });
+            let snap_manager =
+                SnapManager::recovery(&root_dir, limiter, Arc::new(DataKeyManager::plaintext()))
+                    .await
+                    .unwrap();
+
+            let replica_id: u64 = 1;
+            let builder = MemorySnapshotStream {
+                index: 1,
+                key_values: vec![(vec![1], vec![1])],
+            };
+            let create = |builder: MemorySnapshotStream| {
+                let snap_manager = snap_manager.clone();
+                async move {
+                    let builder: Box<dyn SnapshotBuilder> = Box::new(builder);
+                    let snap_id = create::create_snapshot(replica_id, &snap_manager, builder)
+                        .await
+                        .unwrap();
+                    snap_manager.lock_snap(replica_id, &snap_id).unwrap()
+                }
+            };
+
+            let snap = create(builder.clone()).await;
+            assert!(snap.stream.is_some());
+            drop(snap);
+
+            // The second snapshot is staged, since the first stream is not recycled.
+            let snap = create(builder.clone()).await;
+            assert!(snap.stream.is_none());
+            assert_eq!(snap.meta.files.len(), 1);
+            drop(snap);
+
+            snap_manager.recycle_snapshots(replica_id, RecycleSnapMode::All);
+            let snap = create(builder).await;
+            assert!(snap.stream.is_some());
+        });
+    }
+
+    #[test]
+    fn save_snapshot_with_corrupted_file() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let root_dir = TempDir::new("download-snapshot-corrupted-file").unwrap();
+            std::fs::create_dir_all(&root_dir).unwrap();
+
+            let replica_id: u64 = 1;
+            let snap_manager = SnapManager::recovery(
+                &root_dir,
+                SnapLimiter::default(),
+                Arc::new(DataKeyManager::plaintext()),
+            )
+            .await
+            .unwrap();
+
+            let content_1 = vec![1, 2, 3, 4, 5, 6, 7, 1];
+            let content_2 = vec![1, 2, 3, 4, 5, 6, 7, 2];
+            let builder: Box<dyn SnapshotBuilder> = Box::new(MultiFilesSnapshotBuilder {
+                index: 1,
+                content_1: content_1.clone(),
+                content_2: content_2.clone(),
+            });
+            let snap_id = create::create_snapshot(replica_id, &snap_manager, builder)
+                .await
+                .unwrap();
+
+            // The data of the second file is corrupted in the first transfer.
+            let corrupted = RAFTGROUP_SNAPSHOT_CORRUPTED_TOTAL.file.get();
+            let mut start_files = vec![];
+            let retrieve = |point: download::ResumePoint| {
+                let is_first = start_files.is_empty();
+                start_files.push(point.start_file);
+                let snap_manager = snap_manager.clone();
+                let snap_id = snap_id.clone();
+                async move {
+                    let stream = send::send_snapshot(
+                        &snap_manager,
+                        replica_id,
+                        snap_id,
+                        point.start_key,
+                        point.start_file,
+                        SnapPriority::Normal,
+                    )
+                    .await?;
+                    let mut num_data_chunks = 0;
+                    let stream = stream.map(move |mut chunk| {
+                        if let Ok(SnapshotChunk {
+                            value: Some(snapshot_chunk::Value::ChunkData(data)),
+                        }) = &mut chunk
+                        {
+                            num_data_chunks += 1;
+                            if is_first && num_data_chunks == 2 {
+                                data[0] ^= 0xFF;
+                            }
+                        }
+                        chunk
+                    });
+                    Ok::<_, crate::Error>(stream)
+                }
+            };
+            let new_snap_id =
+                download::save_resumable_snapshot(&snap_manager, replica_id + 1, retrieve)
+                    .await
+                    .unwrap();
+            assert_eq!(start_files, vec![0, 1]);
+            assert!(RAFTGROUP_SNAPSHOT_CORRUPTED_TOTAL.file.get() > corrupted);
+
+            let snap = snap_manager
+                .lock_snap(replica_id + 1, &new_snap_id)
+                .unwrap();
+            assert_eq!(snap.meta.files.len(), 2);
+            let data = snap.base_dir.join(SNAP_DATA);
+            assert_eq!(std::fs::read(data.join("1")).unwrap(), content_1);
+            assert_eq!(std::fs::read(data.join("2")).unwrap(), content_2);
+        });
+    }
+
     #[test]
     fn recycle() {
         let owner = ExecutorOwner::new(1);
@@ -646,7 +1220,7 @@ mod tests {
             let snap_meta = SnapshotMeta {
                 apply_state: Some(ApplyState::default()),
                 group_desc: Some(GroupDesc::default()),
-                files: vec![],
+                ..Default::default()
             };
 
             // Install snap in reversed orders.
@@ -672,7 +1246,7 @@ mod tests {
             let snap_meta = SnapshotMeta {
                 apply_state: Some(ApplyState::default()),
                 group_desc: Some(GroupDesc::default()),
-                files: vec![],
+                ..Default::default()
             };
             snap_mgr.recycle_snapshots(replica_id, RecycleSnapMode::RequiredIndex(123123));
             snap_mgr.install(replica_id, &snap_dir_1, &snap_meta);
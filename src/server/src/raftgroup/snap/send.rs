@@ -13,34 +13,57 @@
 // limitations under the License.
 use std::{
     ffi::OsStr,
-    fs::File,
+    future::Future,
     io::Read,
     os::unix::ffi::OsStrExt,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
 };
 
+use prost::Message;
 use tracing::debug;
 
-use super::{SnapManager, SnapshotGuard};
+use super::{
+    limiter::{SnapLimiter, SnapPriority, TransferPermit},
+    SnapManager, SnapshotGuard,
+};
 use crate::{
+    encryption::FileReader,
     raftgroup::metrics::*,
-    serverpb::v1::{snapshot_chunk, SnapshotChunk},
+    serverpb::v1::{snapshot_chunk, SnapshotChunk, SnapshotKeyValues, SnapshotMeta},
     Error, Result,
 };
 
 type SnapResult = Result<SnapshotChunk, tonic::Status>;
 
+/// The limit bytes of key values of each streaming snapshot chunk.
+const STREAM_CHUNK_SIZE: usize = 256 * 1024;
+
 pub struct SnapshotChunkStream {
     info: SnapshotGuard,
-    file: Option<File>,
+    file: Option<FileReader>,
     file_index: usize,
+    /// The last sent key of the streaming snapshot.
+    start_key: Vec<u8>,
+    finished: bool,
+
+    limiter: SnapLimiter,
+    /// Wait for the rate limit before sending the next chunk.
+    delay: Option<Pin<Box<dyn Future<Output = ()> + Send>>>,
+    _permit: TransferPermit,
 }
 
+/// Send the snapshot to remote. The streaming snapshot is resumed after `start_key` if it is not
+/// empty, and the others are resent from the file `start_file`. It waits until the number of
+/// concurrent sending snapshots is under the limit.
 pub async fn send_snapshot(
     snap_mgr: &SnapManager,
     replica_id: u64,
     snapshot_id: Vec<u8>,
+    start_key: Vec<u8>,
+    start_file: usize,
+    priority: SnapPriority,
 ) -> Result<SnapshotChunkStream> {
     let snapshot_info = match snap_mgr.lock_snap(replica_id, &snapshot_id) {
         Some(snap_info) => snap_info,
@@ -49,21 +72,61 @@ pub async fn send_snapshot(
         }
     };
 
+    if !start_key.is_empty() && snapshot_info.stream.is_none() {
+        return Err(Error::InvalidArgument(
+            "only the streaming snapshot could be resumed".to_string(),
+        ));
+    }
+    if start_file > 0 && snapshot_info.stream.is_some() {
+        return Err(Error::InvalidArgument(
+            "the streaming snapshot couldn't be resent from a file".to_string(),
+        ));
+    }
+    if start_file > snapshot_info.meta.files.len() {
+        return Err(Error::InvalidArgument(format!(
+            "the start file {start_file} is out of range, the snapshot has {} files",
+            snapshot_info.meta.files.len()
+        )));
+    }
+
+    let limiter = snap_mgr.limiter().clone();
+    let permit = limiter.acquire_send(priority).await;
     RAFTGROUP_SEND_SNAPSHOT_TOTAL.inc();
-    Ok(SnapshotChunkStream::new(snapshot_info))
+    Ok(SnapshotChunkStream::new(
+        snapshot_info,
+        start_key,
+        start_file,
+        limiter,
+        permit,
+    ))
 }
 
 impl SnapshotChunkStream {
-    fn new(info: SnapshotGuard) -> Self {
+    fn new(
+        info: SnapshotGuard,
+        start_key: Vec<u8>,
+        start_file: usize,
+        limiter: SnapLimiter,
+        permit: TransferPermit,
+    ) -> Self {
         SnapshotChunkStream {
             info,
             file: None,
-            file_index: 0,
+            file_index: start_file,
+            start_key,
+            finished: false,
+            limiter,
+            delay: None,
+            _permit: permit,
         }
     }
 
     fn next_chunk(&mut self) -> Option<SnapResult> {
-        use std::{fs::OpenOptions, io::ErrorKind};
+        use std::io::ErrorKind;
+
+        if self.info.stream.is_some() {
+            return self.next_stream_chunk();
+        }
 
         match self.file.as_mut() {
             // Send snapshot file chunk.
@@ -101,8 +164,9 @@ impl SnapshotChunkStream {
                     file_meta.crc32,
                     file_meta.size
                 );
-                match OpenOptions::new().read(true).open(&path) {
-                    Ok(file) => self.file = Some(file),
+                // The file is decrypted, the transport is protected by the TLS if needed.
+                match self.info.manager.key_manager().open_file(&path) {
+                    Ok(file) => self.file = Some(FileReader::new(Arc::new(file))),
                     Err(err) => return Some(Err(err.into())),
                 }
                 let value = snapshot_chunk::Value::File(file_meta.to_owned());
@@ -118,12 +182,70 @@ impl SnapshotChunkStream {
             None => None,
         }
     }
+
+    fn next_stream_chunk(&mut self) -> Option<SnapResult> {
+        if self.finished {
+            return None;
+        }
+
+        let (key_values, crc32) = {
+            let mut stream = self.info.stream.as_ref().unwrap().lock().unwrap();
+            match stream.next_chunk(&self.start_key, STREAM_CHUNK_SIZE) {
+                Ok(key_values) => (key_values, stream.crc32()),
+                Err(err) => return Some(Err(err.into())),
+            }
+        };
+
+        // All key values are send, send snapshot meta with the checksum of them.
+        if key_values.is_empty() {
+            let stream_crc32 = crc32.expect("all key values of the streaming snapshot are read");
+            debug!(
+                "send streaming snapshot {:?} meta to remote, crc32 {stream_crc32}",
+                self.info.snapshot_id
+            );
+            self.finished = true;
+            let meta = SnapshotMeta {
+                stream_crc32,
+                ..self.info.meta.clone()
+            };
+            let value = snapshot_chunk::Value::Meta(meta);
+            return Some(Ok(SnapshotChunk { value: Some(value) }));
+        }
+
+        let mut chunk = SnapshotKeyValues::default();
+        let mut num_bytes = 0;
+        for (key, value) in key_values {
+            num_bytes += key.len() + value.len();
+            chunk.keys.push(key);
+            chunk.values.push(value);
+        }
+        chunk.crc32 = super::key_values_crc32(&chunk.keys, &chunk.values);
+        self.start_key = chunk.keys.last().cloned().unwrap();
+        RAFTGROUP_SEND_SNAPSHOT_BYTES_TOTAL.inc_by(num_bytes as u64);
+        let value = snapshot_chunk::Value::KeyValues(chunk);
+        Some(Ok(SnapshotChunk { value: Some(value) }))
+    }
 }
 
 impl futures::Stream for SnapshotChunkStream {
     type Item = SnapResult;
 
-    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        Poll::Ready(self.get_mut().next_chunk())
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if let Some(delay) = this.delay.as_mut() {
+            if delay.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+            this.delay = None;
+        }
+
+        let chunk = this.next_chunk();
+        if let Some(Ok(chunk)) = &chunk {
+            let delay = this.limiter.consume_send(chunk.encoded_len());
+            if !delay.is_zero() {
+                this.delay = Some(Box::pin(crate::runtime::time::sleep(delay)));
+            }
+        }
+        Poll::Ready(chunk)
     }
 }
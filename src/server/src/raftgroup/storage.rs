@@ -21,10 +21,10 @@ use std::{
 use engula_api::server::v1::*;
 use prost::Message;
 use raft::{prelude::*, GetEntriesContext, RaftState};
-use raft_engine::{Command, Engine, LogBatch, MessageExt};
+use raft_engine::{Command, LogBatch, MessageExt};
 use tracing::{debug, error, info};
 
-use super::{node::WriteTask, snap::SnapManager, RaftConfig};
+use super::{node::WriteTask, snap::SnapManager, RaftConfig, RaftEngine};
 use crate::{
     serverpb::v1::{EntryId, EvalResult, RaftLocalState},
     Result,
@@ -63,7 +63,7 @@ pub struct Storage {
     pub create_snapshot: Cell<bool>,
     pub is_creating_snapshot: Cell<bool>,
     snap_mgr: SnapManager,
-    engine: Arc<Engine>,
+    engine: Arc<RaftEngine>,
 }
 
 impl Storage {
@@ -72,7 +72,7 @@ impl Storage {
         replica_id: u64,
         applied_index: u64,
         conf_state: ConfState,
-        engine: Arc<Engine>,
+        engine: Arc<RaftEngine>,
         snap_mgr: SnapManager,
     ) -> Result<Self> {
         let hard_state = engine
@@ -510,7 +510,7 @@ impl EntryCache {
 #[allow(clippy::field_reassign_with_default)]
 pub async fn write_initial_state(
     cfg: &RaftConfig,
-    engine: &Engine,
+    engine: &RaftEngine,
     replica_id: u64,
     mut replicas: Vec<ReplicaDesc>,
     initial_eval_results: Vec<EvalResult>,
@@ -584,28 +584,96 @@ pub async fn write_initial_state(
         hard_state.term = 1;
     }
 
+    debug!(
+        "write initial state of {replica_id}, total {} initial entries",
+        initial_entries.len()
+    );
+
+    write_states(
+        engine,
+        replica_id,
+        &initial_entries,
+        &hard_state,
+        &local_state,
+    )
+}
+
+/// Read the log entries in `[low, high)` of the replica, the total size is limited by `max_size`
+/// but at least one entry is returned. An error is returned if some entries are compacted.
+pub fn read_entries(
+    engine: &RaftEngine,
+    replica_id: u64,
+    low: u64,
+    high: u64,
+    max_size: usize,
+) -> Result<Vec<Entry>> {
+    let mut entries = vec![];
+    engine.fetch_entries_to::<MessageExtTyped>(
+        replica_id,
+        low,
+        high,
+        Some(max_size),
+        &mut entries,
+    )?;
+    Ok(entries)
+}
+
+/// Rewrite raft states of an existing replica, all logs will be dropped and the replica will be
+/// restarted from the `applied` entry.
+///
+/// It is used by unsafe recovery, the `ConfState` is rebuilt from the group descriptor when the
+/// replica is opened again, so the caller should persist the new descriptor before it.
+#[allow(clippy::field_reassign_with_default)]
+pub async fn write_recovered_state(
+    engine: &RaftEngine,
+    replica_id: u64,
+    applied: EntryId,
+) -> Result<()> {
+    let prev_hard_state = engine
+        .get_message::<HardState>(replica_id, keys::HARD_STATE_KEY)?
+        .unwrap_or_default();
+
+    // The term must not go backwards, otherwise the replica might vote twice in the same term.
+    let mut hard_state = HardState::default();
+    hard_state.term = prev_hard_state.term.max(applied.term);
+    hard_state.commit = applied.index;
+    let local_state = RaftLocalState {
+        replica_id,
+        last_truncated: Some(applied.clone()),
+    };
+
+    info!(
+        "rewrite raft state of {replica_id}, truncated {applied:?}, term {}",
+        hard_state.term
+    );
+
+    write_states(engine, replica_id, &[], &hard_state, &local_state)
+}
+
+fn write_states(
+    engine: &RaftEngine,
+    replica_id: u64,
+    entries: &[Entry],
+    hard_state: &HardState,
+    local_state: &RaftLocalState,
+) -> Result<()> {
     let mut batch = LogBatch::default();
     batch.add_command(replica_id, Command::Clean);
     batch
-        .add_entries::<MessageExtTyped>(replica_id, &initial_entries)
+        .add_entries::<MessageExtTyped>(replica_id, entries)
         .unwrap();
     batch
-        .put_message(replica_id, keys::HARD_STATE_KEY.to_owned(), &hard_state)
+        .put_message(replica_id, keys::HARD_STATE_KEY.to_owned(), hard_state)
         .unwrap();
     batch
-        .put_message(replica_id, keys::LOCAL_STATE_KEY.to_owned(), &local_state)
+        .put_message(replica_id, keys::LOCAL_STATE_KEY.to_owned(), local_state)
         .unwrap();
 
-    debug!(
-        "write initial state of {replica_id}, total {} initial entries",
-        initial_entries.len()
-    );
-
     engine.write(&mut batch, true)?;
     Ok(())
 }
 
-pub async fn destory(engine: &Engine, replica_id: u64) -> Result<()> {
+pub async fn destory(engine: &RaftEngine, replica_id: u64) -> Result<()> {
     let mut batch = LogBatch::default();
     batch.add_command(replica_id, Command::Clean);
     engine.write(&mut batch, true)?;
@@ -628,7 +696,7 @@ fn other_store_error(e: raft_engine::Error) -> raft::Error {
 mod tests {
     use std::sync::Arc;
 
-    use raft_engine::{Config, Engine};
+    use raft_engine::Config;
     use tempdir::TempDir;
     use tracing::info;
 
@@ -663,7 +731,11 @@ mod tests {
         }
     }
 
-    async fn insert_entries(engine: Arc<Engine>, storage: &mut Storage, entries: Vec<(u64, u64)>) {
+    async fn insert_entries(
+        engine: Arc<RaftEngine>,
+        storage: &mut Storage,
+        entries: Vec<(u64, u64)>,
+    ) {
         let entries: Vec<Entry> = entries
             .into_iter()
             .map(|(idx, term)| {
@@ -731,7 +803,7 @@ mod tests {
             dir: dir.path().join("db").to_str().unwrap().to_owned(),
             ..Default::default()
         };
-        let engine = Arc::new(Engine::open(cfg).unwrap());
+        let engine = Arc::new(RaftEngine::open_with_file_system(cfg, Arc::default()).unwrap());
 
         write_initial_state(&RaftConfig::default(), engine.as_ref(), 1, vec![], vec![])
             .await
@@ -783,7 +855,7 @@ mod tests {
             dir: dir.path().join("db").to_str().unwrap().to_owned(),
             ..Default::default()
         };
-        let engine = Arc::new(Engine::open(cfg).unwrap());
+        let engine = Arc::new(RaftEngine::open_with_file_system(cfg, Arc::default()).unwrap());
 
         write_initial_state(&RaftConfig::default(), engine.as_ref(), 1, vec![], vec![])
             .await
@@ -850,7 +922,7 @@ mod tests {
             dir: dir.path().join("db").to_str().unwrap().to_owned(),
             ..Default::default()
         };
-        let engine = Arc::new(Engine::open(cfg).unwrap());
+        let engine = Arc::new(RaftEngine::open_with_file_system(cfg, Arc::default()).unwrap());
 
         write_initial_state(&RaftConfig::default(), engine.as_ref(), 1, vec![], vec![])
             .await
@@ -933,6 +1005,66 @@ mod tests {
         });
     }
 
+    #[test]
+    fn open_raft_storage_after_writing_recovered_state() {
+        let owner = ExecutorOwner::new(1);
+        owner.executor().block_on(async move {
+            let dir = TempDir::new("raft-storage-recovered-state").unwrap();
+
+            let cfg = Config {
+                dir: dir.path().join("db").to_str().unwrap().to_owned(),
+                ..Default::default()
+            };
+            let engine = Arc::new(RaftEngine::open_with_file_system(cfg, Arc::default()).unwrap());
+
+            write_initial_state(&RaftConfig::default(), engine.as_ref(), 1, vec![], vec![])
+                .await
+                .unwrap();
+
+            let snap_mgr = SnapManager::new(dir.path().join("snap"));
+            let mut storage = Storage::open(
+                &RaftConfig::default(),
+                1,
+                0,
+                ConfState::default(),
+                engine.clone(),
+                snap_mgr.clone(),
+            )
+            .await
+            .unwrap();
+            insert_entries(engine.clone(), &mut storage, mocked_entries(None)).await;
+            drop(storage);
+
+            // Recover from an applied entry in term 2, all entries should be dropped.
+            let (applied_index, applied_term) = *mocked_entries(Some(2)).last().unwrap();
+            let applied = EntryId {
+                index: applied_index,
+                term: applied_term,
+            };
+            write_recovered_state(engine.as_ref(), 1, applied)
+                .await
+                .unwrap();
+
+            let storage = Storage::open(
+                &RaftConfig::default(),
+                1,
+                applied_index,
+                ConfState::default(),
+                engine.clone(),
+                snap_mgr,
+            )
+            .await
+            .unwrap();
+            assert_eq!(storage.hard_state.commit, applied_index);
+            assert_eq!(storage.hard_state.vote, 0);
+            assert!(storage.hard_state.term >= applied_term);
+            assert_eq!(storage.truncated_index(), applied_index);
+            assert_eq!(storage.truncated_term(), applied_term);
+            assert_eq!(storage.first_index, applied_index + 1);
+            assert_eq!(storage.last_index, applied_index);
+        });
+    }
+
     #[test]
     fn fetch_entries_from_both_engine_and_cache_should_be_continuously() {
         let owner = ExecutorOwner::new(1);
@@ -943,7 +1075,7 @@ mod tests {
                 dir: dir.path().join("db").to_str().unwrap().to_owned(),
                 ..Default::default()
             };
-            let engine = Arc::new(Engine::open(cfg).unwrap());
+            let engine = Arc::new(RaftEngine::open_with_file_system(cfg, Arc::default()).unwrap());
 
             write_initial_state(&RaftConfig::default(), engine.as_ref(), 1, vec![], vec![])
                 .await
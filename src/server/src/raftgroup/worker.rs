@@ -27,7 +27,7 @@ use futures::{
     FutureExt, SinkExt, StreamExt,
 };
 use raft::{prelude::*, SoftState, StateRole};
-use raft_engine::{Engine, LogBatch};
+use raft_engine::LogBatch;
 use tokio::time::{interval, Interval, MissedTickBehavior};
 use tracing::{debug, info, warn};
 
@@ -39,7 +39,7 @@ use super::{
     monitor::WorkerPerfContext,
     node::RaftNode,
     snap::{apply::apply_snapshot, RecycleSnapMode, SnapManager},
-    RaftManager, ReadPolicy,
+    RaftEngine, RaftManager, ReadPolicy,
 };
 use crate::{
     raftgroup::monitor::record_perf_point,
@@ -175,8 +175,12 @@ impl<'a> super::node::AdvanceTemplate for AdvanceImpl<'a> {
     }
 
     #[inline]
-    fn apply_snapshot<M: StateMachine>(&mut self, applier: &mut Applier<M>, snapshot: &Snapshot) {
-        apply_snapshot(self.replica_id, self.snap_mgr, applier, snapshot);
+    fn apply_snapshot<M: StateMachine>(
+        &mut self,
+        applier: &mut Applier<M>,
+        snapshot: &Snapshot,
+    ) -> Result<()> {
+        apply_snapshot(self.replica_id, self.snap_mgr, applier, snapshot)
     }
 }
 
@@ -196,7 +200,7 @@ where
     channels: HashMap<u64, Channel>,
     trans_mgr: ChannelManager,
     snap_mgr: SnapManager,
-    engine: Arc<Engine>,
+    engine: Arc<RaftEngine>,
     observer: Box<dyn StateObserver>,
     replica_cache: ReplicaCache,
 
@@ -355,7 +359,7 @@ where
         };
         if let Some(write_task) = self
             .raft_node
-            .advance(&mut ctx.perf_ctx.advance, &mut template)
+            .advance(&mut ctx.perf_ctx.advance, &mut template)?
         {
             let mut batch = LogBatch::default();
             self.raft_node
@@ -468,8 +472,14 @@ where
             if msg.get_msg_type() == MessageType::MsgSnapshot {
                 // TODO(walter) In order to avoid useless downloads, should check whether this
                 // snapshot will be accept.
+                let replication_factor = self
+                    .raft_node
+                    .mut_state_machine()
+                    .descriptor()
+                    .replication_factor;
                 super::snap::dispatch_downloading_snap_task(
                     self.desc.id,
+                    replication_factor,
                     self.request_sender.clone(),
                     self.snap_mgr.clone(),
                     self.trans_mgr.clone(),
@@ -528,6 +538,9 @@ where
         record_latency!(&RAFTGROUP_WORKER_COMPACT_LOG_DURATION_SECONDS);
         record_perf_point(&mut ctx.perf_ctx.compact_log);
         let mut to = self.raft_node.mut_state_machine().flushed_index();
+        if let Some(archived_index) = self.raft_node.mut_state_machine().archived_index() {
+            to = std::cmp::min(archived_index + 1, to);
+        }
 
         let status = self.raft_node.raft_status();
         if status.ss.raft_state == StateRole::Leader {
@@ -0,0 +1,125 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+
+use engula_api::server::v1::{NodeDesc, NodeLocality};
+
+/// The diversity of a set of nodes, the minimum and the sum of the pairwise distances. A larger
+/// value means the nodes are spread more widely, and the minimum distance is compared first.
+pub type Diversity = (usize, usize);
+
+/// The distance between two nodes, it is the number of locality tiers below the outermost tier
+/// they differ, plus one since they are different nodes. So nodes in different regions are the
+/// farthest, and nodes without locality labels are all at distance 1.
+pub fn distance(a: &NodeDesc, b: &NodeDesc) -> usize {
+    if a.id == b.id {
+        return 0;
+    }
+    let default = NodeLocality::default();
+    let a = a.locality.as_ref().unwrap_or(&default);
+    let b = b.locality.as_ref().unwrap_or(&default);
+    let tiers = [
+        (&a.region, &b.region),
+        (&a.zone, &b.zone),
+        (&a.rack, &b.rack),
+        (&a.host, &b.host),
+    ];
+    tiers
+        .iter()
+        .position(|(a, b)| a != b)
+        .map(|idx| tiers.len() - idx + 1)
+        .unwrap_or(1)
+}
+
+/// The diversity of the nodes which serving the replicas of a group.
+pub fn diversity(nodes: &[&NodeDesc]) -> Diversity {
+    let mut min = usize::MAX;
+    let mut sum = 0;
+    for (i, a) in nodes.iter().enumerate() {
+        for b in &nodes[i + 1..] {
+            let d = distance(a, b);
+            min = min.min(d);
+            sum += d;
+        }
+    }
+    (min, sum)
+}
+
+/// The locality diversity of the group nodes after moving the replica from the source node to
+/// the target node, `None` if the descriptor of some node is not found.
+pub fn diversity_after_move(
+    nodes: &HashMap<u64, NodeDesc>,
+    exist_nodes: &HashSet<u64>,
+    moving: Option<(u64, &NodeDesc)>,
+) -> Option<Diversity> {
+    let mut descs = Vec::with_capacity(exist_nodes.len());
+    for node_id in exist_nodes {
+        match moving {
+            Some((src, _)) if src == *node_id => continue,
+            _ => descs.push(nodes.get(node_id)?),
+        }
+    }
+    if let Some((_, target)) = moving {
+        descs.push(target);
+    }
+    Some(diversity(&descs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u64, region: &str, zone: &str, rack: &str, host: &str) -> NodeDesc {
+        NodeDesc {
+            id,
+            locality: Some(NodeLocality {
+                region: region.into(),
+                zone: zone.into(),
+                rack: rack.into(),
+                host: host.into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn locality_distance() {
+        let n1 = node(1, "r1", "z1", "k1", "h1");
+        assert_eq!(distance(&n1, &n1), 0);
+        assert_eq!(distance(&n1, &node(2, "r1", "z1", "k1", "h1")), 1);
+        assert_eq!(distance(&n1, &node(2, "r1", "z1", "k1", "h2")), 2);
+        assert_eq!(distance(&n1, &node(2, "r1", "z1", "k2", "h1")), 3);
+        assert_eq!(distance(&n1, &node(2, "r1", "z2", "k1", "h1")), 4);
+        assert_eq!(distance(&n1, &node(2, "r2", "z1", "k1", "h1")), 5);
+
+        let unlabeled = NodeDesc {
+            id: 3,
+            ..Default::default()
+        };
+        assert_eq!(distance(&unlabeled, &NodeDesc::default()), 1);
+    }
+
+    #[test]
+    fn locality_diversity() {
+        let n1 = node(1, "r1", "z1", "k1", "h1");
+        let n2 = node(2, "r1", "z1", "k1", "h2");
+        let n3 = node(3, "r1", "z1", "k2", "h3");
+        let n4 = node(4, "r1", "z2", "k3", "h4");
+        assert_eq!(diversity(&[&n1]), (usize::MAX, 0));
+        assert!(diversity(&[&n1, &n3, &n4]) > diversity(&[&n1, &n2, &n4]));
+        assert!(diversity(&[&n1, &n2, &n4]) > diversity(&[&n1, &n2, &n3]));
+    }
+}
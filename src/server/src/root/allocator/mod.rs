@@ -12,25 +12,40 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Arc;
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 
-use engula_api::server::v1::{GroupDesc, NodeDesc};
+use engula_api::server::v1::{GroupDesc, NodeDesc, NodeStatus};
 
 use self::{
-    policy_leader_cnt::LeaderCountPolicy, policy_replica_cnt::ReplicaCountPolicy,
-    policy_shard_cnt::ShardCountPolicy, source::NodeFilter,
+    policy_disk_usage::DiskUsagePolicy, policy_leader_cnt::LeaderCountPolicy,
+    policy_load::LoadPolicy, policy_placement::PlacementPolicy,
+    policy_replica_cnt::ReplicaCountPolicy, policy_shard_cnt::ShardCountPolicy, source::NodeFilter,
+};
+use super::{
+    diagnosis::{NodeScore, PlacementViolation, ProposedAction, ScheduleExplain},
+    metrics, OngoingStats, RootShared,
 };
-use super::{metrics, OngoingStats, RootShared};
-use crate::{constants::REPLICA_PER_GROUP, Result, RootConfig};
+use crate::{constants::REPLICA_PER_GROUP, serverpb::v1::ScheduleControl, Result, RootConfig};
 
 #[cfg(test)]
 mod sim_test;
 
+mod locality;
+mod placement;
+mod policy_disk_usage;
 mod policy_leader_cnt;
+mod policy_load;
+mod policy_placement;
 mod policy_replica_cnt;
 mod policy_shard_cnt;
+pub mod sim;
 mod source;
 
+pub use placement::Placement;
 pub use source::{AllocSource, SysAllocSource};
 
 #[derive(Clone, Debug)]
@@ -42,7 +57,10 @@ pub enum ReplicaRoleAction {
 #[derive(Clone, Debug)]
 pub enum GroupAction {
     Noop,
-    Add(usize),
+    Add {
+        count: usize,
+        replicas_per_group: usize,
+    },
     Remove(Vec<u64>),
 }
 
@@ -86,6 +104,112 @@ pub struct ReallocateShard {
     pub target_group: u64,
 }
 
+/// The policies which propose the actions.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Policy {
+    GroupCount,
+    Placement,
+    DiskUsage,
+    Load,
+    Locality,
+    ReplicaCount,
+    ShardCount,
+    LeaderCount,
+}
+
+impl Policy {
+    fn name(&self) -> &'static str {
+        match self {
+            Policy::GroupCount => "group_count",
+            Policy::Placement => "placement",
+            Policy::DiskUsage => "disk_usage",
+            Policy::Load => "load",
+            Policy::Locality => "locality",
+            Policy::ReplicaCount => "replica_count",
+            Policy::ShardCount => "shard_count",
+            Policy::LeaderCount => "leader_count",
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self {
+            Policy::GroupCount => "the number of groups doesn't match the cpus of nodes",
+            Policy::Placement => {
+                "the replica or leader doesn't follow the placement rules of its collections"
+            }
+            Policy::DiskUsage => "the disk usage of the source node exceeds the high watermark",
+            Policy::Load => "the load of the source node exceeds the mean by the threshold",
+            Policy::Locality => "the move improves the locality diversity of the group",
+            Policy::ReplicaCount => "the source node has more replicas than the mean",
+            Policy::ShardCount => {
+                "the source group has more shards than the mean, or its replication factor \
+                 doesn't match the collection"
+            }
+            Policy::LeaderCount => "the source node has more leaders than the mean",
+        }
+    }
+}
+
+/// The classes of balancing, each of them could be paused at runtime.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BalanceClass {
+    Group,
+    Replica,
+    Shard,
+    Leader,
+    Disk,
+    Load,
+}
+
+impl BalanceClass {
+    pub const ALL: [BalanceClass; 6] = [
+        BalanceClass::Group,
+        BalanceClass::Replica,
+        BalanceClass::Shard,
+        BalanceClass::Leader,
+        BalanceClass::Disk,
+        BalanceClass::Load,
+    ];
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BalanceClass::Group => "group",
+            BalanceClass::Replica => "replica",
+            BalanceClass::Shard => "shard",
+            BalanceClass::Leader => "leader",
+            BalanceClass::Disk => "disk",
+            BalanceClass::Load => "load",
+        }
+    }
+
+    pub fn is_paused(&self, control: &ScheduleControl) -> bool {
+        match self {
+            BalanceClass::Group => control.pause_group_balance,
+            BalanceClass::Replica => control.pause_replica_balance,
+            BalanceClass::Shard => control.pause_shard_balance,
+            BalanceClass::Leader => control.pause_leader_balance,
+            BalanceClass::Disk => control.pause_disk_balance,
+            BalanceClass::Load => control.pause_load_balance,
+        }
+    }
+
+    pub fn set_paused(&self, control: &mut ScheduleControl, paused: bool) {
+        let field = match self {
+            BalanceClass::Group => &mut control.pause_group_balance,
+            BalanceClass::Replica => &mut control.pause_replica_balance,
+            BalanceClass::Shard => &mut control.pause_shard_balance,
+            BalanceClass::Leader => &mut control.pause_leader_balance,
+            BalanceClass::Disk => &mut control.pause_disk_balance,
+            BalanceClass::Load => &mut control.pause_load_balance,
+        };
+        *field = paused;
+    }
+}
+
 #[derive(PartialEq, Eq, Debug)]
 enum BalanceStatus {
     Overfull,
@@ -98,6 +222,11 @@ pub struct Allocator<T: AllocSource> {
     alloc_source: Arc<T>,
     ongoing_stats: Arc<OngoingStats>,
     config: RootConfig,
+    /// The last time a leader or replica was moved by load, used to wait the load stats of nodes
+    /// to be refreshed before the next move.
+    load_balanced_at: Arc<Mutex<Option<Instant>>>,
+    /// The runtime controls, which are loaded from the root metadata.
+    control: Arc<Mutex<ScheduleControl>>,
 }
 
 impl<T: AllocSource> Allocator<T> {
@@ -106,87 +235,117 @@ impl<T: AllocSource> Allocator<T> {
             alloc_source,
             config,
             ongoing_stats,
+            load_balanced_at: Arc::default(),
+            control: Arc::default(),
         }
     }
 
+    pub fn schedule_control(&self) -> ScheduleControl {
+        self.control.lock().unwrap().to_owned()
+    }
+
+    pub fn set_schedule_control(&self, control: ScheduleControl) {
+        *self.control.lock().unwrap() = control;
+    }
+
     pub fn replicas_per_group(&self) -> usize {
         self.config.replicas_per_group
     }
 
     /// Compute group change action.
     pub async fn compute_group_action(&self) -> Result<GroupAction> {
-        if !self.config.enable_group_balance {
+        if !self.balance_enabled(BalanceClass::Group) {
             return Ok(GroupAction::Noop);
         }
 
         self.alloc_source.refresh_all().await?;
 
-        if self.alloc_source.nodes(NodeFilter::NotDecommissioned).len()
-            < self.config.replicas_per_group
-        {
-            // group allocator start work after node_count > replicas_per_group.
-            return Ok(GroupAction::Noop);
+        let replicas_per_group = self.config.replicas_per_group;
+        let total_nodes = self.alloc_source.nodes(NodeFilter::NotDecommissioned).len();
+        // group allocator start work after node_count > replicas_per_group.
+        if total_nodes >= replicas_per_group {
+            let desired_groups = self.desired_groups(replicas_per_group);
+            let current_groups = self.current_groups(replicas_per_group);
+            match current_groups.cmp(&desired_groups) {
+                std::cmp::Ordering::Less => {
+                    // it happend when:
+                    // - new join node
+                    // - increase cpu quota for exist node(e.g. via cgroup)
+                    // - increase replica_num configuration
+                    return Ok(GroupAction::Add {
+                        count: desired_groups - current_groups,
+                        replicas_per_group,
+                    });
+                }
+                std::cmp::Ordering::Greater => {
+                    // it happens when:
+                    //  - joined node exit
+                    //  - decrease cpu quota for exist node(e.g. via cgroup)
+                    //  - decrease replica_num configuration
+                    let want_remove = current_groups - desired_groups;
+                    return Ok(GroupAction::Remove(
+                        self.preferred_remove_groups(want_remove),
+                    ));
+                }
+                std::cmp::Ordering::Equal => {}
+            }
         }
 
-        let desired_groups = self.desired_groups(self.config.replicas_per_group);
-        Ok(match self.current_groups().cmp(&desired_groups) {
-            std::cmp::Ordering::Less => {
-                // it happend when:
-                // - new join node
-                // - increase cpu quota for exist node(e.g. via cgroup)
-                // - increase replica_num configuration
-                GroupAction::Add(desired_groups - self.current_groups())
+        // The collections whose replication factor differs from the default one need their own
+        // groups, and at least one group per `replicas` nodes is kept to spread the leaders.
+        let other_replicas = self
+            .alloc_source
+            .collection_replicas()
+            .into_values()
+            .filter(|r| *r != 0 && *r != replicas_per_group && *r <= total_nodes)
+            .collect::<BTreeSet<_>>();
+        for replicas in other_replicas {
+            let desired_groups = std::cmp::max(total_nodes / replicas, 1);
+            let current_groups = self.current_groups(replicas);
+            if current_groups < desired_groups {
+                return Ok(GroupAction::Add {
+                    count: desired_groups - current_groups,
+                    replicas_per_group: replicas,
+                });
             }
-            std::cmp::Ordering::Greater => {
-                // it happens when:
-                //  - joined node exit
-                //  - decrease cpu quota for exist node(e.g. via cgroup)
-                //  - decrease replica_num configuration
-                let want_remove = self.current_groups() - desired_groups;
-                GroupAction::Remove(self.preferred_remove_groups(want_remove))
-            }
-            std::cmp::Ordering::Equal => GroupAction::Noop,
-        })
+        }
+        Ok(GroupAction::Noop)
     }
 
     /// Compute replica change action.
     pub async fn compute_replica_action(&self) -> Result<Vec<ReplicaAction>> {
-        if !self.config.enable_replica_balance {
+        if !self.balance_enabled(BalanceClass::Replica) {
             return Ok(vec![]);
         }
 
         // compute_group_action refreshed.
         // self.alloc_source.refresh_all().await?;
 
-        // TODO: try qps rebalance.
-
-        // try replica-count rebalance.
-        let actions =
-            ReplicaCountPolicy::with(self.alloc_source.to_owned(), self.ongoing_stats.to_owned())
-                .compute_balance()?;
-        if !actions.is_empty() {
-            return Ok(actions);
+        match self.propose_replica_action()? {
+            Some((policy, actions)) => {
+                if policy == Policy::Load {
+                    self.mark_load_balanced();
+                }
+                Ok(actions)
+            }
+            None => Ok(Vec::new()),
         }
-
-        Ok(Vec::new())
     }
 
     pub async fn compute_shard_action(&self) -> Result<Vec<ShardAction>> {
-        if !self.config.enable_shard_balance {
+        if !self.balance_enabled(BalanceClass::Shard) {
             return Ok(vec![]);
         }
 
         // always follow comput_replica_role_action() so no need refresh
         // self.alloc_source.refresh_all().await?;
 
-        if self.alloc_source.nodes(NodeFilter::All).len() >= self.config.replicas_per_group {
-            let actions = ShardCountPolicy::with(self.alloc_source.to_owned()).compute_balance()?;
-            if !actions.is_empty() {
-                metrics::RECONCILE_ALREADY_BALANCED_INFO
-                    .group_shard_count
-                    .set(0);
-                return Ok(actions);
-            }
+        let actions = self.propose_shard_action()?;
+        if !actions.is_empty() {
+            metrics::RECONCILE_ALREADY_BALANCED_INFO
+                .group_shard_count
+                .set(0);
+            return Ok(actions);
         }
         metrics::RECONCILE_ALREADY_BALANCED_INFO
             .group_shard_count
@@ -202,31 +361,318 @@ impl<T: AllocSource> Allocator<T> {
     ) -> Result<Vec<NodeDesc>> {
         self.alloc_source.refresh_all().await?;
 
-        ReplicaCountPolicy::with(self.alloc_source.to_owned(), self.ongoing_stats.to_owned())
-            .allocate_group_replica(existing_replica_nodes, wanted_count)
+        ReplicaCountPolicy::with(
+            self.alloc_source.to_owned(),
+            self.ongoing_stats.to_owned(),
+            self.config.disk_high_watermark,
+        )
+        .allocate_group_replica(
+            existing_replica_nodes,
+            wanted_count,
+            &Placement::default(),
+        )
+    }
+
+    /// Allocate new replicas for an existing group, the placement constraints of the group are
+    /// respected.
+    pub async fn allocate_replica_for_group(
+        &self,
+        group_id: u64,
+        existing_replica_nodes: Vec<u64>,
+        wanted_count: usize,
+    ) -> Result<Vec<NodeDesc>> {
+        self.alloc_source.refresh_all().await?;
+
+        let placement = self
+            .alloc_source
+            .group_placements()
+            .remove(&group_id)
+            .unwrap_or_default();
+        ReplicaCountPolicy::with(
+            self.alloc_source.to_owned(),
+            self.ongoing_stats.to_owned(),
+            self.config.disk_high_watermark,
+        )
+        .allocate_group_replica(existing_replica_nodes, wanted_count, &placement)
+    }
+
+    /// The replicas and leaders which violate the placement rules of their groups.
+    pub async fn placement_violations(&self) -> Result<Vec<PlacementViolation>> {
+        self.alloc_source.refresh_all().await?;
+
+        Ok(self.placement_policy().violations())
     }
 
-    /// Find a group to place shard.
-    pub async fn place_group_for_shard(&self, n: usize) -> Result<Vec<GroupDesc>> {
+    /// Find a group whose replication factor is `replicas` to place shard, 0 means the default
+    /// replicas per group.
+    pub async fn place_group_for_shard(&self, n: usize, replicas: usize) -> Result<Vec<GroupDesc>> {
         self.alloc_source.refresh_all().await?;
 
-        ShardCountPolicy::with(self.alloc_source.to_owned()).allocate_shard(n)
+        ShardCountPolicy::with(self.alloc_source.to_owned(), self.config.replicas_per_group)
+            .allocate_shard(n, replicas)
     }
 
     pub async fn compute_leader_action(&self) -> Result<Vec<LeaderAction>> {
-        if !self.config.enable_leader_balance {
+        if !self.balance_enabled(BalanceClass::Leader) {
             return Ok(vec![]);
         }
         // self.alloc_source.refresh_all().await?;
+        match self.propose_leader_action()? {
+            Some((policy, action)) => {
+                if policy == Policy::Load {
+                    self.mark_load_balanced();
+                }
+                Ok(vec![action])
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Compute the actions against the current state without executing them, along with the
+    /// policies proposing them and the scores of nodes.
+    pub async fn explain(&self) -> Result<ScheduleExplain> {
+        let mut actions = Vec::new();
+        // compute_group_action refreshes the alloc source.
+        match self.compute_group_action().await? {
+            GroupAction::Noop => {}
+            GroupAction::Add {
+                count,
+                replicas_per_group,
+            } => actions.push(ProposedAction {
+                kind: "group".into(),
+                policy: Policy::GroupCount.name().into(),
+                action: format!("add {count} groups with {replicas_per_group} replicas"),
+                reason: Policy::GroupCount.reason().into(),
+                ..Default::default()
+            }),
+            GroupAction::Remove(nodes) => actions.push(ProposedAction {
+                kind: "group".into(),
+                policy: Policy::GroupCount.name().into(),
+                action: format!("remove groups from nodes {nodes:?}"),
+                reason: Policy::GroupCount.reason().into(),
+                ..Default::default()
+            }),
+        }
+
+        let scores = self.node_scores();
+        let score_of = |node_id: u64| scores.iter().find(|s| s.node == node_id).cloned();
+        if self.balance_enabled(BalanceClass::Replica) {
+            if let Some((policy, replica_actions)) = self.propose_replica_action()? {
+                for ReplicaAction::Migrate(action) in replica_actions {
+                    actions.push(ProposedAction {
+                        kind: "replica".into(),
+                        policy: policy.name().into(),
+                        action: format!(
+                            "move replica {} of group {} from node {} to node {}",
+                            action.source_replica,
+                            action.group,
+                            action.source_node,
+                            action.target_node.id
+                        ),
+                        reason: policy.reason().into(),
+                        source: score_of(action.source_node),
+                        target: score_of(action.target_node.id),
+                    });
+                }
+            }
+        }
+        if self.balance_enabled(BalanceClass::Shard) {
+            let groups = self.alloc_source.groups();
+            let shard_count = |group_id: u64| groups.get(&group_id).map(|g| g.shards.len());
+            for ShardAction::Migrate(action) in self.propose_shard_action()? {
+                actions.push(ProposedAction {
+                    kind: "shard".into(),
+                    policy: Policy::ShardCount.name().into(),
+                    action: format!(
+                        "move shard {} from group {} to group {}",
+                        action.shard, action.source_group, action.target_group
+                    ),
+                    reason: format!(
+                        "{}, the groups have {:?} and {:?} shards",
+                        Policy::ShardCount.reason(),
+                        shard_count(action.source_group),
+                        shard_count(action.target_group),
+                    ),
+                    ..Default::default()
+                });
+            }
+        }
+        if self.balance_enabled(BalanceClass::Leader) {
+            if let Some((policy, LeaderAction::Shed(action))) = self.propose_leader_action()? {
+                actions.push(ProposedAction {
+                    kind: "leader".into(),
+                    policy: policy.name().into(),
+                    action: format!(
+                        "transfer leader of group {} from replica {} on node {} to replica {} on node {}",
+                        action.group,
+                        action.src_replica,
+                        action.src_node,
+                        action.target_replica,
+                        action.target_node
+                    ),
+                    reason: policy.reason().into(),
+                    source: score_of(action.src_node),
+                    target: score_of(action.target_node),
+                });
+            }
+        }
+
+        Ok(ScheduleExplain {
+            actions,
+            nodes: scores,
+            load_balance_cooling: self.is_load_cooling(),
+        })
+    }
+}
+
+impl<T: AllocSource> Allocator<T> {
+    /// Propose replica actions without any side effects, along with the policy proposing them.
+    fn propose_replica_action(&self) -> Result<Option<(Policy, Vec<ReplicaAction>)>> {
+        // try to follow the placement constraints.
+        let actions = self.placement_policy().compute_replica_balance()?;
+        if !actions.is_empty() {
+            return Ok(Some((Policy::Placement, actions)));
+        }
+
+        // try disk-usage rebalance.
+        if self.balance_enabled(BalanceClass::Disk) {
+            let actions = DiskUsagePolicy::with(
+                self.alloc_source.to_owned(),
+                self.config.disk_high_watermark,
+                self.config.disk_low_watermark,
+            )
+            .compute_balance()?;
+            if !actions.is_empty() {
+                return Ok(Some((Policy::DiskUsage, actions)));
+            }
+        }
+
+        // try load rebalance.
+        if self.balance_enabled(BalanceClass::Load) && !self.is_load_cooling() {
+            let actions = self.load_policy().compute_replica_balance()?;
+            if !actions.is_empty() {
+                return Ok(Some((Policy::Load, actions)));
+            }
+        }
+
+        // try to spread the replicas of groups across localities, then replica-count rebalance.
+        let policy = ReplicaCountPolicy::with(
+            self.alloc_source.to_owned(),
+            self.ongoing_stats.to_owned(),
+            self.config.disk_high_watermark,
+        );
+        if let Some(action) = policy.compute_locality_balance() {
+            return Ok(Some((Policy::Locality, vec![action])));
+        }
+        let actions = policy.compute_balance()?;
+        if !actions.is_empty() {
+            return Ok(Some((Policy::ReplicaCount, actions)));
+        }
+
+        Ok(None)
+    }
+
+    /// Propose shard actions without any side effects.
+    fn propose_shard_action(&self) -> Result<Vec<ShardAction>> {
+        if self.alloc_source.nodes(NodeFilter::All).len() < self.config.replicas_per_group {
+            return Ok(Vec::new());
+        }
+        ShardCountPolicy::with(self.alloc_source.to_owned(), self.config.replicas_per_group)
+            .compute_balance()
+    }
+
+    /// Propose leader action without any side effects, along with the policy proposing it.
+    fn propose_leader_action(&self) -> Result<Option<(Policy, LeaderAction)>> {
+        if let e @ LeaderAction::Shed { .. } = self.placement_policy().compute_leader_balance()? {
+            return Ok(Some((Policy::Placement, e)));
+        }
+        if self.balance_enabled(BalanceClass::Load) {
+            let policy = self.load_policy();
+            if policy.is_loaded() {
+                // The leader-count rebalance is skipped when the cluster is loaded, otherwise it
+                // might move the leaders back.
+                if !self.is_load_cooling() {
+                    if let e @ LeaderAction::Shed { .. } = policy.compute_leader_balance()? {
+                        return Ok(Some((Policy::Load, e)));
+                    }
+                }
+                return Ok(None);
+            }
+        }
         match LeaderCountPolicy::with(self.alloc_source.to_owned()).compute_balance()? {
-            LeaderAction::Noop => {}
-            e @ LeaderAction::Shed { .. } => return Ok(vec![e]),
+            LeaderAction::Noop => Ok(None),
+            e @ LeaderAction::Shed { .. } => Ok(Some((Policy::LeaderCount, e))),
         }
-        Ok(Vec::new())
+    }
+
+    /// Whether the class of balancing is enabled by config and isn't paused at runtime.
+    fn balance_enabled(&self, class: BalanceClass) -> bool {
+        let enabled = match class {
+            BalanceClass::Group => self.config.enable_group_balance,
+            BalanceClass::Replica => self.config.enable_replica_balance,
+            BalanceClass::Shard => self.config.enable_shard_balance,
+            BalanceClass::Leader => self.config.enable_leader_balance,
+            BalanceClass::Disk => self.config.enable_disk_balance,
+            BalanceClass::Load => self.config.enable_load_balance,
+        };
+        enabled && !class.is_paused(&self.control.lock().unwrap())
+    }
+
+    fn node_scores(&self) -> Vec<NodeScore> {
+        let loads = self.load_policy().node_loads();
+        let mut nodes = self.alloc_source.nodes(NodeFilter::All);
+        nodes.sort_by_key(|n| n.id);
+        nodes
+            .iter()
+            .map(|n| {
+                let capacity = n.capacity.clone().unwrap_or_default();
+                let delta = self.ongoing_stats.get_node_delta(n.id);
+                NodeScore {
+                    node: n.id,
+                    status: NodeStatus::from_i32(n.status)
+                        .map(|s| format!("{s:?}"))
+                        .unwrap_or_default(),
+                    replica_count: std::cmp::max(
+                        capacity.replica_count as i64 + delta.replica_count,
+                        0,
+                    ) as u64,
+                    leader_count: capacity.leader_count,
+                    disk_usage: policy_disk_usage::disk_usage_ratio(n),
+                    load: loads.get(&n.id).cloned().unwrap_or_default(),
+                }
+            })
+            .collect()
     }
 }
 
 impl<T: AllocSource> Allocator<T> {
+    fn placement_policy(&self) -> PlacementPolicy<T> {
+        PlacementPolicy::with(
+            self.alloc_source.to_owned(),
+            self.ongoing_stats.to_owned(),
+            self.config.disk_high_watermark,
+        )
+    }
+
+    fn load_policy(&self) -> LoadPolicy<T> {
+        LoadPolicy::with(
+            self.alloc_source.to_owned(),
+            self.config.load_balance_threshold,
+            self.config.disk_high_watermark,
+        )
+    }
+
+    /// The load stats are reported by heartbeat, so wait a few rounds of heartbeat after moving
+    /// load, to avoid moving the same load twice.
+    fn is_load_cooling(&self) -> bool {
+        let cooldown = self.config.heartbeat_interval() * 3;
+        matches!(*self.load_balanced_at.lock().unwrap(), Some(at) if at.elapsed() < cooldown)
+    }
+
+    fn mark_load_balanced(&self) {
+        *self.load_balanced_at.lock().unwrap() = Some(Instant::now());
+    }
+
     fn preferred_remove_groups(&self, want_remove: usize) -> Vec<u64> {
         // TODO:
         // 1 remove groups from unreachable nodes that indicated by NodeLiveness(they also need
@@ -257,8 +703,16 @@ impl<T: AllocSource> Allocator<T> {
         )
     }
 
-    fn current_groups(&self) -> usize {
-        self.alloc_source.groups().len()
+    /// The number of groups whose replication factor is `replicas`.
+    pub fn current_groups(&self, replicas: usize) -> usize {
+        self.alloc_source
+            .groups()
+            .values()
+            .filter(|g| match g.replication_factor as usize {
+                0 => replicas == self.config.replicas_per_group,
+                n => n == replicas,
+            })
+            .count()
     }
 }
 
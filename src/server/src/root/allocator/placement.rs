@@ -0,0 +1,200 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use engula_api::{
+    server::v1::NodeDesc,
+    v1::{
+        placement_constraint::Kind, CollectionDesc, DatabaseDesc, LeaderPreference,
+        PlacementConstraint,
+    },
+};
+
+/// The placement rules of the replicas of a group.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Placement {
+    pub constraints: Vec<PlacementConstraint>,
+    pub leader_preferences: Vec<LeaderPreference>,
+}
+
+/// Whether the node has the label. The locality tiers `region`, `zone`, `rack` and `host` are
+/// also treated as labels.
+pub fn has_label(n: &NodeDesc, key: &str, value: &str) -> bool {
+    let Some(locality) = n.locality.as_ref() else {
+        return false;
+    };
+    match key {
+        "region" => locality.region == value,
+        "zone" => locality.zone == value,
+        "rack" => locality.rack == value,
+        "host" => locality.host == value,
+        _ => locality
+            .labels
+            .get(key)
+            .map(|v| v == value)
+            .unwrap_or_default(),
+    }
+}
+
+impl Placement {
+    /// The placement of the collection, the rules are inherited from the database if they are not
+    /// specified.
+    pub fn of_collection(db: Option<&DatabaseDesc>, co: &CollectionDesc) -> Self {
+        let constraints = match (co.placement_constraints.is_empty(), db) {
+            (true, Some(db)) => db.placement_constraints.clone(),
+            _ => co.placement_constraints.clone(),
+        };
+        let leader_preferences = match (co.leader_preferences.is_empty(), db) {
+            (true, Some(db)) => db.leader_preferences.clone(),
+            _ => co.leader_preferences.clone(),
+        };
+        Placement {
+            constraints,
+            leader_preferences,
+        }
+    }
+
+    /// Merge the rules of another collection placed on the same group. All constraints must be
+    /// satisfied, and the leader preferences of the former one take precedence.
+    pub fn merge(&mut self, other: &Placement) {
+        for constraint in &other.constraints {
+            if !self.constraints.contains(constraint) {
+                self.constraints.push(constraint.clone());
+            }
+        }
+        if self.leader_preferences.is_empty() {
+            self.leader_preferences = other.leader_preferences.clone();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.constraints.is_empty() && self.leader_preferences.is_empty()
+    }
+
+    /// Whether the replicas could be placed on the node.
+    pub fn allows(&self, n: &NodeDesc) -> bool {
+        self.violated_constraints(n).next().is_none()
+    }
+
+    /// The constraints which are violated if the replica is placed on the node.
+    pub fn violated_constraints<'a>(
+        &'a self,
+        n: &'a NodeDesc,
+    ) -> impl Iterator<Item = &'a PlacementConstraint> {
+        self.constraints.iter().filter(move |c| {
+            let matched = has_label(n, &c.key, &c.value);
+            match Kind::from_i32(c.kind) {
+                Some(Kind::Required) => !matched,
+                Some(Kind::Prohibited) => matched,
+                None => false,
+            }
+        })
+    }
+
+    /// The rank of the node to place the leader, the lower one is preferred. The nodes without
+    /// any preferred label have the lowest priority.
+    pub fn leader_rank(&self, n: &NodeDesc) -> usize {
+        self.leader_preferences
+            .iter()
+            .position(|p| has_label(n, &p.key, &p.value))
+            .unwrap_or(self.leader_preferences.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use engula_api::server::v1::NodeLocality;
+
+    use super::*;
+
+    fn node(zone: &str, disk: &str) -> NodeDesc {
+        NodeDesc {
+            locality: Some(NodeLocality {
+                zone: zone.into(),
+                labels: HashMap::from([("disk".to_owned(), disk.to_owned())]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn constraint(kind: Kind, key: &str, value: &str) -> PlacementConstraint {
+        PlacementConstraint {
+            kind: kind.into(),
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    fn preference(key: &str, value: &str) -> LeaderPreference {
+        LeaderPreference {
+            key: key.into(),
+            value: value.into(),
+        }
+    }
+
+    #[test]
+    fn placement_allows() {
+        let placement = Placement {
+            constraints: vec![
+                constraint(Kind::Required, "zone", "z1"),
+                constraint(Kind::Prohibited, "disk", "hdd"),
+            ],
+            ..Default::default()
+        };
+        assert!(placement.allows(&node("z1", "ssd")));
+        assert!(!placement.allows(&node("z2", "ssd")));
+        assert!(!placement.allows(&node("z1", "hdd")));
+        assert!(!placement.allows(&NodeDesc::default()));
+        assert!(Placement::default().allows(&NodeDesc::default()));
+    }
+
+    #[test]
+    fn placement_leader_rank() {
+        let placement = Placement {
+            leader_preferences: vec![preference("zone", "z1"), preference("disk", "ssd")],
+            ..Default::default()
+        };
+        assert_eq!(placement.leader_rank(&node("z1", "hdd")), 0);
+        assert_eq!(placement.leader_rank(&node("z2", "ssd")), 1);
+        assert_eq!(placement.leader_rank(&node("z2", "hdd")), 2);
+    }
+
+    #[test]
+    fn placement_inherit_and_merge() {
+        let db = DatabaseDesc {
+            placement_constraints: vec![constraint(Kind::Required, "zone", "z1")],
+            leader_preferences: vec![preference("zone", "z1")],
+            ..Default::default()
+        };
+        let co = CollectionDesc {
+            leader_preferences: vec![preference("zone", "z2")],
+            ..Default::default()
+        };
+        let mut placement = Placement::of_collection(Some(&db), &co);
+        assert_eq!(placement.constraints, db.placement_constraints);
+        assert_eq!(placement.leader_preferences, co.leader_preferences);
+
+        placement.merge(&Placement {
+            constraints: vec![
+                constraint(Kind::Required, "zone", "z1"),
+                constraint(Kind::Prohibited, "disk", "hdd"),
+            ],
+            leader_preferences: vec![preference("zone", "z3")],
+        });
+        assert_eq!(placement.constraints.len(), 2);
+        assert_eq!(placement.leader_preferences, co.leader_preferences);
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{cmp::Ordering, collections::HashMap, sync::Arc};
+
+use engula_api::server::v1::{NodeDesc, ReplicaDesc};
+
+use super::{locality, source::NodeFilter, *};
+use crate::{constants::ROOT_GROUP_ID, Result};
+
+/// The disk usage ratio of the node, `None` if the disk usage isn't reported yet.
+pub fn disk_usage_ratio(n: &NodeDesc) -> Option<f64> {
+    let cap = n.capacity.as_ref()?;
+    if cap.disk_capacity == 0 {
+        return None;
+    }
+    Some(cap.disk_used as f64 / cap.disk_capacity as f64)
+}
+
+/// Whether the disk usage ratio of the node reaches the watermark.
+pub fn exceeds_watermark(n: &NodeDesc, watermark: f64) -> bool {
+    disk_usage_ratio(n)
+        .map(|ratio| ratio >= watermark)
+        .unwrap_or_default()
+}
+
+pub struct DiskUsagePolicy<T: AllocSource> {
+    alloc_source: Arc<T>,
+    high_watermark: f64,
+    low_watermark: f64,
+}
+
+impl<T: AllocSource> DiskUsagePolicy<T> {
+    pub fn with(alloc_source: Arc<T>, high_watermark: f64, low_watermark: f64) -> Self {
+        Self {
+            alloc_source,
+            high_watermark,
+            low_watermark,
+        }
+    }
+
+    /// Move a replica out of the node whose disk usage exceeds the high watermark, to a node whose
+    /// disk usage is still below the low watermark after receiving it.
+    pub fn compute_balance(&self) -> Result<Vec<ReplicaAction>> {
+        let sources = self.ranked_nodes(NodeFilter::Alive, |ratio| ratio >= self.high_watermark);
+        if sources.is_empty() {
+            return Ok(Vec::new());
+        }
+        let targets =
+            self.ranked_nodes(NodeFilter::Schedulable, |ratio| ratio < self.low_watermark);
+
+        let nodes = self
+            .alloc_source
+            .nodes(NodeFilter::All)
+            .into_iter()
+            .map(|n| (n.id, n))
+            .collect::<HashMap<_, _>>();
+        let groups = self.alloc_source.groups();
+        let group_nodes = self.alloc_source.group_nodes();
+        let placements = self.alloc_source.group_placements();
+        for src in sources.iter().rev() {
+            tracing::debug!(
+                node = src.id,
+                ratio = ?disk_usage_ratio(src),
+                "node disk usage exceeds high watermark",
+            );
+            for (replica, group, size) in self.preferred_move_replicas(src) {
+                let (Some(desc), Some(exist_nodes)) = (groups.get(&group), group_nodes.get(&group)) else {
+                    continue;
+                };
+                if exist_nodes.len() != desc.replicas.len() {
+                    // There exists ongoing replica changes.
+                    continue;
+                }
+                let current = locality::diversity_after_move(&nodes, exist_nodes, None);
+                for target in &targets {
+                    if exist_nodes.contains(&target.id) || !self.below_low_watermark(target, size) {
+                        continue;
+                    }
+                    if !placements.get(&group).map_or(true, |p| p.allows(target)) {
+                        continue;
+                    }
+                    // Don't reduce the locality diversity of the group.
+                    let moved =
+                        locality::diversity_after_move(&nodes, exist_nodes, Some((src.id, target)));
+                    if !matches!((current, moved), (Some(c), Some(m)) if c <= m) {
+                        continue;
+                    }
+                    return Ok(vec![ReplicaAction::Migrate(ReallocateReplica {
+                        group,
+                        source_node: src.id,
+                        source_replica: replica.id,
+                        target_node: target.to_owned(),
+                    })]);
+                }
+            }
+        }
+
+        Ok(Vec::new())
+    }
+
+    /// The nodes whose disk usage ratio satisfies the predicate, sorted by the ratio in ascending
+    /// order.
+    fn ranked_nodes(&self, filter: NodeFilter, pred: impl Fn(f64) -> bool) -> Vec<NodeDesc> {
+        let mut nodes = self
+            .alloc_source
+            .nodes(filter)
+            .into_iter()
+            .filter_map(|n| {
+                let ratio = disk_usage_ratio(&n)?;
+                pred(ratio).then_some((n, ratio))
+            })
+            .collect::<Vec<_>>();
+        nodes.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+        nodes.into_iter().map(|(n, _)| n).collect()
+    }
+
+    /// The replicas of the node sorted by data size in descending order, so that the disk space
+    /// is released with the fewest moves.
+    fn preferred_move_replicas(&self, src: &NodeDesc) -> Vec<(ReplicaDesc, u64, u64)> {
+        let mut replicas = self
+            .alloc_source
+            .node_replicas(&src.id)
+            .into_iter()
+            .filter(|(_, group)| *group != ROOT_GROUP_ID)
+            .map(|(replica, group)| {
+                let size = self
+                    .alloc_source
+                    .replica_stats(&src.id, &replica.id)
+                    .map(|s| s.data_size)
+                    .unwrap_or_default();
+                (replica, group, size)
+            })
+            .filter(|(_, _, size)| *size > 0)
+            .collect::<Vec<_>>();
+        replicas.sort_by_key(|(_, _, size)| std::cmp::Reverse(*size));
+        replicas
+    }
+
+    fn below_low_watermark(&self, target: &NodeDesc, size: u64) -> bool {
+        let cap = target.capacity.as_ref().unwrap();
+        ((cap.disk_used + size) as f64) < cap.disk_capacity as f64 * self.low_watermark
+    }
+}
@@ -84,10 +84,19 @@ impl<T: AllocSource> LeaderCountPolicy<T> {
     ) -> Result<Option<TransferDescision>> {
         let node_replicas = self.alloc_source.node_replicas(&n.id);
         let groups = self.alloc_source.groups();
+        let placements = self.alloc_source.group_placements();
         for (replica, group_id) in node_replicas
             .iter()
             .filter(|(r, g)| *g != ROOT_GROUP_ID && r.role == ReplicaRole::Voter as i32)
         {
+            if placements
+                .get(group_id)
+                .map(|p| !p.leader_preferences.is_empty())
+                .unwrap_or_default()
+            {
+                // The leader is pinned by the leader preferences.
+                continue;
+            }
             let replica_state = self.alloc_source.replica_state(&replica.id);
             if replica_state.is_none() {
                 // The replica existed in group_desc, but not found in replica_state, the reason(if
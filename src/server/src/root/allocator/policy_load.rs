@@ -0,0 +1,263 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{cmp::Ordering, collections::HashMap, sync::Arc};
+
+use engula_api::server::v1::{NodeDesc, RaftRole, ReplicaDesc, ReplicaRole, ReplicaStats};
+use tracing::debug;
+
+use super::{locality, placement::Placement, policy_disk_usage, source::NodeFilter, *};
+use crate::{constants::ROOT_GROUP_ID, Result};
+
+/// The bytes read or written are counted as one request per `BYTES_PER_LOAD_UNIT`.
+const BYTES_PER_LOAD_UNIT: f64 = 64.0 * 1024.0;
+
+/// The load balancing is skipped if the mean load of nodes is below it, since the rates of a
+/// lightly loaded cluster are dominated by noise.
+const MIN_MEAN_LOAD: f64 = 10.0;
+
+/// The load of a replica, it is the sum of qps and the bytes read and written.
+pub fn replica_load(stats: &ReplicaStats) -> f64 {
+    let qps = (stats.read_qps + stats.write_qps) as f64;
+    let bytes = (stats.read_bytes_per_sec + stats.write_bytes_per_sec) as f64;
+    qps + bytes / BYTES_PER_LOAD_UNIT
+}
+
+pub struct LoadPolicy<T: AllocSource> {
+    alloc_source: Arc<T>,
+    threshold: f64,
+    disk_high_watermark: f64,
+}
+
+impl<T: AllocSource> LoadPolicy<T> {
+    pub fn with(alloc_source: Arc<T>, threshold: f64, disk_high_watermark: f64) -> Self {
+        Self {
+            alloc_source,
+            threshold,
+            disk_high_watermark,
+        }
+    }
+
+    /// Whether the cluster is loaded enough to balance by load.
+    pub fn is_loaded(&self) -> bool {
+        Self::mean(&self.node_loads()) >= MIN_MEAN_LOAD
+    }
+
+    /// Transfer a hot leader out of the overloaded node to a replica of the same group.
+    pub fn compute_leader_balance(&self) -> Result<LeaderAction> {
+        let loads = self.node_loads();
+        let mean = Self::mean(&loads);
+        if mean < MIN_MEAN_LOAD {
+            return Ok(LeaderAction::Noop);
+        }
+
+        let groups = self.alloc_source.groups();
+        let placements = self.alloc_source.group_placements();
+        for src in self.overloaded_nodes(&loads, mean) {
+            for (replica, group, load) in self.leader_replicas(&src) {
+                let Some(desc) = groups.get(&group) else {
+                    continue;
+                };
+                if Self::has_leader_preferences(&placements, group) {
+                    // The leader is pinned by the leader preferences.
+                    continue;
+                }
+                if let Some(target) = self.transfer_target(desc, &replica, load, &loads, mean) {
+                    return Ok(LeaderAction::Shed(TransferLeader {
+                        group,
+                        src_node: src.id,
+                        src_replica: replica.id,
+                        target_node: target.node_id,
+                        target_replica: target.id,
+                    }));
+                }
+            }
+        }
+        Ok(LeaderAction::Noop)
+    }
+
+    /// Move a hot leader replica out of the overloaded node, it is only used if the leader could
+    /// not be transferred to the other replicas of the group.
+    pub fn compute_replica_balance(&self) -> Result<Vec<ReplicaAction>> {
+        let loads = self.node_loads();
+        let mean = Self::mean(&loads);
+        if mean < MIN_MEAN_LOAD {
+            return Ok(Vec::new());
+        }
+
+        let nodes = self
+            .alloc_source
+            .nodes(NodeFilter::All)
+            .into_iter()
+            .map(|n| (n.id, n))
+            .collect::<HashMap<_, _>>();
+        let mut candidate_nodes = self.alloc_source.nodes(NodeFilter::Schedulable);
+        candidate_nodes
+            .retain(|n| !policy_disk_usage::exceeds_watermark(n, self.disk_high_watermark));
+        candidate_nodes.sort_by(|a, b| Self::compare_load(&loads, a.id, b.id));
+
+        let groups = self.alloc_source.groups();
+        let group_nodes = self.alloc_source.group_nodes();
+        let placements = self.alloc_source.group_placements();
+        for src in self.overloaded_nodes(&loads, mean) {
+            for (replica, group, load) in self.leader_replicas(&src) {
+                let (Some(desc), Some(exist_nodes)) = (groups.get(&group), group_nodes.get(&group)) else {
+                    continue;
+                };
+                if exist_nodes.len() != desc.replicas.len() {
+                    // There exists ongoing replica changes.
+                    continue;
+                }
+                if Self::has_leader_preferences(&placements, group) {
+                    continue;
+                }
+                if self
+                    .transfer_target(desc, &replica, load, &loads, mean)
+                    .is_some()
+                {
+                    // Transferring leader is cheaper.
+                    continue;
+                }
+                let current = locality::diversity_after_move(&nodes, exist_nodes, None);
+                for target in &candidate_nodes {
+                    if exist_nodes.contains(&target.id)
+                        || !self.accepts(Self::load_of(&loads, target.id), load, mean)
+                        || !placements.get(&group).map_or(true, |p| p.allows(target))
+                    {
+                        continue;
+                    }
+                    // Don't reduce the locality diversity of the group.
+                    let moved =
+                        locality::diversity_after_move(&nodes, exist_nodes, Some((src.id, target)));
+                    if !matches!((current, moved), (Some(c), Some(m)) if c <= m) {
+                        continue;
+                    }
+                    return Ok(vec![ReplicaAction::Migrate(ReallocateReplica {
+                        group,
+                        source_node: src.id,
+                        source_replica: replica.id,
+                        target_node: target.to_owned(),
+                    })]);
+                }
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// The voter of the group with the lowest load which could accept the leader.
+    fn transfer_target(
+        &self,
+        desc: &GroupDesc,
+        leader: &ReplicaDesc,
+        load: f64,
+        loads: &HashMap<u64, f64>,
+        mean: f64,
+    ) -> Option<ReplicaDesc> {
+        desc.replicas
+            .iter()
+            .filter(|r| r.id != leader.id && r.role == ReplicaRole::Voter as i32)
+            .filter(|r| loads.contains_key(&r.node_id))
+            .filter(|r| self.accepts(Self::load_of(loads, r.node_id), load, mean))
+            .min_by(|a, b| Self::compare_load(loads, a.node_id, b.node_id))
+            .cloned()
+    }
+
+    /// The overloaded nodes, sorted by load in descending order.
+    fn overloaded_nodes(&self, loads: &HashMap<u64, f64>, mean: f64) -> Vec<NodeDesc> {
+        let mut nodes = self
+            .alloc_source
+            .nodes(NodeFilter::Schedulable)
+            .into_iter()
+            .filter(|n| Self::load_of(loads, n.id) > mean * (1.0 + self.threshold))
+            .collect::<Vec<_>>();
+        nodes.sort_by(|a, b| Self::compare_load(loads, b.id, a.id));
+        debug!(
+            overloaded_nodes = ?nodes.iter().map(|n| format!("{}({:.2})", n.id, Self::load_of(loads, n.id))).collect::<Vec<_>>(),
+            mean = mean,
+            "node ranked by load",
+        );
+        nodes
+    }
+
+    /// The leader replicas of the node with its load, sorted by load in descending order.
+    fn leader_replicas(&self, n: &NodeDesc) -> Vec<(ReplicaDesc, u64, f64)> {
+        let mut replicas = self
+            .alloc_source
+            .node_replicas(&n.id)
+            .into_iter()
+            .filter(|(r, g)| *g != ROOT_GROUP_ID && r.role == ReplicaRole::Voter as i32)
+            .filter(|(r, _)| {
+                self.alloc_source
+                    .replica_state(&r.id)
+                    .map(|s| s.role == RaftRole::Leader as i32)
+                    .unwrap_or_default()
+            })
+            .filter_map(|(r, g)| {
+                let stats = self.alloc_source.replica_stats(&n.id, &r.id)?;
+                let load = replica_load(&stats);
+                (load > 0.0).then_some((r, g, load))
+            })
+            .collect::<Vec<_>>();
+        replicas.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(Ordering::Equal));
+        replicas
+    }
+
+    /// Whether the target could accept the load. The target must stay below the mean by half of
+    /// the threshold after moving, so the load won't be moved back and forth.
+    fn accepts(&self, target_load: f64, load: f64, mean: f64) -> bool {
+        target_load + load <= mean * (1.0 + self.threshold / 2.0)
+    }
+
+    /// The load of each schedulable node.
+    pub fn node_loads(&self) -> HashMap<u64, f64> {
+        self.alloc_source
+            .nodes(NodeFilter::Schedulable)
+            .into_iter()
+            .map(|n| {
+                let load = self
+                    .alloc_source
+                    .node_replicas(&n.id)
+                    .iter()
+                    .filter_map(|(r, _)| self.alloc_source.replica_stats(&n.id, &r.id))
+                    .map(|stats| replica_load(&stats))
+                    .sum::<f64>();
+                (n.id, load)
+            })
+            .collect()
+    }
+
+    fn has_leader_preferences(placements: &HashMap<u64, Placement>, group: u64) -> bool {
+        placements
+            .get(&group)
+            .map(|p| !p.leader_preferences.is_empty())
+            .unwrap_or_default()
+    }
+
+    fn mean(loads: &HashMap<u64, f64>) -> f64 {
+        if loads.is_empty() {
+            return 0.0;
+        }
+        loads.values().sum::<f64>() / loads.len() as f64
+    }
+
+    fn load_of(loads: &HashMap<u64, f64>, node_id: u64) -> f64 {
+        loads.get(&node_id).cloned().unwrap_or_default()
+    }
+
+    fn compare_load(loads: &HashMap<u64, f64>, a: u64, b: u64) -> Ordering {
+        Self::load_of(loads, a)
+            .partial_cmp(&Self::load_of(loads, b))
+            .unwrap_or(Ordering::Equal)
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{collections::HashMap, sync::Arc};
+
+use engula_api::{
+    server::v1::{NodeDesc, RaftRole, ReplicaDesc, ReplicaRole},
+    v1::placement_constraint::Kind,
+};
+
+use super::{locality, placement::Placement, policy_disk_usage, source::NodeFilter, *};
+use crate::{
+    constants::ROOT_GROUP_ID,
+    root::{diagnosis::PlacementViolation, OngoingStats},
+    Result,
+};
+
+/// Moves the replicas and leaders which don't follow the placement rules of their groups.
+pub struct PlacementPolicy<T: AllocSource> {
+    alloc_source: Arc<T>,
+    ongoing_stats: Arc<OngoingStats>,
+    disk_high_watermark: f64,
+}
+
+impl<T: AllocSource> PlacementPolicy<T> {
+    pub fn with(
+        alloc_source: Arc<T>,
+        ongoing_stats: Arc<OngoingStats>,
+        disk_high_watermark: f64,
+    ) -> Self {
+        Self {
+            alloc_source,
+            ongoing_stats,
+            disk_high_watermark,
+        }
+    }
+
+    /// Move a replica placed on a node violating the constraints to a node satisfying them.
+    pub fn compute_replica_balance(&self) -> Result<Vec<ReplicaAction>> {
+        let nodes = self.node_descs();
+        let mut candidate_nodes = self.alloc_source.nodes(NodeFilter::Schedulable);
+        candidate_nodes
+            .retain(|n| !policy_disk_usage::exceeds_watermark(n, self.disk_high_watermark));
+
+        let groups = self.alloc_source.groups();
+        let group_nodes = self.alloc_source.group_nodes();
+        for (group_id, placement) in self.alloc_source.group_placements() {
+            if group_id == ROOT_GROUP_ID || placement.constraints.is_empty() {
+                continue;
+            }
+            let (Some(desc), Some(exist_nodes)) = (groups.get(&group_id), group_nodes.get(&group_id)) else {
+                continue;
+            };
+            if exist_nodes.len() != desc.replicas.len() {
+                // There exists ongoing replica changes.
+                continue;
+            }
+            for replica in &desc.replicas {
+                match nodes.get(&replica.node_id) {
+                    Some(n) if !placement.allows(n) => {}
+                    _ => continue,
+                }
+                // Prefer the node maximizing the locality diversity, then the one with less
+                // replicas.
+                let target = candidate_nodes
+                    .iter()
+                    .filter(|n| !exist_nodes.contains(&n.id) && placement.allows(n))
+                    .filter_map(|n| {
+                        let diversity = locality::diversity_after_move(
+                            &nodes,
+                            exist_nodes,
+                            Some((replica.node_id, n)),
+                        )?;
+                        Some((n, diversity))
+                    })
+                    .max_by(|(n1, d1), (n2, d2)| {
+                        d1.cmp(d2).then_with(|| {
+                            self.node_replica_count(n2)
+                                .cmp(&self.node_replica_count(n1))
+                        })
+                    });
+                if let Some((target, _)) = target {
+                    return Ok(vec![ReplicaAction::Migrate(ReallocateReplica {
+                        group: group_id,
+                        source_node: replica.node_id,
+                        source_replica: replica.id,
+                        target_node: target.to_owned(),
+                    })]);
+                }
+                tracing::debug!(
+                    "skip moving replica {} of group {group_id}, no node satisfies the placement constraints",
+                    replica.id,
+                );
+            }
+        }
+        Ok(Vec::new())
+    }
+
+    /// Transfer the leader to the voter on the most preferred node.
+    pub fn compute_leader_balance(&self) -> Result<LeaderAction> {
+        let nodes = self.node_descs();
+        let alive_nodes = self
+            .alloc_source
+            .nodes(NodeFilter::Alive)
+            .into_iter()
+            .map(|n| n.id)
+            .collect::<Vec<_>>();
+        let groups = self.alloc_source.groups();
+        for (group_id, placement) in self.alloc_source.group_placements() {
+            if group_id == ROOT_GROUP_ID || placement.leader_preferences.is_empty() {
+                continue;
+            }
+            let Some(desc) = groups.get(&group_id) else {
+                continue;
+            };
+            let Some((leader, leader_rank)) = self.leader_with_rank(desc, &placement, &nodes) else {
+                continue;
+            };
+            let target = desc
+                .replicas
+                .iter()
+                .filter(|r| r.id != leader.id && r.role == ReplicaRole::Voter as i32)
+                .filter(|r| alive_nodes.contains(&r.node_id))
+                .filter_map(|r| Some((r, placement.leader_rank(nodes.get(&r.node_id)?))))
+                .min_by_key(|(_, rank)| *rank);
+            if let Some((target, rank)) = target {
+                if rank < leader_rank {
+                    return Ok(LeaderAction::Shed(TransferLeader {
+                        group: group_id,
+                        src_node: leader.node_id,
+                        src_replica: leader.id,
+                        target_node: target.node_id,
+                        target_replica: target.id,
+                    }));
+                }
+            }
+        }
+        Ok(LeaderAction::Noop)
+    }
+
+    /// The replicas and leaders which violate the placement rules.
+    pub fn violations(&self) -> Vec<PlacementViolation> {
+        let nodes = self.node_descs();
+        let groups = self.alloc_source.groups();
+        let mut violations = Vec::new();
+        for (group_id, placement) in self.alloc_source.group_placements() {
+            let Some(desc) = groups.get(&group_id) else {
+                continue;
+            };
+            for replica in &desc.replicas {
+                let Some(n) = nodes.get(&replica.node_id) else {
+                    continue;
+                };
+                for c in placement.violated_constraints(n) {
+                    let kind = match Kind::from_i32(c.kind) {
+                        Some(Kind::Prohibited) => "prohibited",
+                        _ => "required",
+                    };
+                    violations.push(PlacementViolation {
+                        group: group_id,
+                        replica: replica.id,
+                        node: n.id,
+                        reason: format!("violates {kind} label {}={}", c.key, c.value),
+                    });
+                }
+            }
+
+            let Some((leader, leader_rank)) = self.leader_with_rank(desc, &placement, &nodes) else {
+                continue;
+            };
+            let best = desc
+                .replicas
+                .iter()
+                .filter(|r| r.role == ReplicaRole::Voter as i32)
+                .filter_map(|r| Some(placement.leader_rank(nodes.get(&r.node_id)?)))
+                .min()
+                .unwrap_or(leader_rank);
+            if best < leader_rank {
+                let preference = &placement.leader_preferences[best];
+                violations.push(PlacementViolation {
+                    group: group_id,
+                    replica: leader.id,
+                    node: leader.node_id,
+                    reason: format!(
+                        "leader prefers label {}={}",
+                        preference.key, preference.value
+                    ),
+                });
+            }
+        }
+        violations.sort_by_key(|v| (v.group, v.replica));
+        violations
+    }
+
+    /// The leader of the group and the rank of its node.
+    fn leader_with_rank<'a>(
+        &self,
+        desc: &'a GroupDesc,
+        placement: &Placement,
+        nodes: &HashMap<u64, NodeDesc>,
+    ) -> Option<(&'a ReplicaDesc, usize)> {
+        let leader = desc.replicas.iter().find(|r| {
+            self.alloc_source
+                .replica_state(&r.id)
+                .map(|s| s.role == RaftRole::Leader as i32)
+                .unwrap_or_default()
+        })?;
+        let rank = placement.leader_rank(nodes.get(&leader.node_id)?);
+        Some((leader, rank))
+    }
+
+    fn node_descs(&self) -> HashMap<u64, NodeDesc> {
+        self.alloc_source
+            .nodes(NodeFilter::All)
+            .into_iter()
+            .map(|n| (n.id, n))
+            .collect()
+    }
+
+    fn node_replica_count(&self, n: &NodeDesc) -> u64 {
+        let cnt = n
+            .capacity
+            .as_ref()
+            .map(|c| c.replica_count)
+            .unwrap_or_default() as i64;
+        let delta = self.ongoing_stats.get_node_delta(n.id);
+        std::cmp::max(cnt + delta.replica_count, 0) as u64
+    }
+}
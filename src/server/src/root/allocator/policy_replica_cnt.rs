@@ -20,19 +20,25 @@ use std::{
 
 use engula_api::server::v1::{NodeDesc, ReplicaDesc};
 
-use super::{source::NodeFilter, *};
+use super::{locality, placement::Placement, policy_disk_usage, source::NodeFilter, *};
 use crate::{constants::ROOT_GROUP_ID, root::OngoingStats, Result};
 
 pub struct ReplicaCountPolicy<T: AllocSource> {
     alloc_source: Arc<T>,
     ongoing_stats: Arc<OngoingStats>,
+    disk_high_watermark: f64,
 }
 
 impl<T: AllocSource> ReplicaCountPolicy<T> {
-    pub fn with(alloc_source: Arc<T>, ongoing_stats: Arc<OngoingStats>) -> Self {
+    pub fn with(
+        alloc_source: Arc<T>,
+        ongoing_stats: Arc<OngoingStats>,
+        disk_high_watermark: f64,
+    ) -> Self {
         Self {
             alloc_source,
             ongoing_stats,
+            disk_high_watermark,
         }
     }
 
@@ -40,11 +46,14 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
         &self,
         existing_replica_nodes: Vec<u64>,
         wanted_count: usize,
+        placement: &Placement,
     ) -> Result<Vec<NodeDesc>> {
-        let mut candidate_nodes = self.alloc_source.nodes(NodeFilter::Schedulable);
+        let mut candidate_nodes = self.schedulable_nodes();
 
-        // skip the nodes already have group replicas.
-        candidate_nodes.retain(|n| !existing_replica_nodes.iter().any(|rn| *rn == n.id));
+        // skip the nodes already have group replicas or violating the placement constraints.
+        candidate_nodes.retain(|n| {
+            !existing_replica_nodes.iter().any(|rn| *rn == n.id) && placement.allows(n)
+        });
 
         // sort by alloc score
         candidate_nodes.sort_by(|n1, n2| {
@@ -53,7 +62,30 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
                 .unwrap()
         });
 
-        Ok(candidate_nodes.into_iter().take(wanted_count).collect())
+        // pick the node maximizing the locality diversity one by one, the one with higher alloc
+        // score is preferred if the diversities are equal.
+        let mut selected = self
+            .alloc_source
+            .nodes(NodeFilter::All)
+            .into_iter()
+            .filter(|n| existing_replica_nodes.contains(&n.id))
+            .collect::<Vec<_>>();
+        let num_existing = selected.len();
+        while selected.len() - num_existing < wanted_count && !candidate_nodes.is_empty() {
+            let mut best = 0;
+            let mut best_diversity = None;
+            for (idx, n) in candidate_nodes.iter().enumerate() {
+                let nodes = selected.iter().chain(Some(n)).collect::<Vec<_>>();
+                let diversity = locality::diversity(&nodes);
+                if best_diversity.map(|d| d < diversity).unwrap_or(true) {
+                    best = idx;
+                    best_diversity = Some(diversity);
+                }
+            }
+            selected.push(candidate_nodes.remove(best));
+        }
+
+        Ok(selected.split_off(num_existing))
     }
 
     pub fn compute_balance(&self) -> Result<Vec<ReplicaAction>> {
@@ -84,32 +116,14 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
         ranked_nodes: &[(NodeDesc, BalanceStatus)],
         mean: f64,
     ) -> Option<ReplicaAction> {
-        let mut groups = self
-            .alloc_source
-            .groups()
-            .into_iter()
-            .map(|(group, desc)| {
-                (
-                    group,
-                    desc.replicas
-                        .iter()
-                        .map(|r| r.node_id)
-                        .collect::<HashSet<u64>>(),
-                )
-            })
-            .collect::<HashMap<_, _>>();
-
-        let replica_states = self.alloc_source.replica_states();
-        for replica_state in replica_states {
-            if let Some(g) = groups.get_mut(&replica_state.group_id) {
-                g.insert(replica_state.node_id);
-            }
-        }
-
+        let groups = self.alloc_source.group_nodes();
         for (target, state) in ranked_nodes.iter().rev() {
             if *state != BalanceStatus::Underfull {
                 break;
             }
+            if policy_disk_usage::exceeds_watermark(target, self.disk_high_watermark) {
+                continue;
+            }
             let sim_count = (self.node_replica_count(target) + 1) as f64;
             if Self::node_balance_state(sim_count, mean) == BalanceStatus::Overfull {
                 continue;
@@ -125,6 +139,81 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
         None
     }
 
+    /// Move a replica to a node so that the locality diversity of its group is improved.
+    pub fn compute_locality_balance(&self) -> Option<ReplicaAction> {
+        let nodes = self.node_descs();
+        let candidate_nodes = self.schedulable_nodes();
+        let group_nodes = self.alloc_source.group_nodes();
+        let placements = self.alloc_source.group_placements();
+        for (group_id, desc) in self.alloc_source.groups() {
+            if group_id == ROOT_GROUP_ID {
+                continue;
+            }
+            let exist_nodes = match group_nodes.get(&group_id) {
+                Some(exist_nodes) if exist_nodes.len() == desc.replicas.len() => exist_nodes,
+                // There exists ongoing replica changes.
+                _ => continue,
+            };
+            let current = match locality::diversity_after_move(&nodes, exist_nodes, None) {
+                Some(d) => d,
+                None => continue,
+            };
+            for replica in &desc.replicas {
+                let mut best: Option<(&NodeDesc, locality::Diversity)> = None;
+                for target in &candidate_nodes {
+                    if exist_nodes.contains(&target.id)
+                        || !placements.get(&group_id).map_or(true, |p| p.allows(target))
+                    {
+                        continue;
+                    }
+                    let diversity = match locality::diversity_after_move(
+                        &nodes,
+                        exist_nodes,
+                        Some((replica.node_id, target)),
+                    ) {
+                        Some(d) => d,
+                        None => continue,
+                    };
+                    let better = match best {
+                        None => current < diversity,
+                        Some((n, d)) => {
+                            d < diversity
+                                || (d == diversity
+                                    && self.node_alloc_score(n) < self.node_alloc_score(target))
+                        }
+                    };
+                    if better {
+                        best = Some((target, diversity));
+                    }
+                }
+                if let Some((target, _)) = best {
+                    return Some(ReplicaAction::Migrate(ReallocateReplica {
+                        group: group_id,
+                        source_node: replica.node_id,
+                        source_replica: replica.id,
+                        target_node: target.to_owned(),
+                    }));
+                }
+            }
+        }
+        None
+    }
+
+    /// The schedulable nodes, excluding the ones whose disk usage exceeds the high watermark.
+    fn schedulable_nodes(&self) -> Vec<NodeDesc> {
+        let mut nodes = self.alloc_source.nodes(NodeFilter::Schedulable);
+        nodes.retain(|n| !policy_disk_usage::exceeds_watermark(n, self.disk_high_watermark));
+        nodes
+    }
+
+    fn node_descs(&self) -> HashMap<u64, NodeDesc> {
+        self.alloc_source
+            .nodes(NodeFilter::All)
+            .into_iter()
+            .map(|n| (n.id, n))
+            .collect()
+    }
+
     fn preferred_remove_replica(
         &self,
         src: &NodeDesc,
@@ -132,6 +221,9 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
         group_nodes: &HashMap<u64, HashSet<u64>>,
     ) -> Option<(ReplicaDesc, u64)> {
         // TODO: sort & rank replica
+        let groups = self.alloc_source.groups();
+        let nodes = self.node_descs();
+        let placements = self.alloc_source.group_placements();
         self.alloc_source
             .node_replicas(&src.id)
             .into_iter()
@@ -139,13 +231,25 @@ impl<T: AllocSource> ReplicaCountPolicy<T> {
                 if *g == ROOT_GROUP_ID {
                     return false;
                 }
+                let required_replicas = match groups.get(g).map(|g| g.replication_factor) {
+                    None | Some(0) => REPLICA_PER_GROUP,
+                    Some(n) => n as usize,
+                };
                 if let Some(exist_nodes) = group_nodes.get(g) {
-                    if exist_nodes.len() < REPLICA_PER_GROUP {
+                    if exist_nodes.len() < required_replicas {
+                        return false;
+                    }
+                    if exist_nodes.contains(&target.id) {
                         return false;
                     }
-                    if !exist_nodes.contains(&target.id) {
-                        return true;
+                    if !placements.get(g).map_or(true, |p| p.allows(target)) {
+                        return false;
                     }
+                    // Don't reduce the locality diversity of the group.
+                    let current = locality::diversity_after_move(&nodes, exist_nodes, None);
+                    let moved =
+                        locality::diversity_after_move(&nodes, exist_nodes, Some((src.id, target)));
+                    return matches!((current, moved), (Some(c), Some(m)) if c <= m);
                 }
                 false
             })
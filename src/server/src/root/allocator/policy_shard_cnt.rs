@@ -12,7 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{cmp::Ordering, sync::Arc};
+use std::{cmp::Ordering, collections::BTreeMap, sync::Arc};
 
 use engula_api::server::v1::{GroupDesc, ShardDesc};
 use tracing::debug;
@@ -22,15 +22,25 @@ use crate::{constants::ROOT_GROUP_ID, root::allocator::BalanceStatus, Result};
 
 pub struct ShardCountPolicy<T: AllocSource> {
     alloc_source: Arc<T>,
+    replicas_per_group: usize,
 }
 
 impl<T: AllocSource> ShardCountPolicy<T> {
-    pub fn with(alloc_source: Arc<T>) -> Self {
-        Self { alloc_source }
+    pub fn with(alloc_source: Arc<T>, replicas_per_group: usize) -> Self {
+        Self {
+            alloc_source,
+            replicas_per_group,
+        }
     }
 
-    pub fn allocate_shard(&self, n: usize) -> Result<Vec<GroupDesc>> {
-        let mut groups = self.current_user_groups();
+    /// Find `n` groups with the least shards, whose replication factor is `replicas`.
+    pub fn allocate_shard(&self, n: usize, replicas: usize) -> Result<Vec<GroupDesc>> {
+        let replicas = self.effective_replicas(replicas);
+        let mut groups = self
+            .current_user_groups()
+            .into_iter()
+            .filter(|g| self.group_replicas(g) == replicas)
+            .collect::<Vec<_>>();
         if groups.is_empty() {
             return Ok(vec![]);
         }
@@ -39,29 +49,90 @@ impl<T: AllocSource> ShardCountPolicy<T> {
     }
 
     pub fn compute_balance(&self) -> Result<Vec<ShardAction>> {
-        let mean_cnt = self.mean_shard_count();
-        let candicate_groups = self.current_user_groups();
+        // Shards placed on groups with an unexpected replication factor go first, it happens when
+        // the replication factor of a collection is changed.
+        if let Some(action) = self.misplaced_shard_action() {
+            return Ok(vec![action]);
+        }
 
-        let ranked_candicates = Self::rank_group_for_balance(candicate_groups, mean_cnt);
-        debug!(
-            scored_nodes = ?ranked_candicates.iter().map(|(g, s)| format!("{}-{}({:?})", g.id, g.shards.len(), s)).collect::<Vec<_>>(),
-            mean = mean_cnt,
-            "group ranked by shard count",
-        );
-        for (src_group, status) in &ranked_candicates {
-            if *status != BalanceStatus::Overfull {
-                break;
-            }
-            if let Some(action) = self.rebalance_target(src_group, &ranked_candicates) {
-                return Ok(vec![action]);
+        // Only groups with the same replication factor are balanced with each other.
+        let mut classes: BTreeMap<usize, Vec<GroupDesc>> = BTreeMap::new();
+        for group in self.current_user_groups() {
+            classes
+                .entry(self.group_replicas(&group))
+                .or_default()
+                .push(group);
+        }
+        for (replicas, candicate_groups) in classes {
+            let mean_cnt = Self::mean_shard_count(&candicate_groups);
+            let ranked_candicates = Self::rank_group_for_balance(candicate_groups, mean_cnt);
+            debug!(
+                scored_nodes = ?ranked_candicates.iter().map(|(g, s)| format!("{}-{}({:?})", g.id, g.shards.len(), s)).collect::<Vec<_>>(),
+                mean = mean_cnt,
+                replicas = replicas,
+                "group ranked by shard count",
+            );
+            for (src_group, status) in &ranked_candicates {
+                if *status != BalanceStatus::Overfull {
+                    break;
+                }
+                if let Some(action) = self.rebalance_target(src_group, &ranked_candicates, mean_cnt)
+                {
+                    return Ok(vec![action]);
+                }
             }
         }
 
         Ok(vec![])
     }
 
-    fn mean_shard_count(&self) -> f64 {
+    fn misplaced_shard_action(&self) -> Option<ShardAction> {
+        let collections = self.alloc_source.collection_replicas();
         let groups = self.current_user_groups();
+        for group in &groups {
+            let group_replicas = self.group_replicas(group);
+            for shard in &group.shards {
+                // The collection might be in creating, skip it.
+                let replicas = match collections.get(&shard.collection_id) {
+                    Some(replicas) => self.effective_replicas(*replicas),
+                    None => continue,
+                };
+                if replicas == group_replicas {
+                    continue;
+                }
+                let target = groups
+                    .iter()
+                    .filter(|g| self.group_replicas(g) == replicas)
+                    .min_by_key(|g| g.shards.len());
+                if let Some(target) = target {
+                    return Some(ShardAction::Migrate(ReallocateShard {
+                        shard: shard.id,
+                        source_group: group.id,
+                        target_group: target.id,
+                    }));
+                }
+                debug!(
+                    "skip migrating shard {} of group {group_replicas} replicas, no group with {replicas} replicas",
+                    shard.id
+                );
+            }
+        }
+        None
+    }
+
+    fn group_replicas(&self, group: &GroupDesc) -> usize {
+        self.effective_replicas(group.replication_factor as usize)
+    }
+
+    fn effective_replicas(&self, replicas: usize) -> usize {
+        if replicas == 0 {
+            self.replicas_per_group
+        } else {
+            replicas
+        }
+    }
+
+    fn mean_shard_count(groups: &[GroupDesc]) -> f64 {
         let total_shards = groups.iter().map(|n| n.shards.len() as u64).sum::<u64>() as f64;
         total_shards / (groups.len() as f64)
     }
@@ -105,8 +176,8 @@ impl<T: AllocSource> ShardCountPolicy<T> {
         &self,
         source_group: &GroupDesc,
         ranked_candicates: &[(GroupDesc, BalanceStatus)],
+        mean: f64,
     ) -> Option<ShardAction> {
-        let mean = self.mean_shard_count();
         for (target, state) in ranked_candicates.iter().rev() {
             if *state != BalanceStatus::Underfull {
                 break;
@@ -134,9 +205,15 @@ impl<T: AllocSource> ShardCountPolicy<T> {
         src_group: &GroupDesc,
         _target_group: &GroupDesc,
     ) -> Option<ShardDesc> {
-        let replicas = src_group.shards.to_owned();
+        // The shards of collections with placement rules stay in their groups, otherwise the rules
+        // would be applied to the target group too.
+        let placements = self.alloc_source.collection_placements();
         // TODO: ranking shards and choose the preferred one
-        replicas.get(0).map(ToOwned::to_owned)
+        src_group
+            .shards
+            .iter()
+            .find(|s| !placements.contains_key(&s.collection_id))
+            .map(ToOwned::to_owned)
     }
 
     fn current_user_groups(&self) -> Vec<GroupDesc> {
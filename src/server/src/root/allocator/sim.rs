@@ -0,0 +1,818 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Drive the allocator against a described cluster without running any node, so the policies
+//! could be evaluated before rolling them out.
+//!
+//! The actions proposed by the allocator are applied immediately, and the cluster is scheduled
+//! step by step until it is balanced after each event.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use engula_api::server::v1::*;
+use serde::{Deserialize, Serialize};
+
+use super::{placement::Placement, policy_load, source::NodeFilter, *};
+use crate::{root::OngoingStats, Error, Result, RootConfig};
+
+const DEFAULT_MAX_STEPS: usize = 1000;
+
+/// The cluster to simulate, along with the events happening in order.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SimSpec {
+    /// The `[root]` section of the server config.
+    pub root: RootConfig,
+    /// The max scheduling steps after each event, the scheduling stops early once the cluster
+    /// is balanced.
+    pub max_steps: usize,
+    pub nodes: Vec<SimNode>,
+    pub groups: Vec<SimGroup>,
+    pub events: Vec<SimEvent>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SimNode {
+    pub id: u64,
+    pub cpu_nums: f64,
+    /// The disk capacity in bytes, 0 means the disk usage is unknown.
+    pub disk_capacity: u64,
+    pub region: String,
+    pub zone: String,
+    pub rack: String,
+    pub host: String,
+    pub labels: HashMap<String, String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SimGroup {
+    pub id: u64,
+    /// The nodes which the replicas are placed on.
+    pub nodes: Vec<u64>,
+    /// The node of the leader replica, the first replica is the leader if it isn't specified.
+    pub leader: Option<u64>,
+    pub shards: Vec<SimShard>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SimShard {
+    pub id: u64,
+    pub collection: u64,
+    /// The data size in bytes.
+    pub size: u64,
+    /// The requests served by the leader per second.
+    pub qps: f64,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SimEvent {
+    AddNode(SimNode),
+    /// The node is dead, its leaders are elected on the other replicas and its replicas are
+    /// replaced once the group still has the majority.
+    FailNode {
+        node: u64,
+    },
+    RecoverNode {
+        node: u64,
+    },
+    /// Change the qps of the shard.
+    LoadSpike {
+        shard: u64,
+        qps: f64,
+    },
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SimReport {
+    pub phases: Vec<SimPhase>,
+    /// The state of nodes after all events.
+    pub nodes: Vec<SimNodeState>,
+}
+
+/// The actions scheduled after an event, the first phase schedules the initial cluster.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SimPhase {
+    pub event: String,
+    /// Whether the cluster is balanced within the max steps.
+    pub balanced: bool,
+    pub actions: Vec<SimAction>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SimAction {
+    pub step: usize,
+    pub policy: String,
+    pub action: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct SimNodeState {
+    pub node: u64,
+    pub alive: bool,
+    pub replica_count: u64,
+    pub leader_count: u64,
+    pub disk_used: u64,
+    pub load: f64,
+}
+
+impl Default for SimSpec {
+    fn default() -> Self {
+        SimSpec {
+            root: RootConfig::default(),
+            max_steps: DEFAULT_MAX_STEPS,
+            nodes: Vec::new(),
+            groups: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+}
+
+impl Default for SimNode {
+    fn default() -> Self {
+        SimNode {
+            id: 0,
+            cpu_nums: 2.0,
+            disk_capacity: 0,
+            region: String::new(),
+            zone: String::new(),
+            rack: String::new(),
+            host: String::new(),
+            labels: HashMap::new(),
+        }
+    }
+}
+
+impl std::fmt::Display for SimEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SimEvent::AddNode(n) => write!(f, "add node {}", n.id),
+            SimEvent::FailNode { node } => write!(f, "fail node {node}"),
+            SimEvent::RecoverNode { node } => write!(f, "recover node {node}"),
+            SimEvent::LoadSpike { shard, qps } => write!(f, "set qps of shard {shard} to {qps}"),
+        }
+    }
+}
+
+/// Simulate the scheduling of the cluster.
+pub fn run(spec: SimSpec) -> Result<SimReport> {
+    futures::executor::block_on(Simulator::new(&spec)?.run(spec))
+}
+
+struct Simulator {
+    source: Arc<SimAllocSource>,
+    config: RootConfig,
+    max_steps: usize,
+}
+
+impl Simulator {
+    fn new(spec: &SimSpec) -> Result<Self> {
+        let source = Arc::new(SimAllocSource::default());
+        {
+            let mut state = source.state.lock().unwrap();
+            for node in &spec.nodes {
+                state.add_node(node.clone())?;
+            }
+            for group in &spec.groups {
+                state.add_group(group)?;
+            }
+        }
+        Ok(Simulator {
+            source,
+            config: spec.root.clone(),
+            max_steps: spec.max_steps,
+        })
+    }
+
+    async fn run(self, spec: SimSpec) -> Result<SimReport> {
+        let mut phases = vec![self.schedule("initial".into()).await?];
+        for event in spec.events {
+            let name = event.to_string();
+            let actions = self.apply_event(event).await?;
+            let mut phase = self.schedule(name).await?;
+            phase.actions.splice(0..0, actions);
+            phases.push(phase);
+        }
+        let nodes = self.source.state.lock().unwrap().node_states();
+        Ok(SimReport { phases, nodes })
+    }
+
+    async fn apply_event(&self, event: SimEvent) -> Result<Vec<SimAction>> {
+        let mut actions = Vec::new();
+        match event {
+            SimEvent::AddNode(node) => self.source.state.lock().unwrap().add_node(node)?,
+            SimEvent::FailNode { node } => {
+                let groups = {
+                    let mut state = self.source.state.lock().unwrap();
+                    state.node(node)?;
+                    state.failed.insert(node);
+                    actions.extend(state.elect_leaders());
+                    state
+                        .groups
+                        .values()
+                        .filter(|g| g.replicas.iter().any(|r| r.node_id == node))
+                        .cloned()
+                        .collect::<Vec<_>>()
+                };
+                for group in groups {
+                    actions.push(self.cure_group(group, node).await?);
+                }
+            }
+            SimEvent::RecoverNode { node } => {
+                let mut state = self.source.state.lock().unwrap();
+                state.node(node)?;
+                state.failed.remove(&node);
+            }
+            SimEvent::LoadSpike { shard, qps } => {
+                let mut state = self.source.state.lock().unwrap();
+                let shard = state
+                    .shards
+                    .get_mut(&shard)
+                    .ok_or_else(|| Error::InvalidArgument(format!("shard {shard} not found")))?;
+                shard.qps = qps;
+            }
+        }
+        Ok(actions)
+    }
+
+    /// Replace the replica on the failed node, like the group does once the replica is lost.
+    async fn cure_group(&self, group: GroupDesc, failed_node: u64) -> Result<SimAction> {
+        let replica = group
+            .replicas
+            .iter()
+            .find(|r| r.node_id == failed_node)
+            .unwrap();
+        let alive = {
+            let state = self.source.state.lock().unwrap();
+            group
+                .replicas
+                .iter()
+                .filter(|r| !state.failed.contains(&r.node_id))
+                .count()
+        };
+        let action = |action: String| SimAction {
+            step: 0,
+            policy: "cure".into(),
+            action,
+        };
+        if alive <= group.replicas.len() / 2 {
+            return Ok(action(format!(
+                "group {} lost the majority, replica {} on node {failed_node} is not replaced",
+                group.id, replica.id
+            )));
+        }
+        let exist_nodes = group.replicas.iter().map(|r| r.node_id).collect();
+        let target = self
+            .allocator()
+            .allocate_replica_for_group(group.id, exist_nodes, 1)
+            .await?
+            .pop();
+        let Some(target) = target else {
+            return Ok(action(format!(
+                "no node to replace replica {} of group {} on node {failed_node}",
+                replica.id, group.id
+            )));
+        };
+        self.source
+            .state
+            .lock()
+            .unwrap()
+            .move_replica(group.id, replica.id, target.id);
+        Ok(action(format!(
+            "replace replica {} of group {} on node {failed_node} with node {}",
+            replica.id, group.id, target.id
+        )))
+    }
+
+    /// Schedule the cluster until it is balanced or the max steps is reached.
+    async fn schedule(&self, event: String) -> Result<SimPhase> {
+        let mut phase = SimPhase {
+            event,
+            ..Default::default()
+        };
+        for step in 1..=self.max_steps {
+            let actions = self.step().await?;
+            if actions.is_empty() {
+                phase.balanced = true;
+                break;
+            }
+            phase
+                .actions
+                .extend(actions.into_iter().map(|(policy, action)| SimAction {
+                    step,
+                    policy,
+                    action,
+                }));
+        }
+        Ok(phase)
+    }
+
+    /// Compute and apply the actions of one scheduling round, like the reconcile scheduler does.
+    async fn step(&self) -> Result<Vec<(String, String)>> {
+        // The stats are refreshed as soon as the actions are applied, so the load balancing
+        // doesn't need to wait for them.
+        let alloc = self.allocator();
+        let mut actions = Vec::new();
+        match alloc.compute_group_action().await? {
+            GroupAction::Noop => {}
+            GroupAction::Add {
+                count,
+                replicas_per_group,
+            } => {
+                for _ in 0..count {
+                    let nodes = alloc
+                        .allocate_group_replica(vec![], replicas_per_group)
+                        .await?;
+                    if nodes.len() < replicas_per_group {
+                        break;
+                    }
+                    let nodes = nodes.iter().map(|n| n.id).collect::<Vec<_>>();
+                    let mut state = self.source.state.lock().unwrap();
+                    let id = state.groups.keys().last().map(|id| id + 1).unwrap_or(1);
+                    state.add_group(&SimGroup {
+                        id,
+                        nodes: nodes.clone(),
+                        ..Default::default()
+                    })?;
+                    actions.push((
+                        Policy::GroupCount.name().into(),
+                        format!("create group {id} on nodes {nodes:?}"),
+                    ));
+                }
+                return Ok(actions);
+            }
+            // Removing groups isn't supported by the scheduler either.
+            GroupAction::Remove(_) => {}
+        }
+
+        if alloc.balance_enabled(BalanceClass::Replica) {
+            if let Some((policy, replica_actions)) = alloc.propose_replica_action()? {
+                let mut state = self.source.state.lock().unwrap();
+                for ReplicaAction::Migrate(action) in replica_actions {
+                    state.move_replica(action.group, action.source_replica, action.target_node.id);
+                    actions.push((
+                        policy.name().into(),
+                        format!(
+                            "move replica {} of group {} from node {} to node {}",
+                            action.source_replica,
+                            action.group,
+                            action.source_node,
+                            action.target_node.id
+                        ),
+                    ));
+                }
+            }
+        }
+        if alloc.balance_enabled(BalanceClass::Shard) {
+            let shard_actions = alloc.propose_shard_action()?;
+            let mut state = self.source.state.lock().unwrap();
+            for ShardAction::Migrate(action) in shard_actions {
+                state.move_shard(action.shard, action.source_group, action.target_group);
+                actions.push((
+                    Policy::ShardCount.name().into(),
+                    format!(
+                        "move shard {} from group {} to group {}",
+                        action.shard, action.source_group, action.target_group
+                    ),
+                ));
+            }
+        }
+        if alloc.balance_enabled(BalanceClass::Leader) {
+            if let Some((policy, LeaderAction::Shed(action))) = alloc.propose_leader_action()? {
+                let mut state = self.source.state.lock().unwrap();
+                state.leaders.insert(action.group, action.target_replica);
+                actions.push((
+                    policy.name().into(),
+                    format!(
+                        "transfer leader of group {} from node {} to node {}",
+                        action.group, action.src_node, action.target_node
+                    ),
+                ));
+            }
+        }
+        Ok(actions)
+    }
+
+    fn allocator(&self) -> Allocator<SimAllocSource> {
+        Allocator::new(
+            self.source.clone(),
+            Arc::new(OngoingStats::default()),
+            self.config.clone(),
+        )
+    }
+}
+
+/// An `AllocSource` whose nodes, groups and stats are maintained by the simulator.
+#[derive(Default)]
+struct SimAllocSource {
+    state: Mutex<SimState>,
+}
+
+#[derive(Default)]
+struct SimState {
+    nodes: BTreeMap<u64, SimNode>,
+    failed: HashSet<u64>,
+    groups: BTreeMap<u64, GroupDesc>,
+    /// The leader replica of each group.
+    leaders: HashMap<u64, u64>,
+    shards: HashMap<u64, SimShard>,
+    next_replica_id: u64,
+}
+
+impl SimState {
+    fn node(&self, id: u64) -> Result<&SimNode> {
+        self.nodes
+            .get(&id)
+            .ok_or_else(|| Error::InvalidArgument(format!("node {id} not found")))
+    }
+
+    fn add_node(&mut self, node: SimNode) -> Result<()> {
+        if self.nodes.contains_key(&node.id) {
+            return Err(Error::InvalidArgument(format!(
+                "node {} already exists",
+                node.id
+            )));
+        }
+        self.nodes.insert(node.id, node);
+        Ok(())
+    }
+
+    fn add_group(&mut self, group: &SimGroup) -> Result<()> {
+        if self.groups.contains_key(&group.id) {
+            return Err(Error::InvalidArgument(format!(
+                "group {} already exists",
+                group.id
+            )));
+        }
+        let mut replicas = Vec::with_capacity(group.nodes.len());
+        for node_id in &group.nodes {
+            self.node(*node_id)?;
+            self.next_replica_id += 1;
+            replicas.push(ReplicaDesc {
+                id: self.next_replica_id,
+                node_id: *node_id,
+                role: ReplicaRole::Voter as i32,
+            });
+        }
+        let leader_node = group.leader.or_else(|| group.nodes.first().cloned());
+        if let Some(leader) = replicas.iter().find(|r| Some(r.node_id) == leader_node) {
+            self.leaders.insert(group.id, leader.id);
+        } else if group.leader.is_some() {
+            return Err(Error::InvalidArgument(format!(
+                "leader of group {} isn't placed on its nodes",
+                group.id
+            )));
+        }
+        let mut shards = Vec::with_capacity(group.shards.len());
+        for shard in &group.shards {
+            if self.shards.insert(shard.id, shard.clone()).is_some() {
+                return Err(Error::InvalidArgument(format!(
+                    "shard {} already exists",
+                    shard.id
+                )));
+            }
+            shards.push(ShardDesc {
+                id: shard.id,
+                collection_id: shard.collection,
+                ..Default::default()
+            });
+        }
+        self.groups.insert(
+            group.id,
+            GroupDesc {
+                id: group.id,
+                epoch: 1,
+                shards,
+                replicas,
+                ..Default::default()
+            },
+        );
+        Ok(())
+    }
+
+    /// Elect a leader for the groups whose leader is placed on the failed nodes.
+    fn elect_leaders(&mut self) -> Vec<SimAction> {
+        let mut actions = Vec::new();
+        for group in self.groups.values() {
+            let Some(leader) = self.leaders.get(&group.id) else {
+                continue;
+            };
+            let on_failed_node = group
+                .replicas
+                .iter()
+                .any(|r| r.id == *leader && self.failed.contains(&r.node_id));
+            if !on_failed_node {
+                continue;
+            }
+            if let Some(r) = group
+                .replicas
+                .iter()
+                .find(|r| !self.failed.contains(&r.node_id))
+            {
+                actions.push((group.id, r.id, r.node_id));
+            }
+        }
+        actions
+            .into_iter()
+            .map(|(group, replica, node)| {
+                self.leaders.insert(group, replica);
+                SimAction {
+                    step: 0,
+                    policy: "election".into(),
+                    action: format!(
+                        "elect replica {replica} on node {node} as leader of group {group}"
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    /// Replace the replica with a new one on the target node.
+    fn move_replica(&mut self, group_id: u64, replica_id: u64, target_node: u64) {
+        self.next_replica_id += 1;
+        let new_replica_id = self.next_replica_id;
+        let Some(group) = self.groups.get_mut(&group_id) else {
+            return;
+        };
+        let Some(replica) = group.replicas.iter_mut().find(|r| r.id == replica_id) else {
+            return;
+        };
+        replica.id = new_replica_id;
+        replica.node_id = target_node;
+        group.epoch += 1;
+        if self.leaders.get(&group_id) == Some(&replica_id) {
+            // The leader is transferred before the replica is removed.
+            if let Some(r) = group
+                .replicas
+                .iter()
+                .find(|r| r.id != new_replica_id && !self.failed.contains(&r.node_id))
+            {
+                self.leaders.insert(group_id, r.id);
+            }
+        }
+    }
+
+    fn move_shard(&mut self, shard_id: u64, src_group: u64, target_group: u64) {
+        let Some(src) = self.groups.get_mut(&src_group) else {
+            return;
+        };
+        let Some(pos) = src.shards.iter().position(|s| s.id == shard_id) else {
+            return;
+        };
+        let shard = src.shards.remove(pos);
+        src.epoch += 1;
+        if let Some(target) = self.groups.get_mut(&target_group) {
+            target.shards.push(shard);
+            target.epoch += 1;
+        }
+    }
+
+    fn is_leader(&self, group_id: u64, replica_id: u64) -> bool {
+        self.leaders.get(&group_id) == Some(&replica_id)
+    }
+
+    fn replica_stats(&self, group: &GroupDesc, replica_id: u64) -> ReplicaStats {
+        let shards = group.shards.iter().filter_map(|s| self.shards.get(&s.id));
+        let (size, qps) = shards.fold((0, 0.0), |(size, qps), s| (size + s.size, qps + s.qps));
+        ReplicaStats {
+            replica_id,
+            group_id: group.id,
+            read_qps: if self.is_leader(group.id, replica_id) {
+                qps as f32
+            } else {
+                0.0
+            },
+            data_size: size,
+            ..Default::default()
+        }
+    }
+
+    fn node_replicas(&self, node_id: u64) -> Vec<(ReplicaDesc, u64)> {
+        self.groups
+            .values()
+            .flat_map(|g| {
+                g.replicas
+                    .iter()
+                    .filter(|r| r.node_id == node_id)
+                    .map(|r| (r.clone(), g.id))
+            })
+            .collect()
+    }
+
+    fn node_desc(&self, node: &SimNode) -> NodeDesc {
+        let replicas = self.node_replicas(node.id);
+        let leader_count = replicas
+            .iter()
+            .filter(|(r, g)| self.is_leader(*g, r.id))
+            .count();
+        let disk_used = replicas
+            .iter()
+            .map(|(r, g)| self.replica_stats(&self.groups[g], r.id).data_size)
+            .sum();
+        NodeDesc {
+            id: node.id,
+            addr: String::new(),
+            capacity: Some(NodeCapacity {
+                cpu_nums: node.cpu_nums,
+                replica_count: replicas.len() as u64,
+                leader_count: leader_count as u64,
+                disk_capacity: node.disk_capacity,
+                disk_used,
+            }),
+            status: NodeStatus::Active as i32,
+            locality: Some(NodeLocality {
+                region: node.region.clone(),
+                zone: node.zone.clone(),
+                rack: node.rack.clone(),
+                host: node.host.clone(),
+                labels: node.labels.clone(),
+            }),
+        }
+    }
+
+    fn node_states(&self) -> Vec<SimNodeState> {
+        self.nodes
+            .values()
+            .map(|n| {
+                let desc = self.node_desc(n);
+                let capacity = desc.capacity.unwrap_or_default();
+                let load = self
+                    .node_replicas(n.id)
+                    .iter()
+                    .map(|(r, g)| {
+                        policy_load::replica_load(&self.replica_stats(&self.groups[g], r.id))
+                    })
+                    .sum();
+                SimNodeState {
+                    node: n.id,
+                    alive: !self.failed.contains(&n.id),
+                    replica_count: capacity.replica_count,
+                    leader_count: capacity.leader_count,
+                    disk_used: capacity.disk_used,
+                    load,
+                }
+            })
+            .collect()
+    }
+}
+
+#[crate::async_trait]
+impl AllocSource for SimAllocSource {
+    async fn refresh_all(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn nodes(&self, filter: NodeFilter) -> Vec<NodeDesc> {
+        let state = self.state.lock().unwrap();
+        state
+            .nodes
+            .values()
+            .filter(|n| match filter {
+                NodeFilter::All | NodeFilter::NotDecommissioned => true,
+                NodeFilter::Alive | NodeFilter::Schedulable => !state.failed.contains(&n.id),
+            })
+            .map(|n| state.node_desc(n))
+            .collect()
+    }
+
+    fn groups(&self) -> HashMap<u64, GroupDesc> {
+        let state = self.state.lock().unwrap();
+        state
+            .groups
+            .iter()
+            .map(|(id, g)| (*id, g.clone()))
+            .collect()
+    }
+
+    fn collection_replicas(&self) -> HashMap<u64, usize> {
+        HashMap::new()
+    }
+
+    fn collection_placements(&self) -> HashMap<u64, Placement> {
+        HashMap::new()
+    }
+
+    fn node_replicas(&self, node_id: &u64) -> Vec<(ReplicaDesc, u64)> {
+        self.state.lock().unwrap().node_replicas(*node_id)
+    }
+
+    fn replica_state(&self, replica_id: &u64) -> Option<ReplicaState> {
+        self.replica_states()
+            .into_iter()
+            .find(|s| s.replica_id == *replica_id)
+    }
+
+    fn replica_states(&self) -> Vec<ReplicaState> {
+        let state = self.state.lock().unwrap();
+        state
+            .groups
+            .values()
+            .flat_map(|g| {
+                g.replicas.iter().map(|r| ReplicaState {
+                    replica_id: r.id,
+                    group_id: g.id,
+                    term: 1,
+                    voted_for: 0,
+                    role: if state.is_leader(g.id, r.id) {
+                        RaftRole::Leader as i32
+                    } else {
+                        RaftRole::Follower as i32
+                    },
+                    node_id: r.node_id,
+                })
+            })
+            .collect()
+    }
+
+    fn replica_stats(&self, _: &u64, replica_id: &u64) -> Option<ReplicaStats> {
+        let state = self.state.lock().unwrap();
+        let group = state
+            .groups
+            .values()
+            .find(|g| g.replicas.iter().any(|r| r.id == *replica_id))?;
+        Some(state.replica_stats(group, *replica_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(id: u64, zone: &str) -> SimNode {
+        SimNode {
+            id,
+            zone: zone.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn sim_node_failure() {
+        let spec = SimSpec {
+            nodes: vec![node(1, "z1"), node(2, "z2"), node(3, "z3"), node(4, "z1")],
+            groups: (1..=4)
+                .map(|id| SimGroup {
+                    id,
+                    nodes: vec![1, 2, 3],
+                    shards: vec![SimShard {
+                        id,
+                        size: 1 << 20,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                })
+                .collect(),
+            events: vec![SimEvent::FailNode { node: 1 }],
+            ..Default::default()
+        };
+        let report = run(spec).unwrap();
+        assert_eq!(report.phases.len(), 2);
+        assert!(report.phases.iter().all(|p| p.balanced));
+
+        // The replicas on node 1 are replaced by the ones on node 4.
+        let failure = &report.phases[1];
+        assert!(failure.actions.iter().any(|a| a.policy == "election"));
+        assert!(failure.actions.iter().any(|a| a.policy == "cure"));
+        let nodes = report
+            .nodes
+            .iter()
+            .map(|n| (n.node, n))
+            .collect::<HashMap<_, _>>();
+        assert!(!nodes[&1].alive);
+        assert_eq!(nodes[&1].leader_count, 0);
+        assert!(nodes[&4].replica_count > 0);
+        let leaders = nodes.values().map(|n| n.leader_count).sum::<u64>();
+        assert_eq!(leaders, 4);
+    }
+
+    #[test]
+    fn sim_spec_validation() {
+        let spec = SimSpec {
+            nodes: vec![node(1, "z1")],
+            groups: vec![SimGroup {
+                id: 1,
+                nodes: vec![2],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        assert!(run(spec).is_err());
+    }
+}
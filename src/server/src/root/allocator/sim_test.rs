@@ -13,16 +13,19 @@
 // limitations under the License.
 
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, HashMap, HashSet},
     sync::{
         atomic::{AtomicU64, Ordering},
         Mutex,
     },
 };
 
-use engula_api::server::v1::*;
+use engula_api::{
+    server::v1::*,
+    v1::{placement_constraint, LeaderPreference, PlacementConstraint},
+};
 
-use super::*;
+use super::{placement::Placement, *};
 use crate::{
     constants::REPLICA_PER_GROUP, root::allocator::source::NodeFilter, runtime::ExecutorOwner,
 };
@@ -46,6 +49,7 @@ fn sim_boostrap_join_node_balance() {
                 node_id: 1,
                 role: ReplicaRole::Voter.into(),
             }],
+            ..Default::default()
         }]);
         p.set_nodes(vec![NodeDesc {
             id: 1,
@@ -54,8 +58,10 @@ fn sim_boostrap_join_node_balance() {
                 cpu_nums: 2.0,
                 replica_count: 1,
                 leader_count: 1,
+                ..Default::default()
             }),
             status: NodeStatus::Active as i32,
+            ..Default::default()
         }]);
         p.set_replica_states(vec![ReplicaState {
             replica_id: 1,
@@ -80,8 +86,10 @@ fn sim_boostrap_join_node_balance() {
                     cpu_nums: 2.0,
                     replica_count: 0,
                     leader_count: 0,
+                    ..Default::default()
                 }),
                 status: NodeStatus::Active as i32,
+                ..Default::default()
             },
             NodeDesc {
                 id: 3,
@@ -90,8 +98,10 @@ fn sim_boostrap_join_node_balance() {
                     cpu_nums: 2.0,
                     replica_count: 0,
                     leader_count: 0,
+                    ..Default::default()
                 }),
                 status: NodeStatus::Active as i32,
+                ..Default::default()
             },
         ]);
         p.set_nodes(nodes);
@@ -119,6 +129,7 @@ fn sim_boostrap_join_node_balance() {
                     role: ReplicaRole::Voter.into(),
                 },
             ],
+            ..Default::default()
         }]);
         p.set_replica_states(vec![
             ReplicaState {
@@ -153,8 +164,8 @@ fn sim_boostrap_join_node_balance() {
 
         let act = a.compute_group_action().await.unwrap();
         match act {
-            GroupAction::Add(n) => {
-                for _ in 0..n {
+            GroupAction::Add { count, .. } => {
+                for _ in 0..count {
                     let nodes = a
                         .allocate_group_replica(vec![], REPLICA_PER_GROUP)
                         .await
@@ -197,6 +208,7 @@ fn sim_boostrap_join_node_balance() {
                             epoch: 0,
                             shards: vec![],
                             replicas,
+                            ..Default::default()
                         },
                     );
                     p.set_groups(groups.values().into_iter().map(ToOwned::to_owned).collect());
@@ -214,7 +226,7 @@ fn sim_boostrap_join_node_balance() {
         p.display();
 
         println!("5. assign shard in groups");
-        let cg = a.place_group_for_shard(9).await.unwrap();
+        let cg = a.place_group_for_shard(9, 0).await.unwrap();
         for id in 0..9 {
             let group = cg.get(id % cg.len()).unwrap();
             p.assign_shard(group.id);
@@ -234,8 +246,10 @@ fn sim_boostrap_join_node_balance() {
                 cpu_nums: 2.0,
                 replica_count: 0,
                 leader_count: 0,
+                ..Default::default()
             }),
             status: NodeStatus::Active as i32,
+            ..Default::default()
         }]);
         p.set_nodes(nodes);
         p.display();
@@ -243,8 +257,8 @@ fn sim_boostrap_join_node_balance() {
         println!("7. balance group for new node");
         let act = a.compute_group_action().await.unwrap();
         match act {
-            GroupAction::Add(n) => {
-                for _ in 0..n {
+            GroupAction::Add { count, .. } => {
+                for _ in 0..count {
                     let nodes = a
                         .allocate_group_replica(vec![], REPLICA_PER_GROUP)
                         .await
@@ -287,6 +301,7 @@ fn sim_boostrap_join_node_balance() {
                             epoch: 0,
                             shards: vec![],
                             replicas,
+                            ..Default::default()
                         },
                     );
                     p.set_groups(groups.values().into_iter().map(ToOwned::to_owned).collect());
@@ -401,10 +416,625 @@ fn sim_boostrap_join_node_balance() {
     });
 }
 
+#[test]
+fn sim_collection_replication_factor() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+
+        println!("1. three nodes with three default groups");
+        p.set_nodes(
+            (1..=3)
+                .map(|id| NodeDesc {
+                    id,
+                    addr: "".into(),
+                    capacity: Some(NodeCapacity {
+                        cpu_nums: 2.0,
+                        replica_count: 0,
+                        leader_count: 0,
+                        ..Default::default()
+                    }),
+                    status: NodeStatus::Active as i32,
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        let mut replica_id_gen = 1;
+        let mut groups = Vec::new();
+        for group_id in 1..=3 {
+            let mut replicas = Vec::new();
+            for node_id in 1..=3 {
+                replicas.push(ReplicaDesc {
+                    id: replica_id_gen,
+                    node_id,
+                    role: ReplicaRole::Voter.into(),
+                });
+                replica_id_gen += 1;
+            }
+            groups.push(GroupDesc {
+                id: group_id,
+                replicas,
+                ..Default::default()
+            });
+        }
+        p.set_groups(groups);
+        let act = a.compute_group_action().await.unwrap();
+        assert!(matches!(act, GroupAction::Noop));
+
+        println!("2. collection 1 asks for RF=1 groups");
+        p.set_collection_replicas(1, 1);
+        let act = a.compute_group_action().await.unwrap();
+        let count = match act {
+            GroupAction::Add {
+                count,
+                replicas_per_group: 1,
+            } => count,
+            _ => unreachable!(),
+        };
+        assert_eq!(count, 3);
+        let mut group_id_gen = 4;
+        for _ in 0..count {
+            let nodes = a.allocate_group_replica(vec![], 1).await.unwrap();
+            assert_eq!(nodes.len(), 1);
+            let mut groups = p.groups();
+            groups.insert(
+                group_id_gen,
+                GroupDesc {
+                    id: group_id_gen,
+                    replicas: vec![ReplicaDesc {
+                        id: replica_id_gen,
+                        node_id: nodes[0].id,
+                        role: ReplicaRole::Voter.into(),
+                    }],
+                    replication_factor: 1,
+                    ..Default::default()
+                },
+            );
+            p.set_groups(groups.into_values().collect());
+            replica_id_gen += 1;
+            group_id_gen += 1;
+        }
+        let act = a.compute_group_action().await.unwrap();
+        assert!(matches!(act, GroupAction::Noop));
+        assert_eq!(a.current_groups(1), 3);
+        assert_eq!(a.current_groups(REPLICA_PER_GROUP), 3);
+
+        println!("3. shards of collection 1 only placed on RF=1 groups");
+        let cg = a.place_group_for_shard(3, 1).await.unwrap();
+        assert_eq!(cg.len(), 3);
+        assert!(cg.iter().all(|g| g.replication_factor == 1));
+        let cg = a.place_group_for_shard(3, 0).await.unwrap();
+        assert!(cg.iter().all(|g| g.replication_factor == 0));
+
+        println!("4. misplaced shard migrates to RF=1 group");
+        p.assign_collection_shard(2, 1);
+        let sact = a.compute_shard_action().await.unwrap();
+        assert_eq!(sact.len(), 1);
+        match &sact[0] {
+            ShardAction::Migrate(ReallocateShard {
+                shard,
+                source_group,
+                target_group,
+            }) => {
+                assert_eq!(*source_group, 2);
+                assert!(*target_group >= 4);
+                p.move_shards(*source_group, *target_group, *shard);
+            }
+        }
+        let sact = a.compute_shard_action().await.unwrap();
+        assert!(sact.is_empty());
+        p.display();
+    });
+}
+
+#[test]
+fn sim_locality_aware_placement() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+
+        let zone_of = |node_id: u64| format!("zone-{}", (node_id + 1) / 2);
+
+        println!("1. six nodes across three zones");
+        p.set_nodes(
+            (1..=6)
+                .map(|id| NodeDesc {
+                    id,
+                    addr: "".into(),
+                    capacity: Some(NodeCapacity {
+                        cpu_nums: 2.0,
+                        replica_count: 0,
+                        leader_count: 0,
+                        ..Default::default()
+                    }),
+                    status: NodeStatus::Active as i32,
+                    locality: Some(NodeLocality {
+                        region: "region".into(),
+                        zone: zone_of(id),
+                        rack: format!("rack-{id}"),
+                        host: format!("host-{id}"),
+                        ..Default::default()
+                    }),
+                })
+                .collect(),
+        );
+        let mut replica_id_gen = 1;
+        let mut groups = Vec::new();
+        for (group_id, nodes) in [(1, [1, 3, 5]), (2, [2, 4, 6]), (3, [1, 2, 3])] {
+            let mut replicas = Vec::new();
+            for node_id in nodes {
+                replicas.push(ReplicaDesc {
+                    id: replica_id_gen,
+                    node_id,
+                    role: ReplicaRole::Voter.into(),
+                });
+                replica_id_gen += 1;
+            }
+            groups.push(GroupDesc {
+                id: group_id,
+                replicas,
+                ..Default::default()
+            });
+        }
+        p.set_groups(groups);
+        p.display();
+
+        println!("2. new group replicas are spread across zones");
+        let nodes = a.allocate_group_replica(vec![], 3).await.unwrap();
+        let zones = nodes.iter().map(|n| zone_of(n.id)).collect::<HashSet<_>>();
+        assert_eq!(zones.len(), 3);
+        let nodes = a.allocate_group_replica(vec![1], 2).await.unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert!(nodes.iter().all(|n| zone_of(n.id) != zone_of(1)));
+        assert_ne!(zone_of(nodes[0].id), zone_of(nodes[1].id));
+
+        println!("3. rebalance replicas of group 3 across zones");
+        loop {
+            let racts = a.compute_replica_action().await.unwrap();
+            if racts.is_empty() {
+                break;
+            }
+            for act in &racts {
+                match act {
+                    ReplicaAction::Migrate(ReallocateReplica {
+                        group,
+                        source_replica,
+                        target_node,
+                        ..
+                    }) => {
+                        println!(
+                            "move group {} replica {} to {}",
+                            group, source_replica, target_node.id
+                        );
+                        p.move_replica(*source_replica, target_node.id)
+                    }
+                }
+            }
+        }
+        for group in p.groups().values() {
+            let zones = group
+                .replicas
+                .iter()
+                .map(|r| zone_of(r.node_id))
+                .collect::<HashSet<_>>();
+            assert_eq!(zones.len(), 3, "group {} is not spread", group.id);
+        }
+        p.display();
+    });
+}
+
+#[test]
+fn sim_disk_usage_balance() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+
+        println!("1. node 1 is nearly full while the replica counts are balanced");
+        let disk_used = [90, 50, 50, 10];
+        p.set_nodes(
+            (1..=4)
+                .map(|id| NodeDesc {
+                    id,
+                    addr: "".into(),
+                    capacity: Some(NodeCapacity {
+                        cpu_nums: 2.0,
+                        disk_capacity: 100,
+                        disk_used: disk_used[id as usize - 1],
+                        ..Default::default()
+                    }),
+                    status: NodeStatus::Active as i32,
+                    ..Default::default()
+                })
+                .collect(),
+        );
+        let mut replica_id_gen = 1;
+        let mut groups = Vec::new();
+        for (group_id, nodes) in [(1, [1, 2, 3]), (2, [1, 2, 4]), (3, [1, 3, 4])] {
+            let mut replicas = Vec::new();
+            for node_id in nodes {
+                replicas.push(ReplicaDesc {
+                    id: replica_id_gen,
+                    node_id,
+                    role: ReplicaRole::Voter.into(),
+                });
+                replica_id_gen += 1;
+            }
+            groups.push(GroupDesc {
+                id: group_id,
+                replicas,
+                ..Default::default()
+            });
+        }
+        p.set_groups(groups);
+        // The replicas of group 1, 2 and 3 on node 1.
+        p.set_replica_size(1, 30);
+        p.set_replica_size(4, 20);
+        p.set_replica_size(7, 5);
+        p.display();
+
+        println!("2. no replicas are placed on the nearly full node");
+        let nodes = a.allocate_group_replica(vec![], 3).await.unwrap();
+        assert_eq!(nodes.len(), 3);
+        assert!(nodes.iter().all(|n| n.id != 1));
+
+        println!("3. the largest replica is moved to the node with lowest disk usage");
+        let racts = a.compute_replica_action().await.unwrap();
+        assert_eq!(racts.len(), 1);
+        let ReplicaAction::Migrate(ReallocateReplica {
+            group,
+            source_node,
+            source_replica,
+            target_node,
+        }) = &racts[0];
+        assert_eq!(*group, 1);
+        assert_eq!(*source_node, 1);
+        assert_eq!(*source_replica, 1);
+        assert_eq!(target_node.id, 4);
+
+        println!("4. no more moves after the disk usage is below the high watermark");
+        p.move_replica(*source_replica, target_node.id);
+        p.set_disk_used(1, 60);
+        p.set_disk_used(4, 40);
+        let racts = a.compute_replica_action().await.unwrap();
+        assert!(racts.is_empty());
+    });
+}
+
+#[test]
+fn sim_load_balance() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+
+        println!("1. node 1 serves all leaders and all load");
+        setup_hot_leaders(&p);
+        p.display();
+
+        println!("2. a hot leader is transferred to an idle node");
+        let lacts = a.compute_leader_action().await.unwrap();
+        assert_eq!(lacts.len(), 1);
+        let LeaderAction::Shed(TransferLeader {
+            src_node,
+            src_replica,
+            target_node,
+            target_replica,
+            ..
+        }) = &lacts[0] else {
+            panic!("expect shed leader action");
+        };
+        assert_eq!(*src_node, 1);
+        assert_ne!(*target_node, 1);
+        p.transfer_leader(*src_replica, *target_replica);
+        p.set_replica_load(*src_replica, 0.0);
+        p.set_replica_load(*target_replica, 100.0);
+
+        println!("3. no more moves until the load stats are refreshed");
+        let lacts = a.compute_leader_action().await.unwrap();
+        assert!(lacts.is_empty());
+        let racts = a.compute_replica_action().await.unwrap();
+        assert!(racts.is_empty());
+
+        println!("4. the next hot leader is transferred to the remaining idle node");
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+        let lacts = a.compute_leader_action().await.unwrap();
+        assert_eq!(lacts.len(), 1);
+        let LeaderAction::Shed(TransferLeader {
+            src_node: next_src_node,
+            src_replica,
+            target_node: next_target_node,
+            target_replica,
+            ..
+        }) = &lacts[0] else {
+            panic!("expect shed leader action");
+        };
+        assert_eq!(*next_src_node, 1);
+        assert_ne!(*next_target_node, 1);
+        assert_ne!(next_target_node, target_node);
+        p.transfer_leader(*src_replica, *target_replica);
+        p.set_replica_load(*src_replica, 0.0);
+        p.set_replica_load(*target_replica, 100.0);
+
+        println!("5. no moves after the load is balanced");
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+        let lacts = a.compute_leader_action().await.unwrap();
+        assert!(lacts.is_empty());
+        let racts = a.compute_replica_action().await.unwrap();
+        assert!(racts.is_empty());
+        p.display();
+    });
+}
+
+#[test]
+fn sim_placement_constraints() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+
+        println!("1. node 3 has hdd disk, and the leader of group 1 is placed on zone z2");
+        p.set_nodes(
+            (1..=5)
+                .map(|id| NodeDesc {
+                    id,
+                    addr: "".into(),
+                    capacity: Some(NodeCapacity {
+                        cpu_nums: 2.0,
+                        ..Default::default()
+                    }),
+                    status: NodeStatus::Active as i32,
+                    locality: Some(NodeLocality {
+                        zone: if id <= 3 { "z1" } else { "z2" }.into(),
+                        labels: HashMap::from([(
+                            "disk".to_owned(),
+                            if id == 3 { "hdd" } else { "ssd" }.to_owned(),
+                        )]),
+                        ..Default::default()
+                    }),
+                })
+                .collect(),
+        );
+        let replicas = [1, 3, 4]
+            .into_iter()
+            .map(|node_id| ReplicaDesc {
+                id: node_id,
+                node_id,
+                role: ReplicaRole::Voter.into(),
+            })
+            .collect::<Vec<_>>();
+        p.set_groups(vec![GroupDesc {
+            id: 1,
+            replicas: replicas.clone(),
+            ..Default::default()
+        }]);
+        p.set_replica_states(
+            replicas
+                .iter()
+                .map(|r| ReplicaState {
+                    replica_id: r.id,
+                    group_id: 1,
+                    term: 1,
+                    voted_for: 0,
+                    role: if r.node_id == 4 {
+                        RaftRole::Leader
+                    } else {
+                        RaftRole::Follower
+                    }
+                    .into(),
+                    node_id: r.node_id,
+                })
+                .collect(),
+        );
+        p.assign_collection_shard(1, 10);
+        p.set_collection_placement(
+            10,
+            Placement {
+                constraints: vec![PlacementConstraint {
+                    kind: placement_constraint::Kind::Prohibited.into(),
+                    key: "disk".into(),
+                    value: "hdd".into(),
+                }],
+                leader_preferences: vec![LeaderPreference {
+                    key: "zone".into(),
+                    value: "z1".into(),
+                }],
+            },
+        );
+        p.display();
+
+        println!("2. the violations are reported");
+        let violations = a.placement_violations().await.unwrap();
+        assert_eq!(violations.len(), 2);
+        assert_eq!((violations[0].replica, violations[0].node), (3, 3));
+        assert_eq!((violations[1].replica, violations[1].node), (4, 4));
+
+        println!("3. new replicas are not placed on the prohibited node");
+        let nodes = a.allocate_replica_for_group(1, vec![], 5).await.unwrap();
+        assert_eq!(nodes.len(), 4);
+        assert!(nodes.iter().all(|n| n.id != 3));
+
+        println!("4. the replica on the prohibited node is moved out");
+        let racts = a.compute_replica_action().await.unwrap();
+        assert_eq!(racts.len(), 1);
+        let ReplicaAction::Migrate(ReallocateReplica {
+            source_replica,
+            target_node,
+            ..
+        }) = &racts[0];
+        assert_eq!(*source_replica, 3);
+        assert!(target_node.id == 2 || target_node.id == 5);
+        p.move_replica(*source_replica, target_node.id);
+
+        println!("5. the leader is transferred to the preferred zone");
+        let lacts = a.compute_leader_action().await.unwrap();
+        assert_eq!(lacts.len(), 1);
+        let LeaderAction::Shed(TransferLeader {
+            src_replica,
+            target_node,
+            target_replica,
+            ..
+        }) = &lacts[0] else {
+            panic!("expect shed leader action");
+        };
+        assert_eq!(*src_replica, 4);
+        assert_eq!(*target_node, 1);
+        p.transfer_leader(*src_replica, *target_replica);
+
+        println!("6. no more violations");
+        assert!(a.placement_violations().await.unwrap().is_empty());
+        assert!(a.compute_replica_action().await.unwrap().is_empty());
+        assert!(a.compute_leader_action().await.unwrap().is_empty());
+        p.display();
+    });
+}
+
+/// Three groups with replicas on node 1, 2 and 3, all leaders are placed on node 1 and serve
+/// 100 qps.
+fn setup_hot_leaders(p: &MockInfoProvider) {
+    p.set_nodes(
+        (1..=3)
+            .map(|id| NodeDesc {
+                id,
+                addr: "".into(),
+                capacity: Some(NodeCapacity {
+                    cpu_nums: 2.0,
+                    ..Default::default()
+                }),
+                status: NodeStatus::Active as i32,
+                ..Default::default()
+            })
+            .collect(),
+    );
+    let mut replica_id_gen = 1;
+    let mut groups = Vec::new();
+    let mut replica_states = Vec::new();
+    for group_id in 1..=3 {
+        let mut replicas = Vec::new();
+        for node_id in 1..=3 {
+            replicas.push(ReplicaDesc {
+                id: replica_id_gen,
+                node_id,
+                role: ReplicaRole::Voter.into(),
+            });
+            let role = if node_id == 1 {
+                p.set_replica_load(replica_id_gen, 100.0);
+                RaftRole::Leader
+            } else {
+                RaftRole::Follower
+            };
+            replica_states.push(ReplicaState {
+                replica_id: replica_id_gen,
+                group_id,
+                term: 1,
+                voted_for: 0,
+                role: role.into(),
+                node_id,
+            });
+            replica_id_gen += 1;
+        }
+        groups.push(GroupDesc {
+            id: group_id,
+            replicas,
+            ..Default::default()
+        });
+    }
+    p.set_groups(groups);
+    p.set_replica_states(replica_states);
+}
+
+#[test]
+fn sim_explain_schedule() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+        setup_hot_leaders(&p);
+
+        println!("1. the proposed actions are explained without executing");
+        for _ in 0..2 {
+            let explain = a.explain().await.unwrap();
+            assert!(!explain.load_balance_cooling);
+            assert_eq!(explain.nodes.len(), 3);
+            assert_eq!(explain.nodes[0].load, 300.0);
+            let leader_actions = explain
+                .actions
+                .iter()
+                .filter(|act| act.kind == "leader")
+                .collect::<Vec<_>>();
+            assert_eq!(leader_actions.len(), 1);
+            assert_eq!(leader_actions[0].policy, "load");
+            assert_eq!(leader_actions[0].source.as_ref().unwrap().node, 1);
+        }
+
+        println!("2. the dry run doesn't affect the following scheduling");
+        let lacts = a.compute_leader_action().await.unwrap();
+        assert_eq!(lacts.len(), 1);
+        assert!(a.explain().await.unwrap().load_balance_cooling);
+    });
+}
+
+#[test]
+fn sim_pause_balance() {
+    let executor_owner = ExecutorOwner::new(1);
+    let executor = executor_owner.executor();
+    executor.block_on(async {
+        let p = Arc::new(MockInfoProvider::new());
+        let d = Arc::new(OngoingStats::default());
+        let a = Allocator::new(p.clone(), d.clone(), RootConfig::default());
+        setup_hot_leaders(&p);
+        let leader_policies = |explain: ScheduleExplain| {
+            explain
+                .actions
+                .into_iter()
+                .filter(|act| act.kind == "leader")
+                .map(|act| act.policy)
+                .collect::<Vec<_>>()
+        };
+
+        println!("1. no leader is moved if the leader balancing is paused");
+        let mut control = ScheduleControl::default();
+        BalanceClass::Leader.set_paused(&mut control, true);
+        a.set_schedule_control(control.clone());
+        assert!(a.compute_leader_action().await.unwrap().is_empty());
+        assert!(leader_policies(a.explain().await.unwrap()).is_empty());
+
+        println!("2. the leaders are balanced by count if the load balancing is paused");
+        BalanceClass::Leader.set_paused(&mut control, false);
+        BalanceClass::Load.set_paused(&mut control, true);
+        a.set_schedule_control(control.clone());
+        assert_eq!(
+            leader_policies(a.explain().await.unwrap()),
+            vec!["leader_count"]
+        );
+
+        println!("3. the leaders are balanced by load after resuming");
+        a.set_schedule_control(ScheduleControl::default());
+        assert_eq!(leader_policies(a.explain().await.unwrap()), vec!["load"]);
+    });
+}
+
 pub struct MockInfoProvider {
     nodes: Arc<Mutex<Vec<NodeDesc>>>,
     groups: Arc<Mutex<GroupInfo>>,
     replicas: Arc<Mutex<HashMap<u64, ReplicaState>>>,
+    collections: Arc<Mutex<HashMap<u64, usize>>>,
+    placements: Arc<Mutex<HashMap<u64, Placement>>>,
+    replica_stats: Arc<Mutex<HashMap<u64, ReplicaStats>>>,
     shard_id_gen: AtomicU64,
 }
 
@@ -420,6 +1050,9 @@ impl MockInfoProvider {
             nodes: Default::default(),
             groups: Default::default(),
             replicas: Default::default(),
+            collections: Default::default(),
+            placements: Default::default(),
+            replica_stats: Default::default(),
             shard_id_gen: AtomicU64::new(1),
         }
     }
@@ -441,6 +1074,14 @@ impl AllocSource for MockInfoProvider {
         groups.descs.to_owned()
     }
 
+    fn collection_replicas(&self) -> HashMap<u64, usize> {
+        self.collections.lock().unwrap().to_owned()
+    }
+
+    fn collection_placements(&self) -> HashMap<u64, Placement> {
+        self.placements.lock().unwrap().to_owned()
+    }
+
     fn node_replicas(&self, node_id: &u64) -> Vec<(ReplicaDesc, u64)> {
         let groups = self.groups.lock().unwrap();
         groups
@@ -459,6 +1100,11 @@ impl AllocSource for MockInfoProvider {
         let replica_info = self.replicas.lock().unwrap();
         replica_info.iter().map(|e| e.1.to_owned()).collect()
     }
+
+    fn replica_stats(&self, _: &u64, replica_id: &u64) -> Option<ReplicaStats> {
+        let replica_stats = self.replica_stats.lock().unwrap();
+        replica_stats.get(replica_id).cloned()
+    }
 }
 
 impl MockInfoProvider {
@@ -600,12 +1246,47 @@ impl MockInfoProvider {
         self.set_groups(groups.values().map(ToOwned::to_owned).collect());
     }
 
+    fn set_replica_size(&self, replica_id: u64, size: u64) {
+        let mut replica_stats = self.replica_stats.lock().unwrap();
+        replica_stats.entry(replica_id).or_default().data_size = size;
+    }
+
+    fn set_replica_load(&self, replica_id: u64, qps: f32) {
+        let mut replica_stats = self.replica_stats.lock().unwrap();
+        let stats = replica_stats.entry(replica_id).or_default();
+        stats.read_qps = qps;
+        stats.write_qps = 0.0;
+    }
+
+    fn set_disk_used(&self, node_id: u64, disk_used: u64) {
+        let mut nodes = self.nodes(NodeFilter::All);
+        for n in nodes.iter_mut().filter(|n| n.id == node_id) {
+            n.capacity.as_mut().unwrap().disk_used = disk_used;
+        }
+        self.set_nodes(nodes);
+    }
+
+    fn set_collection_replicas(&self, collection_id: u64, replicas: usize) {
+        let mut collections = self.collections.lock().unwrap();
+        collections.insert(collection_id, replicas);
+    }
+
+    fn set_collection_placement(&self, collection_id: u64, placement: Placement) {
+        let mut placements = self.placements.lock().unwrap();
+        placements.insert(collection_id, placement);
+    }
+
     pub fn assign_shard(&self, group_id: u64) {
+        self.assign_collection_shard(group_id, 0)
+    }
+
+    pub fn assign_collection_shard(&self, group_id: u64, collection_id: u64) {
         let mut groups = self.groups();
         for group in groups.values_mut() {
             if group.id == group_id {
                 let s = ShardDesc {
                     id: self.shard_id_gen.fetch_add(1, Ordering::Relaxed),
+                    collection_id,
                     ..Default::default()
                 };
                 group.shards.push(s);
@@ -13,18 +13,17 @@
 // limitations under the License.
 
 use std::{
-    collections::{hash_map::Entry, HashMap},
+    collections::{hash_map::Entry, BTreeSet, HashMap, HashSet},
     sync::{Arc, Mutex},
 };
 
 use engula_api::server::v1::*;
 
-use super::RootShared;
+use super::{placement::Placement, RootShared};
 use crate::{root::liveness::Liveness, Result};
 
 pub enum NodeFilter {
     All,
-    #[allow(dead_code)]
     Alive,
     Schedulable,
     NotDecommissioned,
@@ -38,11 +37,70 @@ pub trait AllocSource {
 
     fn groups(&self) -> HashMap<u64, GroupDesc>;
 
+    /// The effective replication factor of each collection, 0 means the default replicas per
+    /// group.
+    fn collection_replicas(&self) -> HashMap<u64, usize>;
+
+    /// The effective placement rules of each collection.
+    fn collection_placements(&self) -> HashMap<u64, Placement>;
+
     fn node_replicas(&self, node_id: &u64) -> Vec<(ReplicaDesc, u64)>;
 
     fn replica_state(&self, replica_id: &u64) -> Option<ReplicaState>;
 
     fn replica_states(&self) -> Vec<ReplicaState>;
+
+    /// The stats of the replica which placed on the node.
+    fn replica_stats(&self, node_id: &u64, replica_id: &u64) -> Option<ReplicaStats>;
+
+    /// The nodes of each group, include the ones in replica states.
+    fn group_nodes(&self) -> HashMap<u64, HashSet<u64>> {
+        let mut groups = self
+            .groups()
+            .into_iter()
+            .map(|(group, desc)| {
+                (
+                    group,
+                    desc.replicas
+                        .iter()
+                        .map(|r| r.node_id)
+                        .collect::<HashSet<u64>>(),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+
+        let replica_states = self.replica_states();
+        for replica_state in replica_states {
+            if let Some(g) = groups.get_mut(&replica_state.group_id) {
+                g.insert(replica_state.node_id);
+            }
+        }
+        groups
+    }
+
+    /// The placement rules of each group, merged from the collections whose shards are placed on
+    /// the group. The groups without any rules are not included.
+    fn group_placements(&self) -> HashMap<u64, Placement> {
+        let collections = self.collection_placements();
+        let mut placements = HashMap::new();
+        for (group_id, desc) in self.groups() {
+            let collection_ids = desc
+                .shards
+                .iter()
+                .map(|s| s.collection_id)
+                .collect::<BTreeSet<_>>();
+            let mut placement = Placement::default();
+            for id in collection_ids {
+                if let Some(p) = collections.get(&id) {
+                    placement.merge(p);
+                }
+            }
+            if !placement.is_empty() {
+                placements.insert(group_id, placement);
+            }
+        }
+        placements
+    }
 }
 
 #[derive(Clone)]
@@ -53,6 +111,8 @@ pub struct SysAllocSource {
     nodes: Arc<Mutex<Vec<NodeDesc>>>,
     groups: Arc<Mutex<GroupInfo>>,
     replicas: Arc<Mutex<ReplicaInfo>>,
+    collections: Arc<Mutex<HashMap<u64, usize>>>,
+    placements: Arc<Mutex<HashMap<u64, Placement>>>,
 }
 
 #[derive(Default)]
@@ -74,6 +134,8 @@ impl SysAllocSource {
             nodes: Default::default(),
             groups: Default::default(),
             replicas: Default::default(),
+            collections: Default::default(),
+            placements: Default::default(),
         }
     }
 }
@@ -87,6 +149,8 @@ impl AllocSource for SysAllocSource {
         crate::runtime::yield_now().await;
         self.reload_replica_status().await?;
         crate::runtime::yield_now().await;
+        self.reload_collections().await?;
+        crate::runtime::yield_now().await;
         Ok(())
     }
 
@@ -116,6 +180,14 @@ impl AllocSource for SysAllocSource {
         groups.descs.to_owned()
     }
 
+    fn collection_replicas(&self) -> HashMap<u64, usize> {
+        self.collections.lock().unwrap().to_owned()
+    }
+
+    fn collection_placements(&self) -> HashMap<u64, Placement> {
+        self.placements.lock().unwrap().to_owned()
+    }
+
     fn node_replicas(&self, node_id: &u64) -> Vec<(ReplicaDesc, u64)> {
         let groups = self.groups.lock().unwrap();
         groups
@@ -138,6 +210,10 @@ impl AllocSource for SysAllocSource {
             .map(|e| e.1.to_owned())
             .collect()
     }
+
+    fn replica_stats(&self, node_id: &u64, replica_id: &u64) -> Option<ReplicaStats> {
+        self.root.replica_stats(*node_id, *replica_id)
+    }
 }
 
 impl SysAllocSource {
@@ -206,4 +282,41 @@ impl SysAllocSource {
             },
         );
     }
+
+    async fn reload_collections(&self) -> Result<()> {
+        let schema = self.root.schema()?;
+        let databases = schema
+            .list_database()
+            .await?
+            .into_iter()
+            .map(|db| (db.id, db))
+            .collect::<HashMap<_, _>>();
+        let mut collections = HashMap::new();
+        let mut placements = HashMap::new();
+        for co in schema.list_collection().await? {
+            let db = databases.get(&co.db);
+            let replicas = match co.replication_factor {
+                0 => db.map(|db| db.replication_factor).unwrap_or_default(),
+                n => n,
+            };
+            let placement = Placement::of_collection(db, &co);
+            if !placement.is_empty() {
+                placements.insert(co.id, placement);
+            }
+            collections.insert(co.id, replicas as usize);
+        }
+        self.set_collections(collections);
+        self.set_placements(placements);
+        Ok(())
+    }
+
+    fn set_collections(&self, cs: HashMap<u64, usize>) {
+        let mut collections = self.collections.lock().unwrap();
+        let _ = std::mem::replace(&mut *collections, cs);
+    }
+
+    fn set_placements(&self, ps: HashMap<u64, Placement>) {
+        let mut placements = self.placements.lock().unwrap();
+        let _ = std::mem::replace(&mut *placements, ps);
+    }
 }
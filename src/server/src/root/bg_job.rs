@@ -13,20 +13,29 @@
 // limitations under the License.
 
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashMap, HashSet},
     sync::{atomic, Arc, Mutex},
     task::{Poll, Waker},
+    time::Duration,
 };
 
-use engula_api::server::v1::{GroupDesc, ReplicaDesc, ReplicaRole, RootDesc, ShardDesc};
+use engula_api::{
+    server::v1::{
+        watch_response::{update_event, UpdateEvent},
+        GroupDesc, RaftRole, ReplicaDesc, ReplicaRole, RestoreReplicaRequest, RootDesc, ShardDesc,
+    },
+    v1::{index_desc, CollectionDesc, DatabaseDesc},
+};
 use futures::future::poll_fn;
 use prometheus::HistogramTimer;
+use prost::Message;
 use tokio::time::Instant;
 use tracing::{error, info, warn};
 
-use super::{allocator::*, HeartbeatQueue, HeartbeatTask, RootShared, Schema};
+use super::{allocator::*, HeartbeatQueue, HeartbeatTask, RootShared, Schema, SYSTEM_DATABASE_ID};
 use crate::{
-    constants::INITIAL_EPOCH,
+    backup,
+    constants::{INITIAL_EPOCH, ROOT_GROUP_ID},
     root::metrics,
     serverpb::v1::{background_job::Job, *},
     Result,
@@ -63,6 +72,107 @@ impl Jobs {
         Ok(())
     }
 
+    /// Submit a reconcile task on behalf of operators. The task is executed by the reconcile
+    /// scheduler, and the job is moved to history once the task is finished or aborted.
+    pub async fn submit_manual_schedule(&self, task: ReconcileTask) -> Result<u64> {
+        self.core.check_root_leader()?;
+        let job = self
+            .core
+            .append(BackgroundJob {
+                job: Some(Job::ManualSchedule(ManualScheduleJob {
+                    task: Some(task),
+                    status: ManualScheduleStatus::ManualSchedulePending as i32,
+                    created_time: format!("{:?}", Instant::now()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            })
+            .await?;
+        Ok(job.id)
+    }
+
+    /// Submit a backup of the cluster to the destination, the id of the job is returned.
+    pub async fn submit_backup(&self, destination: String) -> Result<u64> {
+        self.core.check_root_leader()?;
+        let job = self
+            .core
+            .append(BackgroundJob {
+                job: Some(Job::Backup(BackupJob {
+                    destination,
+                    status: BackupStatus::BackupInit as i32,
+                    created_time: format!("{:?}", Instant::now()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            })
+            .await?;
+        Ok(job.id)
+    }
+
+    /// Submit a restore of the backup. All databases and collections of the backup are restored
+    /// if `collection` is empty, otherwise only `database`.`collection` is restored under the
+    /// name `new_name`. The id of the job is returned.
+    pub async fn submit_restore(
+        &self,
+        backup: String,
+        database: String,
+        collection: String,
+        new_name: String,
+        restore_time: u64,
+    ) -> Result<u64> {
+        self.core.check_root_leader()?;
+        let job = self
+            .core
+            .append(BackgroundJob {
+                job: Some(Job::Restore(RestoreJob {
+                    backup,
+                    status: RestoreStatus::RestoreInit as i32,
+                    database,
+                    collection,
+                    new_name,
+                    restore_time,
+                    created_time: format!("{:?}", Instant::now()),
+                    ..Default::default()
+                })),
+                ..Default::default()
+            })
+            .await?;
+        Ok(job.id)
+    }
+
+    /// The pending manual schedule jobs.
+    pub fn manual_schedule_jobs(&self) -> Vec<(u64, ManualScheduleJob)> {
+        let mem_jobs = self.core.mem_jobs.lock().unwrap();
+        mem_jobs
+            .jobs
+            .iter()
+            .filter_map(|j| match j.job.as_ref() {
+                Some(Job::ManualSchedule(manual)) => Some((j.id, manual.to_owned())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub async fn finish_manual_schedule(
+        &self,
+        id: u64,
+        status: ManualScheduleStatus,
+        remark: String,
+    ) -> Result<()> {
+        let job = {
+            let mem_jobs = self.core.mem_jobs.lock().unwrap();
+            mem_jobs.jobs.iter().find(|j| j.id == id).cloned()
+        };
+        let Some(mut job) = job else {
+            return Ok(());
+        };
+        if let Some(Job::ManualSchedule(manual)) = job.job.as_mut() {
+            manual.status = status as i32;
+            manual.remark = remark;
+        }
+        self.core.finish(job).await
+    }
+
     pub async fn wait_more_jobs(&self) {
         self.core.wait_more_jobs().await;
     }
@@ -101,6 +211,16 @@ impl Jobs {
             background_job::Job::PurgeDatabase(purge_database) => {
                 self.handle_purge_database(job, purge_database).await
             }
+            background_job::Job::BackfillIndex(backfill_index) => {
+                self.handle_backfill_index(job, backfill_index).await
+            }
+            background_job::Job::SyncCollection(sync_collection) => {
+                self.handle_sync_collection(job, sync_collection).await
+            }
+            background_job::Job::Backup(backup) => self.handle_backup(job, backup).await,
+            background_job::Job::Restore(restore) => self.handle_restore(job, restore).await,
+            // The manual schedule jobs are executed by the reconcile scheduler.
+            background_job::Job::ManualSchedule(_) => Ok(()),
         };
         info!("backgroud job: {job:?}, handle result: {r:?}");
         r
@@ -153,7 +273,11 @@ impl Jobs {
                 break;
             }
             let shard = shard.unwrap();
-            let groups = self.core.alloc.place_group_for_shard(1).await?;
+            let groups = self
+                .core
+                .alloc
+                .place_group_for_shard(1, create_collection.replication_factor as usize)
+                .await?;
             if groups.is_empty() {
                 return Err(crate::Error::ResourceExhausted("no engouth groups".into()));
             }
@@ -361,6 +485,7 @@ impl Jobs {
             epoch: INITIAL_EPOCH,
             shards: vec![],
             replicas,
+            replication_factor: create_group.request_replica_cnt as u32,
         };
         create_group.group_desc = Some(group_desc);
         create_group.wait_create = nodes;
@@ -566,6 +691,620 @@ impl Jobs {
     }
 }
 
+impl Jobs {
+    async fn handle_backfill_index(
+        &self,
+        job: &BackgroundJob,
+        backfill_index: &BackfillIndexJob,
+    ) -> Result<()> {
+        const BACKFILL_BATCH_SIZE: u64 = 256;
+
+        let mut backfill_index = backfill_index.to_owned();
+        let index = backfill_index.index.clone().unwrap();
+        let schema = self.core.root_shared.schema()?;
+        let Some(desc) = schema.get_collection_by_id(backfill_index.collection_id).await? else {
+            warn!(
+                collection = backfill_index.collection_id,
+                "backfill index: collection is not found, skip it"
+            );
+            let mut job = job.to_owned();
+            job.job = Some(background_job::Job::BackfillIndex(backfill_index));
+            return self.core.finish(job).await;
+        };
+        // The writes maintain the index entries once the index is synchronized to the shards, so
+        // the records written after the sync are never missed by backfilling.
+        self.sync_collection_meta(&desc).await?;
+
+        let group_shards = schema
+            .get_collection_shards(backfill_index.collection_id)
+            .await?;
+        while let Some(shard_id) = backfill_index.wait_backfill.last().cloned() {
+            let group_id = match group_shards.iter().find(|(_, s)| s.id == shard_id) {
+                Some((group_id, _)) => *group_id,
+                None => {
+                    warn!(
+                        shard = shard_id,
+                        "backfill index: shard is not found, skip it"
+                    );
+                    backfill_index.wait_backfill.pop();
+                    backfill_index.next_key.clear();
+                    continue;
+                }
+            };
+            let mut group_client = self
+                .core
+                .root_shared
+                .transport_manager
+                .lazy_group_client(group_id);
+            let next_key = group_client
+                .backfill_index(
+                    shard_id,
+                    index.id,
+                    backfill_index.next_key.to_owned(),
+                    BACKFILL_BATCH_SIZE,
+                )
+                .await?;
+            match next_key {
+                Some(next_key) => backfill_index.next_key = next_key,
+                None => {
+                    backfill_index.wait_backfill.pop();
+                    backfill_index.next_key.clear();
+                }
+            }
+            self.save_backfill_index(job.id, &backfill_index).await?;
+        }
+
+        // Publish the index so that it is available for scanning.
+        if let Some(mut desc) = schema
+            .get_collection_by_id(backfill_index.collection_id)
+            .await?
+        {
+            for i in desc.indexes.iter_mut().filter(|i| i.id == index.id) {
+                i.state = index_desc::State::Public as i32;
+            }
+            desc.epoch += 1;
+            schema.update_collection(desc.to_owned()).await?;
+            self.sync_collection_meta(&desc).await?;
+            self.core
+                .root_shared
+                .watcher_hub
+                .notify_updates(vec![UpdateEvent {
+                    event: Some(update_event::Event::Collection(desc)),
+                }])
+                .await;
+        }
+
+        let mut job = job.to_owned();
+        job.job = Some(background_job::Job::BackfillIndex(backfill_index));
+        self.core.finish(job).await?;
+        Ok(())
+    }
+
+    async fn handle_sync_collection(
+        &self,
+        job: &BackgroundJob,
+        sync_collection: &SyncCollectionJob,
+    ) -> Result<()> {
+        let schema = self.core.root_shared.schema()?;
+        // The latest metadata is synchronized, so the delayed jobs never roll it back.
+        if let Some(desc) = schema
+            .get_collection_by_id(sync_collection.collection_id)
+            .await?
+        {
+            self.sync_collection_meta(&desc).await?;
+        }
+        self.core.finish(job.to_owned()).await?;
+        Ok(())
+    }
+
+    /// Synchronize the collection metadata to the groups, until all shards of the collection are
+    /// observed with it. The shards migrated during synchronizing are synchronized again.
+    async fn sync_collection_meta(&self, desc: &CollectionDesc) -> Result<()> {
+        let schema = self.core.root_shared.schema()?;
+        // The collections of a dropped database are being purged, nothing is inherited from it.
+        let db = schema
+            .get_database_by_id(desc.db)
+            .await?
+            .unwrap_or_default();
+        let meta = super::collection_meta(&db, desc);
+        loop {
+            self.core.check_root_leader()?;
+            let stale_groups = schema
+                .get_collection_shards(desc.id)
+                .await?
+                .into_iter()
+                .filter(|(_, s)| s.collection_meta.as_ref().map(|m| m.epoch) < Some(meta.epoch))
+                .map(|(group_id, _)| group_id)
+                .collect::<BTreeSet<_>>();
+            if stale_groups.is_empty() {
+                return Ok(());
+            }
+            for group_id in stale_groups {
+                let mut group_client = self
+                    .core
+                    .root_shared
+                    .transport_manager
+                    .lazy_group_client(group_id);
+                if let Err(err) = group_client.sync_collection(desc.id, meta.clone()).await {
+                    warn!(
+                        group = group_id,
+                        collection = desc.id,
+                        "sync collection meta: {err:?}"
+                    );
+                }
+            }
+            // Wait the group descriptors to be reported.
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    async fn save_backfill_index(
+        &self,
+        job_id: u64,
+        backfill_index: &BackfillIndexJob,
+    ) -> Result<()> {
+        self.core
+            .update(BackgroundJob {
+                id: job_id,
+                job: Some(background_job::Job::BackfillIndex(
+                    backfill_index.to_owned(),
+                )),
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+impl Jobs {
+    async fn handle_backup(&self, job: &BackgroundJob, backup: &BackupJob) -> Result<()> {
+        let mut backup = backup.to_owned();
+        loop {
+            match BackupStatus::from_i32(backup.status).unwrap() {
+                BackupStatus::BackupInit => self.handle_init_backup(job, &mut backup).await?,
+                BackupStatus::BackupRunning => self.handle_running_backup(job, &mut backup).await?,
+                BackupStatus::BackupFinish | BackupStatus::BackupAbort => {
+                    return self.handle_finish_backup(job, backup).await
+                }
+            }
+        }
+    }
+
+    async fn handle_init_backup(&self, job: &BackgroundJob, backup: &mut BackupJob) -> Result<()> {
+        let storage = match backup::open_storage(&backup.destination) {
+            Ok(storage) => storage,
+            Err(err) => {
+                backup.status = BackupStatus::BackupAbort as i32;
+                backup.remark = format!("open storage: {err}");
+                return self.save_backup(job.id, backup).await;
+            }
+        };
+        if storage.exists(backup::MANIFEST).await? {
+            backup.status = BackupStatus::BackupAbort as i32;
+            backup.remark = "the destination already contains a backup".into();
+            return self.save_backup(job.id, backup).await;
+        }
+
+        let schema = self.core.root_shared.schema()?;
+        backup.manifest = Some(BackupManifest {
+            backup_id: job.id,
+            cluster_id: schema.cluster_id().await?.unwrap_or_default(),
+            created_time: backup.created_time.clone(),
+            databases: schema.list_database().await?,
+            collections: schema.list_collection().await?,
+            groups: vec![],
+        });
+        backup.wait_groups = schema
+            .list_group()
+            .await?
+            .into_iter()
+            .filter(|g| g.id != ROOT_GROUP_ID && !g.shards.is_empty())
+            .map(|g| g.id)
+            .collect();
+        backup.status = BackupStatus::BackupRunning as i32;
+        info!(
+            job = job.id,
+            groups = backup.wait_groups.len(),
+            "start backup to {}",
+            backup.destination
+        );
+        self.save_backup(job.id, backup).await
+    }
+
+    async fn handle_running_backup(
+        &self,
+        job: &BackgroundJob,
+        backup: &mut BackupJob,
+    ) -> Result<()> {
+        const MAX_RETAKE_ROUNDS: u32 = 3;
+
+        let schema = self.core.root_shared.schema()?;
+        while let Some(group_id) = backup.wait_groups.last().cloned() {
+            let group_backup = self.backup_group(group_id, &backup.destination).await?;
+            let manifest = backup.manifest.as_mut().unwrap();
+            manifest.groups.retain(|g| g.group_id != group_id);
+            manifest.groups.extend(group_backup);
+            backup.wait_groups.pop();
+            self.save_backup(job.id, backup).await?;
+        }
+
+        // The shards might be moved between groups during backup, so a shard could be missed or
+        // be taken twice. Take the checkpoints of the involved groups again in such cases.
+        let groups = schema.list_group().await?;
+        let retake_groups = verify_backup_shards(backup.manifest.as_ref().unwrap(), &groups);
+        if !retake_groups.is_empty() {
+            if backup.retake_rounds >= MAX_RETAKE_ROUNDS {
+                backup.status = BackupStatus::BackupAbort as i32;
+                backup.remark = format!(
+                    "the shards are still moving after {} rounds, groups {retake_groups:?}",
+                    backup.retake_rounds
+                );
+            } else {
+                warn!(
+                    job = job.id,
+                    "backup: retake the checkpoints of groups {retake_groups:?}"
+                );
+                backup.retake_rounds += 1;
+                backup.wait_groups = retake_groups;
+            }
+            return self.save_backup(job.id, backup).await;
+        }
+
+        let storage = backup::open_storage(&backup.destination)?;
+        let manifest = backup.manifest.as_mut().unwrap();
+        manifest.finished_time = unix_timestamp_secs();
+        storage
+            .put(backup::MANIFEST, manifest.encode_to_vec())
+            .await?;
+        info!(
+            job = job.id,
+            groups = manifest.groups.len(),
+            size = manifest.groups.iter().map(|g| g.size).sum::<u64>(),
+            "backup to {} is finished",
+            backup.destination
+        );
+        backup.status = BackupStatus::BackupFinish as i32;
+        self.save_backup(job.id, backup).await
+    }
+
+    /// Take the checkpoint of the group from its leader, `None` is returned if the group doesn't
+    /// exist anymore.
+    async fn backup_group(&self, group_id: u64, destination: &str) -> Result<Option<GroupBackup>> {
+        let schema = self.core.root_shared.schema()?;
+        if schema.get_group(group_id).await?.is_none() {
+            return Ok(None);
+        }
+        let leader = schema
+            .group_replica_states(group_id)
+            .await?
+            .into_iter()
+            .filter(|s| s.role == RaftRole::Leader as i32)
+            .max_by_key(|s| s.term)
+            .ok_or(crate::Error::GroupNotFound(group_id))?;
+        let node = schema
+            .get_node(leader.node_id)
+            .await?
+            .ok_or_else(|| crate::Error::InvalidData(format!("node {}", leader.node_id)))?;
+        let client = self
+            .core
+            .root_shared
+            .transport_manager
+            .get_node_client(node.addr)?;
+        let resp = client
+            .backup_replica(group_id, leader.replica_id, destination.to_owned())
+            .await?;
+        Ok(Some(GroupBackup {
+            group_id,
+            desc: resp.desc,
+            apply_index: resp.apply_index,
+            apply_term: resp.apply_term,
+            files: resp.files,
+            size: resp.size,
+        }))
+    }
+
+    async fn handle_finish_backup(&self, job: &BackgroundJob, backup: BackupJob) -> Result<()> {
+        let mut job = job.to_owned();
+        job.job = Some(background_job::Job::Backup(backup));
+        self.core.finish(job).await
+    }
+
+    async fn save_backup(&self, job_id: u64, backup: &BackupJob) -> Result<()> {
+        self.core
+            .update(BackgroundJob {
+                id: job_id,
+                job: Some(background_job::Job::Backup(backup.to_owned())),
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+impl Jobs {
+    async fn handle_restore(&self, job: &BackgroundJob, restore: &RestoreJob) -> Result<()> {
+        let mut restore = restore.to_owned();
+        loop {
+            match RestoreStatus::from_i32(restore.status).unwrap() {
+                RestoreStatus::RestoreInit => self.handle_init_restore(job, &mut restore).await?,
+                RestoreStatus::RestoreCreating => {
+                    self.handle_creating_restore(job, &mut restore).await?
+                }
+                RestoreStatus::RestorePublish => {
+                    self.handle_publish_restore(job, &mut restore).await?
+                }
+                RestoreStatus::RestoreFinish | RestoreStatus::RestoreAbort => {
+                    return self.handle_finish_restore(job, restore).await
+                }
+            }
+        }
+    }
+
+    async fn handle_init_restore(
+        &self,
+        job: &BackgroundJob,
+        restore: &mut RestoreJob,
+    ) -> Result<()> {
+        let manifest = match self.read_backup_manifest(&restore.backup).await {
+            Ok(manifest) => manifest,
+            Err(crate::Error::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                restore.status = RestoreStatus::RestoreAbort as i32;
+                restore.remark = "the backup is incomplete or doesn't exist".into();
+                return self.save_restore(job.id, restore).await;
+            }
+            Err(err @ (crate::Error::InvalidData(_) | crate::Error::InvalidArgument(_))) => {
+                restore.status = RestoreStatus::RestoreAbort as i32;
+                restore.remark = format!("read manifest: {err}");
+                return self.save_restore(job.id, restore).await;
+            }
+            Err(err) => return Err(err),
+        };
+
+        // The checkpoints of groups are taken at different points, they are only close to a common
+        // cut after replaying the archived logs to the time all of them are taken.
+        if restore.restore_time == 0 && manifest.groups.len() > 1 {
+            warn!(
+                job = job.id,
+                "restore {}: the checkpoints of groups are not consistent with each other without \
+                 replaying the archived logs",
+                restore.backup
+            );
+        }
+        if restore.restore_time != 0 && restore.restore_time < manifest.finished_time {
+            restore.status = RestoreStatus::RestoreAbort as i32;
+            restore.remark = format!(
+                "the restore time is earlier than the backup finished at {}",
+                manifest.finished_time
+            );
+            return self.save_restore(job.id, restore).await;
+        }
+
+        let (databases, mut collections) = match select_restore_collections(
+            &manifest,
+            &restore.database,
+            &restore.collection,
+            &restore.new_name,
+        ) {
+            Ok(selected) => selected,
+            Err(remark) => {
+                restore.status = RestoreStatus::RestoreAbort as i32;
+                restore.remark = remark;
+                return self.save_restore(job.id, restore).await;
+            }
+        };
+
+        let schema = self.core.root_shared.schema()?;
+        for co in &collections {
+            let db = databases.iter().find(|db| db.id == co.db).unwrap();
+            if let Some(exist_db) = schema.get_database(&db.name).await? {
+                if schema
+                    .get_collection(exist_db.id, &co.name)
+                    .await?
+                    .is_some()
+                {
+                    restore.status = RestoreStatus::RestoreAbort as i32;
+                    restore.remark = format!("collection {}.{} already exists", db.name, co.name);
+                    return self.save_restore(job.id, restore).await;
+                }
+            }
+        }
+
+        // Allocate the new ids, the data of the collections and indexes is rewritten with them.
+        let mut id_mapping = HashMap::new();
+        for co in &mut collections {
+            let id = schema.next_collection_id().await?;
+            id_mapping.insert(co.id, id);
+            co.id = id;
+            for index in &mut co.indexes {
+                let id = schema.next_index_id().await?;
+                id_mapping.insert(index.id, id);
+                index.id = id;
+            }
+        }
+
+        let mut wait_groups = vec![];
+        for group in &manifest.groups {
+            let Some(desc) = group.desc.as_ref() else {
+                continue;
+            };
+            let mut shards = vec![];
+            let mut replication_factor = 1;
+            for shard in &desc.shards {
+                let Some(collection_id) = id_mapping.get(&shard.collection_id) else {
+                    continue;
+                };
+                let co = collections.iter().find(|c| c.id == *collection_id).unwrap();
+                let db = databases.iter().find(|db| db.id == co.db).unwrap();
+                replication_factor =
+                    replication_factor.max(super::effective_replication_factor(db, co));
+                shards.push(ShardDesc {
+                    id: schema.next_shard_id().await?,
+                    collection_id: *collection_id,
+                    partition: shard.partition.clone(),
+                    collection_meta: Some(super::collection_meta(db, co)),
+                    ..Default::default()
+                });
+            }
+            if shards.is_empty() {
+                continue;
+            }
+
+            let node = self
+                .core
+                .alloc
+                .allocate_group_replica(vec![], 1)
+                .await?
+                .pop()
+                .ok_or_else(|| crate::Error::ResourceExhausted("no node to restore".into()))?;
+            let replica_id = schema.next_replica_id().await?;
+            wait_groups.push(RestoreGroup {
+                files: group.files.clone(),
+                desc: Some(GroupDesc {
+                    id: schema.next_group_id().await?,
+                    epoch: INITIAL_EPOCH,
+                    shards,
+                    replicas: vec![ReplicaDesc {
+                        id: replica_id,
+                        node_id: node.id,
+                        role: ReplicaRole::Voter as i32,
+                    }],
+                    replication_factor,
+                }),
+                node: Some(node),
+                source_group: group.group_id,
+                apply_index: group.apply_index,
+            });
+        }
+
+        info!(
+            job = job.id,
+            collections = collections.len(),
+            groups = wait_groups.len(),
+            "start restore from {}",
+            restore.backup
+        );
+        restore.databases = databases;
+        restore.collections = collections;
+        restore.id_mapping = id_mapping;
+        restore.wait_groups = wait_groups;
+        restore.status = RestoreStatus::RestoreCreating as i32;
+        self.save_restore(job.id, restore).await
+    }
+
+    async fn read_backup_manifest(&self, backup: &str) -> Result<BackupManifest> {
+        let storage = backup::open_storage(backup)?;
+        let content = storage.get(backup::MANIFEST).await?;
+        BackupManifest::decode(&*content)
+            .map_err(|e| crate::Error::InvalidData(format!("backup manifest: {e}")))
+    }
+
+    async fn handle_creating_restore(
+        &self,
+        job: &BackgroundJob,
+        restore: &mut RestoreJob,
+    ) -> Result<()> {
+        while let Some(group) = restore.wait_groups.last().cloned() {
+            let desc = group.desc.unwrap();
+            let node = group.node.unwrap();
+            let client = self
+                .core
+                .root_shared
+                .transport_manager
+                .get_node_client(node.addr)?;
+            let result = client
+                .restore_replica(RestoreReplicaRequest {
+                    replica_id: desc.replicas[0].id,
+                    group: Some(desc.clone()),
+                    backup: restore.backup.clone(),
+                    files: group.files,
+                    id_mapping: restore.id_mapping.clone(),
+                    source_group: group.source_group,
+                    apply_index: group.apply_index,
+                    restore_time: restore.restore_time,
+                })
+                .await;
+            match result.map_err(crate::Error::from) {
+                Ok(()) => {}
+                Err(crate::Error::InvalidArgument(msg)) => {
+                    restore.status = RestoreStatus::RestoreAbort as i32;
+                    restore.remark = msg;
+                    return self.save_restore(job.id, restore).await;
+                }
+                Err(err) => return Err(err),
+            }
+            info!(
+                job = job.id,
+                group = desc.id,
+                node = node.id,
+                "restore group with {} shards",
+                desc.shards.len()
+            );
+            restore.wait_groups.pop();
+            self.save_restore(job.id, restore).await?;
+        }
+        restore.status = RestoreStatus::RestorePublish as i32;
+        self.save_restore(job.id, restore).await
+    }
+
+    /// Create the databases and collections, it is safe to retry since the existing ones are
+    /// skipped.
+    async fn handle_publish_restore(
+        &self,
+        job: &BackgroundJob,
+        restore: &mut RestoreJob,
+    ) -> Result<()> {
+        let schema = self.core.root_shared.schema()?;
+        let mut events = vec![];
+        let mut db_mapping = HashMap::new();
+        for db in &restore.databases {
+            let desc = match schema.get_database(&db.name).await? {
+                Some(desc) => desc,
+                None => {
+                    let desc = schema.create_database(db.to_owned()).await?;
+                    events.push(UpdateEvent {
+                        event: Some(update_event::Event::Database(desc.clone())),
+                    });
+                    desc
+                }
+            };
+            db_mapping.insert(db.id, desc.id);
+        }
+        for co in &restore.collections {
+            let mut desc = co.to_owned();
+            desc.db = db_mapping[&co.db];
+            if schema.get_collection(desc.db, &desc.name).await?.is_none() {
+                let desc = schema.create_collection(desc).await?;
+                events.push(UpdateEvent {
+                    event: Some(update_event::Event::Collection(desc)),
+                });
+            }
+        }
+        self.core
+            .root_shared
+            .watcher_hub
+            .notify_updates(events)
+            .await;
+
+        info!(job = job.id, "restore from {} is finished", restore.backup);
+        restore.status = RestoreStatus::RestoreFinish as i32;
+        self.save_restore(job.id, restore).await
+    }
+
+    async fn handle_finish_restore(&self, job: &BackgroundJob, restore: RestoreJob) -> Result<()> {
+        let mut job = job.to_owned();
+        job.job = Some(background_job::Job::Restore(restore));
+        self.core.finish(job).await
+    }
+
+    async fn save_restore(&self, job_id: u64, restore: &RestoreJob) -> Result<()> {
+        self.core
+            .update(BackgroundJob {
+                id: job_id,
+                job: Some(background_job::Job::Restore(restore.to_owned())),
+            })
+            .await?;
+        Ok(())
+    }
+}
+
 impl Jobs {
     async fn try_create_shard(&self, group_id: u64, desc: &ShardDesc) -> Result<()> {
         let mut group_client = self
@@ -749,7 +1488,7 @@ impl JobCore {
     pub async fn wait_more_jobs(&self) {
         poll_fn(|ctx| {
             let mut mem_jobs = self.mem_jobs.lock().unwrap();
-            if mem_jobs.jobs.is_empty() {
+            if !mem_jobs.jobs.iter().any(|j| !is_manual_schedule(j)) {
                 mem_jobs.added_wakers.push(ctx.waker().clone());
                 Poll::Pending
             } else {
@@ -809,7 +1548,11 @@ impl JobCore {
 
     pub fn need_handle_jobs(&self) -> Vec<BackgroundJob> {
         let jobs = self.mem_jobs.lock().unwrap();
-        jobs.jobs.to_owned()
+        jobs.jobs
+            .iter()
+            .filter(|j| !is_manual_schedule(j))
+            .cloned()
+            .collect()
     }
 
     fn try_lock_res(&self, res_key: Vec<u8>) -> bool {
@@ -835,6 +1578,203 @@ fn res_key(job: &BackgroundJob) -> Option<Vec<u8>> {
             key.extend_from_slice(job.collection_name.as_bytes());
             Some(key)
         }
-        background_job::Job::CreateOneGroup(_) | background_job::Job::PurgeDatabase(_) => None,
+        background_job::Job::Backup(job) => {
+            Some(format!("backup:{}", job.destination).into_bytes())
+        }
+        background_job::Job::Restore(job) => {
+            Some(format!("restore:{}:{}.{}", job.backup, job.database, job.collection).into_bytes())
+        }
+        background_job::Job::CreateOneGroup(_)
+        | background_job::Job::PurgeDatabase(_)
+        | background_job::Job::BackfillIndex(_)
+        | background_job::Job::SyncCollection(_)
+        | background_job::Job::ManualSchedule(_) => None,
+    }
+}
+
+fn is_manual_schedule(job: &BackgroundJob) -> bool {
+    matches!(job.job, Some(background_job::Job::ManualSchedule(_)))
+}
+
+/// Select the databases and collections to restore from the manifest. The collection is renamed
+/// to `new_name` if it is specified. The reason is returned if the collection is not found.
+fn select_restore_collections(
+    manifest: &BackupManifest,
+    database: &str,
+    collection: &str,
+    new_name: &str,
+) -> std::result::Result<(Vec<DatabaseDesc>, Vec<CollectionDesc>), String> {
+    if collection.is_empty() {
+        let databases = manifest
+            .databases
+            .iter()
+            .filter(|db| db.id != SYSTEM_DATABASE_ID)
+            .cloned()
+            .collect::<Vec<_>>();
+        let collections = manifest
+            .collections
+            .iter()
+            .filter(|co| databases.iter().any(|db| db.id == co.db))
+            .cloned()
+            .collect();
+        return Ok((databases, collections));
+    }
+
+    let db = manifest
+        .databases
+        .iter()
+        .find(|db| db.name == database && db.id != SYSTEM_DATABASE_ID)
+        .ok_or_else(|| format!("database {database} not found in backup"))?;
+    let mut co = manifest
+        .collections
+        .iter()
+        .find(|co| co.db == db.id && co.name == collection)
+        .cloned()
+        .ok_or_else(|| format!("collection {database}.{collection} not found in backup"))?;
+    if !new_name.is_empty() {
+        co.name = new_name.to_owned();
+    }
+    Ok((vec![db.clone()], vec![co]))
+}
+
+/// Return the groups to take checkpoints again, if any shard of the backup collections is missed
+/// or is taken by more than one group.
+fn verify_backup_shards(manifest: &BackupManifest, groups: &[GroupDesc]) -> Vec<u64> {
+    let collections = manifest
+        .collections
+        .iter()
+        .map(|c| c.id)
+        .collect::<HashSet<_>>();
+    let mut taken: HashMap<u64, Vec<u64>> = HashMap::new();
+    for group in &manifest.groups {
+        let Some(desc) = group.desc.as_ref() else {
+            continue;
+        };
+        for shard in desc
+            .shards
+            .iter()
+            .filter(|s| collections.contains(&s.collection_id))
+        {
+            taken.entry(shard.id).or_default().push(group.group_id);
+        }
+    }
+
+    let mut retake_groups = BTreeSet::new();
+    for group_ids in taken.values().filter(|g| g.len() > 1) {
+        retake_groups.extend(group_ids.iter().cloned());
+    }
+    for group in groups.iter().filter(|g| g.id != ROOT_GROUP_ID) {
+        if group
+            .shards
+            .iter()
+            .any(|s| collections.contains(&s.collection_id) && !taken.contains_key(&s.id))
+        {
+            retake_groups.insert(group.id);
+        }
+    }
+    retake_groups.into_iter().collect()
+}
+
+fn unix_timestamp_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn group_desc(id: u64, shards: &[u64]) -> GroupDesc {
+        GroupDesc {
+            id,
+            shards: shards
+                .iter()
+                .map(|&id| ShardDesc {
+                    id,
+                    collection_id: 1,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn manifest(groups: Vec<GroupDesc>) -> BackupManifest {
+        BackupManifest {
+            collections: vec![CollectionDesc {
+                id: 1,
+                ..Default::default()
+            }],
+            groups: groups
+                .into_iter()
+                .map(|desc| GroupBackup {
+                    group_id: desc.id,
+                    desc: Some(desc),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn verify_backup_shards_after_moving() {
+        let current = vec![group_desc(1, &[1]), group_desc(2, &[2, 3])];
+
+        // All shards are taken exactly once.
+        let m = manifest(vec![group_desc(1, &[1]), group_desc(2, &[2, 3])]);
+        assert!(verify_backup_shards(&m, &current).is_empty());
+
+        // Shard 3 is moved from group 1 to group 2 after group 1 is taken.
+        let m = manifest(vec![group_desc(1, &[1, 3]), group_desc(2, &[2, 3])]);
+        assert_eq!(verify_backup_shards(&m, &current), vec![1, 2]);
+
+        // Shard 3 is moved from group 1 to group 2 before group 1 is taken, but after group 2.
+        let m = manifest(vec![group_desc(1, &[1]), group_desc(2, &[2])]);
+        assert_eq!(verify_backup_shards(&m, &current), vec![2]);
+    }
+
+    #[test]
+    fn select_restore_collections_by_name() {
+        let database = |id: u64, name: &str| DatabaseDesc {
+            id,
+            name: name.to_owned(),
+            ..Default::default()
+        };
+        let collection = |id: u64, db: u64, name: &str| CollectionDesc {
+            id,
+            db,
+            name: name.to_owned(),
+            ..Default::default()
+        };
+        let manifest = BackupManifest {
+            databases: vec![
+                database(SYSTEM_DATABASE_ID, "__system__"),
+                database(2, "db"),
+            ],
+            collections: vec![
+                collection(3, SYSTEM_DATABASE_ID, "meta"),
+                collection(4, 2, "a"),
+                collection(5, 2, "b"),
+            ],
+            ..Default::default()
+        };
+
+        let (databases, collections) = select_restore_collections(&manifest, "", "", "").unwrap();
+        assert_eq!(databases, vec![database(2, "db")]);
+        assert_eq!(
+            collections,
+            vec![collection(4, 2, "a"), collection(5, 2, "b")]
+        );
+
+        let (databases, collections) =
+            select_restore_collections(&manifest, "db", "b", "b_restored").unwrap();
+        assert_eq!(databases, vec![database(2, "db")]);
+        assert_eq!(collections, vec![collection(5, 2, "b_restored")]);
+
+        assert!(select_restore_collections(&manifest, "db", "c", "").is_err());
+        assert!(select_restore_collections(&manifest, "__system__", "meta", "").is_err());
     }
 }
@@ -34,6 +34,7 @@ impl Root {
         let all_nodes = schema.list_node().await?;
         let nodes = all_nodes
             .iter()
+            .filter(|n| n.status != NodeStatus::Decommissioned as i32)
             .filter(|n| tasks.iter().any(|t| t.node_id == n.id))
             .collect::<Vec<_>>();
 
@@ -161,20 +162,29 @@ impl Root {
         resp: &CollectStatsResponse,
         node: &NodeDesc,
     ) -> Result<()> {
+        self.shared
+            .update_replica_stats(node.id, &resp.replica_stats);
         if let Some(ns) = &resp.node_stats {
             let mut node = node.to_owned();
             let _timer = super::metrics::HEARTBEAT_HANDLE_NODE_STATS_DURATION_SECONDS.start_timer();
             let new_group_count = ns.group_count as u64;
             let new_leader_count = ns.leader_count as u64;
             let mut cap = node.capacity.take().unwrap();
-            if new_group_count != cap.replica_count || new_leader_count != cap.leader_count {
+            if new_group_count != cap.replica_count
+                || new_leader_count != cap.leader_count
+                || disk_usage_changed(&cap, ns)
+            {
                 super::metrics::HEARTBEAT_UPDATE_NODE_STATS_TOTAL.inc();
                 cap.replica_count = new_group_count;
                 cap.leader_count = new_leader_count;
+                cap.disk_capacity = ns.disk_capacity;
+                cap.disk_used = ns.disk_used;
                 info!(
                     node = node.id,
                     replica_count = cap.replica_count,
                     leader_count = cap.leader_count,
+                    disk_capacity = cap.disk_capacity,
+                    disk_used = cap.disk_used,
                     "update node stats by heartbeat response",
                 );
                 node.capacity = Some(cap);
@@ -268,3 +278,13 @@ impl Root {
         Ok(())
     }
 }
+
+/// Whether the disk usage changed significantly, it avoids updating node desc on every heartbeat.
+fn disk_usage_changed(cap: &NodeCapacity, ns: &NodeStats) -> bool {
+    const MIN_CHANGED_FRACTION: f64 = 0.01;
+    if cap.disk_capacity != ns.disk_capacity {
+        return true;
+    }
+    let delta = cap.disk_used.abs_diff(ns.disk_used) as f64;
+    delta > cap.disk_capacity as f64 * MIN_CHANGED_FRACTION
+}
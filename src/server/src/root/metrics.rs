@@ -282,4 +282,14 @@ lazy_static! {
         exponential_buckets(0.00005, 1.8, 26).unwrap(),
     )
     .unwrap();
+    pub static ref WATCH_RESUME_TOTAL: IntCounter = register_int_counter!(
+        "root_watch_resume_total",
+        "the total of watchers resumed from the revision"
+    )
+    .unwrap();
+    pub static ref WATCH_RESYNC_TOTAL: IntCounter = register_int_counter!(
+        "root_watch_resync_total",
+        "the total of watchers receiving the full state"
+    )
+    .unwrap();
 }
@@ -28,8 +28,9 @@ use std::{collections::*, sync::*, task::Poll, time::Duration};
 use engula_api::{
     server::v1::{report_request::GroupUpdates, watch_response::*, *},
     v1::{
-        collection_desc as co_desc, create_collection_request as co_req, CollectionDesc,
-        DatabaseDesc,
+        collection_desc as co_desc, create_collection_request as co_req, index_desc,
+        placement_constraint, CollectionDesc, DatabaseDesc, IndexDesc, LeaderPreference,
+        PlacementConstraint, ValueSchema,
     },
 };
 use tokio::time::Instant;
@@ -37,17 +38,18 @@ use tokio_util::time::delay_queue;
 use tracing::{error, info, trace, warn};
 
 pub(crate) use self::schema::*;
-use self::{
-    allocator::SysAllocSource, bg_job::Jobs, diagnosis::Metadata, schedule::ReconcileScheduler,
-    schema::ReplicaNodes, store::RootStore,
-};
 pub use self::{
+    allocator::sim,
     collector::RootCollector,
     watch::{WatchHub, Watcher, WatcherInitializer},
 };
+use self::{
+    allocator::SysAllocSource, bg_job::Jobs, diagnosis::Metadata, schedule::ReconcileScheduler,
+    schema::ReplicaNodes, store::RootStore,
+};
 use crate::{
     constants::{ROOT_GROUP_ID, SHARD_MAX, SHARD_MIN},
-    node::{Node, Replica, ReplicaRouteTable},
+    node::{replica::check_value_schema, Node, Replica, ReplicaRouteTable},
     runtime::{self, TaskPriority},
     serverpb::v1::{background_job::Job, reconcile_task, *},
     transport::TransportManager,
@@ -71,8 +73,13 @@ pub struct RootShared {
     node_ident: NodeIdent,
     local_addr: String,
     cfg_cpu_nums: u32,
+    cfg_locality: NodeLocality,
+    restore_from: Option<String>,
+    restore_time: u64,
     core: Mutex<Option<RootCore>>,
     watcher_hub: Arc<WatchHub>,
+    /// The stats of the replicas of each node, it is collected by heartbeat.
+    replica_stats: Mutex<HashMap<u64 /* node */, HashMap<u64 /* replica */, ReplicaStats>>>,
 }
 
 impl RootShared {
@@ -82,6 +89,23 @@ impl RootShared {
             .map(|c| c.schema.clone())
             .ok_or_else(|| Error::NotRootLeader(RootDesc::default(), 0, None))
     }
+
+    pub fn replica_stats(&self, node_id: u64, replica_id: u64) -> Option<ReplicaStats> {
+        let replica_stats = self.replica_stats.lock().unwrap();
+        replica_stats
+            .get(&node_id)
+            .and_then(|replicas| replicas.get(&replica_id))
+            .cloned()
+    }
+
+    fn update_replica_stats(&self, node_id: u64, stats: &[ReplicaStats]) {
+        let replicas = stats
+            .iter()
+            .map(|s| (s.replica_id, s.clone()))
+            .collect::<HashMap<_, _>>();
+        let mut replica_stats = self.replica_stats.lock().unwrap();
+        replica_stats.insert(node_id, replicas);
+    }
 }
 
 struct RootCore {
@@ -96,14 +120,19 @@ impl Root {
     ) -> Self {
         let local_addr = cfg.addr.clone();
         let cfg_cpu_nums = cfg.cpu_nums;
+        let cfg_locality = cfg.locality.to_node_locality();
         let ongoing_stats = Arc::new(OngoingStats::default());
         let shared = Arc::new(RootShared {
             transport_manager,
             local_addr,
             cfg_cpu_nums,
+            cfg_locality,
+            restore_from: cfg.restore_from.clone(),
+            restore_time: cfg.restore_time.unwrap_or_default(),
             core: Mutex::new(None),
             node_ident: node_ident.to_owned(),
-            watcher_hub: Default::default(),
+            watcher_hub: Arc::new(WatchHub::new(cfg.root.watch_log_capacity)),
+            replica_stats: Default::default(),
         });
         let liveness = Arc::new(liveness::Liveness::new(Duration::from_secs(
             cfg.root.liveness_threshold_sec,
@@ -191,12 +220,13 @@ impl Root {
             let root_replica = fetch_root_replica(&replica_table).await;
 
             // Wait the current root replica becomes a leader.
-            if let Ok(Some(_)) = root_replica.on_leader("root", false).await {
+            if let Ok(Some(term)) = root_replica.on_leader("root", false).await {
                 match self
                     .step_leader(
                         &self.shared.local_addr,
                         self.shared.cfg_cpu_nums,
                         root_replica,
+                        term,
                         &mut bootstrapped,
                     )
                     .await
@@ -251,6 +281,7 @@ impl Root {
         local_addr: &str,
         cfg_cpu_nums: u32,
         root_replica: Arc<Replica>,
+        term: u64,
         bootstrapped: &mut bool,
     ) -> Result<()> {
         let store = Arc::new(RootStore::new(root_replica.to_owned()));
@@ -263,7 +294,10 @@ impl Root {
                 .try_bootstrap_root(
                     local_addr,
                     cfg_cpu_nums,
+                    self.shared.cfg_locality.clone(),
                     self.shared.node_ident.cluster_id.clone(),
+                    self.shared.restore_from.clone(),
+                    self.shared.restore_time,
                 )
                 .await
             {
@@ -283,8 +317,11 @@ impl Root {
         self::metrics::LEADER_STATE_INFO.set(1);
 
         self.ongoing_stats.reset();
+        self.watcher_hub().reset_revision(term).await;
         self.heartbeat_queue.enable(true).await;
         self.jobs.on_step_leader().await?;
+        self.alloc
+            .set_schedule_control(schema.get_schedule_control().await?);
 
         let node_id = self.shared.node_ident.node_id;
         info!(
@@ -354,10 +391,7 @@ impl Root {
             .ok_or_else(|| crate::Error::InvalidArgument("node not found".into()))?;
 
         let current_status = NodeStatus::from_i32(node_desc.status).unwrap();
-        if !matches!(
-            current_status,
-            NodeStatus::Cordoned | NodeStatus::Drained | NodeStatus::Decommissioned
-        ) {
+        if !matches!(current_status, NodeStatus::Cordoned | NodeStatus::Drained) {
             return Err(crate::Error::InvalidArgument(
                 "node status unsupport uncordon".into(),
             ));
@@ -378,6 +412,7 @@ impl Root {
                     task: Some(reconcile_task::Task::ShedRoot(ShedRootLeaderTask {
                         node_id,
                     })),
+                    ..Default::default()
                 })
                 .await;
             return Err(crate::Error::InvalidArgument(
@@ -403,12 +438,470 @@ impl Root {
         self.scheduler
             .setup_task(ReconcileTask {
                 task: Some(reconcile_task::Task::ShedLeader(ShedLeaderTask { node_id })),
+                ..Default::default()
             })
             .await;
 
         Ok(())
     }
 
+    /// Begin to decommission the node, all replicas of it will be moved out by the reconcile
+    /// scheduler, then the node is marked as decommissioned and never serves any replicas again.
+    pub async fn decommission_node(&self, node_id: u64) -> Result<()> {
+        let schema = self.schema()?;
+
+        if self.current_node_id() == node_id {
+            info!("try to decommission root leader and move root leadership out first");
+            self.scheduler
+                .setup_task(ReconcileTask {
+                    task: Some(reconcile_task::Task::ShedRoot(ShedRootLeaderTask {
+                        node_id,
+                    })),
+                    ..Default::default()
+                })
+                .await;
+            return Err(crate::Error::InvalidArgument(
+                "node is root leader, try again later".into(),
+            ));
+        }
+
+        let mut node_desc = schema
+            .get_node(node_id)
+            .await?
+            .ok_or_else(|| crate::Error::InvalidArgument("node not found".into()))?;
+
+        let current_status = NodeStatus::from_i32(node_desc.status).unwrap();
+        if matches!(
+            current_status,
+            NodeStatus::Decommissioning | NodeStatus::Decommissioned
+        ) {
+            // Already in progress.
+            return Ok(());
+        }
+
+        check_decommission_capacity(
+            node_id,
+            &schema.list_node().await?,
+            &schema.list_group().await?,
+            &schema.list_database().await?,
+            &schema.list_collection().await?,
+            self.alloc.replicas_per_group(),
+        )?;
+
+        node_desc.status = NodeStatus::Decommissioning as i32;
+        schema.update_node(node_desc).await?; // TODO: cas
+        info!(node = node_id, "begin decommission node");
+
+        Ok(())
+    }
+
+    /// Return the status of node and the number of replicas still placed on it.
+    pub async fn decommission_progress(&self, node_id: u64) -> Result<(NodeStatus, usize)> {
+        let schema = self.schema()?;
+        let node_desc = schema
+            .get_node(node_id)
+            .await?
+            .ok_or_else(|| crate::Error::InvalidArgument("node not found".into()))?;
+
+        let remaining_replicas = schema
+            .list_group()
+            .await?
+            .iter()
+            .flat_map(|g| g.replicas.iter())
+            .filter(|r| r.node_id == node_id)
+            .count();
+
+        Ok((
+            NodeStatus::from_i32(node_desc.status).unwrap(),
+            remaining_replicas,
+        ))
+    }
+
+    /// The replicas and leaders which violate the placement rules of their collections.
+    pub async fn placement_violations(&self) -> Result<Vec<diagnosis::PlacementViolation>> {
+        // Only the root leader could serve it.
+        self.schema()?;
+        self.alloc.placement_violations().await
+    }
+
+    /// Run the scheduler against the current state without executing anything.
+    pub async fn explain_schedule(&self) -> Result<diagnosis::ScheduleExplain> {
+        // Only the root leader could serve it.
+        self.schema()?;
+        self.alloc.explain().await
+    }
+
+    /// The runtime controls of the reconcile scheduler.
+    pub async fn schedule_control(&self) -> Result<diagnosis::ScheduleControlState> {
+        let schema = self.schema()?;
+        let control = schema.get_schedule_control().await?;
+        Ok(diagnosis::ScheduleControlState::from(&control))
+    }
+
+    /// Pause or resume the classes of balancing, and limit the concurrent replica moves and shard
+    /// migrations. The controls are persisted in the root metadata, so they survive root leader
+    /// changes.
+    pub async fn update_schedule_control(
+        &self,
+        pause: &[String],
+        resume: &[String],
+        max_concurrent_moves: Option<u64>,
+    ) -> Result<diagnosis::ScheduleControlState> {
+        fn parse_classes(names: &[String]) -> Result<Vec<allocator::BalanceClass>> {
+            let mut classes = Vec::new();
+            for name in names {
+                if name == "all" {
+                    classes.extend(allocator::BalanceClass::ALL);
+                    continue;
+                }
+                let class = allocator::BalanceClass::parse(name).ok_or_else(|| {
+                    Error::InvalidArgument(format!("unknown balance class `{name}`"))
+                })?;
+                classes.push(class);
+            }
+            Ok(classes)
+        }
+
+        let pause = parse_classes(pause)?;
+        let resume = parse_classes(resume)?;
+        let schema = self.schema()?;
+        let mut control = schema.get_schedule_control().await?;
+        for class in resume {
+            class.set_paused(&mut control, false);
+        }
+        for class in pause {
+            class.set_paused(&mut control, true);
+        }
+        if let Some(limit) = max_concurrent_moves {
+            control.max_concurrent_moves = limit;
+        }
+        schema.put_schedule_control(control.clone()).await?;
+        self.alloc.set_schedule_control(control.clone());
+        info!(control = ?control, "update schedule control");
+        Ok(diagnosis::ScheduleControlState::from(&control))
+    }
+
+    /// Permanently remove a dead or decommissioned node from the cluster metadata. The node must
+    /// not be referenced by any group, the replicas of groups which lost the majority should be
+    /// recovered by `unsafe_recover_group` first.
+    pub async fn forget_node(&self, node_id: u64) -> Result<()> {
+        let schema = self.schema()?;
+        let node_desc = schema
+            .get_node(node_id)
+            .await?
+            .ok_or_else(|| crate::Error::InvalidArgument("node not found".into()))?;
+
+        let current_status = NodeStatus::from_i32(node_desc.status).unwrap();
+        if !matches!(current_status, NodeStatus::Decommissioned)
+            && !self.liveness.get(&node_id).is_dead()
+        {
+            return Err(crate::Error::InvalidArgument(
+                "only dead or decommissioned node can be forgotten".into(),
+            ));
+        }
+
+        let groups = schema
+            .list_group()
+            .await?
+            .into_iter()
+            .filter(|g| g.replicas.iter().any(|r| r.node_id == node_id))
+            .map(|g| g.id)
+            .collect::<Vec<_>>();
+        if !groups.is_empty() {
+            return Err(crate::Error::InvalidArgument(format!(
+                "node still serves replicas of groups {groups:?}"
+            )));
+        }
+
+        for state in schema.list_replica_state().await? {
+            if state.node_id == node_id {
+                schema
+                    .remove_replica_state(state.group_id, state.replica_id)
+                    .await?;
+            }
+        }
+        schema.delete_node(node_id).await?;
+        self.watcher_hub()
+            .notify_deletes(vec![DeleteEvent {
+                event: Some(delete_event::Event::Node(node_id)),
+            }])
+            .await;
+        info!(node = node_id, "forget node");
+
+        Ok(())
+    }
+
+    /// Unsafely recover a group which lost the majority from the specified surviving replica. The
+    /// group will only contain the survivor after recovery, and the other replicas will be added
+    /// back by the reconcile scheduler.
+    pub async fn unsafe_recover_group(&self, group_id: u64, replica_id: u64) -> Result<GroupDesc> {
+        let schema = self.schema()?;
+        let group_desc = schema
+            .get_group(group_id)
+            .await?
+            .ok_or_else(|| crate::Error::InvalidArgument("group not found".into()))?;
+        let replica = group_desc
+            .replicas
+            .iter()
+            .find(|r| r.id == replica_id)
+            .cloned()
+            .ok_or_else(|| crate::Error::InvalidArgument("replica not found in group".into()))?;
+        let node_desc = schema
+            .get_node(replica.node_id)
+            .await?
+            .ok_or_else(|| crate::Error::InvalidArgument("node not found".into()))?;
+
+        warn!(
+            group = group_id,
+            replica = replica_id,
+            node = node_desc.id,
+            "unsafe recover group"
+        );
+        let client = self
+            .shared
+            .transport_manager
+            .get_node_client(node_desc.addr.to_owned())?;
+        let new_desc = client
+            .unsafe_recover_replica(replica_id, group_desc.clone())
+            .await?;
+
+        schema
+            .update_group_replica(Some(new_desc.clone()), None)
+            .await?;
+        for r in &group_desc.replicas {
+            if r.id != replica_id {
+                schema.remove_replica_state(group_id, r.id).await?;
+            }
+        }
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent {
+                event: Some(update_event::Event::Group(new_desc.clone())),
+            }])
+            .await;
+        info!(
+            group = group_id,
+            replica = replica_id,
+            epoch = new_desc.epoch,
+            "unsafe recover group success"
+        );
+
+        Ok(new_desc)
+    }
+
+    /// Move the replica to the target node, the task is executed by the reconcile scheduler and
+    /// the id of the manual schedule job is returned.
+    pub async fn move_replica(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+        dest_node: u64,
+    ) -> Result<u64> {
+        let schema = self.schema()?;
+        let group_desc = self.manual_schedule_group(&schema, group_id).await?;
+        let replica = group_desc
+            .replicas
+            .iter()
+            .find(|r| r.id == replica_id)
+            .ok_or_else(|| crate::Error::InvalidArgument("replica not found in group".into()))?;
+        if group_desc.replicas.iter().any(|r| r.node_id == dest_node) {
+            return Err(crate::Error::InvalidArgument(
+                "target node already has a replica of the group".into(),
+            ));
+        }
+        let node_desc = self.manual_schedule_node(&schema, dest_node).await?;
+
+        let task = ReconcileTask {
+            task: Some(reconcile_task::Task::ReallocateReplica(
+                ReallocateReplicaTask {
+                    group: group_id,
+                    src_node: replica.node_id,
+                    src_replica: replica_id,
+                    dest_node: Some(node_desc),
+                    dest_replica: None,
+                },
+            )),
+            ..Default::default()
+        };
+        let job_id = self.jobs.submit_manual_schedule(task).await?;
+        info!(
+            job = job_id,
+            group = group_id,
+            replica = replica_id,
+            dest_node = dest_node,
+            "submit manual move replica"
+        );
+        Ok(job_id)
+    }
+
+    /// Start a backup of the cluster to the destination, see `crate::backup` for the supported
+    /// destinations and the consistency of backups, which isn't kept across groups. The id of the
+    /// backup job is returned.
+    pub async fn backup(&self, destination: String) -> Result<u64> {
+        crate::backup::open_storage(&destination)?;
+        let job_id = self.jobs.submit_backup(destination.clone()).await?;
+        info!(job = job_id, "submit backup to {destination}");
+        Ok(job_id)
+    }
+
+    /// Restore the databases and collections of the backup, or only restore the collection
+    /// `database`.`collection` under the name `new_name` if `collection` is specified. The
+    /// archived log entries are replayed up to `restore_time` if it isn't 0. The id of the restore
+    /// job is returned.
+    pub async fn restore(
+        &self,
+        backup: String,
+        database: String,
+        collection: String,
+        new_name: String,
+        restore_time: u64,
+    ) -> Result<u64> {
+        crate::backup::open_storage(&backup)?;
+        if collection.is_empty() && (!database.is_empty() || !new_name.is_empty()) {
+            return Err(Error::InvalidArgument("collection is required".into()));
+        }
+        if !collection.is_empty() && database.is_empty() {
+            return Err(Error::InvalidArgument("database is required".into()));
+        }
+        let job_id = self
+            .jobs
+            .submit_restore(backup.clone(), database, collection, new_name, restore_time)
+            .await?;
+        info!(job = job_id, "submit restore from {backup}");
+        Ok(job_id)
+    }
+
+    /// Transfer the leadership of the group to the target replica, the task is executed by the
+    /// reconcile scheduler and the id of the manual schedule job is returned.
+    pub async fn transfer_leader(&self, group_id: u64, target_replica: u64) -> Result<u64> {
+        let schema = self.schema()?;
+        let group_desc = self.manual_schedule_group(&schema, group_id).await?;
+        let target = group_desc
+            .replicas
+            .iter()
+            .find(|r| r.id == target_replica)
+            .ok_or_else(|| crate::Error::InvalidArgument("replica not found in group".into()))?;
+        if target.role != ReplicaRole::Voter as i32 {
+            return Err(crate::Error::InvalidArgument(
+                "only voter could be the leader".into(),
+            ));
+        }
+        self.manual_schedule_node(&schema, target.node_id).await?;
+        let leader = schema
+            .group_replica_states(group_id)
+            .await?
+            .into_iter()
+            .find(|s| s.role == RaftRole::Leader as i32)
+            .ok_or_else(|| crate::Error::InvalidArgument("group has no leader".into()))?;
+        if leader.replica_id == target_replica {
+            return Err(crate::Error::InvalidArgument(
+                "replica is already the leader".into(),
+            ));
+        }
+
+        let task = ReconcileTask {
+            task: Some(reconcile_task::Task::TransferGroupLeader(
+                TransferGroupLeaderTask {
+                    group: group_id,
+                    target_replica,
+                    src_node: leader.node_id,
+                    dest_node: target.node_id,
+                },
+            )),
+            ..Default::default()
+        };
+        let job_id = self.jobs.submit_manual_schedule(task).await?;
+        info!(
+            job = job_id,
+            group = group_id,
+            target_replica = target_replica,
+            "submit manual transfer leader"
+        );
+        Ok(job_id)
+    }
+
+    /// Migrate the shard to the target group, the task is executed by the reconcile scheduler
+    /// and the id of the manual schedule job is returned.
+    pub async fn migrate_shard(&self, shard_id: u64, dest_group: u64) -> Result<u64> {
+        let schema = self.schema()?;
+        let src_group = schema
+            .list_group()
+            .await?
+            .into_iter()
+            .find(|g| g.shards.iter().any(|s| s.id == shard_id))
+            .ok_or_else(|| crate::Error::InvalidArgument("shard not found".into()))?;
+        if src_group.id == dest_group {
+            return Err(crate::Error::InvalidArgument(
+                "shard is already placed on the target group".into(),
+            ));
+        }
+        self.manual_schedule_group(&schema, src_group.id).await?;
+        self.manual_schedule_group(&schema, dest_group).await?;
+
+        let task = ReconcileTask {
+            task: Some(reconcile_task::Task::MigrateShard(MigrateShardTask {
+                shard: shard_id,
+                src_group: src_group.id,
+                dest_group,
+            })),
+            ..Default::default()
+        };
+        let job_id = self.jobs.submit_manual_schedule(task).await?;
+        info!(
+            job = job_id,
+            shard = shard_id,
+            src_group = src_group.id,
+            dest_group = dest_group,
+            "submit manual migrate shard"
+        );
+        Ok(job_id)
+    }
+
+    /// The group which could be scheduled manually. The root group is excluded, and a group
+    /// accepts at most one pending manual task.
+    async fn manual_schedule_group(&self, schema: &Schema, group_id: u64) -> Result<GroupDesc> {
+        if group_id == ROOT_GROUP_ID {
+            return Err(crate::Error::InvalidArgument(
+                "root group could not be scheduled manually".into(),
+            ));
+        }
+        let group_desc = schema
+            .get_group(group_id)
+            .await?
+            .ok_or_else(|| crate::Error::InvalidArgument("group not found".into()))?;
+        let pending = self
+            .jobs
+            .manual_schedule_jobs()
+            .into_iter()
+            .any(|(_, job)| match job.task.and_then(|t| t.task) {
+                Some(reconcile_task::Task::ReallocateReplica(t)) => t.group == group_id,
+                Some(reconcile_task::Task::TransferGroupLeader(t)) => t.group == group_id,
+                Some(reconcile_task::Task::MigrateShard(t)) => {
+                    t.src_group == group_id || t.dest_group == group_id
+                }
+                _ => false,
+            });
+        if pending {
+            return Err(crate::Error::AlreadyExists(format!(
+                "manual schedule of group {group_id}"
+            )));
+        }
+        Ok(group_desc)
+    }
+
+    /// The node which could accept replicas or leaders, it must be active and alive.
+    async fn manual_schedule_node(&self, schema: &Schema, node_id: u64) -> Result<NodeDesc> {
+        let node_desc = schema
+            .get_node(node_id)
+            .await?
+            .ok_or_else(|| crate::Error::InvalidArgument("node not found".into()))?;
+        if node_desc.status != NodeStatus::Active as i32 || self.liveness.get(&node_id).is_dead() {
+            return Err(crate::Error::InvalidArgument(
+                "target node is not schedulable".into(),
+            ));
+        }
+        Ok(node_desc)
+    }
+
     pub async fn node_status(&self, node_id: u64) -> Result<NodeStatus> {
         let schema = self.schema()?;
         let node_desc = schema
@@ -479,6 +972,91 @@ impl Root {
                         "database": p.database_id,
                     })
                 }
+                Job::BackfillIndex(b) => {
+                    json!({
+                        "type": "backfill index",
+                        "collection": b.collection_id,
+                        "index": b.index.as_ref().map(|i| i.name.to_owned()).unwrap_or_default(),
+                        "wait_backfill": b.wait_backfill.len(),
+                    })
+                }
+                Job::SyncCollection(s) => {
+                    json!({
+                        "type": "sync collection",
+                        "collection": s.collection_id,
+                    })
+                }
+                Job::Backup(b) => {
+                    let status = format!("{:?}", BackupStatus::from_i32(b.status).unwrap());
+                    let groups = b
+                        .manifest
+                        .as_ref()
+                        .map(|m| m.groups.as_slice())
+                        .unwrap_or_default();
+                    json!({
+                        "type": "backup",
+                        "id": j.id,
+                        "destination": b.destination,
+                        "status": status,
+                        "finished_groups": groups.len(),
+                        "wait_groups": b.wait_groups.len(),
+                        "size": groups.iter().map(|g| g.size).sum::<u64>(),
+                        "retake_rounds": b.retake_rounds,
+                        "remark": b.remark,
+                        "created_time": b.created_time,
+                    })
+                }
+                Job::Restore(r) => {
+                    let status = format!("{:?}", RestoreStatus::from_i32(r.status).unwrap());
+                    json!({
+                        "type": "restore",
+                        "id": j.id,
+                        "backup": r.backup,
+                        "status": status,
+                        "database": r.database,
+                        "collection": r.collection,
+                        "new_name": r.new_name,
+                        "restore_time": r.restore_time,
+                        "collections": r.collections.len(),
+                        "wait_groups": r.wait_groups.len(),
+                        "remark": r.remark,
+                        "created_time": r.created_time,
+                    })
+                }
+                Job::ManualSchedule(m) => {
+                    let status = format!("{:?}", ManualScheduleStatus::from_i32(m.status).unwrap());
+                    let task = match m.task.as_ref().and_then(|t| t.task.as_ref()) {
+                        Some(reconcile_task::Task::ReallocateReplica(t)) => json!({
+                            "type": "move replica",
+                            "group": t.group,
+                            "src_node": t.src_node,
+                            "src_replica": t.src_replica,
+                            "dest_node": t.dest_node.as_ref().map(|n| n.id).unwrap_or_default(),
+                        }),
+                        Some(reconcile_task::Task::TransferGroupLeader(t)) => json!({
+                            "type": "transfer leader",
+                            "group": t.group,
+                            "src_node": t.src_node,
+                            "dest_node": t.dest_node,
+                            "target_replica": t.target_replica,
+                        }),
+                        Some(reconcile_task::Task::MigrateShard(t)) => json!({
+                            "type": "migrate shard",
+                            "shard": t.shard,
+                            "src_group": t.src_group,
+                            "dest_group": t.dest_group,
+                        }),
+                        _ => json!({}),
+                    };
+                    json!({
+                        "type": "manual schedule",
+                        "id": j.id,
+                        "status": status,
+                        "task": task,
+                        "remark": m.remark,
+                        "created_time": m.created_time,
+                    })
+                }
             }
         }
 
@@ -669,12 +1247,62 @@ impl Root {
         Ok(())
     }
 
+    pub async fn update_database(
+        &self,
+        name: &str,
+        desc: DatabaseDesc,
+        update_mask: &[String],
+    ) -> Result<DatabaseDesc> {
+        let schema = self.schema()?;
+        let prev = schema
+            .get_database(name)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(name.to_owned()))?;
+        if prev.id == SYSTEM_DATABASE_ID {
+            return Err(Error::InvalidArgument(
+                "unsupported update system database".into(),
+            ));
+        }
+
+        let prev_ttl = prev.default_ttl;
+        let desc = apply_database_update(prev, desc, update_mask)?;
+        self.ensure_group_with_replicas(desc.replication_factor as usize)
+            .await?;
+        schema.update_database(name, desc.to_owned()).await?;
+        let mut events = vec![UpdateEvent {
+            event: Some(update_event::Event::Database(desc.to_owned())),
+        }];
+        if desc.default_ttl != prev_ttl {
+            // The collections inheriting the TTL take the new one once it is synchronized to their
+            // shards.
+            for mut co in schema.list_database_collections(desc.id).await? {
+                if co.default_ttl != 0 {
+                    continue;
+                }
+                co.epoch += 1;
+                schema.update_collection(co.to_owned()).await?;
+                self.submit_sync_collection(co.id).await?;
+                events.push(UpdateEvent {
+                    event: Some(update_event::Event::Collection(co)),
+                });
+            }
+        }
+        self.watcher_hub().notify_updates(events).await;
+        trace!(database = ?name, desc = ?desc, "update database");
+        Ok(desc)
+    }
+
     pub async fn create_collection(
         &self,
         name: String,
         database: String,
         partition: Option<co_req::Partition>,
+        value_schema: Option<ValueSchema>,
     ) -> Result<CollectionDesc> {
+        if let Some(value_schema) = value_schema.as_ref() {
+            check_value_schema(value_schema)?;
+        }
+
         let schema = self.schema()?;
         let db = schema
             .get_database(&database)
@@ -693,12 +1321,15 @@ impl Root {
                         co_desc::Partition::Range(co_desc::RangePartition {})
                     }
                 }),
+                schema: value_schema,
                 ..Default::default()
             })
             .await?;
         trace!(database = ?database, collection = ?collection, collection_id = collection.id, "prepare create collection");
 
-        self.do_create_collection(schema.to_owned(), collection.to_owned())
+        let replicas = effective_replication_factor(&db, &collection);
+        self.ensure_group_with_replicas(replicas as usize).await?;
+        self.do_create_collection(schema.to_owned(), &db, collection.to_owned(), replicas)
             .await?;
 
         self.watcher_hub()
@@ -713,7 +1344,9 @@ impl Root {
     async fn do_create_collection(
         &self,
         schema: Arc<Schema>,
+        db: &DatabaseDesc,
         collection: CollectionDesc,
+        replication_factor: u32,
     ) -> Result<()> {
         let wait_create = {
             let partition = collection
@@ -749,6 +1382,7 @@ impl Root {
                     id,
                     collection_id: collection.id.to_owned(),
                     partition: Some(partition),
+                    collection_meta: Some(collection_meta(db, &collection)),
                 };
                 wait_create.push(shard);
             }
@@ -764,6 +1398,7 @@ impl Root {
                         wait_create,
                         status: CreateCollectionJobStatus::CreateCollectionCreating as i32,
                         desc: Some(collection.to_owned()),
+                        replication_factor,
                         ..Default::default()
                     })),
                     ..Default::default()
@@ -775,6 +1410,117 @@ impl Root {
         Ok(())
     }
 
+    pub async fn update_collection(
+        &self,
+        name: &str,
+        database: &DatabaseDesc,
+        desc: CollectionDesc,
+        update_mask: &[String],
+    ) -> Result<CollectionDesc> {
+        let schema = self.schema()?;
+        let db = schema
+            .get_database(&database.name)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        let prev = schema
+            .get_collection(db.id, name)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("collection {name} not found")))?;
+        if prev.id < USER_COLLECTION_INIT_ID {
+            return Err(Error::InvalidArgument(
+                "unsupported update system collection".into(),
+            ));
+        }
+
+        let prev_epoch = prev.epoch;
+        let desc = apply_collection_update(prev, desc, update_mask)?;
+        self.ensure_group_with_replicas(effective_replication_factor(&db, &desc) as usize)
+            .await?;
+        if desc.name != name {
+            schema.rename_collection(name, desc.to_owned()).await?;
+        } else {
+            schema.update_collection(desc.to_owned()).await?;
+        }
+        if desc.epoch != prev_epoch {
+            // The schema and the TTL take effect once they are synchronized to the shards.
+            self.submit_sync_collection(desc.id).await?;
+        }
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent {
+                event: Some(update_event::Event::Collection(desc.to_owned())),
+            }])
+            .await;
+        trace!(database = ?database.name, collection = ?name, desc = ?desc, "update collection");
+        Ok(desc)
+    }
+
+    async fn submit_sync_collection(&self, collection_id: u64) -> Result<()> {
+        self.jobs
+            .submit(
+                BackgroundJob {
+                    job: Some(Job::SyncCollection(SyncCollectionJob {
+                        collection_id,
+                        created_time: format!("{:?}", Instant::now()),
+                    })),
+                    ..Default::default()
+                },
+                true,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Make sure there is at least one user group whose replication factor is `replicas`, so that
+    /// the shards of the collections requiring it could be placed immediately. The other groups are
+    /// created by the allocator in the background.
+    async fn ensure_group_with_replicas(&self, replicas: usize) -> Result<()> {
+        let replicas_per_group = self.alloc.replicas_per_group();
+        let replicas = if replicas == 0 {
+            replicas_per_group
+        } else {
+            replicas
+        };
+
+        let schema = self.schema()?;
+        let exists = schema.list_group().await?.iter().any(|g| {
+            let group_replicas = match g.replication_factor as usize {
+                0 => replicas_per_group,
+                n => n,
+            };
+            g.id != ROOT_GROUP_ID && group_replicas == replicas
+        });
+        if exists {
+            return Ok(());
+        }
+
+        let num_nodes = schema
+            .list_node()
+            .await?
+            .iter()
+            .filter(|n| n.status != NodeStatus::Decommissioned as i32)
+            .count();
+        if num_nodes < replicas {
+            return Err(Error::InvalidArgument(format!(
+                "replication factor {replicas} exceeds the number of nodes {num_nodes}"
+            )));
+        }
+
+        info!("create group with {replicas} replicas");
+        self.jobs
+            .submit(
+                BackgroundJob {
+                    job: Some(Job::CreateOneGroup(CreateOneGroupJob {
+                        request_replica_cnt: replicas as u64,
+                        status: CreateOneGroupStatus::CreateOneGroupInit as i32,
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                },
+                true,
+            )
+            .await
+    }
+
     pub async fn delete_collection(&self, name: &str, database: &DatabaseDesc) -> Result<()> {
         let schema = self.schema()?;
         let db = self
@@ -821,6 +1567,76 @@ impl Root {
         Ok(())
     }
 
+    pub async fn create_index(
+        &self,
+        database: &DatabaseDesc,
+        collection: &str,
+        index: IndexDesc,
+    ) -> Result<CollectionDesc> {
+        let schema = self.schema()?;
+        let db = schema
+            .get_database(&database.name)
+            .await?
+            .ok_or_else(|| Error::DatabaseNotFound(database.name.clone()))?;
+        let mut desc = schema
+            .get_collection(db.id, collection)
+            .await?
+            .ok_or_else(|| Error::InvalidArgument(format!("collection {collection} not found")))?;
+        if desc.id < USER_COLLECTION_INIT_ID {
+            return Err(Error::InvalidArgument(
+                "unsupported index system collection".into(),
+            ));
+        }
+        if index.extractor.is_none() {
+            return Err(Error::InvalidArgument("IndexDesc::extractor".into()));
+        }
+        if desc.indexes.iter().any(|i| i.name == index.name) {
+            return Err(Error::AlreadyExists(format!("index {}", index.name)));
+        }
+
+        let index = IndexDesc {
+            id: schema.next_index_id().await?,
+            name: index.name,
+            extractor: index.extractor,
+            state: index_desc::State::Backfilling as i32,
+        };
+        desc.indexes.push(index.to_owned());
+        desc.epoch += 1;
+        schema.update_collection(desc.to_owned()).await?;
+        self.watcher_hub()
+            .notify_updates(vec![UpdateEvent {
+                event: Some(update_event::Event::Collection(desc.to_owned())),
+            }])
+            .await;
+
+        // The index is synchronized to the shards before backfilling, so that the new writes
+        // maintain the index entries, and the existing records are backfilled in background.
+        let wait_backfill = schema
+            .get_collection_shards(desc.id)
+            .await?
+            .into_iter()
+            .map(|(_, shard)| shard.id)
+            .collect::<Vec<_>>();
+        self.jobs
+            .submit(
+                BackgroundJob {
+                    job: Some(Job::BackfillIndex(BackfillIndexJob {
+                        database_id: db.id,
+                        collection_id: desc.id,
+                        index: Some(index.to_owned()),
+                        wait_backfill,
+                        next_key: vec![],
+                        created_time: format!("{:?}", Instant::now()),
+                    })),
+                    ..Default::default()
+                },
+                false,
+            )
+            .await?;
+        trace!(collection = collection, index = ?index, "create index");
+        Ok(desc)
+    }
+
     pub async fn list_database(&self) -> Result<Vec<DatabaseDesc>> {
         self.schema()?.list_database().await
     }
@@ -856,14 +1672,25 @@ impl Root {
         self.schema()?.get_collection(db.id, name).await
     }
 
-    pub async fn watch(&self, cur_groups: HashMap<u64, u64>) -> Result<Watcher> {
+    /// Watch the changes of metadata. The changes after `start_revision` are sent if root still
+    /// keeps them, otherwise the full state is sent.
+    pub async fn watch(
+        &self,
+        cur_groups: HashMap<u64, u64>,
+        start_revision: u64,
+    ) -> Result<Watcher> {
         let schema = self.schema()?;
 
         let watcher = {
             let hub = self.watcher_hub();
             let (watcher, mut initializer) = hub.create_watcher().await;
-            let (updates, deletes) = schema.list_all_events(cur_groups).await?;
-            initializer.set_init_resp(updates, deletes);
+            if start_revision != 0 && initializer.resume(start_revision) {
+                metrics::WATCH_RESUME_TOTAL.inc();
+            } else {
+                metrics::WATCH_RESYNC_TOTAL.inc();
+                let (updates, deletes) = schema.list_all_events(cur_groups).await?;
+                initializer.set_init_resp(updates, deletes);
+            }
             watcher
         };
         Ok(watcher)
@@ -873,12 +1700,14 @@ impl Root {
         &self,
         addr: String,
         capacity: NodeCapacity,
+        locality: NodeLocality,
     ) -> Result<(Vec<u8>, NodeDesc, RootDesc)> {
         let schema = self.schema()?;
         let node = schema
             .add_node(NodeDesc {
                 addr,
                 capacity: Some(capacity),
+                locality: Some(locality),
                 ..Default::default()
             })
             .await?;
@@ -1021,7 +1850,8 @@ impl Root {
 
         let nodes = self
             .alloc
-            .allocate_group_replica(
+            .allocate_replica_for_group(
+                group_id,
                 existing_replicas.into_iter().collect(),
                 requested_cnt as usize,
             )
@@ -1237,6 +2067,16 @@ impl OngoingStats {
         rs
     }
 
+    /// The number of groups which are moving replicas.
+    pub fn ongoing_moves(&self) -> usize {
+        let inner = self.sched_stats.lock().unwrap();
+        inner
+            .raw_group_delta
+            .values()
+            .filter(|d| !d.incoming.is_empty() || !d.outgoing.is_empty())
+            .count()
+    }
+
     pub fn reset(&self) {
         {
             let mut inner = self.sched_stats.lock().unwrap();
@@ -1303,19 +2143,212 @@ impl SchedStats {
     }
 }
 
+/// Apply the fields of `desc` listed in `update_mask` to `prev`.
+fn apply_database_update(
+    mut prev: DatabaseDesc,
+    desc: DatabaseDesc,
+    update_mask: &[String],
+) -> Result<DatabaseDesc> {
+    if update_mask.is_empty() {
+        return Err(Error::InvalidArgument("update mask is empty".into()));
+    }
+    for path in update_mask {
+        match path.as_str() {
+            "name" => {
+                if desc.name.is_empty() {
+                    return Err(Error::InvalidArgument("database name is empty".into()));
+                }
+                prev.name = desc.name.clone();
+            }
+            "replication_factor" => prev.replication_factor = desc.replication_factor,
+            "default_ttl" => prev.default_ttl = desc.default_ttl,
+            "placement_constraints" => {
+                check_placement_constraints(&desc.placement_constraints)?;
+                prev.placement_constraints = desc.placement_constraints.clone();
+            }
+            "leader_preferences" => {
+                check_leader_preferences(&desc.leader_preferences)?;
+                prev.leader_preferences = desc.leader_preferences.clone();
+            }
+            _ => {
+                return Err(Error::InvalidArgument(format!(
+                    "unsupported update path `{path}` of database"
+                )))
+            }
+        }
+    }
+    Ok(prev)
+}
+
+/// Apply the fields of `desc` listed in `update_mask` to `prev`.
+fn apply_collection_update(
+    mut prev: CollectionDesc,
+    desc: CollectionDesc,
+    update_mask: &[String],
+) -> Result<CollectionDesc> {
+    if update_mask.is_empty() {
+        return Err(Error::InvalidArgument("update mask is empty".into()));
+    }
+    for path in update_mask {
+        match path.as_str() {
+            "name" => {
+                if desc.name.is_empty() {
+                    return Err(Error::InvalidArgument("collection name is empty".into()));
+                }
+                prev.name = desc.name.clone();
+            }
+            "replication_factor" => prev.replication_factor = desc.replication_factor,
+            "default_ttl" => {
+                prev.default_ttl = desc.default_ttl;
+                prev.epoch += 1;
+            }
+            "placement_constraints" => {
+                check_placement_constraints(&desc.placement_constraints)?;
+                prev.placement_constraints = desc.placement_constraints.clone();
+            }
+            "leader_preferences" => {
+                check_leader_preferences(&desc.leader_preferences)?;
+                prev.leader_preferences = desc.leader_preferences.clone();
+            }
+            "schema" => {
+                if let Some(value_schema) = desc.schema.as_ref() {
+                    check_value_schema(value_schema)?;
+                }
+                prev.schema = desc.schema.clone();
+                prev.epoch += 1;
+            }
+            _ => {
+                return Err(Error::InvalidArgument(format!(
+                    "unsupported update path `{path}` of collection"
+                )))
+            }
+        }
+    }
+    Ok(prev)
+}
+
+fn check_placement_constraints(constraints: &[PlacementConstraint]) -> Result<()> {
+    for constraint in constraints {
+        if constraint.key.is_empty() {
+            return Err(Error::InvalidArgument(
+                "the key of placement constraint is empty".into(),
+            ));
+        }
+        if placement_constraint::Kind::from_i32(constraint.kind).is_none() {
+            return Err(Error::InvalidArgument(format!(
+                "unknown placement constraint kind {}",
+                constraint.kind
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn check_leader_preferences(preferences: &[LeaderPreference]) -> Result<()> {
+    if preferences.iter().any(|p| p.key.is_empty()) {
+        return Err(Error::InvalidArgument(
+            "the key of leader preference is empty".into(),
+        ));
+    }
+    Ok(())
+}
+
+/// The replication factor of the collection, inherits from the database if it is not specified.
+/// 0 means the default replicas per group.
+fn effective_replication_factor(db: &DatabaseDesc, collection: &CollectionDesc) -> u32 {
+    match collection.replication_factor {
+        0 => db.replication_factor,
+        n => n,
+    }
+}
+
+/// The TTL of values in seconds, inherits from the database if it is not specified. 0 means that
+/// values never expire.
+fn effective_ttl(db: &DatabaseDesc, collection: &CollectionDesc) -> u64 {
+    match collection.default_ttl {
+        0 => db.default_ttl,
+        n => n,
+    }
+}
+
+/// Check whether the remaining active nodes could still hold the replicas of every group and
+/// collection after the node is decommissioned. The replicas of a collection could only be placed
+/// on the nodes which satisfy its placement constraints.
+fn check_decommission_capacity(
+    node_id: u64,
+    nodes: &[NodeDesc],
+    groups: &[GroupDesc],
+    databases: &[DatabaseDesc],
+    collections: &[CollectionDesc],
+    replicas_per_group: usize,
+) -> Result<()> {
+    let remaining = nodes
+        .iter()
+        .filter(|n| n.id != node_id && n.status == NodeStatus::Active as i32)
+        .collect::<Vec<_>>();
+    let required = groups
+        .iter()
+        .map(|g| match g.replication_factor as usize {
+            0 => replicas_per_group,
+            n => n,
+        })
+        .fold(replicas_per_group, usize::max);
+    if remaining.len() < required {
+        return Err(Error::InvalidArgument(format!(
+            "only {} active nodes remain after decommission, at least {required} are required",
+            remaining.len()
+        )));
+    }
+
+    let node = nodes.iter().find(|n| n.id == node_id);
+    let databases = databases
+        .iter()
+        .map(|db| (db.id, db))
+        .collect::<HashMap<_, _>>();
+    for co in collections {
+        let db = databases.get(&co.db).copied();
+        let replicas = match db
+            .map(|db| effective_replication_factor(db, co))
+            .unwrap_or(co.replication_factor) as usize
+        {
+            0 => replicas_per_group,
+            n => n,
+        };
+        let placement = allocator::Placement::of_collection(db, co);
+        if !node.map(|n| placement.allows(n)).unwrap_or_default() {
+            // The node doesn't hold the replicas of this collection, the capacity is not changed.
+            continue;
+        }
+        let candidates = remaining.iter().filter(|n| placement.allows(n)).count();
+        if candidates < replicas {
+            return Err(Error::InvalidArgument(format!(
+                "collection {} requires {replicas} replicas, but only {candidates} active nodes satisfy its placement constraints after decommission",
+                co.name
+            )));
+        }
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod root_test {
     use engula_api::{
         server::v1::{
             watch_response::{update_event, UpdateEvent},
-            GroupDesc,
+            GroupDesc, NodeDesc, NodeLocality, NodeStatus,
+        },
+        v1::{
+            placement_constraint::Kind, CollectionDesc, DatabaseDesc, LeaderPreference,
+            PlacementConstraint,
         },
-        v1::DatabaseDesc,
     };
     use futures::StreamExt;
     use tempdir::TempDir;
 
-    use super::Config;
+    use super::{
+        apply_collection_update, apply_database_update, check_decommission_capacity, Config,
+        WatchHub,
+    };
     use crate::{
         bootstrap::bootstrap_cluster,
         constants::{INITIAL_EPOCH, ROOT_GROUP_ID},
@@ -1328,7 +2361,7 @@ mod root_test {
     };
 
     async fn create_root_and_node(config: &Config, node_ident: &NodeIdent) -> (Root, Node) {
-        let engines = Engines::open(&config.root_dir, &config.db).unwrap();
+        let engines = Engines::open(&config.root_dir, &config.db, &config.encryption).unwrap();
         let root_list = if config.init {
             vec![config.addr.clone()]
         } else {
@@ -1391,6 +2424,7 @@ mod root_test {
                     epoch: INITIAL_EPOCH,
                     shards: vec![],
                     replicas: vec![],
+                    ..Default::default()
                 },
             )
             .await
@@ -1420,6 +2454,7 @@ mod root_test {
             let _create_db1_event = Some(update_event::Event::Database(DatabaseDesc {
                 id: 1,
                 name: "db1".into(),
+                ..Default::default()
             }));
             let mut w = {
                 let (w, mut initializer) = hub.create_watcher().await;
@@ -1442,6 +2477,7 @@ mod root_test {
             let _create_db2_event = Some(update_event::Event::Database(DatabaseDesc {
                 id: 2,
                 name: "db2".into(),
+                ..Default::default()
             }));
             hub.notify_updates(vec![UpdateEvent {
                 event: _create_db2_event,
@@ -1454,11 +2490,227 @@ mod root_test {
             // hub.notify_error(Error::NotRootLeader(vec![])).await;
         });
     }
+
+    #[test]
+    fn watch_hub_resume() {
+        let executor_owner = ExecutorOwner::new(1);
+        let executor = executor_owner.executor();
+        executor.block_on(async {
+            let db_event = |id: u64| UpdateEvent {
+                event: Some(update_event::Event::Database(DatabaseDesc {
+                    id,
+                    ..Default::default()
+                })),
+            };
+            let hub = WatchHub::new(2);
+            hub.reset_revision(1).await;
+            let mut w = hub.create_watcher().await.0;
+            for id in 1..=3 {
+                hub.notify_updates(vec![db_event(id)]).await;
+            }
+            let resp = w.next().await.unwrap().unwrap();
+            assert_eq!(resp.updates.len(), 3);
+            assert_eq!(resp.revision, (1 << 32) + 3);
+
+            // Only the changes after the revision are sent.
+            let (mut w, mut initializer) = hub.create_watcher().await;
+            assert!(initializer.resume((1 << 32) + 1));
+            drop(initializer);
+            let resp = w.next().await.unwrap().unwrap();
+            assert_eq!(resp.updates.len(), 2);
+            assert_eq!(resp.revision, (1 << 32) + 3);
+
+            // The change of revision 1 is truncated.
+            let (_w, mut initializer) = hub.create_watcher().await;
+            assert!(!initializer.resume(1 << 32));
+            drop(initializer);
+
+            // The revisions of the previous term are unknown.
+            hub.reset_revision(2).await;
+            let (_w, mut initializer) = hub.create_watcher().await;
+            assert!(!initializer.resume((1 << 32) + 3));
+            assert!(initializer.resume(2 << 32));
+        });
+    }
+
+    #[test]
+    fn apply_update_with_mask() {
+        let prev = DatabaseDesc {
+            id: 2,
+            name: "db".into(),
+            replication_factor: 3,
+            ..Default::default()
+        };
+        let desc = DatabaseDesc {
+            name: "db1".into(),
+            replication_factor: 5,
+            default_ttl: 60,
+            ..Default::default()
+        };
+        let new_desc =
+            apply_database_update(prev.clone(), desc.clone(), &["default_ttl".into()]).unwrap();
+        assert_eq!(new_desc.id, 2);
+        assert_eq!(new_desc.name, "db");
+        assert_eq!(new_desc.replication_factor, 3);
+        assert_eq!(new_desc.default_ttl, 60);
+
+        let new_desc = apply_database_update(
+            prev.clone(),
+            desc.clone(),
+            &["name".into(), "replication_factor".into()],
+        )
+        .unwrap();
+        assert_eq!(new_desc.name, "db1");
+        assert_eq!(new_desc.replication_factor, 5);
+        assert_eq!(new_desc.default_ttl, 0);
+
+        assert!(apply_database_update(prev.clone(), desc.clone(), &[]).is_err());
+        assert!(apply_database_update(prev.clone(), desc, &["id".into()]).is_err());
+        assert!(apply_database_update(prev, DatabaseDesc::default(), &["name".into()]).is_err());
+
+        let prev = CollectionDesc {
+            id: 10,
+            name: "co".into(),
+            db: 2,
+            ..Default::default()
+        };
+        let desc = CollectionDesc {
+            placement_constraints: vec![PlacementConstraint {
+                key: "zone".into(),
+                value: "z1".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+        let new_desc = apply_collection_update(
+            prev.clone(),
+            desc.clone(),
+            &["placement_constraints".into(), "schema".into()],
+        )
+        .unwrap();
+        assert_eq!((new_desc.id, new_desc.db), (10, 2));
+        assert_eq!(new_desc.placement_constraints, desc.placement_constraints);
+
+        // The collection inherits the TTL from the database unless it is specified.
+        let db = DatabaseDesc {
+            default_ttl: 60,
+            ..Default::default()
+        };
+        assert_eq!(effective_ttl(&db, &prev), 60);
+        let desc = CollectionDesc {
+            default_ttl: 10,
+            ..Default::default()
+        };
+        let new_desc =
+            apply_collection_update(prev.clone(), desc, &["default_ttl".into()]).unwrap();
+        assert_eq!(new_desc.epoch, prev.epoch + 1);
+        assert_eq!(effective_ttl(&db, &new_desc), 10);
+
+        let desc = CollectionDesc {
+            placement_constraints: vec![PlacementConstraint::default()],
+            ..Default::default()
+        };
+        assert!(
+            apply_collection_update(prev.clone(), desc, &["placement_constraints".into()]).is_err()
+        );
+
+        let desc = CollectionDesc {
+            leader_preferences: vec![LeaderPreference {
+                key: "zone".into(),
+                value: "z1".into(),
+            }],
+            ..Default::default()
+        };
+        let new_desc =
+            apply_collection_update(prev.clone(), desc.clone(), &["leader_preferences".into()])
+                .unwrap();
+        assert_eq!(new_desc.leader_preferences, desc.leader_preferences);
+        let desc = CollectionDesc {
+            leader_preferences: vec![LeaderPreference::default()],
+            ..Default::default()
+        };
+        assert!(apply_collection_update(prev, desc, &["leader_preferences".into()]).is_err());
+    }
+
+    fn zone_node(id: u64, zone: &str, status: NodeStatus) -> NodeDesc {
+        NodeDesc {
+            id,
+            status: status as i32,
+            locality: Some(NodeLocality {
+                zone: zone.into(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn decommission_capacity() {
+        let nodes = vec![
+            zone_node(1, "z1", NodeStatus::Active),
+            zone_node(2, "z1", NodeStatus::Active),
+            zone_node(3, "z2", NodeStatus::Active),
+            zone_node(4, "z2", NodeStatus::Active),
+            zone_node(5, "z2", NodeStatus::Decommissioning),
+        ];
+        let check =
+            |node_id, groups: &[GroupDesc], dbs: &[DatabaseDesc], cos: &[CollectionDesc]| {
+                check_decommission_capacity(node_id, &nodes, groups, dbs, cos, 3)
+            };
+
+        // The decommissioning nodes are not counted.
+        assert!(check(1, &[], &[], &[]).is_ok());
+        let groups_with_4_replicas = [GroupDesc {
+            replication_factor: 4,
+            ..Default::default()
+        }];
+        assert!(check(1, &groups_with_4_replicas, &[], &[]).is_err());
+
+        // The replication factor of collections is inherited from the database.
+        let db = DatabaseDesc {
+            id: 1,
+            replication_factor: 4,
+            ..Default::default()
+        };
+        let co = CollectionDesc {
+            id: 10,
+            db: 1,
+            ..Default::default()
+        };
+        assert!(check(1, &[], &[db.clone()], &[co.clone()]).is_err());
+        let co_with_3_replicas = CollectionDesc {
+            replication_factor: 3,
+            ..co.clone()
+        };
+        assert!(check(1, &[], &[db], &[co_with_3_replicas]).is_ok());
+
+        // Only the nodes satisfying the placement constraints could hold the replicas.
+        let co = CollectionDesc {
+            replication_factor: 2,
+            placement_constraints: vec![PlacementConstraint {
+                kind: Kind::Required.into(),
+                key: "zone".into(),
+                value: "z1".into(),
+            }],
+            ..co
+        };
+        assert!(check(1, &[], &[], &[co.clone()]).is_err());
+        // The node out of the placement doesn't change the capacity of the collection.
+        assert!(check(3, &[], &[], &[co.clone()]).is_ok());
+        let co = CollectionDesc {
+            replication_factor: 1,
+            ..co
+        };
+        assert!(check(1, &[], &[], &[co]).is_ok());
+    }
 }
 
 pub mod diagnosis {
     use serde::{Deserialize, Serialize};
 
+    use super::allocator::BalanceClass;
+    use crate::serverpb::v1::ScheduleControl;
+
     #[derive(Serialize, Deserialize)]
     pub struct Metadata {
         pub databases: Vec<Database>,
@@ -1521,4 +2773,67 @@ pub mod diagnosis {
         pub id: u64,
         pub partition: String,
     }
+
+    /// A replica or leader which doesn't follow the placement rules of its group.
+    #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+    pub struct PlacementViolation {
+        pub group: u64,
+        pub replica: u64,
+        pub node: u64,
+        pub reason: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Default)]
+    pub struct ScheduleControlState {
+        /// The classes of balancing which are paused.
+        pub paused: Vec<String>,
+        /// 0 means unlimited.
+        pub max_concurrent_moves: u64,
+    }
+
+    impl From<&ScheduleControl> for ScheduleControlState {
+        fn from(control: &ScheduleControl) -> Self {
+            ScheduleControlState {
+                paused: BalanceClass::ALL
+                    .iter()
+                    .filter(|c| c.is_paused(control))
+                    .map(|c| c.name().to_owned())
+                    .collect(),
+                max_concurrent_moves: control.max_concurrent_moves,
+            }
+        }
+    }
+
+    /// The actions proposed by the scheduler against the current state, they are not executed.
+    #[derive(Serialize, Deserialize, Debug, Clone, Default)]
+    pub struct ScheduleExplain {
+        pub actions: Vec<ProposedAction>,
+        pub nodes: Vec<NodeScore>,
+        /// Whether the load balancing is waiting for the stats of the last move.
+        pub load_balance_cooling: bool,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone, Default)]
+    pub struct ProposedAction {
+        /// One of `group`, `replica`, `shard` and `leader`.
+        pub kind: String,
+        pub policy: String,
+        pub action: String,
+        pub reason: String,
+        pub source: Option<NodeScore>,
+        pub target: Option<NodeScore>,
+    }
+
+    /// The metrics of a node which the scheduler makes decisions by.
+    #[derive(Serialize, Deserialize, Debug, Clone, Default)]
+    pub struct NodeScore {
+        pub node: u64,
+        pub status: String,
+        /// The number of replicas, include the ones being moved in or out.
+        pub replica_count: u64,
+        pub leader_count: u64,
+        /// The used ratio of disk, `None` if it isn't reported yet.
+        pub disk_usage: Option<f64>,
+        pub load: f64,
+    }
 }
@@ -12,7 +12,10 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::{collections::LinkedList, sync::Arc};
+use std::{
+    collections::{HashSet, LinkedList},
+    sync::Arc,
+};
 
 use engula_api::server::v1::*;
 use prometheus::HistogramTimer;
@@ -70,12 +73,60 @@ impl ReconcileScheduler {
     async fn is_empty(&self) -> bool {
         self.tasks.lock().await.is_empty()
     }
+
+    /// Setup the tasks of the pending manual schedule jobs, which are not set up yet.
+    async fn setup_manual_tasks(&self) {
+        let jobs = self.ctx.jobs.manual_schedule_jobs();
+        if jobs.is_empty() {
+            return;
+        }
+        let mut tasks = self.tasks.lock().await;
+        for (id, job) in jobs {
+            if tasks.iter().any(|t| t.manual_job == id) {
+                continue;
+            }
+            let Some(mut task) = job.task else {
+                continue;
+            };
+            task.manual_job = id;
+            info!(job = id, task = ?task, "setup manual reconcile task");
+            tasks.push_back(task);
+        }
+    }
+
+    /// The groups involved by the pending manual tasks, the automatic balancer leaves them alone
+    /// until the manual tasks are finished.
+    async fn manual_groups(&self) -> HashSet<u64> {
+        let tasks = self.tasks.lock().await;
+        tasks
+            .iter()
+            .filter(|t| t.manual_job != 0)
+            .flat_map(|t| match t.task.as_ref() {
+                Some(Task::ReallocateReplica(t)) => vec![t.group],
+                Some(Task::TransferGroupLeader(t)) => vec![t.group],
+                Some(Task::MigrateShard(t)) => vec![t.src_group, t.dest_group],
+                _ => vec![],
+            })
+            .collect()
+    }
+
+    async fn finish_manual_job(&self, id: u64, status: ManualScheduleStatus, remark: String) {
+        info!(job = id, status = ?status, remark = remark, "manual reconcile task finished");
+        if let Err(err) = self
+            .ctx
+            .jobs
+            .finish_manual_schedule(id, status, remark)
+            .await
+        {
+            warn!(job = id, err = ?err, "finish manual schedule job");
+        }
+    }
 }
 
 impl ReconcileScheduler {
     pub async fn need_reconcile(&self) -> Result<bool> {
         let group_action = self.ctx.alloc.compute_group_action().await?;
-        if matches!(group_action, GroupAction::Add(_)) {
+        if matches!(group_action, GroupAction::Add { .. }) {
             return Ok(true);
         }
 
@@ -93,18 +144,23 @@ impl ReconcileScheduler {
 
     pub async fn check(&self) -> Result<bool> {
         let _timer = super::metrics::RECONCILE_CHECK_DURATION_SECONDS.start_timer();
+        self.setup_manual_tasks().await;
         let group_action = self.ctx.alloc.compute_group_action().await?;
-        if let GroupAction::Add(cnt) = group_action {
+        if let GroupAction::Add {
+            count,
+            replicas_per_group,
+        } = group_action
+        {
             metrics::RECONCILE_ALREADY_BALANCED_INFO
                 .cluster_groups
                 .set(0);
-            for _ in 0..cnt {
+            for _ in 0..count {
                 self.ctx
                     .jobs
                     .submit(
                         BackgroundJob {
                             job: Some(Job::CreateOneGroup(CreateOneGroupJob {
-                                request_replica_cnt: self.ctx.alloc.replicas_per_group() as u64,
+                                request_replica_cnt: replicas_per_group as u64,
                                 status: CreateOneGroupStatus::CreateOneGroupInit as i32,
                                 ..Default::default()
                             })),
@@ -120,15 +176,22 @@ impl ReconcileScheduler {
             .cluster_groups
             .set(1);
 
+        self.check_decommission().await?;
+
         let ractions = self.comput_replica_role_action().await?;
         let sactions = self.ctx.alloc.compute_shard_action().await?;
         if ractions.is_empty() && sactions.is_empty() {
             return Ok(!self.is_empty().await);
         }
 
+        let manual_groups = self.manual_groups().await;
+        let mut moves_budget = self.moves_budget().await;
         for action in ractions {
             match action {
-                ReplicaRoleAction::Replica(ReplicaAction::Migrate(action)) => {
+                ReplicaRoleAction::Replica(ReplicaAction::Migrate(action))
+                    if !manual_groups.contains(&action.group)
+                        && take_move_budget(&mut moves_budget) =>
+                {
                     self.setup_task(ReconcileTask {
                         task: Some(reconcile_task::Task::ReallocateReplica(
                             ReallocateReplicaTask {
@@ -139,10 +202,13 @@ impl ReconcileScheduler {
                                 dest_replica: None,
                             },
                         )),
+                        ..Default::default()
                     })
                     .await;
                 }
-                ReplicaRoleAction::Leader(LeaderAction::Shed(action)) => {
+                ReplicaRoleAction::Leader(LeaderAction::Shed(action))
+                    if !manual_groups.contains(&action.group) =>
+                {
                     self.setup_task(ReconcileTask {
                         task: Some(reconcile_task::Task::TransferGroupLeader(
                             TransferGroupLeaderTask {
@@ -152,6 +218,7 @@ impl ReconcileScheduler {
                                 dest_node: action.target_node,
                             },
                         )),
+                        ..Default::default()
                     })
                     .await;
                 }
@@ -161,12 +228,21 @@ impl ReconcileScheduler {
 
         for action in sactions {
             let ShardAction::Migrate(action) = action;
+            if manual_groups.contains(&action.source_group)
+                || manual_groups.contains(&action.target_group)
+            {
+                continue;
+            }
+            if !take_move_budget(&mut moves_budget) {
+                break;
+            }
             self.setup_task(ReconcileTask {
                 task: Some(reconcile_task::Task::MigrateShard(MigrateShardTask {
                     shard: action.shard,
                     src_group: action.source_group,
                     dest_group: action.target_group,
                 })),
+                ..Default::default()
             })
             .await;
         }
@@ -174,6 +250,114 @@ impl ReconcileScheduler {
         Ok(!self.is_empty().await)
     }
 
+    /// Setup tasks to move replicas out of the decommissioning nodes, and mark the node as
+    /// decommissioned once no replica is left on it.
+    async fn check_decommission(&self) -> Result<()> {
+        let schema = self.ctx.shared.schema()?;
+        let nodes = schema
+            .list_node()
+            .await?
+            .into_iter()
+            .filter(|n| n.status == NodeStatus::Decommissioning as i32)
+            .collect::<Vec<_>>();
+        if nodes.is_empty() {
+            return Ok(());
+        }
+
+        let groups = schema.list_group().await?;
+        let mut moves_budget = self.moves_budget().await;
+        for mut node in nodes {
+            let replicas = groups
+                .iter()
+                .flat_map(|g| g.replicas.iter().map(move |r| (g, r)))
+                .filter(|(_, r)| r.node_id == node.id)
+                .collect::<Vec<_>>();
+            if replicas.is_empty() {
+                info!(
+                    node = node.id,
+                    "all replicas are moved out, node is decommissioned"
+                );
+                node.status = NodeStatus::Decommissioned as i32;
+                schema.update_node(node).await?; // TODO: cas
+                continue;
+            }
+
+            info!(
+                node = node.id,
+                replicas = replicas.len(),
+                "decommission node in progress"
+            );
+            for (group, replica) in replicas {
+                if self.is_reallocating(replica.id).await || !take_move_budget(&mut moves_budget) {
+                    continue;
+                }
+                let exist_nodes = group.replicas.iter().map(|r| r.node_id).collect();
+                let mut targets = self
+                    .ctx
+                    .alloc
+                    .allocate_replica_for_group(group.id, exist_nodes, 1)
+                    .await?;
+                let Some(target) = targets.pop() else {
+                    warn!(
+                        node = node.id,
+                        group = group.id,
+                        replica = replica.id,
+                        "no suitable target to move replica out of decommissioning node"
+                    );
+                    continue;
+                };
+                self.setup_task(ReconcileTask {
+                    task: Some(reconcile_task::Task::ReallocateReplica(
+                        ReallocateReplicaTask {
+                            group: group.id,
+                            src_node: node.id,
+                            src_replica: replica.id,
+                            dest_node: Some(target),
+                            dest_replica: None,
+                        },
+                    )),
+                    ..Default::default()
+                })
+                .await;
+            }
+        }
+        Ok(())
+    }
+
+    /// The number of replica moves and shard migrations which could be set up without exceeding
+    /// the concurrency limit, `None` means unlimited.
+    async fn moves_budget(&self) -> Option<usize> {
+        let limit = self.ctx.alloc.schedule_control().max_concurrent_moves as usize;
+        if limit == 0 {
+            return None;
+        }
+        let queued = {
+            let tasks = self.tasks.lock().await;
+            tasks
+                .iter()
+                .filter(|t| {
+                    matches!(
+                        t.task,
+                        Some(Task::ReallocateReplica(_)) | Some(Task::MigrateShard(_))
+                    )
+                })
+                .count()
+        };
+        let inflight = queued + self.ctx.ongoing_stats.ongoing_moves();
+        Some(limit.saturating_sub(inflight))
+    }
+
+    async fn is_reallocating(&self, replica_id: u64) -> bool {
+        let tasks = self.tasks.lock().await;
+        tasks.iter().any(|t| {
+            matches!(
+                &t.task,
+                Some(Task::ReallocateReplica(ReallocateReplicaTask { src_replica, .. }))
+                    if *src_replica == replica_id
+            )
+        })
+    }
+
     pub async fn comput_replica_role_action(&self) -> Result<Vec<ReplicaRoleAction>> {
         let mut actions = Vec::new();
         let replica_actions = self.ctx.alloc.compute_replica_action().await?;
@@ -222,14 +406,34 @@ impl ReconcileScheduler {
         let mut cursor = task.cursor_front_mut();
         while let Some(task) = cursor.current() {
             let _timer = Self::record_exec(task);
+            let manual_job = task.manual_job;
             let rs = self.ctx.handle_task(task).await;
             match rs {
                 Ok((true /* ack */, immediately_next)) => {
                     cursor.remove_current();
+                    if manual_job != 0 {
+                        self.finish_manual_job(
+                            manual_job,
+                            ManualScheduleStatus::ManualScheduleFinish,
+                            String::new(),
+                        )
+                        .await;
+                    }
                     if !immediately_next {
                         nowait_next = false
                     }
                 }
+                Err(err) if manual_job != 0 => {
+                    // The manual tasks aren't retried, the operators decide whether to submit
+                    // them again.
+                    cursor.remove_current();
+                    self.finish_manual_job(
+                        manual_job,
+                        ManualScheduleStatus::ManualScheduleAbort,
+                        format!("{err:?}"),
+                    )
+                    .await;
+                }
                 _ => {
                     Self::record_retry(task);
                     // ack == false or meet error, skip current task and retry later.
@@ -294,6 +498,18 @@ impl ReconcileScheduler {
     }
 }
 
+/// Take one move from the budget, returns false if the budget is exhausted.
+fn take_move_budget(budget: &mut Option<usize>) -> bool {
+    match budget {
+        None => true,
+        Some(0) => false,
+        Some(n) => {
+            *n -= 1;
+            true
+        }
+    }
+}
+
 impl ScheduleContext {
     pub(crate) fn new(
         shared: Arc<RootShared>,
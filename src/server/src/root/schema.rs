@@ -23,7 +23,7 @@ use engula_api::{
         watch_response::{delete_event, update_event, DeleteEvent, UpdateEvent},
         *,
     },
-    v1::{collection_desc, CollectionDesc, DatabaseDesc, PutRequest},
+    v1::{collection_desc, CollectionDesc, DatabaseDesc, DeleteRequest, PutRequest},
 };
 use futures::lock::Mutex;
 use prost::Message;
@@ -33,7 +33,7 @@ use super::store::RootStore;
 use crate::{
     constants::*,
     engine::{GroupEngine, SnapshotMode},
-    serverpb::v1::BackgroundJob,
+    serverpb::v1::{background_job, BackgroundJob, RestoreJob, RestoreStatus, ScheduleControl},
     transport::TransportManager,
     Error, Result,
 };
@@ -75,6 +75,7 @@ const META_NODE_ID_KEY: &str = "node_id";
 const META_REPLICA_ID_KEY: &str = "replica_id";
 const META_SHARD_ID_KEY: &str = "shard_id";
 const META_JOB_ID_KEY: &str = "job_id";
+const META_SCHEDULE_CONTROL_KEY: &str = "schedule_control";
 
 lazy_static::lazy_static! {
     pub static ref SYSTEM_COLLECTION_SHARD: BTreeMap<u64, u64> = BTreeMap::from([
@@ -118,6 +119,27 @@ impl Schema {
         Ok(None)
     }
 
+    pub async fn get_schedule_control(&self) -> Result<ScheduleControl> {
+        let val = self.get_meta(META_SCHEDULE_CONTROL_KEY.as_bytes()).await?;
+        match val {
+            Some(val) => ScheduleControl::decode(&*val)
+                .map_err(|_| Error::InvalidData("schedule control".into())),
+            None => Ok(ScheduleControl::default()),
+        }
+    }
+
+    pub async fn put_schedule_control(&self, control: ScheduleControl) -> Result<()> {
+        self.batch_write(
+            PutBatchBuilder::default()
+                .put_meta(
+                    META_SCHEDULE_CONTROL_KEY.as_bytes().to_vec(),
+                    control.encode_to_vec(),
+                )
+                .build(),
+        )
+        .await
+    }
+
     pub async fn create_database(&self, desc: DatabaseDesc) -> Result<DatabaseDesc> {
         if self.get_database(&desc.name).await?.is_some() {
             return Err(Error::AlreadyExists(format!(
@@ -149,8 +171,21 @@ impl Schema {
         Ok(Some(desc))
     }
 
-    pub async fn update_database(&self, _desc: DatabaseDesc) -> Result<()> {
-        todo!()
+    /// Update the database desc, the database is renamed if the name of desc is different from
+    /// `name`.
+    pub async fn update_database(&self, name: &str, desc: DatabaseDesc) -> Result<()> {
+        if self.get_database(name).await?.is_none() {
+            return Err(Error::DatabaseNotFound(name.to_owned()));
+        }
+
+        let mut batch = PutBatchBuilder::default();
+        if desc.name != name {
+            if self.get_database(&desc.name).await?.is_some() {
+                return Err(Error::AlreadyExists(format!("database {}", desc.name)));
+            }
+            batch.delete_database(name);
+        }
+        self.batch_write(batch.put_database(desc).build()).await
     }
 
     pub async fn delete_database(&self, db: &DatabaseDesc) -> Result<u64> {
@@ -171,6 +206,11 @@ impl Schema {
         Ok(databases)
     }
 
+    pub async fn get_database_by_id(&self, id: u64) -> Result<Option<DatabaseDesc>> {
+        let databases = self.list_database().await?;
+        Ok(databases.into_iter().find(|db| db.id == id))
+    }
+
     pub async fn prepare_create_collection(&self, desc: CollectionDesc) -> Result<CollectionDesc> {
         if self.get_collection(desc.db, &desc.name).await?.is_some() {
             return Err(Error::AlreadyExists(format!(
@@ -214,6 +254,11 @@ impl Schema {
         Ok(Some(desc))
     }
 
+    pub async fn get_collection_by_id(&self, id: u64) -> Result<Option<CollectionDesc>> {
+        let collections = self.list_collection().await?;
+        Ok(collections.into_iter().find(|c| c.id == id))
+    }
+
     pub async fn get_collection_shards(&self, collection_id: u64) -> Result<Vec<(u64, ShardDesc)>> {
         let groups = self.list_group().await?;
         let group_shards = groups
@@ -228,8 +273,32 @@ impl Schema {
         Ok(group_shards)
     }
 
-    pub async fn update_collection(&self, _desc: CollectionDesc) -> Result<()> {
-        todo!()
+    pub async fn update_collection(&self, desc: CollectionDesc) -> Result<()> {
+        if self.get_collection(desc.db, &desc.name).await?.is_none() {
+            return Err(Error::InvalidArgument(format!(
+                "collection {} not found",
+                desc.name
+            )));
+        }
+        self.batch_write(PutBatchBuilder::default().put_collection(desc).build())
+            .await?;
+        Ok(())
+    }
+
+    /// Rename the collection `name` to the name of desc, and update the desc.
+    pub async fn rename_collection(&self, name: &str, desc: CollectionDesc) -> Result<()> {
+        if self.get_collection(desc.db, name).await?.is_none() {
+            return Err(Error::InvalidArgument(format!(
+                "collection {name} not found"
+            )));
+        }
+        if self.get_collection(desc.db, &desc.name).await?.is_some() {
+            return Err(Error::AlreadyExists(format!("collection {}", desc.name)));
+        }
+
+        let mut batch = PutBatchBuilder::default();
+        batch.delete_collection(desc.db, name);
+        self.batch_write(batch.put_collection(desc).build()).await
     }
 
     pub async fn delete_collection(&self, collection: CollectionDesc) -> Result<()> {
@@ -685,7 +754,10 @@ impl Schema {
         &mut self,
         addr: &str,
         cfg_cpu_nums: u32,
+        locality: NodeLocality,
         cluster_id: Vec<u8>,
+        restore_from: Option<String>,
+        restore_time: u64,
     ) -> Result<()> {
         debug_assert_ne!(cfg_cpu_nums, 0);
         let _timer = super::metrics::BOOTSTRAP_DURATION_SECONDS.start_timer();
@@ -705,11 +777,35 @@ impl Schema {
 
         let (shards, next_shard_id) = Schema::init_shards();
 
-        Self::init_meta_collection(&mut batch, next_shard_id, cluster_id.to_owned());
+        // The restoring job is submitted along with the bootstrapping, so it is submitted exactly
+        // once.
+        let mut next_job_id = INITIAL_JOB_ID;
+        if let Some(backup) = restore_from {
+            info!("submit restore job from {backup}");
+            batch.put_job(BackgroundJob {
+                id: next_job_id,
+                job: Some(background_job::Job::Restore(RestoreJob {
+                    backup,
+                    status: RestoreStatus::RestoreInit as i32,
+                    restore_time,
+                    created_time: format!("{:?}", std::time::Instant::now()),
+                    ..Default::default()
+                })),
+            });
+            next_job_id += 1;
+        }
+
+        Self::init_meta_collection(
+            &mut batch,
+            next_shard_id,
+            next_job_id,
+            cluster_id.to_owned(),
+        );
 
         batch.put_database(DatabaseDesc {
             id: SYSTEM_DATABASE_ID.to_owned(),
             name: SYSTEM_DATABASE_NAME.to_owned(),
+            ..Default::default()
         });
 
         batch.put_node(NodeDesc {
@@ -719,8 +815,10 @@ impl Schema {
                 cpu_nums: cfg_cpu_nums as f64,
                 replica_count: 1,
                 leader_count: 0,
+                ..Default::default()
             }),
             status: NodeStatus::Active as i32,
+            locality: Some(locality),
         });
 
         batch.put_group(GroupDesc {
@@ -732,6 +830,7 @@ impl Schema {
                 role: ReplicaRole::Voter.into(),
             }],
             shards,
+            ..Default::default()
         });
 
         batch.put_group(GroupDesc {
@@ -743,6 +842,7 @@ impl Schema {
                 role: ReplicaRole::Voter.into(),
             }],
             shards: vec![],
+            ..Default::default()
         });
 
         batch.put_replica_state(ReplicaState {
@@ -780,6 +880,7 @@ impl Schema {
                     start: SHARD_MIN.to_owned(),
                     end: SHARD_MAX.to_owned(),
                 })),
+                ..Default::default()
             })
         }
         (desc, SYSTEM_JOB_HISTORY_COLLECTION_SHARD + 1)
@@ -805,6 +906,15 @@ impl Schema {
         self.next_id(META_SHARD_ID_KEY).await
     }
 
+    pub async fn next_collection_id(&self) -> Result<u64> {
+        self.next_id(META_COLLECTION_ID_KEY).await
+    }
+
+    /// The index entries are keyed by index id, so it shares the same id space with collections.
+    pub async fn next_index_id(&self) -> Result<u64> {
+        self.next_id(META_COLLECTION_ID_KEY).await
+    }
+
     fn init_system_collections(batch: &mut PutBatchBuilder) {
         let self_collection = CollectionDesc {
             id: SYSTEM_COLLECTION_COLLECTION_ID,
@@ -813,6 +923,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(self_collection);
 
@@ -823,6 +934,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(db_collection);
 
@@ -833,6 +945,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(meta_collection);
 
@@ -843,6 +956,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(node_collection);
 
@@ -853,6 +967,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(group_collection);
 
@@ -863,6 +978,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(replica_state_collection);
 
@@ -873,6 +989,7 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(job_collection);
 
@@ -883,11 +1000,17 @@ impl Schema {
             partition: Some(collection_desc::Partition::Range(
                 collection_desc::RangePartition {},
             )),
+            ..Default::default()
         };
         batch.put_collection(job_history_collection);
     }
 
-    fn init_meta_collection(batch: &mut PutBatchBuilder, next_shard_id: u64, cluster_id: Vec<u8>) {
+    fn init_meta_collection(
+        batch: &mut PutBatchBuilder,
+        next_shard_id: u64,
+        next_job_id: u64,
+        cluster_id: Vec<u8>,
+    ) {
         batch.put_meta(META_CLUSTER_ID_KEY.into(), cluster_id);
         batch.put_meta(
             META_DATABASE_ID_KEY.into(),
@@ -913,10 +1036,7 @@ impl Schema {
             META_SHARD_ID_KEY.into(),
             next_shard_id.to_le_bytes().to_vec(),
         );
-        batch.put_meta(
-            META_JOB_ID_KEY.into(),
-            INITIAL_JOB_ID.to_le_bytes().to_vec(),
-        );
+        batch.put_meta(META_JOB_ID_KEY.into(), next_job_id.to_le_bytes().to_vec());
     }
 }
 
@@ -1018,6 +1138,7 @@ impl RemoteStore {
 #[derive(Default)]
 struct PutBatchBuilder {
     batch: Vec<(u64, Vec<u8>, Vec<u8>)>,
+    deletes: Vec<(u64, Vec<u8>)>,
 }
 
 impl PutBatchBuilder {
@@ -1026,6 +1147,11 @@ impl PutBatchBuilder {
         self.batch.push((shard_id, key, val));
     }
 
+    fn delete(&mut self, collection_id: u64, key: Vec<u8>) {
+        let shard_id = Schema::system_shard_id(collection_id);
+        self.deletes.push((shard_id, key));
+    }
+
     fn build(&self) -> BatchWriteRequest {
         let puts = self
             .batch
@@ -1036,10 +1162,16 @@ impl PutBatchBuilder {
                 put: Some(PutRequest { key, value }),
             })
             .collect::<Vec<_>>();
-        BatchWriteRequest {
-            puts,
-            ..Default::default()
-        }
+        let deletes = self
+            .deletes
+            .iter()
+            .cloned()
+            .map(|(shard_id, key)| ShardDeleteRequest {
+                shard_id,
+                delete: Some(DeleteRequest { key }),
+            })
+            .collect::<Vec<_>>();
+        BatchWriteRequest { deletes, puts }
     }
 
     fn put_meta(&mut self, key: Vec<u8>, val: Vec<u8>) -> &mut Self {
@@ -1083,6 +1215,11 @@ impl PutBatchBuilder {
         self
     }
 
+    fn delete_database(&mut self, name: &str) -> &mut Self {
+        self.delete(SYSTEM_DATABASE_COLLECTION_ID, name.as_bytes().to_vec());
+        self
+    }
+
     fn put_collection(&mut self, desc: CollectionDesc) -> &mut Self {
         self.put(
             SYSTEM_COLLECTION_COLLECTION_ID,
@@ -1092,6 +1229,14 @@ impl PutBatchBuilder {
         self
     }
 
+    fn delete_collection(&mut self, database_id: u64, name: &str) -> &mut Self {
+        self.delete(
+            SYSTEM_COLLECTION_COLLECTION_ID,
+            collection_key(database_id, name),
+        );
+        self
+    }
+
     fn put_job(&mut self, desc: BackgroundJob) -> &mut Self {
         self.put(
             SYSTEM_JOB_COLLECTION_ID,
@@ -1111,11 +1256,21 @@ impl PutBatchBuilder {
     }
 
     fn is_empty(&self) -> bool {
-        self.batch.is_empty()
+        self.batch.is_empty() && self.deletes.is_empty()
     }
 }
 
 #[inline]
+/// The collection metadata replicated along with the shards of the collection.
+pub fn collection_meta(db: &DatabaseDesc, desc: &CollectionDesc) -> CollectionMeta {
+    CollectionMeta {
+        epoch: desc.epoch,
+        indexes: desc.indexes.clone(),
+        schema: desc.schema.clone(),
+        ttl: super::effective_ttl(db, desc),
+    }
+}
+
 fn collection_key(database_id: u64, collection_name: &str) -> Vec<u8> {
     let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + collection_name.len());
     buf.extend_from_slice(database_id.to_le_bytes().as_slice());
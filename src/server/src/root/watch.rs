@@ -13,7 +13,7 @@
 // limitations under the License.
 
 use std::{
-    collections::HashMap,
+    collections::{HashMap, VecDeque},
     sync::{Arc, Mutex},
     task::{Poll, Waker},
     vec,
@@ -28,23 +28,49 @@ use tokio::sync::{RwLock, RwLockWriteGuard};
 
 use crate::{Error, Result};
 
-#[derive(Default)]
 pub struct WatchHub {
     inner: Arc<RwLock<WatchHubInner>>,
 }
 
-#[derive(Default)]
 pub struct WatchHubInner {
     next_watcher_id: u64,
     watchers: HashMap<u64, Watcher>,
+    log: ChangeLog,
+}
+
+/// The recent changes notified to watchers. A revision is composed of the term of root leader
+/// and the sequence of the change within the term, so the revisions are monotonic across leaders
+/// and the ones from different leaders never overlap.
+struct ChangeLog {
+    capacity: usize,
+    revision: u64,
+    changes: VecDeque<Change>,
+}
+
+struct Change {
+    revision: u64,
+    updates: Vec<UpdateEvent>,
+    deletes: Vec<DeleteEvent>,
 }
 
 pub struct WatcherInitializer<'a> {
-    _guard: RwLockWriteGuard<'a, WatchHubInner>,
+    guard: RwLockWriteGuard<'a, WatchHubInner>,
     watcher_inner: Arc<Mutex<WatcherInner>>,
 }
 
 impl<'a> WatcherInitializer<'a> {
+    /// Send the changes after the revision, returns false if some of them are truncated, then
+    /// the full state should be sent instead.
+    pub fn resume(&mut self, revision: u64) -> bool {
+        match self.guard.log.changes_since(revision) {
+            Some((updates, deletes)) => {
+                self.set_init_resp(updates, deletes);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn set_init_resp(&mut self, updates: Vec<UpdateEvent>, deletes: Vec<DeleteEvent>) {
         let mut inner = self.watcher_inner.lock().unwrap();
         inner.updates.extend_from_slice(&updates);
@@ -53,10 +79,24 @@ impl<'a> WatcherInitializer<'a> {
 }
 
 impl WatchHub {
+    pub fn new(log_capacity: usize) -> Self {
+        let inner = WatchHubInner {
+            next_watcher_id: 0,
+            watchers: HashMap::default(),
+            log: ChangeLog::new(log_capacity),
+        };
+        WatchHub {
+            inner: Arc::new(RwLock::new(inner)),
+        }
+    }
+
     pub async fn create_watcher(&self) -> (Watcher, WatcherInitializer) {
         let mut inner = self.inner.write().await;
         inner.next_watcher_id += 1;
-        let watcher_inner = Arc::new(Mutex::new(WatcherInner::default()));
+        let watcher_inner = Arc::new(Mutex::new(WatcherInner {
+            revision: inner.log.revision,
+            ..Default::default()
+        }));
         let watcher = Watcher {
             id: inner.next_watcher_id,
             inner: watcher_inner.to_owned(),
@@ -66,7 +106,7 @@ impl WatchHub {
         (
             watcher,
             WatcherInitializer {
-                _guard: inner,
+                guard: inner,
                 watcher_inner,
             },
         )
@@ -78,6 +118,13 @@ impl WatchHub {
         super::metrics::WATCH_TABLE_SIZE.set(inner.watchers.len() as i64);
     }
 
+    /// Truncate the change log and start the revisions of the term, it should be called once the
+    /// node becomes the root leader, since the changes made by the other leaders are unknown.
+    pub async fn reset_revision(&self, term: u64) {
+        let mut inner = self.inner.write().await;
+        inner.log.reset(term);
+    }
+
     pub async fn notify_updates(&self, updates: Vec<UpdateEvent>) {
         self.notify(updates, vec![], None).await;
     }
@@ -96,9 +143,12 @@ impl WatchHub {
         deletes: Vec<DeleteEvent>,
         _err: Option<Error>,
     ) {
-        let inner = self.inner.read().await;
+        // The log is appended under the write lock, so the watchers receive the changes in the
+        // order of revisions.
+        let mut inner = self.inner.write().await;
+        let revision = inner.log.append(&updates, &deletes);
         for w in inner.watchers.values() {
-            w.notify(&updates, &deletes, None) // TODO: clonable error
+            w.notify(&updates, &deletes, revision, None) // TODO: clonable error
         }
     }
 
@@ -111,6 +161,56 @@ impl WatchHub {
     }
 }
 
+impl ChangeLog {
+    fn new(capacity: usize) -> Self {
+        ChangeLog {
+            capacity,
+            revision: 0,
+            changes: VecDeque::default(),
+        }
+    }
+
+    fn reset(&mut self, term: u64) {
+        self.revision = std::cmp::max(term << 32, self.revision);
+        self.changes.clear();
+    }
+
+    fn append(&mut self, updates: &[UpdateEvent], deletes: &[DeleteEvent]) -> u64 {
+        self.revision += 1;
+        if self.capacity > 0 {
+            if self.changes.len() >= self.capacity {
+                self.changes.pop_front();
+            }
+            self.changes.push_back(Change {
+                revision: self.revision,
+                updates: updates.to_owned(),
+                deletes: deletes.to_owned(),
+            });
+        }
+        self.revision
+    }
+
+    /// The changes after the revision, `None` if some of them are truncated or the revision
+    /// isn't issued by this log.
+    fn changes_since(&self, revision: u64) -> Option<(Vec<UpdateEvent>, Vec<DeleteEvent>)> {
+        let first = self
+            .changes
+            .front()
+            .map(|c| c.revision)
+            .unwrap_or(self.revision + 1);
+        if revision > self.revision || revision + 1 < first {
+            return None;
+        }
+        let mut updates = Vec::new();
+        let mut deletes = Vec::new();
+        for change in self.changes.iter().filter(|c| c.revision > revision) {
+            updates.extend_from_slice(&change.updates);
+            deletes.extend_from_slice(&change.deletes);
+        }
+        Some((updates, deletes))
+    }
+}
+
 #[derive(Clone)]
 pub struct Watcher {
     #[allow(dead_code)]
@@ -121,6 +221,7 @@ pub struct Watcher {
 #[derive(Default)]
 struct WatcherInner {
     waker: Option<Waker>,
+    revision: u64,
     updates: Vec<UpdateEvent>,
     deletes: Vec<DeleteEvent>,
     err: Option<Error>,
@@ -128,12 +229,19 @@ struct WatcherInner {
 }
 
 impl Watcher {
-    fn notify(&self, updates: &[UpdateEvent], deletes: &[DeleteEvent], err: Option<Error>) {
+    fn notify(
+        &self,
+        updates: &[UpdateEvent],
+        deletes: &[DeleteEvent],
+        revision: u64,
+        err: Option<Error>,
+    ) {
         let _timer = super::metrics::WATCH_NOTIFY_DURATION_SECONDS.start_timer();
         let mut inner = self.inner.lock().unwrap();
         if inner.dropped {
             return;
         }
+        inner.revision = revision;
         inner.updates.extend_from_slice(updates); // TODO: set capcity limit
         inner.deletes.extend_from_slice(deletes);
         if err.is_some() && inner.err.is_none() {
@@ -163,6 +271,7 @@ impl Stream for Watcher {
             let resp = WatchResponse {
                 updates: std::mem::take(&mut inner.updates),
                 deletes: std::mem::take(&mut inner.deletes),
+                revision: inner.revision,
             };
             return Poll::Ready(Some(Ok(resp)));
         }
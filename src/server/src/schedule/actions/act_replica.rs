@@ -50,12 +50,15 @@ impl CreateReplicas {
     async fn create_replica(
         &self,
         group_id: u64,
+        replication_factor: u32,
         r: &ReplicaDesc,
         transport_manager: &TransportManager,
     ) -> Result<(), engula_client::Error> {
         let client = transport_manager.find_node_client(r.node_id)?;
+        // The replication factor is required to decide the priority of the first snapshot.
         let desc = GroupDesc {
             id: group_id,
+            replication_factor,
             ..Default::default()
         };
         client.create_replica(r.id, desc).await?;
@@ -68,10 +71,11 @@ impl Action for CreateReplicas {
     async fn setup(&mut self, task_id: u64, ctx: &mut ScheduleContext<'_>) -> ActionState {
         let group_id = ctx.group_id;
         let replica_id = ctx.replica_id;
+        let replication_factor = ctx.replica.descriptor().replication_factor;
 
         while let Some(r) = self.replicas.last() {
             match self
-                .create_replica(group_id, r, ctx.transport_manager)
+                .create_replica(group_id, replication_factor, r, ctx.transport_manager)
                 .await
             {
                 Ok(()) => {
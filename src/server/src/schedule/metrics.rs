@@ -0,0 +1,32 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use lazy_static::lazy_static;
+use prometheus::*;
+
+lazy_static! {
+    pub static ref SCHEDULE_SCRUB_TOTAL: IntCounter =
+        register_int_counter!("schedule_scrub_total", "The total scrubbing of groups").unwrap();
+    pub static ref SCHEDULE_SCRUB_DURATION_SECONDS: Histogram = register_histogram!(
+        "schedule_scrub_duration_seconds",
+        "The intervals of scrubbing of groups",
+        exponential_buckets(0.005, 1.8, 26).unwrap(),
+    )
+    .unwrap();
+    pub static ref SCHEDULE_SCRUB_MISMATCHED_REPLICAS_TOTAL: IntCounter = register_int_counter!(
+        "schedule_scrub_mismatched_replicas_total",
+        "The total replicas whose checksums are different from the majority"
+    )
+    .unwrap();
+}
@@ -23,6 +23,7 @@ use engula_client::Router;
 use futures::channel::oneshot;
 
 use crate::{
+    constants::REPLICA_PER_GROUP,
     node::Replica,
     raftgroup::RaftGroupState,
     schedule::{
@@ -59,6 +60,7 @@ pub struct GroupProviders {
     pub replica_states: Arc<ReplicaStatesProvider>,
     pub raft_state: Arc<RaftStateProvider>,
     pub move_replicas: Arc<MoveReplicasProvider>,
+    pub corrupted_replicas: Arc<CorruptedReplicasProvider>,
 }
 
 pub struct GroupDescProvider {
@@ -106,6 +108,13 @@ struct MoveReplicasProviderInner {
     duty: Option<MoveReplicas>,
 }
 
+/// CorruptedReplicasProvider records the replicas whose checksums are different from the majority,
+/// they are considered as offline and replaced.
+#[derive(Default)]
+pub struct CorruptedReplicasProvider {
+    replicas: Mutex<HashSet<u64>>,
+}
+
 pub struct MoveReplicas {
     pub epoch: u64,
     pub incoming_replicas: Vec<ReplicaDesc>,
@@ -133,6 +142,15 @@ impl GroupDescProvider {
         inner.desc.clone()
     }
 
+    /// The desired number of voters of this group.
+    pub fn replication_factor(&self) -> usize {
+        let inner = self.inner.lock().unwrap();
+        match inner.desc.replication_factor {
+            0 => REPLICA_PER_GROUP,
+            n => n as usize,
+        }
+    }
+
     pub fn update(&self, desc: GroupDesc) {
         let mut inner = self.inner.lock().unwrap();
         if inner.desc.epoch < desc.epoch {
@@ -252,6 +270,16 @@ impl MoveReplicasProvider {
     }
 }
 
+impl CorruptedReplicasProvider {
+    pub fn replicas(&self) -> HashSet<u64> {
+        self.replicas.lock().unwrap().clone()
+    }
+
+    pub fn update(&self, replicas: HashSet<u64>) {
+        *self.replicas.lock().unwrap() = replicas;
+    }
+}
+
 impl GroupProviders {
     pub fn new(
         replica: Arc<Replica>,
@@ -265,6 +293,7 @@ impl GroupProviders {
             replica_states: Arc::new(ReplicaStatesProvider::new()),
             raft_state: Arc::new(RaftStateProvider::new()),
             move_replicas,
+            corrupted_replicas: Arc::default(),
         }
     }
 }
@@ -198,7 +198,7 @@ impl DurableGroup {
         ctx: &mut ScheduleContext<'_>,
         stats: ReplicaStats,
     ) -> TaskState {
-        let num_required = 3;
+        let num_required = self.providers.descriptor.replication_factor();
         self.providers.descriptor.watch(self.id());
 
         // Offline learners have no use value, remove them to simplify the logic.
@@ -324,7 +324,9 @@ impl Task for DurableGroup {
             return TaskState::Pending(Some(Duration::from_secs(1)));
         }
 
-        let lost_peers = self.providers.raft_state.lost_peers();
+        // The corrupted replicas are replaced like the offline ones.
+        let mut lost_peers = self.providers.raft_state.lost_peers();
+        lost_peers.extend(self.providers.corrupted_replicas.replicas());
         let mut stats = ReplicaStats::default();
         for r in &replicas {
             if ctx.group_lock_table.is_replica_locked(r.id) {
@@ -16,6 +16,7 @@ mod durable;
 mod migration;
 mod orphan_replica;
 mod promote;
+mod scrub;
 mod watch_descriptor;
 mod watch_raft_state;
 mod watch_replica_states;
@@ -26,7 +27,7 @@ use engula_api::server::v1::{ReplicaDesc, ScheduleState};
 
 pub use self::{
     durable::DurableGroup, migration::ReplicaMigration, orphan_replica::RemoveOrphanReplica,
-    promote::PromoteGroup, watch_descriptor::WatchGroupDescriptor,
+    promote::PromoteGroup, scrub::ScrubGroup, watch_descriptor::WatchGroupDescriptor,
     watch_raft_state::WatchRaftState, watch_replica_states::WatchReplicaStates,
 };
 use super::ActionTask;
@@ -26,16 +26,12 @@ use crate::schedule::{
 };
 
 pub struct PromoteGroup {
-    required_replicas: usize,
     providers: Arc<GroupProviders>,
 }
 
 impl PromoteGroup {
     pub fn new(providers: Arc<GroupProviders>) -> Self {
-        PromoteGroup {
-            required_replicas: 3,
-            providers,
-        }
+        PromoteGroup { providers }
     }
 
     async fn setup(
@@ -144,9 +140,10 @@ impl Task for PromoteGroup {
             return TaskState::Pending(Some(Duration::from_secs(1)));
         }
 
+        let required_replicas = self.providers.descriptor.replication_factor();
         let num_online_nodes = self.providers.node.num_online_nodes();
-        if 1 < self.required_replicas && self.required_replicas <= num_online_nodes {
-            let num_acquire = self.required_replicas - 1;
+        if 1 < required_replicas && required_replicas <= num_online_nodes {
+            let num_acquire = required_replicas - 1;
             self.setup(num_acquire, former_replica_id, ctx).await;
         }
 
@@ -0,0 +1,341 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use engula_api::server::v1::{ReplicaChecksum, ReplicaDesc};
+use rand::Rng;
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    node::replica::ScrubReport,
+    schedule::{
+        metrics::*,
+        provider::GroupProviders,
+        scheduler::ScheduleContext,
+        task::{Task, TaskState},
+        tasks::SCRUB_GROUP_TASK_ID,
+    },
+    transport::TransportManager,
+};
+
+/// The duration to wait for the replicas to report their checksums, the lagging replicas are
+/// considered as missing after it.
+const COLLECT_TIMEOUT: Duration = Duration::from_secs(600);
+const COLLECT_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Compare the checksums of all replicas periodically, to detect the silent data corruption.
+pub struct ScrubGroup {
+    providers: Arc<GroupProviders>,
+    next_scrub: Option<Instant>,
+    collecting: Option<Collecting>,
+}
+
+struct Collecting {
+    checksum_id: u64,
+    start: Instant,
+    deadline: Instant,
+    replicas: Vec<ReplicaDesc>,
+    checksums: HashMap<u64, ReplicaChecksum>,
+}
+
+impl ScrubGroup {
+    pub fn new(providers: Arc<GroupProviders>) -> Self {
+        ScrubGroup {
+            providers,
+            next_scrub: None,
+            collecting: None,
+        }
+    }
+
+    /// The scheduler is rebuilt once the leadership changes, so the last scrubbing of this
+    /// replica is used to decide the next one. If there is no such scrubbing, a random delay is
+    /// used to avoid scrubbing all groups at the same time.
+    fn initial_scrub_time(ctx: &ScheduleContext<'_>, interval: u64) -> Instant {
+        let delay = match ctx.replica.checksum_table().last_report() {
+            Some(report) => (report.finished_at + interval).saturating_sub(unix_secs()),
+            None => rand::thread_rng().gen_range(0..interval),
+        };
+        Instant::now() + Duration::from_secs(delay)
+    }
+
+    async fn start(&mut self, ctx: &mut ScheduleContext<'_>) -> TaskState {
+        let group_id = ctx.group_id;
+        let replica_id = ctx.replica_id;
+        let checksum_id = rand::random::<u64>();
+        if let Err(err) = ctx.replica.compute_checksum(checksum_id).await {
+            warn!("group {group_id} replica {replica_id} propose compute checksum: {err}");
+            return TaskState::Pending(Some(Duration::from_secs(10)));
+        }
+
+        info!("group {group_id} replica {replica_id} start scrubbing with checksum {checksum_id}");
+        SCHEDULE_SCRUB_TOTAL.inc();
+        let now = Instant::now();
+        self.collecting = Some(Collecting {
+            checksum_id,
+            start: now,
+            deadline: now + COLLECT_TIMEOUT,
+            replicas: self.providers.descriptor.replicas(),
+            checksums: HashMap::default(),
+        });
+        TaskState::Pending(Some(COLLECT_INTERVAL))
+    }
+
+    async fn collect(&mut self, ctx: &mut ScheduleContext<'_>) -> TaskState {
+        let collecting = self.collecting.as_mut().expect("already checked");
+        for r in &collecting.replicas {
+            if collecting.checksums.contains_key(&r.id) {
+                continue;
+            }
+            let checksum = if r.id == ctx.replica_id {
+                Ok(ctx.replica.checksum_table().get(collecting.checksum_id))
+            } else {
+                fetch_checksum(
+                    ctx.transport_manager,
+                    ctx.group_id,
+                    r,
+                    collecting.checksum_id,
+                )
+                .await
+            };
+            match checksum {
+                Ok(Some(checksum)) if checksum.finished => {
+                    collecting.checksums.insert(r.id, checksum);
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    debug!(
+                        "group {} replica {} get checksum {} from replica {}: {err}",
+                        ctx.group_id, ctx.replica_id, collecting.checksum_id, r.id
+                    );
+                }
+            }
+        }
+
+        if collecting.checksums.len() < collecting.replicas.len()
+            && Instant::now() < collecting.deadline
+        {
+            return TaskState::Pending(Some(COLLECT_INTERVAL));
+        }
+
+        let collecting = self.collecting.take().expect("already checked");
+        self.finish(ctx, collecting)
+    }
+
+    fn finish(&mut self, ctx: &mut ScheduleContext<'_>, collecting: Collecting) -> TaskState {
+        let group_id = ctx.group_id;
+        let replica_id = ctx.replica_id;
+        SCHEDULE_SCRUB_DURATION_SECONDS.observe(collecting.start.elapsed().as_secs_f64());
+
+        let mut report = compare_checksums(&collecting.replicas, &collecting.checksums);
+        report.checksum_id = collecting.checksum_id;
+        report.finished_at = unix_secs();
+        if report.mismatched.is_empty() {
+            info!(
+                "group {group_id} replica {replica_id} scrubbing at index {} is finished, missing replicas {:?}",
+                report.index, report.missing
+            );
+        } else {
+            error!(
+                "group {group_id} replica {replica_id} scrubbing at index {}: the checksums of replicas {:?} are different from {:?}",
+                report.index, report.mismatched, report.matched
+            );
+            SCHEDULE_SCRUB_MISMATCHED_REPLICAS_TOTAL.inc_by(report.mismatched.len() as u64);
+        }
+        if ctx.cfg.scrub_replace_corrupted {
+            self.replace_corrupted(ctx, &collecting.replicas, &report);
+        }
+        ctx.replica.checksum_table().set_last_report(report);
+
+        let interval = Duration::from_secs(ctx.cfg.scrub_interval_sec);
+        self.next_scrub = Some(Instant::now() + interval);
+        TaskState::Pending(Some(interval))
+    }
+
+    fn replace_corrupted(
+        &self,
+        ctx: &ScheduleContext<'_>,
+        replicas: &[ReplicaDesc],
+        report: &ScrubReport,
+    ) {
+        let group_id = ctx.group_id;
+        let replica_id = ctx.replica_id;
+        if report.matched.len() * 2 <= replicas.len() {
+            warn!(
+                "group {group_id} replica {replica_id} skip replacing corrupted replicas, since no majority of checksums"
+            );
+            return;
+        }
+
+        let corrupted = report.mismatched.iter().cloned().collect::<HashSet<_>>();
+        if corrupted.contains(&replica_id) {
+            // The leader couldn't replace itself, transfer the leadership to a healthy replica,
+            // and let the new leader detect and replace this replica.
+            let Some(&transferee) = report.matched.first() else {
+                return;
+            };
+            warn!(
+                "group {group_id} replica {replica_id} is corrupted, transfer leadership to {transferee}"
+            );
+            if let Err(err) = ctx.replica.raft_node().transfer_leader(transferee) {
+                warn!("group {group_id} replica {replica_id} transfer leadership: {err}");
+            }
+            return;
+        }
+        self.providers.corrupted_replicas.update(corrupted);
+    }
+}
+
+#[crate::async_trait]
+impl Task for ScrubGroup {
+    fn id(&self) -> u64 {
+        SCRUB_GROUP_TASK_ID
+    }
+
+    async fn poll(&mut self, ctx: &mut ScheduleContext<'_>) -> TaskState {
+        let interval = ctx.cfg.scrub_interval_sec;
+        if interval == 0 {
+            return TaskState::Pending(None);
+        }
+
+        if self.collecting.is_some() {
+            return self.collect(ctx).await;
+        }
+
+        let now = Instant::now();
+        let next_scrub = *self
+            .next_scrub
+            .get_or_insert_with(|| Self::initial_scrub_time(ctx, interval));
+        if now < next_scrub {
+            return TaskState::Pending(Some(next_scrub - now));
+        }
+        self.start(ctx).await
+    }
+}
+
+async fn fetch_checksum(
+    transport_manager: &TransportManager,
+    group_id: u64,
+    r: &ReplicaDesc,
+    checksum_id: u64,
+) -> Result<Option<ReplicaChecksum>, engula_client::Error> {
+    let client = transport_manager.find_node_client(r.node_id)?;
+    let checksum = client.get_checksum(group_id, r.id, checksum_id).await?;
+    Ok(checksum)
+}
+
+/// Compare the checksums with the majority. The replicas failed to compute the checksums are
+/// considered as mismatched, and there is no matched replica if no checksum is agreed by the
+/// majority.
+fn compare_checksums(
+    replicas: &[ReplicaDesc],
+    checksums: &HashMap<u64, ReplicaChecksum>,
+) -> ScrubReport {
+    let mut report = ScrubReport {
+        index: checksums
+            .values()
+            .map(|c| c.index)
+            .next()
+            .unwrap_or_default(),
+        ..Default::default()
+    };
+
+    let mut votes: HashMap<&[u8], Vec<u64>> = HashMap::default();
+    for (id, checksum) in checksums {
+        if checksum.error.is_empty() {
+            votes
+                .entry(checksum.checksum.as_slice())
+                .or_default()
+                .push(*id);
+        }
+    }
+    let majority = votes
+        .into_values()
+        .max_by_key(|ids| ids.len())
+        .filter(|ids| ids.len() * 2 > replicas.len())
+        .unwrap_or_default();
+
+    for r in replicas {
+        if majority.contains(&r.id) {
+            report.matched.push(r.id);
+        } else if checksums.contains_key(&r.id) {
+            report.mismatched.push(r.id);
+        } else {
+            report.missing.push(r.id);
+        }
+    }
+    report
+}
+
+fn unix_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn replicas(ids: &[u64]) -> Vec<ReplicaDesc> {
+        ids.iter()
+            .map(|&id| ReplicaDesc {
+                id,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn checksum(checksum: &[u8], error: &str) -> ReplicaChecksum {
+        ReplicaChecksum {
+            index: 10,
+            finished: true,
+            checksum: checksum.to_owned(),
+            error: error.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn compare_checksums_with_majority() {
+        let replicas = replicas(&[1, 2, 3, 4, 5]);
+        let checksums = HashMap::from([
+            (1, checksum(b"a", "")),
+            (2, checksum(b"a", "")),
+            (3, checksum(b"a", "")),
+            (4, checksum(b"b", "")),
+            (5, checksum(b"", "corruption")),
+        ]);
+        let report = compare_checksums(&replicas, &checksums);
+        assert_eq!(report.index, 10);
+        assert_eq!(report.matched, vec![1, 2, 3]);
+        assert_eq!(report.mismatched, vec![4, 5]);
+        assert!(report.missing.is_empty());
+    }
+
+    #[test]
+    fn compare_checksums_without_majority() {
+        let replicas = replicas(&[1, 2, 3]);
+        let checksums = HashMap::from([(1, checksum(b"a", "")), (2, checksum(b"b", ""))]);
+        let report = compare_checksums(&replicas, &checksums);
+        assert!(report.matched.is_empty());
+        assert_eq!(report.mismatched, vec![1, 2]);
+        assert_eq!(report.missing, vec![3]);
+    }
+}
@@ -19,7 +19,7 @@ pub use self::{
     action::ActionTask,
     group::{
         DurableGroup, GroupLockTable, PromoteGroup, RemoveOrphanReplica, ReplicaMigration,
-        WatchGroupDescriptor, WatchRaftState, WatchReplicaStates,
+        ScrubGroup, WatchGroupDescriptor, WatchRaftState, WatchReplicaStates,
     },
 };
 
@@ -30,5 +30,6 @@ pub const REPLICA_MIGRATION_TASK_ID: u64 = 4;
 pub const WATCH_REPLICA_STATES_TASK_ID: u64 = 5;
 pub const WATCH_RAFT_STATE_TASK_ID: u64 = 6;
 pub const WATCH_GROUP_DESCRIPTOR_TASK_ID: u64 = 7;
+pub const SCRUB_GROUP_TASK_ID: u64 = 8;
 
 pub const GENERATED_TASK_ID: u64 = 10;
@@ -15,7 +15,7 @@
 #![allow(clippy::all)]
 
 pub mod v1 {
-    use engula_api::server::v1::{MigrationDesc, ShardDesc};
+    use engula_api::server::v1::{CollectionMeta, MigrationDesc, ShardDesc};
 
     tonic::include_proto!("serverpb.v1");
 
@@ -52,6 +52,46 @@ pub mod v1 {
                 ..Default::default()
             })
         }
+
+        #[inline]
+        pub fn archive_state(index: u64) -> Box<Self> {
+            Box::new(SyncOp {
+                archive_state: Some(ArchiveState { index }),
+                ..Default::default()
+            })
+        }
+
+        #[inline]
+        pub fn ingest_sst(shard_id: u64, stage_id: u64, num_files: u32) -> Box<Self> {
+            Box::new(SyncOp {
+                ingest_sst: Some(IngestSst {
+                    shard_id,
+                    stage_id,
+                    num_files,
+                }),
+                ..Default::default()
+            })
+        }
+
+        #[inline]
+        pub fn compute_checksum(checksum_id: u64) -> Box<Self> {
+            Box::new(SyncOp {
+                compute_checksum: Some(ComputeChecksum { checksum_id }),
+                ..Default::default()
+            })
+        }
+
+        #[inline]
+        pub fn sync_collection(collection_id: u64, meta: CollectionMeta) -> Box<Self> {
+            Box::new(SyncOp {
+                sync_collection: Some(SyncCollection {
+                    collection_id,
+                    meta: Some(meta),
+                }),
+                ..Default::default()
+            })
+        }
+
         #[inline]
         pub fn ingest(key: Vec<u8>) -> Box<Self> {
             Box::new(SyncOp {
@@ -0,0 +1,103 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use serde_json::json;
+use tonic::{async_trait, codegen::http};
+
+use crate::{Result, Server};
+
+/// Start a backup of the cluster to the `destination`, the progress could be found in
+/// `/admin/job`. The groups are taken one by one, so the backup is not consistent across groups,
+/// see `crate::backup`.
+pub(super) struct BackupHandle {
+    server: Server,
+}
+
+impl BackupHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for BackupHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let destination = params
+            .get("destination")
+            .filter(|d| !d.is_empty())
+            .ok_or_else(|| crate::Error::InvalidArgument("destination is required".into()))?;
+        let job_id = self.server.root.backup(destination.to_owned()).await?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(json!({ "job_id": job_id }).to_string())
+            .unwrap())
+    }
+}
+
+/// Restore the collection `database`.`collection` of the `backup` under the name `new_name`, or
+/// restore all databases and collections if `collection` is not specified. The archived log
+/// entries are replayed up to `restore_time`, in unix seconds, if it is specified. The time is
+/// compared with when the entries are archived, so the writes in the last archiving interval
+/// before it might not be restored.
+pub(super) struct RestoreHandle {
+    server: Server,
+}
+
+impl RestoreHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for RestoreHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let backup = params
+            .get("backup")
+            .filter(|d| !d.is_empty())
+            .ok_or_else(|| crate::Error::InvalidArgument("backup is required".into()))?;
+        let param = |name: &str| params.get(name).cloned().unwrap_or_default();
+        let restore_time = match params.get("restore_time") {
+            Some(time) => time.parse::<u64>().map_err(|_| {
+                crate::Error::InvalidArgument("restore_time must be a unix timestamp".into())
+            })?,
+            None => 0,
+        };
+        let job_id = self
+            .server
+            .root
+            .restore(
+                backup.to_owned(),
+                param("database"),
+                param("collection"),
+                param("new_name"),
+                restore_time,
+            )
+            .await?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(json!({ "job_id": job_id }).to_string())
+            .unwrap())
+    }
+}
@@ -109,6 +109,123 @@ impl super::service::HttpHandle for DrainHandle {
     }
 }
 
+pub(super) struct DecommissionHandle {
+    server: Server,
+}
+
+impl DecommissionHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for DecommissionHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let node_id = params
+            .get("node_id")
+            .ok_or_else(|| crate::Error::InvalidArgument("node_id is required".into()))?
+            .parse::<u64>()
+            .map_err(|_| crate::Error::InvalidArgument("illegal node_id".into()))?;
+        // Only report the progress if `progress` is specified.
+        if !params.contains_key("progress") {
+            self.server.root.decommission_node(node_id).await?;
+        }
+        let (status, remaining_replicas) = self.server.root.decommission_progress(node_id).await?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(
+                json!({
+                    "node_id": node_id,
+                    "node_status": format!("{:?}", status).to_uppercase(),
+                    "remaining_replicas": remaining_replicas,
+                })
+                .to_string(),
+            )
+            .unwrap())
+    }
+}
+
+pub(super) struct ForgetNodeHandle {
+    server: Server,
+}
+
+impl ForgetNodeHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for ForgetNodeHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let node_id = params
+            .get("node_id")
+            .ok_or_else(|| crate::Error::InvalidArgument("node_id is required".into()))?
+            .parse::<u64>()
+            .map_err(|_| crate::Error::InvalidArgument("illegal node_id".into()))?;
+        self.server.root.forget_node(node_id).await?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body("".to_owned())
+            .unwrap())
+    }
+}
+
+pub(super) struct UnsafeRecoverGroupHandle {
+    server: Server,
+}
+
+impl UnsafeRecoverGroupHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for UnsafeRecoverGroupHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let group_id = params
+            .get("group_id")
+            .ok_or_else(|| crate::Error::InvalidArgument("group_id is required".into()))?
+            .parse::<u64>()
+            .map_err(|_| crate::Error::InvalidArgument("illegal group_id".into()))?;
+        let replica_id = params
+            .get("replica_id")
+            .ok_or_else(|| crate::Error::InvalidArgument("replica_id is required".into()))?
+            .parse::<u64>()
+            .map_err(|_| crate::Error::InvalidArgument("illegal replica_id".into()))?;
+        let desc = self
+            .server
+            .root
+            .unsafe_recover_group(group_id, replica_id)
+            .await?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(
+                json!({
+                    "group_id": group_id,
+                    "epoch": desc.epoch,
+                    "replicas": desc.replicas.iter().map(|r| r.id).collect::<Vec<_>>(),
+                })
+                .to_string(),
+            )
+            .unwrap())
+    }
+}
+
 pub(super) struct StatusHandle {
     server: Server,
 }
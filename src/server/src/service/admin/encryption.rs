@@ -0,0 +1,50 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use tonic::codegen::*;
+
+use crate::{Result, Server};
+
+/// Return the metadata of the encryption keys of this node, the key materials are never exposed.
+/// The data key is rotated before returning if `rotate` is specified.
+pub(super) struct EncryptionHandle {
+    server: Server,
+}
+
+impl EncryptionHandle {
+    pub fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[crate::async_trait]
+impl super::service::HttpHandle for EncryptionHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let key_manager = self.server.node.key_manager();
+        if params.contains_key("rotate") {
+            key_manager.rotate()?;
+        }
+        let metadata = key_manager.metadata();
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(serde_json::to_string(&metadata).unwrap_or_else(|e| e.to_string()))
+            .unwrap())
+    }
+}
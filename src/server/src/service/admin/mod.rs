@@ -12,12 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod backup;
 mod cluster;
+mod encryption;
 mod health;
 mod job;
 mod metadata;
 mod metrics;
 mod monitor;
+mod schedule;
+mod scrub;
 mod service;
 
 pub use self::service::AdminService;
@@ -45,6 +49,51 @@ pub fn make_admin_service(server: Server) -> AdminService {
             self::cluster::UncordonHandle::new(server.to_owned()),
         )
         .route("/drain", self::cluster::DrainHandle::new(server.to_owned()))
+        .route(
+            "/decommission",
+            self::cluster::DecommissionHandle::new(server.to_owned()),
+        )
+        .route(
+            "/forget_node",
+            self::cluster::ForgetNodeHandle::new(server.to_owned()),
+        )
+        .route(
+            "/unsafe_recover_group",
+            self::cluster::UnsafeRecoverGroupHandle::new(server.to_owned()),
+        )
+        .route(
+            "/explain_schedule",
+            self::schedule::ExplainScheduleHandle::new(server.to_owned()),
+        )
+        .route(
+            "/schedule_control",
+            self::schedule::ScheduleControlHandle::new(server.to_owned()),
+        )
+        .route(
+            "/move_replica",
+            self::schedule::MoveReplicaHandle::new(server.to_owned()),
+        )
+        .route(
+            "/transfer_leader",
+            self::schedule::TransferLeaderHandle::new(server.to_owned()),
+        )
+        .route(
+            "/migrate_shard",
+            self::schedule::MigrateShardHandle::new(server.to_owned()),
+        )
+        .route(
+            "/backup",
+            self::backup::BackupHandle::new(server.to_owned()),
+        )
+        .route(
+            "/restore",
+            self::backup::RestoreHandle::new(server.to_owned()),
+        )
+        .route("/scrub", self::scrub::ScrubHandle::new(server.to_owned()))
+        .route(
+            "/encryption",
+            self::encryption::EncryptionHandle::new(server.to_owned()),
+        )
         .route(
             "/node_status",
             self::cluster::StatusHandle::new(server.to_owned()),
@@ -18,8 +18,8 @@ use serde::Serialize;
 use tonic::codegen::*;
 
 use crate::{
-    node::replica::ReplicaPerfContext, raftgroup::perf_point_micros, runtime::TaskPriority, Error,
-    Result, Server,
+    node::replica::ReplicaPerfContext, raftgroup::perf_point_micros,
+    root::diagnosis::PlacementViolation, runtime::TaskPriority, Error, Result, Server,
 };
 
 #[derive(Default, Debug, Clone, Serialize)]
@@ -29,6 +29,12 @@ pub struct PerfContext {
     pub replica: ReplicaPerfContext,
 }
 
+/// The cluster wide status, only served by the root leader.
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct ClusterMonitor {
+    pub placement_violations: Vec<PlacementViolation>,
+}
+
 pub(super) struct MonitorHandle {
     server: Server,
 }
@@ -37,6 +43,16 @@ impl MonitorHandle {
     pub(crate) fn new(server: Server) -> Self {
         Self { server }
     }
+
+    async fn cluster_monitor(&self) -> Result<http::Response<String>> {
+        let monitor = ClusterMonitor {
+            placement_violations: self.server.root.placement_violations().await?,
+        };
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(serde_json::to_string(&monitor).unwrap_or_else(|e| e.to_string()))
+            .unwrap())
+    }
 }
 
 #[async_trait]
@@ -46,9 +62,10 @@ impl super::service::HttpHandle for MonitorHandle {
         _: &str,
         params: &HashMap<String, String>,
     ) -> Result<http::Response<String>> {
-        let group_id = params
-            .get("group_id")
-            .ok_or_else(|| Error::InvalidArgument("group_id is required".into()))?
+        let Some(group_id) = params.get("group_id") else {
+            return self.cluster_monitor().await;
+        };
+        let group_id = group_id
             .parse::<u64>()
             .map_err(|_| Error::InvalidArgument("illegal group_id".into()))?;
 
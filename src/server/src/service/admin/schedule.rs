@@ -0,0 +1,196 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use serde_json::json;
+use tonic::{async_trait, codegen::http};
+
+use crate::{Result, Server};
+
+/// Run the scheduler of root against the current state without executing anything, and return
+/// the proposed actions with the reasons behind them.
+pub(super) struct ExplainScheduleHandle {
+    server: Server,
+}
+
+impl ExplainScheduleHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for ExplainScheduleHandle {
+    async fn call(&self, _: &str, _: &HashMap<String, String>) -> Result<http::Response<String>> {
+        let explain = self.server.root.explain_schedule().await?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(serde_json::to_string(&explain).unwrap_or_else(|e| e.to_string()))
+            .unwrap())
+    }
+}
+
+/// Show or update the runtime controls of the scheduler. The `pause` and `resume` params accept
+/// comma separated classes of balancing, or `all`.
+pub(super) struct ScheduleControlHandle {
+    server: Server,
+}
+
+impl ScheduleControlHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for ScheduleControlHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let classes = |name: &str| -> Vec<String> {
+            params
+                .get(name)
+                .map(|v| {
+                    v.split(',')
+                        .map(|c| c.trim().to_owned())
+                        .filter(|c| !c.is_empty())
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        let pause = classes("pause");
+        let resume = classes("resume");
+        let max_concurrent_moves = if params.contains_key("max_concurrent_moves") {
+            Some(parse_param(params, "max_concurrent_moves")?)
+        } else {
+            None
+        };
+        let control = if pause.is_empty() && resume.is_empty() && max_concurrent_moves.is_none() {
+            self.server.root.schedule_control().await?
+        } else {
+            self.server
+                .root
+                .update_schedule_control(&pause, &resume, max_concurrent_moves)
+                .await?
+        };
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(serde_json::to_string(&control).unwrap_or_else(|e| e.to_string()))
+            .unwrap())
+    }
+}
+
+/// Move a replica of the group to the target node, the progress could be found in `/admin/job`.
+pub(super) struct MoveReplicaHandle {
+    server: Server,
+}
+
+impl MoveReplicaHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for MoveReplicaHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let group_id = parse_param(params, "group_id")?;
+        let replica_id = parse_param(params, "replica_id")?;
+        let dest_node = parse_param(params, "dest_node")?;
+        let job_id = self
+            .server
+            .root
+            .move_replica(group_id, replica_id, dest_node)
+            .await?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(json!({ "job_id": job_id }).to_string())
+            .unwrap())
+    }
+}
+
+/// Transfer the leadership of the group to the target replica.
+pub(super) struct TransferLeaderHandle {
+    server: Server,
+}
+
+impl TransferLeaderHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for TransferLeaderHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let group_id = parse_param(params, "group_id")?;
+        let replica_id = parse_param(params, "replica_id")?;
+        let job_id = self
+            .server
+            .root
+            .transfer_leader(group_id, replica_id)
+            .await?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(json!({ "job_id": job_id }).to_string())
+            .unwrap())
+    }
+}
+
+/// Migrate the shard to the target group.
+pub(super) struct MigrateShardHandle {
+    server: Server,
+}
+
+impl MigrateShardHandle {
+    pub(crate) fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[async_trait]
+impl super::service::HttpHandle for MigrateShardHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let shard_id = parse_param(params, "shard_id")?;
+        let dest_group = parse_param(params, "dest_group")?;
+        let job_id = self.server.root.migrate_shard(shard_id, dest_group).await?;
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(json!({ "job_id": job_id }).to_string())
+            .unwrap())
+    }
+}
+
+fn parse_param(params: &HashMap<String, String>, name: &str) -> Result<u64> {
+    params
+        .get(name)
+        .ok_or_else(|| crate::Error::InvalidArgument(format!("{name} is required")))?
+        .parse::<u64>()
+        .map_err(|_| crate::Error::InvalidArgument(format!("illegal {name}")))
+}
@@ -0,0 +1,55 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use tonic::codegen::*;
+
+use crate::{Error, Result, Server};
+
+/// Return the results of the last scrubbing of the groups, which are led by this node. Only the
+/// groups with mismatched replicas are returned if `mismatched` is specified.
+pub(super) struct ScrubHandle {
+    server: Server,
+}
+
+impl ScrubHandle {
+    pub fn new(server: Server) -> Self {
+        Self { server }
+    }
+}
+
+#[crate::async_trait]
+impl super::service::HttpHandle for ScrubHandle {
+    async fn call(
+        &self,
+        _: &str,
+        params: &HashMap<String, String>,
+    ) -> Result<http::Response<String>> {
+        let mut reports = self.server.node.scrub_reports().await;
+        if let Some(group_id) = params.get("group_id") {
+            let group_id = group_id
+                .parse::<u64>()
+                .map_err(|_| Error::InvalidArgument("illegal group_id".into()))?;
+            reports.retain(|id, _| *id == group_id);
+        }
+        if params.contains_key("mismatched") {
+            reports.retain(|_, report| !report.mismatched.is_empty());
+        }
+        Ok(http::Response::builder()
+            .status(http::StatusCode::OK)
+            .body(serde_json::to_string(&reports).unwrap_or_else(|e| e.to_string()))
+            .unwrap())
+    }
+}
@@ -29,6 +29,10 @@ make_static_metric! {
             create_shard,
             move_replicas,
             change_replicas,
+            index_scan,
+            backfill_index,
+            ingest_sst,
+            sync_collection,
         }
     }
     pub struct GroupRequestDuration: Histogram {
@@ -43,6 +47,10 @@ make_static_metric! {
             create_shard,
             move_replicas,
             change_replicas,
+            index_scan,
+            backfill_index,
+            ingest_sst,
+            sync_collection,
         }
     }
 }
@@ -113,6 +121,22 @@ pub fn take_group_request_metrics(request: &GroupRequest) -> Option<&'static His
             NODE_SERVICE_GROUP_REQUEST_TOTAL.move_replicas.inc();
             Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.move_replicas)
         }
+        Some(Request::IndexScan(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.index_scan.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.index_scan)
+        }
+        Some(Request::BackfillIndex(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.backfill_index.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.backfill_index)
+        }
+        Some(Request::IngestSst(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.ingest_sst.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.ingest_sst)
+        }
+        Some(Request::SyncCollection(_)) => {
+            NODE_SERVICE_GROUP_REQUEST_TOTAL.sync_collection.inc();
+            Some(&NODE_SERVICE_GROUP_REQUEST_DURATION_SECONDS.sync_collection)
+        }
         None => None,
     }
 }
@@ -172,6 +196,11 @@ macro_rules! simple_node_method {
 simple_node_method!(get_root);
 simple_node_method!(create_replica);
 simple_node_method!(remove_replica);
+simple_node_method!(unsafe_recover_replica);
+simple_node_method!(backup_replica);
+simple_node_method!(restore_replica);
+simple_node_method!(get_checksum);
+simple_node_method!(stage_sst);
 simple_node_method!(root_heartbeat);
 simple_node_method!(migrate);
 simple_node_method!(forward);
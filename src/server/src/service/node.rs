@@ -75,6 +75,23 @@ impl node_server::Node for Server {
             node_admin_request::Request::Heartbeat(req) => {
                 node_admin_response::Response::Heartbeat(self.root_heartbeat(req).await?)
             }
+            node_admin_request::Request::UnsafeRecoverReplica(req) => {
+                node_admin_response::Response::UnsafeRecoverReplica(
+                    self.unsafe_recover_replica(req).await?,
+                )
+            }
+            node_admin_request::Request::BackupReplica(req) => {
+                node_admin_response::Response::BackupReplica(self.backup_replica(req).await?)
+            }
+            node_admin_request::Request::RestoreReplica(req) => {
+                node_admin_response::Response::RestoreReplica(self.restore_replica(req).await?)
+            }
+            node_admin_request::Request::GetChecksum(req) => {
+                node_admin_response::Response::GetChecksum(self.get_checksum(req).await?)
+            }
+            node_admin_request::Request::StageSst(req) => {
+                node_admin_response::Response::StageSst(self.stage_sst(req).await?)
+            }
         };
         Ok(Response::new(NodeAdminResponse {
             response: Some(resp),
@@ -154,6 +171,60 @@ impl Server {
         Ok(RemoveReplicaResponse {})
     }
 
+    async fn unsafe_recover_replica(
+        &self,
+        request: UnsafeRecoverReplicaRequest,
+    ) -> Result<UnsafeRecoverReplicaResponse, Status> {
+        record_latency!(take_unsafe_recover_replica_request_metrics());
+        let group_desc = request
+            .group
+            .ok_or_else(|| Status::invalid_argument("the field `group` is empty"))?;
+        let replica_id = request.replica_id;
+        let group = self
+            .node
+            .unsafe_recover_replica(replica_id, &group_desc)
+            .await?;
+        Ok(UnsafeRecoverReplicaResponse { group: Some(group) })
+    }
+
+    async fn backup_replica(
+        &self,
+        request: BackupReplicaRequest,
+    ) -> Result<BackupReplicaResponse, Status> {
+        record_latency!(take_backup_replica_request_metrics());
+        let resp = self
+            .node
+            .backup_replica(request.group_id, request.replica_id, &request.destination)
+            .await?;
+        Ok(resp)
+    }
+
+    async fn restore_replica(
+        &self,
+        request: RestoreReplicaRequest,
+    ) -> Result<RestoreReplicaResponse, Status> {
+        record_latency!(take_restore_replica_request_metrics());
+        self.node.restore_replica(request).await?;
+        Ok(RestoreReplicaResponse {})
+    }
+
+    async fn get_checksum(
+        &self,
+        request: GetChecksumRequest,
+    ) -> Result<GetChecksumResponse, Status> {
+        record_latency!(take_get_checksum_request_metrics());
+        let checksum =
+            self.node
+                .get_checksum(request.group_id, request.replica_id, request.checksum_id)?;
+        Ok(GetChecksumResponse { checksum })
+    }
+
+    async fn stage_sst(&self, request: StageSstRequest) -> Result<StageSstResponse, Status> {
+        record_latency!(take_stage_sst_request_metrics());
+        let checksums = self.node.stage_sst(&request).await?;
+        Ok(StageSstResponse { checksums })
+    }
+
     async fn root_heartbeat(&self, request: HeartbeatRequest) -> Result<HeartbeatResponse, Status> {
         record_latency!(take_root_heartbeat_request_metrics());
         let mut piggybacks_resps = Vec::with_capacity(request.piggybacks.len());
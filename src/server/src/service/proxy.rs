@@ -60,6 +60,7 @@ impl engula_server::Engula for ProxyServer {
             Request::DeleteCollection(req) => {
                 Response::DeleteCollection(self.delete_collection(req).await?)
             }
+            Request::CreateIndex(req) => Response::CreateIndex(self.create_index(req).await?),
         };
 
         Ok(tonic::Response::new(AdminResponse {
@@ -139,9 +140,19 @@ impl ProxyServer {
 
     async fn update_database(
         &self,
-        _req: UpdateDatabaseRequest,
+        req: UpdateDatabaseRequest,
     ) -> Result<UpdateDatabaseResponse, Status> {
-        Err(Status::unimplemented("ProxyServer::update_database"))
+        let desc = req.database.ok_or_else(|| {
+            Error::InvalidArgument("UpdateDatabaseRequest::database is required".to_owned())
+        })?;
+        let update_mask = req.update_mask.map(|m| m.paths).unwrap_or_default();
+        let database = self
+            .client
+            .update_database(req.name, desc, update_mask)
+            .await?;
+        Ok(UpdateDatabaseResponse {
+            database: Some(database.desc()),
+        })
     }
 
     async fn delete_database(
@@ -197,7 +208,7 @@ impl ProxyServer {
         let name = req.name;
         let database = Database::new(self.client.clone(), desc, None);
         let collection = database
-            .create_collection(name, Some(partition.into()))
+            .create_collection_with_schema(name, Some(partition.into()), req.schema)
             .await?;
         Ok(CreateCollectionResponse {
             collection: Some(collection.desc()),
@@ -206,9 +217,22 @@ impl ProxyServer {
 
     async fn update_collection(
         &self,
-        _req: UpdateCollectionRequest,
+        req: UpdateCollectionRequest,
     ) -> Result<UpdateCollectionResponse, Status> {
-        Err(Status::unimplemented("ProxyServer::update_collection"))
+        let db_desc = req.database.ok_or_else(|| {
+            Error::InvalidArgument("UpdateCollectionRequest::database is required".to_owned())
+        })?;
+        let desc = req.collection.ok_or_else(|| {
+            Error::InvalidArgument("UpdateCollectionRequest::collection is required".to_owned())
+        })?;
+        let update_mask = req.update_mask.map(|m| m.paths).unwrap_or_default();
+        let database = Database::new(self.client.clone(), db_desc, None);
+        let collection = database
+            .update_collection(req.name, desc, update_mask)
+            .await?;
+        Ok(UpdateCollectionResponse {
+            collection: Some(collection.desc()),
+        })
     }
 
     async fn delete_collection(
@@ -223,6 +247,25 @@ impl ProxyServer {
         database.delete_collection(name).await?;
         Ok(DeleteCollectionResponse {})
     }
+
+    async fn create_index(&self, req: CreateIndexRequest) -> Result<CreateIndexResponse, Status> {
+        let desc = req.database.ok_or_else(|| {
+            Error::InvalidArgument("CreateIndexRequest::database is required".to_owned())
+        })?;
+        let index = req.index.ok_or_else(|| {
+            Error::InvalidArgument("CreateIndexRequest::index is required".to_owned())
+        })?;
+        let extractor = index
+            .extractor
+            .ok_or_else(|| Error::InvalidArgument("IndexDesc::extractor is required".to_owned()))?;
+        let database = Database::new(self.client.clone(), desc, None);
+        let collection = database
+            .create_index(req.collection, index.name, extractor)
+            .await?;
+        Ok(CreateIndexResponse {
+            collection: Some(collection.desc()),
+        })
+    }
 }
 
 impl ProxyServer {
@@ -17,7 +17,10 @@ use tonic::{Request, Response, Status, Streaming};
 use tracing::{error, warn};
 
 use crate::{
-    raftgroup::snap::send::{send_snapshot, SnapshotChunkStream},
+    raftgroup::snap::{
+        send::{send_snapshot, SnapshotChunkStream},
+        SnapPriority,
+    },
     serverpb::v1::*,
     service::metrics::*,
     Server,
@@ -76,7 +79,20 @@ impl raft_server::Raft for Server {
         let request = request.into_inner();
         let snap_mgr = self.node.raft_manager().snapshot_manager();
 
-        let stream = send_snapshot(snap_mgr, request.replica_id, request.snapshot_id).await?;
+        let priority = if request.high_priority {
+            SnapPriority::High
+        } else {
+            SnapPriority::Normal
+        };
+        let stream = send_snapshot(
+            snap_mgr,
+            request.replica_id,
+            request.snapshot_id,
+            request.start_key,
+            request.start_file as usize,
+            priority,
+        )
+        .await?;
         Ok(Response::new(stream))
     }
 }
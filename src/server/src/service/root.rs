@@ -36,7 +36,11 @@ impl root_server::Root for Server {
         record_latency!(take_watch_request_metrics());
         let req = req.into_inner();
         let watcher = self
-            .wrap(self.root.watch(req.cur_group_epochs).await)
+            .wrap(
+                self.root
+                    .watch(req.cur_group_epochs, req.start_revision)
+                    .await,
+            )
             .await?;
         Ok(Response::new(watcher))
     }
@@ -51,7 +55,11 @@ impl root_server::Root for Server {
             .capacity
             .ok_or_else(|| Error::InvalidArgument("capacity is required".into()))?;
         let (cluster_id, node, root) = self
-            .wrap(self.root.join(request.addr, capacity).await)
+            .wrap(
+                self.root
+                    .join(request.addr, capacity, request.locality.unwrap_or_default())
+                    .await,
+            )
             .await?;
         Ok::<Response<JoinNodeResponse>, Status>(Response::new(JoinNodeResponse {
             cluster_id,
@@ -106,8 +114,9 @@ impl Server {
                 let res = self.handle_create_database(req).await?;
                 admin_response_union::Response::CreateDatabase(res)
             }
-            admin_request_union::Request::UpdateDatabase(_req) => {
-                todo!()
+            admin_request_union::Request::UpdateDatabase(req) => {
+                let res = self.handle_update_database(req).await?;
+                admin_response_union::Response::UpdateDatabase(res)
             }
             admin_request_union::Request::DeleteDatabase(req) => {
                 let res = self.handle_delete_database(req).await?;
@@ -125,8 +134,9 @@ impl Server {
                 let res = self.handle_create_collection(req).await?;
                 admin_response_union::Response::CreateCollection(res)
             }
-            admin_request_union::Request::UpdateCollection(_req) => {
-                todo!()
+            admin_request_union::Request::UpdateCollection(req) => {
+                let res = self.handle_update_collection(req).await?;
+                admin_response_union::Response::UpdateCollection(res)
             }
             admin_request_union::Request::DeleteCollection(req) => {
                 let res = self.handle_delete_collection(req).await?;
@@ -140,6 +150,10 @@ impl Server {
                 let res = self.handle_list_collection(req).await?;
                 admin_response_union::Response::ListCollections(res)
             }
+            admin_request_union::Request::CreateIndex(req) => {
+                let res = self.handle_create_index(req).await?;
+                admin_response_union::Response::CreateIndex(res)
+            }
         };
         Ok(AdminResponseUnion {
             response: Some(res),
@@ -156,6 +170,23 @@ impl Server {
         })
     }
 
+    async fn handle_update_database(
+        &self,
+        req: UpdateDatabaseRequest,
+    ) -> Result<UpdateDatabaseResponse> {
+        let desc = req.database.ok_or_else(|| {
+            Error::InvalidArgument("UpdateDatabaseRequest::database is required".to_owned())
+        })?;
+        let update_mask = req.update_mask.map(|m| m.paths).unwrap_or_default();
+        let desc = self
+            .root
+            .update_database(&req.name, desc, &update_mask)
+            .await?;
+        Ok(UpdateDatabaseResponse {
+            database: Some(desc),
+        })
+    }
+
     async fn handle_delete_database(
         &self,
         req: DeleteDatabaseRequest,
@@ -186,13 +217,49 @@ impl Server {
         })?;
         let desc = self
             .root
-            .create_collection(req.name, database.name, req.partition)
+            .create_collection(req.name, database.name, req.partition, req.schema)
             .await?;
         Ok(CreateCollectionResponse {
             collection: Some(desc),
         })
     }
 
+    async fn handle_create_index(&self, req: CreateIndexRequest) -> Result<CreateIndexResponse> {
+        let database = req.database.ok_or_else(|| {
+            Error::InvalidArgument("CreateIndexRequest::database is required".to_owned())
+        })?;
+        let index = req.index.ok_or_else(|| {
+            Error::InvalidArgument("CreateIndexRequest::index is required".to_owned())
+        })?;
+        let desc = self
+            .root
+            .create_index(&database, &req.collection, index)
+            .await?;
+        Ok(CreateIndexResponse {
+            collection: Some(desc),
+        })
+    }
+
+    async fn handle_update_collection(
+        &self,
+        req: UpdateCollectionRequest,
+    ) -> Result<UpdateCollectionResponse> {
+        let database = req.database.ok_or_else(|| {
+            Error::InvalidArgument("UpdateCollectionRequest::database is required".to_owned())
+        })?;
+        let desc = req.collection.ok_or_else(|| {
+            Error::InvalidArgument("UpdateCollectionRequest::collection is required".to_owned())
+        })?;
+        let update_mask = req.update_mask.map(|m| m.paths).unwrap_or_default();
+        let desc = self
+            .root
+            .update_collection(&req.name, &database, desc, &update_mask)
+            .await?;
+        Ok(UpdateCollectionResponse {
+            collection: Some(desc),
+        })
+    }
+
     async fn handle_delete_collection(
         &self,
         req: DeleteCollectionRequest,
@@ -105,6 +105,67 @@ fn admin_delete() {
     })
 }
 
+#[test]
+fn admin_update() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("db-col-mng-4");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(1).await;
+        let addrs = nodes.values().cloned().collect::<Vec<_>>();
+        let c = EngulaClient::new(ClientOptions::default(), addrs.to_owned())
+            .await
+            .unwrap();
+
+        c.create_database("test_db".into()).await.unwrap();
+        let desc = DatabaseDesc {
+            name: "test_db1".into(),
+            default_ttl: 3600,
+            ..Default::default()
+        };
+        let db = c
+            .update_database(
+                "test_db".into(),
+                desc,
+                vec!["name".into(), "default_ttl".into()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(db.desc().default_ttl, 3600);
+        assert!(c.open_database("test_db".into()).await.is_err());
+        let db = c.open_database("test_db1".into()).await.unwrap();
+        assert_eq!(db.desc().default_ttl, 3600);
+
+        let co = db
+            .create_collection("test_co".into(), Some(Partition::Hash { slots: 1 }))
+            .await
+            .unwrap();
+        co.put("k1".into(), "v1".into()).await.unwrap();
+        let desc = CollectionDesc {
+            name: "test_co1".into(),
+            replication_factor: 1,
+            ..Default::default()
+        };
+        let co = db
+            .update_collection(
+                "test_co".into(),
+                desc,
+                vec!["name".into(), "replication_factor".into()],
+            )
+            .await
+            .unwrap();
+        assert_eq!(co.desc().replication_factor, 1);
+        assert!(db.open_collection("test_co".into()).await.is_err());
+        let co = db.open_collection("test_co1".into()).await.unwrap();
+        assert_eq!(co.get("k1".into()).await.unwrap(), Some("v1".into()));
+
+        // unsupported field
+        assert!(db
+            .update_collection("test_co1".into(), co.desc(), vec!["id".into()])
+            .await
+            .is_err());
+    })
+}
+
 #[test]
 fn admin_basic() {
     block_on_current(async {
@@ -200,6 +261,58 @@ fn admin_basic() {
     })
 }
 
+#[test]
+fn admin_decommission() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("db-col-mng-5");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(4).await;
+        let addrs = nodes.values().cloned().collect::<Vec<_>>();
+        let root_addr = find_root(addrs).await;
+        let mut node_ids = nodes
+            .iter()
+            .filter(|(_, addr)| **addr != root_addr)
+            .map(|(id, _)| *id);
+        let node_id = node_ids.next().unwrap();
+        let other_node_id = node_ids.next().unwrap();
+
+        let (status, body) = decommission(&root_addr, node_id, false).await;
+        assert_eq!(status, reqwest::StatusCode::OK, "{body}");
+
+        // Only two active nodes would remain, which can't hold the replicas of a group.
+        let (status, body) = decommission(&root_addr, other_node_id, false).await;
+        assert_eq!(status, reqwest::StatusCode::INTERNAL_SERVER_ERROR, "{body}");
+        assert!(body.contains("at least 3 are required"), "{body}");
+
+        // The replicas are moved out by the scheduler, then the node is decommissioned.
+        for _ in 0..300 {
+            let (status, body) = decommission(&root_addr, node_id, true).await;
+            assert_eq!(status, reqwest::StatusCode::OK, "{body}");
+            let progress: serde_json::Value = serde_json::from_str(&body).unwrap();
+            if progress["node_status"] == "DECOMMISSIONED" {
+                assert_eq!(progress["remaining_replicas"], 0);
+                return;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        panic!("node {node_id} is not decommissioned");
+    })
+}
+
+async fn decommission(
+    root_addr: &str,
+    node_id: u64,
+    progress: bool,
+) -> (reqwest::StatusCode, String) {
+    let mut url = format!("http://{root_addr}/admin/decommission?node_id={node_id}");
+    if progress {
+        url.push_str("&progress=true");
+    }
+    let resp = reqwest::get(url).await.unwrap();
+    let status = resp.status();
+    (status, resp.text().await.unwrap())
+}
+
 fn collection_key(database_id: u64, collection_name: &str) -> Vec<u8> {
     let mut buf = Vec::with_capacity(core::mem::size_of::<u64>() + collection_name.len());
     buf.extend_from_slice(database_id.to_le_bytes().as_slice());
@@ -0,0 +1,135 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::Duration;
+
+use engula_client::{Collection, Partition};
+use helper::context::TestContext;
+use tempdir::TempDir;
+use tracing::info;
+
+use crate::helper::{client::*, init::setup_panic_hook, runtime::block_on_current};
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn key(i: u64) -> Vec<u8> {
+    format!("key-{i}").into_bytes()
+}
+
+fn value(i: u64) -> Vec<u8> {
+    format!("value-{i}").into_bytes()
+}
+
+async fn validate(co: &Collection, range: std::ops::Range<u64>) {
+    for i in range {
+        assert_eq!(co.get(key(i)).await.unwrap(), Some(value(i)), "key {i}");
+    }
+}
+
+/// Submit the job by the admin api of the root, and wait until it is finished.
+async fn run_job(root_addr: &str, path: &str, finished_status: &str) {
+    let resp = reqwest::get(format!("http://{root_addr}/admin{path}"))
+        .await
+        .unwrap();
+    let status = resp.status();
+    let body = resp.text().await.unwrap();
+    assert!(status.is_success(), "{path}: {body}");
+    let resp: serde_json::Value = serde_json::from_str(&body).unwrap();
+    let job_id = resp["job_id"].as_u64().unwrap();
+    info!("{path}: job {job_id} is submitted");
+
+    for _ in 0..600 {
+        let resp = reqwest::get(format!("http://{root_addr}/admin/job"))
+            .await
+            .unwrap();
+        let jobs: serde_json::Value = serde_json::from_str(&resp.text().await.unwrap()).unwrap();
+        let job = jobs["history"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|j| j["id"] == job_id);
+        if let Some(job) = job {
+            assert_eq!(job["status"], finished_status, "{path}: {job}");
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("{path}: job {job_id} is not finished");
+}
+
+#[test]
+fn backup_and_restore() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("backup-test--backup-and-restore");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(1).await;
+        let root_addr = nodes.get(&0).unwrap().clone();
+        let c = ClusterClient::new(nodes).await;
+        let client = c.app_client().await;
+        let db = client.create_database("db".into()).await.unwrap();
+        let co = db
+            .create_collection("co".into(), Some(Partition::Hash { slots: 4 }))
+            .await
+            .unwrap();
+        for i in 0..100 {
+            co.put(key(i), value(i)).await.unwrap();
+        }
+
+        let dir = TempDir::new("backup-test").unwrap();
+        let backup = format!("file://{}", dir.path().display());
+        run_job(
+            &root_addr,
+            &format!("/backup?destination={backup}"),
+            "BackupFinish",
+        )
+        .await;
+        co.put(key(100), value(100)).await.unwrap();
+
+        info!("restore the collection under a new name");
+        run_job(
+            &root_addr,
+            &format!("/restore?backup={backup}&database=db&collection=co&new_name=co1"),
+            "RestoreFinish",
+        )
+        .await;
+        let co1 = db.open_collection("co1".into()).await.unwrap();
+        validate(&co1, 0..100).await;
+        assert!(co1.get(key(100)).await.unwrap().is_none());
+        // The restored collection is independent of the source one.
+        co1.put(key(0), value(1)).await.unwrap();
+        validate(&co, 0..101).await;
+
+        info!("restore all databases and collections to a new cluster");
+        let mut new_ctx = TestContext::new("backup-test--restore-cluster");
+        new_ctx.disable_all_balance();
+        let new_nodes = new_ctx.bootstrap_servers(1).await;
+        let new_root_addr = new_nodes.get(&0).unwrap().clone();
+        let new_client = ClusterClient::new(new_nodes).await.app_client().await;
+        run_job(
+            &new_root_addr,
+            &format!("/restore?backup={backup}"),
+            "RestoreFinish",
+        )
+        .await;
+        let db = new_client.open_database("db".into()).await.unwrap();
+        let co = db.open_collection("co".into()).await.unwrap();
+        validate(&co, 0..100).await;
+        assert!(co.get(key(100)).await.unwrap().is_none());
+    });
+}
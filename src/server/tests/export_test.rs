@@ -0,0 +1,185 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::time::{Duration, Instant};
+
+use engula_client::{
+    export::{export_collection, import_collection, Format},
+    Partition,
+};
+use helper::context::TestContext;
+use tempdir::TempDir;
+
+use crate::helper::{client::*, init::setup_panic_hook, runtime::block_on_current};
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn records(n: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+    (0..n)
+        .map(|i| {
+            // The keys and values are not valid utf-8, and some values are empty.
+            let key = [b"key-".as_slice(), &(i as u32).to_be_bytes(), &[0xff]].concat();
+            let value = if i % 10 == 0 {
+                vec![]
+            } else {
+                [b"value-".as_slice(), &[0xfe], &(i as u32).to_le_bytes()].concat()
+            };
+            (key, value)
+        })
+        .collect()
+}
+
+#[test]
+fn export_and_import_records() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("export-test--export-and-import-records");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(1).await;
+        let c = ClusterClient::new(nodes).await;
+        let app = c.app_client().await;
+
+        let db = app.create_database("db".into()).await.unwrap();
+        let src = db
+            .create_collection("src".into(), Some(Partition::Hash { slots: 3 }))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&src.desc()).await;
+        let records = records(100);
+        src.batch_put(&records).await.unwrap();
+
+        let dir = TempDir::new("export-test").unwrap();
+        for (name, format) in [("json", Format::Json), ("binary", Format::Binary)] {
+            let path = dir.path().join(name);
+            let file = std::fs::File::create(&path).unwrap();
+            let num_records = export_collection(&src, file, format, 7).await.unwrap();
+            assert_eq!(num_records, records.len());
+
+            let dst = db
+                .create_collection(format!("dst-{name}"), Some(Partition::Range))
+                .await
+                .unwrap();
+            c.assert_collection_ready(&dst.desc()).await;
+            let file = std::fs::File::open(&path).unwrap();
+            let num_records = import_collection(&dst, file, format, 16, 0).await.unwrap();
+            assert_eq!(num_records, records.len() as u64);
+            for (key, value) in &records {
+                let got = dst.get(key.clone()).await.unwrap();
+                assert_eq!(got.as_ref(), Some(value), "key {key:?}");
+            }
+
+            // Exporting the imported collection gets the same records.
+            let path = dir.path().join(format!("dst-{name}"));
+            let mut output = Vec::new();
+            let num_records = export_collection(&dst, &mut output, format, 1000)
+                .await
+                .unwrap();
+            assert_eq!(output, std::fs::read(&path).unwrap());
+            assert_eq!(num_records, records.len());
+        }
+    });
+}
+
+#[test]
+fn import_records_with_rate_limit() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("export-test--import-records-with-rate-limit");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(1).await;
+        let c = ClusterClient::new(nodes).await;
+        let app = c.app_client().await;
+
+        let db = app.create_database("db".into()).await.unwrap();
+        let co = db
+            .create_collection("co".into(), Some(Partition::Range))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        let records = records(200);
+        let mut input = String::new();
+        for (key, value) in &records {
+            input += &format!(
+                "{{\"key\":\"{}\",\"value\":\"{}\"}}\n",
+                hex::encode(key),
+                hex::encode(value)
+            );
+        }
+
+        // 200 records are written at 100 records per second.
+        let start_at = Instant::now();
+        let num_records = import_collection(&co, input.as_bytes(), Format::Json, 50, 100)
+            .await
+            .unwrap();
+        assert_eq!(num_records, 200);
+        let elapsed = start_at.elapsed();
+        assert!(elapsed >= Duration::from_secs(2), "elapsed {elapsed:?}");
+        for (key, value) in &records {
+            let got = co.get(key.clone()).await.unwrap();
+            assert_eq!(got.as_ref(), Some(value));
+        }
+    });
+}
+
+#[test]
+fn scan_shard_at_changed_epoch() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("export-test--scan-shard-at-changed-epoch");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let app = c.app_client().await;
+
+        let db = app.create_database("db".into()).await.unwrap();
+        let co = db
+            .create_collection("co".into(), Some(Partition::Range))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+        co.batch_put(&records(10)).await.unwrap();
+
+        let (shard, epoch) = co.shards().await.unwrap().pop().unwrap();
+        let records = co.scan_shard(shard.id, epoch, None, 100).await.unwrap();
+        assert_eq!(records.map(|r| r.len()), Some(10));
+
+        // Removing a replica changes the epoch of the group.
+        let group_id = c
+            .find_router_group_state_by_key(&co.desc(), b"key")
+            .await
+            .unwrap()
+            .id;
+        let follower = c.must_group_any_follower(group_id).await;
+        c.group_remove_node(group_id, follower.node_id)
+            .await
+            .unwrap();
+        c.assert_large_group_epoch(group_id, epoch).await;
+
+        let records = co.scan_shard(shard.id, epoch, None, 100).await.unwrap();
+        assert!(records.is_none());
+        for _ in 0..1000 {
+            let (shard, new_epoch) = co.shards().await.unwrap().pop().unwrap();
+            if new_epoch != epoch {
+                let records = co.scan_shard(shard.id, new_epoch, None, 100).await.unwrap();
+                assert_eq!(records.map(|r| r.len()), Some(10));
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("the epoch of group {group_id} isn't changed");
+    });
+}
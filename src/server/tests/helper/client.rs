@@ -96,6 +96,20 @@ impl ClusterClient {
         }
     }
 
+    pub async fn group_replica_nodes(&self, group_id: u64) -> Vec<u64> {
+        if let Ok(state) = self.router.find_group(group_id) {
+            let mut nodes = state
+                .replicas
+                .values()
+                .map(|r| r.node_id)
+                .collect::<Vec<_>>();
+            nodes.sort_unstable();
+            nodes
+        } else {
+            vec![]
+        }
+    }
+
     pub async fn assert_group_members(&self, group_id: u64, mut replicas: Vec<u64>) {
         replicas.sort_unstable();
         for _ in 0..10000 {
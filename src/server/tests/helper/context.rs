@@ -137,6 +137,9 @@ impl TestContext {
             init,
             enable_proxy_service: false,
             join_list,
+            restore_from: None,
+            restore_time: None,
+            locality: LocalityConfig::default(),
             node: NodeConfig {
                 replica: ReplicaConfig {
                     testing_knobs: self.replica_knobs.clone(),
@@ -152,6 +155,7 @@ impl TestContext {
             root,
             executor: ExecutorConfig::default(),
             db: DbConfig::default(),
+            encryption: EncryptionConfig::default(),
         };
         let notifier = ShutdownNotifier::new();
         let shutdown = notifier.subscribe();
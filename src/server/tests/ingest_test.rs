@@ -0,0 +1,160 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::{path::Path, time::Duration};
+
+use engula_api::{
+    server::v1::ShardDesc,
+    v1::{index_desc, value_schema, ValueSchema},
+};
+use engula_client::{AppError, Collection, Database, Partition};
+use engula_server::bulkload::write_sst_file;
+use helper::context::TestContext;
+use tempdir::TempDir;
+
+use crate::helper::{client::*, init::setup_panic_hook, runtime::block_on_current};
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+fn write_sst(dir: &Path, name: &str, shard: &ShardDesc, records: &[(&str, &str)]) -> String {
+    let records = records
+        .iter()
+        .map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec()))
+        .collect();
+    write_sst_file(dir.join(name), shard, records).unwrap();
+    name.to_owned()
+}
+
+/// Wait until the backfilling of the indexes is finished, and return the reopened collection.
+async fn wait_index_public(db: &Database, name: &str) -> Collection {
+    for _ in 0..600 {
+        let co = db.open_collection(name.to_owned()).await.unwrap();
+        if co
+            .desc()
+            .indexes
+            .iter()
+            .all(|i| i.state == index_desc::State::Public as i32)
+        {
+            return co;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the indexes of collection {name} are not public");
+}
+
+#[test]
+fn ingest_sst_files() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("ingest-test--ingest-sst-files");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let app = c.app_client().await;
+
+        let db = app.create_database("db".into()).await.unwrap();
+        let schema = ValueSchema {
+            schema: Some(value_schema::Schema::JsonSchema(
+                r#"{"type": "object", "required": ["name"]}"#.to_owned(),
+            )),
+        };
+        let co = db
+            .create_collection_with_schema("co".into(), Some(Partition::Range), Some(schema))
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+        let extractor = index_desc::Extractor::JsonPath(index_desc::JsonPath {
+            path: "name".into(),
+        });
+        db.create_index("co".into(), "name".into(), extractor)
+            .await
+            .unwrap();
+        let co = wait_index_public(&db, "co").await;
+        co.put(b"k0".to_vec(), br#"{"name": "a"}"#.to_vec())
+            .await
+            .unwrap();
+
+        let dir = TempDir::new("ingest-test").unwrap();
+        let storage = format!("file://{}", dir.path().display());
+        let shard = co.locate(b"k0").await.unwrap();
+
+        // The later file shadows the former one, and the index entries follow the ingested values.
+        let files = vec![
+            write_sst(dir.path(), "1.sst", &shard, &[("k1", r#"{"name": "a"}"#)]),
+            write_sst(
+                dir.path(),
+                "2.sst",
+                &shard,
+                &[("k0", r#"{"name": "b"}"#), ("k1", r#"{"name": "b"}"#)],
+            ),
+        ];
+        co.ingest_sst(shard.id, storage.clone(), files)
+            .await
+            .unwrap();
+        for key in [b"k0", b"k1"] {
+            let value = co.get(key.to_vec()).await.unwrap();
+            assert_eq!(value, Some(br#"{"name": "b"}"#.to_vec()));
+        }
+        assert!(co
+            .index_scan("name", b"a".to_vec())
+            .await
+            .unwrap()
+            .is_empty());
+        let mut keys = co
+            .index_scan("name", b"b".to_vec())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, vec![b"k0".to_vec(), b"k1".to_vec()]);
+
+        // The records violating the schema are rejected.
+        let files = vec![write_sst(
+            dir.path(),
+            "3.sst",
+            &shard,
+            &[("k2", r#"{"age": 18}"#)],
+        )];
+        let r = co.ingest_sst(shard.id, storage.clone(), files).await;
+        assert!(matches!(r, Err(AppError::InvalidArgument(_))), "{r:?}");
+        assert!(co.get(b"k2".to_vec()).await.unwrap().is_none());
+
+        // The records not belonging to the shard are rejected.
+        let other_shard = ShardDesc {
+            collection_id: shard.collection_id + 1,
+            ..shard.clone()
+        };
+        let files = vec![write_sst(
+            dir.path(),
+            "4.sst",
+            &other_shard,
+            &[("k3", r#"{"name": "c"}"#)],
+        )];
+        let r = co.ingest_sst(shard.id, storage.clone(), files).await;
+        assert!(matches!(r, Err(AppError::InvalidArgument(_))), "{r:?}");
+        assert!(co.get(b"k3".to_vec()).await.unwrap().is_none());
+
+        // The missing files are rejected before they are staged by the other replicas.
+        let r = co
+            .ingest_sst(shard.id, storage, vec!["missing.sst".into()])
+            .await;
+        assert!(r.is_err(), "{r:?}");
+    });
+}
@@ -176,6 +176,7 @@ fn single_replica_empty_shard_migration() {
             partition: Some(shard_desc::Partition::Range(
                 shard_desc::RangePartition::default(),
             )),
+            ..Default::default()
         };
         let replica_desc_1 = ReplicaDesc {
             id: replica_1,
@@ -248,6 +249,7 @@ fn single_replica_migration() {
             partition: Some(shard_desc::Partition::Range(
                 shard_desc::RangePartition::default(),
             )),
+            ..Default::default()
         };
         let replica_desc_1 = ReplicaDesc {
             id: replica_1,
@@ -338,6 +340,7 @@ async fn create_two_groups(
         partition: Some(shard_desc::Partition::Range(
             shard_desc::RangePartition::default(),
         )),
+        ..Default::default()
     };
     create_group(c, group_id_1, nodes.clone(), vec![shard_desc.clone()]).await;
 
@@ -557,6 +560,7 @@ fn receive_forward_request_after_shard_migrated() {
             partition: Some(shard_desc::Partition::Range(
                 shard_desc::RangePartition::default(),
             )),
+            ..Default::default()
         };
         let replica_desc_1 = ReplicaDesc {
             id: replica_1,
@@ -607,6 +611,7 @@ fn receive_forward_request_after_shard_migrated() {
                 key: b"a".to_vec(),
                 value: b"b".to_vec(),
                 version: 1,
+                ..Default::default()
             }],
             request: Some(GroupRequestUnion {
                 request: Some(Request::Put(ShardPutRequest {
@@ -0,0 +1,210 @@
+// Copyright 2022 The Engula Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+mod helper;
+
+use std::{collections::HashMap, time::Duration};
+
+use engula_api::{
+    server::v1::{group_request_union::Request, group_response_union::Response, *},
+    v1::{GetRequest, PutRequest},
+};
+use engula_client::RetryState;
+use helper::context::TestContext;
+use tracing::info;
+
+use crate::helper::{client::*, init::setup_panic_hook, runtime::block_on_current};
+
+#[ctor::ctor]
+fn init() {
+    setup_panic_hook();
+    tracing_subscriber::fmt::init();
+}
+
+async fn create_group(c: &ClusterClient, group_id: u64, nodes: Vec<u64>, shards: Vec<ShardDesc>) {
+    let replicas = nodes
+        .iter()
+        .cloned()
+        .map(|node_id| {
+            let replica_id = group_id * 10 + node_id;
+            ReplicaDesc {
+                id: replica_id,
+                node_id,
+                role: ReplicaRole::Voter as i32,
+            }
+        })
+        .collect::<Vec<_>>();
+    let group_desc = GroupDesc {
+        id: group_id,
+        shards,
+        replicas: replicas.clone(),
+        ..Default::default()
+    };
+    for replica in replicas {
+        c.create_replica(replica.node_id, replica.id, group_desc.clone())
+            .await;
+    }
+}
+
+async fn insert(c: &ClusterClient, group_id: u64, shard_id: u64, range: std::ops::Range<u64>) {
+    let mut c = c.group(group_id);
+    for i in range {
+        let put = PutRequest {
+            key: format!("key-{i}").into_bytes(),
+            value: format!("value-{i}").into_bytes(),
+        };
+        let req = Request::Put(ShardPutRequest {
+            shard_id,
+            put: Some(put),
+        });
+
+        let mut retry_state = RetryState::default();
+        loop {
+            match c.request(&req).await {
+                Ok(_) => break,
+                Err(err) => {
+                    retry_state.retry(err).await.unwrap();
+                }
+            }
+        }
+    }
+}
+
+async fn validate(c: &ClusterClient, group_id: u64, shard_id: u64, range: std::ops::Range<u64>) {
+    let mut c = c.group(group_id);
+    for i in range {
+        let expected_value = format!("value-{i}").into_bytes();
+        let req = Request::Get(ShardGetRequest {
+            shard_id,
+            get: Some(GetRequest {
+                key: format!("key-{i}").into_bytes(),
+            }),
+        });
+
+        let mut retry_state = RetryState::default();
+        loop {
+            match c.request(&req).await {
+                Ok(resp) => {
+                    let Response::Get(resp) = resp else { panic!("Invalid response type") };
+                    assert!(matches!(resp.value, Some(v) if v == expected_value));
+                    break;
+                }
+                Err(err) => {
+                    retry_state.retry(err).await.unwrap();
+                }
+            }
+        }
+    }
+}
+
+/// Call the admin api of the root leader, and return the status and body of the response.
+async fn admin(c: &ClusterClient, nodes: &HashMap<u64, String>, path: &str) -> (u16, String) {
+    c.assert_group_leader(0).await;
+    let root_node = c.get_group_leader_node_id(0).await.unwrap();
+    let root_addr = nodes.get(&root_node).unwrap();
+    let resp = reqwest::get(format!("http://{root_addr}/admin{path}"))
+        .await
+        .unwrap();
+    let status = resp.status().as_u16();
+    (status, resp.text().await.unwrap())
+}
+
+#[test]
+fn unsafe_recover_group_and_forget_node() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("recovery-test--unsafe-recover-group");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(5).await;
+        let c = ClusterClient::new(nodes.clone()).await;
+        c.assert_root_group_has_promoted().await;
+        c.assert_group_leader(0).await;
+
+        // Place the group on two nodes which don't serve the root group, so the root survives after
+        // they are stopped.
+        let root_nodes = c.group_replica_nodes(0).await;
+        let mut dead_nodes = nodes
+            .keys()
+            .cloned()
+            .filter(|id| !root_nodes.contains(id))
+            .collect::<Vec<_>>();
+        dead_nodes.sort_unstable();
+        assert_eq!(dead_nodes.len(), 2);
+        let survivor = root_nodes[0];
+
+        let group_id = 100;
+        let shard_id = 101;
+        let shard_desc = ShardDesc {
+            id: shard_id,
+            collection_id: shard_id,
+            partition: Some(shard_desc::Partition::Range(
+                shard_desc::RangePartition::default(),
+            )),
+            ..Default::default()
+        };
+        let mut group_nodes = dead_nodes.clone();
+        group_nodes.push(survivor);
+        create_group(&c, group_id, group_nodes, vec![shard_desc]).await;
+        c.assert_group_leader(group_id).await;
+        insert(&c, group_id, shard_id, 0..10).await;
+
+        info!("stop nodes {dead_nodes:?}, group {group_id} loses the quorum");
+        for node_id in &dead_nodes {
+            ctx.stop_server(*node_id).await;
+        }
+        ctx.wait_election_timeout().await;
+
+        // The group desc might not be reported to root yet.
+        let survivor_replica = group_id * 10 + survivor;
+        let path =
+            format!("/unsafe_recover_group?group_id={group_id}&replica_id={survivor_replica}");
+        let mut recovered = false;
+        for _ in 0..100 {
+            let (status, body) = admin(&c, &nodes, &path).await;
+            if status == 200 {
+                info!("unsafe recover group {group_id}: {body}");
+                recovered = true;
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(recovered, "unsafe recover group {group_id} failed");
+
+        info!("group {group_id} serves again with the surviving replica");
+        for node_id in &dead_nodes {
+            c.assert_group_not_contains_node(group_id, *node_id).await;
+        }
+        validate(&c, group_id, shard_id, 0..10).await;
+        insert(&c, group_id, shard_id, 10..20).await;
+        validate(&c, group_id, shard_id, 10..20).await;
+
+        info!("forget the dead nodes");
+        let (status, body) = admin(&c, &nodes, &format!("/forget_node?node_id={survivor}")).await;
+        assert_ne!(status, 200, "a living node is forgotten: {body}");
+        for node_id in &dead_nodes {
+            // The node is forgettable once it is considered dead by liveness.
+            let path = format!("/forget_node?node_id={node_id}");
+            let mut forgotten = false;
+            for _ in 0..120 {
+                let (status, _) = admin(&c, &nodes, &path).await;
+                if status == 200 {
+                    forgotten = true;
+                    break;
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            assert!(forgotten, "forget node {node_id} failed");
+            let (_, body) = admin(&c, &nodes, &path).await;
+            assert!(body.contains("node not found"), "{body}");
+        }
+    });
+}
@@ -13,8 +13,11 @@
 // limitations under the License.
 mod helper;
 
-use engula_api::server::v1::ReplicaRole;
-use engula_client::{ClientOptions, EngulaClient, Partition};
+use engula_api::{
+    server::v1::ReplicaRole,
+    v1::{value_schema, ValueSchema},
+};
+use engula_client::{AppError, ClientOptions, EngulaClient, Partition};
 use rand::{prelude::SmallRng, Rng, SeedableRng};
 use tracing::info;
 
@@ -282,3 +285,49 @@ fn single_server_large_read_write() {
         }
     });
 }
+
+#[test]
+fn cluster_put_with_value_schema() {
+    block_on_current(async {
+        let mut ctx = TestContext::new("rw_test__cluster_put_with_value_schema");
+        ctx.disable_all_balance();
+        let nodes = ctx.bootstrap_servers(3).await;
+        let c = ClusterClient::new(nodes).await;
+        let app = c.app_client().await;
+
+        let db = app.create_database("test_db".to_string()).await.unwrap();
+        let schema = ValueSchema {
+            schema: Some(value_schema::Schema::JsonSchema(
+                r#"{"type": "object", "required": ["name"]}"#.to_owned(),
+            )),
+        };
+        let co = db
+            .create_collection_with_schema(
+                "test_co".to_string(),
+                Some(Partition::Hash { slots: 3 }),
+                Some(schema),
+            )
+            .await
+            .unwrap();
+        c.assert_collection_ready(&co.desc()).await;
+
+        co.put(b"k1".to_vec(), br#"{"name": "walter"}"#.to_vec())
+            .await
+            .unwrap();
+        let r = co.put(b"k2".to_vec(), br#"{"age": 18}"#.to_vec()).await;
+        assert!(matches!(r, Err(AppError::InvalidArgument(_))), "{r:?}");
+        assert!(co.get(b"k2".to_vec()).await.unwrap().is_none());
+
+        let invalid_schema = ValueSchema {
+            schema: Some(value_schema::Schema::JsonSchema("{".to_owned())),
+        };
+        let r = db
+            .create_collection_with_schema(
+                "test_co_1".to_string(),
+                Some(Partition::Hash { slots: 3 }),
+                Some(invalid_schema),
+            )
+            .await;
+        assert!(matches!(r, Err(AppError::InvalidArgument(_))));
+    });
+}
@@ -100,6 +100,7 @@ fn send_snapshot() {
             partition: Some(shard_desc::Partition::Range(
                 shard_desc::RangePartition::default(),
             )),
+            ..Default::default()
         };
         create_group(&c, group_id, node_ids.clone(), vec![shard_desc]).await;
         insert(&c, group_id, shard_id, 1..100).await;